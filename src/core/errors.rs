@@ -1,3 +1,5 @@
+use std::io;
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -8,6 +10,103 @@ pub enum Error {
     NotImplemented(&'static str),
     #[error("io error: {0}")]
     Io(#[from] std::io::Error),
+    #[error("permission denied: {}", path.display())]
+    PermissionDenied { path: PathBuf },
+    /// The path does not exist, typically because it was removed after being listed.
+    #[error("not found: {}", path.display())]
+    Vanished { path: PathBuf },
+    #[error("already exists: {}", path.display())]
+    AlreadyExists { path: PathBuf },
+    /// The item was modified since nohrs last saw it, so acting on it could lose data.
+    #[error("changed since the operation: {}", path.display())]
+    Changed { path: PathBuf },
+    /// Following a symlink would revisit one of its own ancestors.
+    #[error("symlink loop: {}", path.display())]
+    SymlinkLoop { path: PathBuf },
+    #[error("file name is not valid UTF-8: {}", path.display())]
+    InvalidName { path: PathBuf },
+    #[error("{}: {source}", path.display())]
+    Fs {
+        path: PathBuf,
+        #[source]
+        source: io::Error,
+    },
+    /// A destructive action was requested without the caller confirming it.
+    #[error("confirmation required: {0}")]
+    ConfirmationRequired(&'static str),
+    #[error("cancelled")]
+    Cancelled,
+    #[error("invalid cursor: {0}")]
+    InvalidCursor(String),
+    /// A search pattern that does not compile, with the reason.
+    #[error("invalid pattern: {0}")]
+    InvalidPattern(String),
+    /// A search query that does not parse. `column` is 1-based and counts characters.
+    #[error("invalid query at column {column}: {message}")]
+    InvalidQuery { column: usize, message: String },
+    /// The search index could not be opened, read or written.
+    #[error("index error: {0}")]
+    Index(String),
+    /// A local database, such as the frecency records, could not be opened, read or
+    /// written.
+    #[error("database error: {0}")]
+    Database(String),
+    /// An image could not be decoded, or its thumbnail could not be written.
+    #[error("image error: {0}")]
+    Image(String),
     #[error("other error: {0}")]
     Other(String),
 }
+
+impl Error {
+    /// Attach a path to an IO error, classifying the common cases.
+    pub fn from_io(path: impl Into<PathBuf>, err: io::Error) -> Self {
+        let path = path.into();
+        match err.kind() {
+            io::ErrorKind::PermissionDenied => Error::PermissionDenied { path },
+            io::ErrorKind::NotFound => Error::Vanished { path },
+            io::ErrorKind::AlreadyExists => Error::AlreadyExists { path },
+            _ => Error::Fs { path, source: err },
+        }
+    }
+
+    /// The path the error refers to, if it carries one.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Error::PermissionDenied { path }
+            | Error::Vanished { path }
+            | Error::AlreadyExists { path }
+            | Error::Changed { path }
+            | Error::SymlinkLoop { path }
+            | Error::InvalidName { path }
+            | Error::Fs { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_io_errors_and_keeps_the_path() {
+        let err = |kind| Error::from_io("/x", io::Error::from(kind));
+        assert!(matches!(
+            err(io::ErrorKind::PermissionDenied),
+            Error::PermissionDenied { .. }
+        ));
+        assert!(matches!(
+            err(io::ErrorKind::NotFound),
+            Error::Vanished { .. }
+        ));
+        assert!(matches!(
+            err(io::ErrorKind::AlreadyExists),
+            Error::AlreadyExists { .. }
+        ));
+        let other = err(io::ErrorKind::Interrupted);
+        assert!(matches!(other, Error::Fs { .. }));
+        assert_eq!(other.path(), Some(Path::new("/x")));
+        assert_eq!(Error::Cancelled.path(), None);
+    }
+}
@@ -1,2 +1,6 @@
+pub mod cancel;
 pub mod errors;
+pub mod paths;
 pub mod telemetry;
+#[cfg(test)]
+pub(crate) mod test_support;
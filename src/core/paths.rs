@@ -0,0 +1,30 @@
+//! Locations of nohrs' own files.
+
+use std::path::PathBuf;
+
+/// Directory for persistent state such as the operation journal.
+///
+/// `NOHRS_DATA_DIR` overrides the platform default (e.g. `~/.local/share/nohrs`).
+pub fn data_dir() -> PathBuf {
+    std::env::var_os("NOHRS_DATA_DIR")
+        .map(PathBuf::from)
+        .or_else(|| dirs::data_dir().map(|d| d.join("nohrs")))
+        .unwrap_or_else(|| PathBuf::from(".nohrs"))
+}
+
+/// Directory of the shared thumbnail cache, laid out as the freedesktop thumbnail spec
+/// describes so other file managers reuse nohrs' thumbnails and the other way round.
+///
+/// `NOHRS_THUMBNAIL_DIR` overrides the default, `$XDG_CACHE_HOME/thumbnails` (usually
+/// `~/.cache/thumbnails`). Platforms without a shared cache get one of nohrs' own.
+pub fn thumbnail_dir() -> PathBuf {
+    if let Some(dir) = std::env::var_os("NOHRS_THUMBNAIL_DIR") {
+        return PathBuf::from(dir);
+    }
+    let shared = cfg!(all(unix, not(target_os = "macos")));
+    match dirs::cache_dir() {
+        Some(cache) if shared => cache.join("thumbnails"),
+        Some(cache) => cache.join("nohrs").join("thumbnails"),
+        None => data_dir().join("thumbnails"),
+    }
+}
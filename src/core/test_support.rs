@@ -0,0 +1,72 @@
+//! Helpers shared by the unit tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// A fresh directory under the system temp dir, removed with everything in it on drop.
+pub(crate) struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    pub(crate) fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "nohrs-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("create temp dir");
+        Self { path }
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Write `contents` to `rel`, creating parent directories. Returns the full path.
+    pub(crate) fn write(&self, rel: &str, contents: impl AsRef<[u8]>) -> PathBuf {
+        let path = self.path.join(rel);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).expect("create parent dir");
+        }
+        fs::write(&path, contents).expect("write file");
+        path
+    }
+
+    /// Create the directory `rel` and its parents. Returns the full path.
+    pub(crate) fn mkdir(&self, rel: &str) -> PathBuf {
+        let path = self.path.join(rel);
+        fs::create_dir_all(&path).expect("create dir");
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Where this test process keeps the desktop trash and nohrs' own data.
+fn data_home() -> PathBuf {
+    std::env::temp_dir().join(format!("nohrs-test-data-{}", std::process::id()))
+}
+
+/// Point the desktop trash and the data directory at a directory of this test process,
+/// so tests never touch the user's own trash and concurrent test runs do not share one.
+/// Runs when the test binary is loaded, before the harness starts any thread, so the
+/// environment is never written while something reads it.
+#[ctor::ctor]
+fn isolate_data_home() {
+    let dir = data_home();
+    fs::create_dir_all(&dir).expect("create test data dir");
+    std::env::set_var("XDG_DATA_HOME", dir);
+}
+
+#[ctor::dtor]
+fn remove_data_home() {
+    let _ = fs::remove_dir_all(data_home());
+}
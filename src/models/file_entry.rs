@@ -1,14 +1,289 @@
-#[derive(Debug, Clone)]
+use crate::core::errors::Result;
+use crate::models::os_path;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A directory entry as produced by the listing services and consumed by the UI.
+///
+/// Serializes to the same JSON shape the listing API has always returned, with `kind`
+/// as a lowercase string.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct FileEntry {
+    /// Display name. Lossy when the name is not valid UTF-8.
     pub name: String,
+    /// Display path. Lossy when `raw_path` is set; use [`FileEntry::fs_path`] to get back
+    /// to the file.
     pub path: String,
+    /// Base64 of the raw path bytes, present only when the path is not valid UTF-8.
+    pub raw_path: Option<String>,
     pub kind: FileKind,
     pub size: u64,
+    /// Modification time in whole seconds since the epoch.
+    pub modified: u64,
+    /// Sub-second part of the modification time.
+    pub modified_nanos: u32,
+    pub accessed: Option<Timestamp>,
+    /// Birth time, where the filesystem records one.
+    pub created: Option<Timestamp>,
+    /// Unix mode bits including the file type (`st_mode`).
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub owner: Option<String>,
+    pub group: Option<String>,
+    pub inode: Option<u64>,
+    pub dev: Option<u64>,
+    /// Hard link count.
+    pub nlink: Option<u64>,
+    /// Target of a symlink, as stored in the link.
+    pub link_target: Option<String>,
+    /// The entry is a symlink whose target does not exist.
+    pub link_broken: bool,
+    pub hidden: bool,
 }
 
-#[derive(Debug, Clone)]
+impl FileEntry {
+    /// The exact filesystem path of this entry, honoring `raw_path`. Fails when
+    /// `raw_path` does not decode, rather than falling back to the lossy display path,
+    /// which may name a different file.
+    pub fn fs_path(&self) -> Result<PathBuf> {
+        os_path::resolve(&self.path, self.raw_path.as_deref())
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    pub fn is_file(&self) -> bool {
+        self.kind == FileKind::File
+    }
+
+    /// Lowercased extension of regular files.
+    pub fn extension(&self) -> Option<String> {
+        if !self.is_file() {
+            return None;
+        }
+        Path::new(&self.name)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FileKind {
     File,
     Dir,
-    Link,
+    Symlink,
+    Fifo,
+    Socket,
+    BlockDevice,
+    CharDevice,
+    /// A file type this platform reports but nohrs does not model.
+    Other,
+    /// The entry could not be inspected.
+    #[default]
+    Unknown,
+}
+
+impl FileKind {
+    pub fn from_file_type(ft: fs::FileType) -> Self {
+        if ft.is_dir() {
+            return FileKind::Dir;
+        }
+        if ft.is_file() {
+            return FileKind::File;
+        }
+        if ft.is_symlink() {
+            return FileKind::Symlink;
+        }
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            if ft.is_fifo() {
+                return FileKind::Fifo;
+            }
+            if ft.is_socket() {
+                return FileKind::Socket;
+            }
+            if ft.is_block_device() {
+                return FileKind::BlockDevice;
+            }
+            if ft.is_char_device() {
+                return FileKind::CharDevice;
+            }
+        }
+        FileKind::Other
+    }
+
+    /// The serialized name, e.g. `"dir"` or `"char_device"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            FileKind::File => "file",
+            FileKind::Dir => "dir",
+            FileKind::Symlink => "symlink",
+            FileKind::Fifo => "fifo",
+            FileKind::Socket => "socket",
+            FileKind::BlockDevice => "block_device",
+            FileKind::CharDevice => "char_device",
+            FileKind::Other => "other",
+            FileKind::Unknown => "unknown",
+        }
+    }
+
+    /// Human-readable label for the UI's type column.
+    pub fn label(&self) -> &'static str {
+        match self {
+            FileKind::File => "File",
+            FileKind::Dir => "Folder",
+            FileKind::Symlink => "Link",
+            FileKind::Fifo => "FIFO",
+            FileKind::Socket => "Socket",
+            FileKind::BlockDevice => "Block Device",
+            FileKind::CharDevice => "Char Device",
+            FileKind::Other => "Other",
+            FileKind::Unknown => "Unknown",
+        }
+    }
+}
+
+impl std::str::FromStr for FileKind {
+    type Err = ();
+
+    /// Parse a name produced by [`FileKind::as_str`].
+    fn from_str(name: &str) -> std::result::Result<Self, ()> {
+        Ok(match name {
+            "file" => FileKind::File,
+            "dir" => FileKind::Dir,
+            "symlink" => FileKind::Symlink,
+            "fifo" => FileKind::Fifo,
+            "socket" => FileKind::Socket,
+            "block_device" => FileKind::BlockDevice,
+            "char_device" => FileKind::CharDevice,
+            "other" => FileKind::Other,
+            "unknown" => FileKind::Unknown,
+            _ => return Err(()),
+        })
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// A point in time relative to the Unix epoch with nanosecond precision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Timestamp {
+    pub secs: i64,
+    pub nanos: u32,
+}
+
+impl Timestamp {
+    pub fn from_system_time(t: SystemTime) -> Self {
+        match t.duration_since(UNIX_EPOCH) {
+            Ok(d) => Self {
+                secs: d.as_secs() as i64,
+                nanos: d.subsec_nanos(),
+            },
+            Err(e) => {
+                // Before the epoch: keep `nanos` positive by borrowing from `secs`.
+                let d = e.duration();
+                let (secs, nanos) = match d.subsec_nanos() {
+                    0 => (-(d.as_secs() as i64), 0),
+                    n => (-(d.as_secs() as i64) - 1, 1_000_000_000 - n),
+                };
+                Self { secs, nanos }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const KINDS: [FileKind; 9] = [
+        FileKind::File,
+        FileKind::Dir,
+        FileKind::Symlink,
+        FileKind::Fifo,
+        FileKind::Socket,
+        FileKind::BlockDevice,
+        FileKind::CharDevice,
+        FileKind::Other,
+        FileKind::Unknown,
+    ];
+
+    #[test]
+    fn kind_serializes_as_its_name() {
+        for kind in KINDS {
+            let json = serde_json::to_string(&kind).unwrap();
+            assert_eq!(json, format!("\"{}\"", kind.as_str()));
+            assert_eq!(serde_json::from_str::<FileKind>(&json).unwrap(), kind);
+            assert_eq!(kind.as_str().parse::<FileKind>(), Ok(kind));
+        }
+        assert!("link".parse::<FileKind>().is_err());
+    }
+
+    #[test]
+    fn entry_keeps_json_shape() {
+        let entry = FileEntry {
+            name: "src".into(),
+            path: "/p/src".into(),
+            kind: FileKind::Dir,
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&entry).unwrap();
+        assert_eq!(json["kind"], "dir");
+        assert_eq!(json["name"], "src");
+        assert_eq!(json["size"], 0);
+        assert!(json["raw_path"].is_null());
+    }
+
+    #[test]
+    fn fs_path_fails_on_undecodable_raw_path() {
+        let mut entry = FileEntry {
+            path: "/p/name".into(),
+            ..Default::default()
+        };
+        assert_eq!(entry.fs_path().unwrap(), PathBuf::from("/p/name"));
+        entry.raw_path = Some("%%%".into());
+        assert!(entry.fs_path().is_err());
+    }
+
+    #[test]
+    fn extension_is_lowercased_for_files_only() {
+        let mut entry = FileEntry {
+            name: "Photo.JPG".into(),
+            kind: FileKind::File,
+            ..Default::default()
+        };
+        assert_eq!(entry.extension().as_deref(), Some("jpg"));
+        entry.kind = FileKind::Dir;
+        assert_eq!(entry.extension(), None);
+    }
+
+    #[test]
+    fn timestamps_before_epoch_keep_nanos_positive() {
+        let t = UNIX_EPOCH - Duration::new(1, 250_000_000);
+        assert_eq!(
+            Timestamp::from_system_time(t),
+            Timestamp {
+                secs: -2,
+                nanos: 750_000_000
+            }
+        );
+        let t = UNIX_EPOCH + Duration::new(5, 7);
+        assert_eq!(
+            Timestamp::from_system_time(t),
+            Timestamp { secs: 5, nanos: 7 }
+        );
+    }
 }
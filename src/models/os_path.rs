@@ -0,0 +1,84 @@
+//! Lossless string form of OS paths.
+//!
+//! Paths travel through the UI and JSON as `String`s, which cannot hold arbitrary Unix
+//! bytes. Alongside the lossy display string we carry the raw bytes as base64 whenever the
+//! path is not valid UTF-8, and services turn the pair back into the exact `PathBuf`.
+
+use crate::core::errors::{Error, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::path::{Path, PathBuf};
+
+/// Base64 of the raw path bytes, or `None` when the path is valid UTF-8 and the display
+/// string is already exact.
+pub fn to_raw(path: &Path) -> Option<String> {
+    if path.to_str().is_some() {
+        return None;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        Some(STANDARD.encode(path.as_os_str().as_bytes()))
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// Decode a value produced by [`to_raw`].
+pub fn from_raw(raw: &str) -> Result<PathBuf> {
+    let bytes = STANDARD
+        .decode(raw)
+        .map_err(|e| Error::Other(format!("invalid raw path: {}", e)))?;
+    #[cfg(unix)]
+    {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+        Ok(PathBuf::from(OsString::from_vec(bytes)))
+    }
+    #[cfg(not(unix))]
+    {
+        String::from_utf8(bytes)
+            .map(PathBuf::from)
+            .map_err(|e| Error::InvalidName {
+                path: PathBuf::from(String::from_utf8_lossy(e.as_bytes()).into_owned()),
+            })
+    }
+}
+
+/// The exact path for a display string and its optional raw form.
+pub fn resolve(display: &str, raw: Option<&str>) -> Result<PathBuf> {
+    match raw {
+        Some(raw) => from_raw(raw),
+        None => Ok(PathBuf::from(display)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf8_paths_need_no_raw_form() {
+        assert_eq!(to_raw(Path::new("/tmp/ünïcode")), None);
+        assert_eq!(resolve("/tmp/a", None).unwrap(), PathBuf::from("/tmp/a"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn round_trips_non_utf8_paths() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = Path::new(OsStr::from_bytes(b"/tmp/bad\xffname"));
+        let raw = to_raw(path).unwrap();
+        assert_eq!(from_raw(&raw).unwrap(), path);
+        assert_eq!(resolve("/tmp/bad\u{fffd}name", Some(&raw)).unwrap(), path);
+    }
+
+    #[test]
+    fn rejects_invalid_raw_form() {
+        assert!(from_raw("not base64!").is_err());
+    }
+}
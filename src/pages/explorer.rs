@@ -1,10 +1,37 @@
-use crate::services::fs::listing::{list_dir_sync, FileEntryDto, ListParams};
+use crate::core::cancel::CancelToken;
+use crate::core::errors::Error;
+use crate::models::file_entry::{FileEntry, FileKind};
+use crate::services::frecency::{Access, Frecency, Scores};
+use crate::services::fs::journal::Journal;
+use crate::services::fs::listing::stat_entry;
+use crate::services::fs::ops::{
+    self, Conflict, ConflictAction, ConflictPolicy, JobEvent, JobHandle, Operation, Progress,
+    Report, Resolution,
+};
+use crate::services::fs::size::{compute_sizes, DirSize, SizeCache, SizeStream};
+use crate::services::fs::sort::{self, SortDirection, SortKey, SortOptions};
+use crate::services::fs::stream::{list_dir_stream, ListStream, StreamEvent, StreamParams};
+use crate::services::fs::watch::{self, Change, DirWatcher, WatchEvent, WatchParams};
+use crate::services::preview::code::{CodeOptions, CodePreview, SpanStyle};
+use crate::services::preview::hex::{HexPreview, BYTES_PER_ROW};
+use crate::services::preview::markdown::{
+    Alignment, Block, ImageSource, MarkdownDocument, RichText, TextStyle,
+};
+use crate::services::preview::{self, Preview, MAX_TEXT_SIZE};
+use crate::services::search::fuzzy::FuzzyQuery;
+use crate::services::search::index::{IndexUpdater, SearchIndex};
+use crate::services::search::query::{Candidate, Query};
+use crate::services::search::saved::{run_saved_search, ResultSource, SavedSearch, SavedSearches};
+use crate::services::thumbnail::{
+    self, generate_thumbnails, ThumbnailCache, ThumbnailSize, ThumbnailStream,
+};
+use crate::services::trash::{self, TrashEntry};
 use crate::ui::components::file_list::FileListDelegate;
 use crate::ui::theme::theme;
 
 use gpui::{
-    div, prelude::*, px, rgb, size, AnyElement, Context, Entity, FocusHandle, Focusable,
-    IntoElement, Render, Window,
+    div, prelude::*, px, rgb, size, uniform_list, AnyElement, Context, Entity, FocusHandle,
+    Focusable, HighlightStyle, IntoElement, Render, StyledText, Window,
 };
 use gpui_component::breadcrumb::{Breadcrumb, BreadcrumbItem};
 use gpui_component::input::{InputState, TextInput};
@@ -12,19 +39,15 @@ use gpui_component::list::{List, ListEvent};
 use gpui_component::resizable::{h_resizable, resizable_panel, ResizableState};
 use gpui_component::{v_virtual_list, Icon, IconName, VirtualListScrollHandle};
 use std::{
+    cmp::Reverse,
+    collections::HashMap,
+    ops::Range,
+    path::{Component, Path, PathBuf},
     rc::Rc,
-    time::{Duration, Instant},
+    sync::{mpsc::TryRecvError, Arc, Mutex},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
-#[derive(Clone, Copy, PartialEq, Eq)]
-#[allow(dead_code)]
-enum SortKey {
-    Name,
-    Size,
-    Modified,
-    Type,
-}
-
 #[derive(Clone, Copy, PartialEq, Eq)]
 enum ViewMode {
     List,
@@ -32,21 +55,103 @@ enum ViewMode {
 }
 
 pub struct ExplorerPage {
-    cwd: String,
-    history: Vec<String>,
+    cwd: PathBuf,
+    history: Vec<PathBuf>,
     history_index: usize,
-    entries: Vec<FileEntryDto>,
-    filtered_entries: Vec<FileEntryDto>,
+    entries: Vec<FileEntry>,
+    filtered_entries: Vec<FileEntry>,
     sort_key: SortKey,
     sort_asc: bool,
+    show_hidden: bool,
+    needs_reload: bool,
+    /// In-flight streaming listing of `cwd` and the sort it was started with.
+    stream: Option<ListStream>,
+    stream_sort: SortOptions,
+    /// Per-entry problems reported while listing `cwd`.
+    listing_errors: Vec<String>,
+    load_task: Option<gpui::Task<()>>,
+    /// Live changes to `cwd`, patched into the listing once the stream has finished.
+    watcher: Option<DirWatcher>,
+    watch_task: Option<gpui::Task<()>>,
+    /// Recursive sizes of the directories in the listing, filled in as they are computed.
+    dir_sizes: HashMap<PathBuf, DirSize>,
+    size_cache: Arc<SizeCache>,
+    sizes: Option<SizeStream>,
+    size_task: Option<gpui::Task<()>>,
+    /// Grid thumbnails of the images in the listing, by path; `None` where an image has
+    /// none. Only made while the grid is shown.
+    thumbnails: HashMap<PathBuf, Option<PathBuf>>,
+    thumbnail_cache: Arc<ThumbnailCache>,
+    thumbnail_stream: Option<ThumbnailStream>,
+    thumbnail_task: Option<gpui::Task<()>>,
+    /// Paths copied or cut with the keyboard, pasted into `cwd` on request.
+    clipboard: Option<Clipboard>,
+    /// Running file operation, its header label and latest progress.
+    job: Option<JobHandle>,
+    job_op: Option<Operation>,
+    job_label: String,
+    job_progress: Option<Progress>,
+    job_task: Option<gpui::Task<()>>,
+    /// Conflict the running job is waiting on, and whether the answer applies to the rest.
+    job_conflict: Option<Conflict>,
+    job_apply_all: bool,
+    /// Status line for the last file operation, undo or redo.
+    job_message: Option<String>,
+    /// Undo/redo history shared with other nohrs processes; `None` if it could not be opened.
+    journal: Option<Arc<Mutex<Journal>>>,
+    journal_task: Option<gpui::Task<()>>,
+    /// Showing the trash instead of `cwd`; rows carry the trash id as their path.
+    in_trash: bool,
+    trash_entries: Vec<TrashEntry>,
+    trash_task: Option<gpui::Task<()>>,
+    /// Saved searches listed as smart folders; `None` if they could not be opened.
+    saved_searches: Option<SavedSearches>,
+    /// Showing the results of this saved search instead of `cwd`.
+    smart_folder: Option<SavedSearch>,
+    /// Stops the run filling the smart folder once it is left or run again.
+    smart_cancel: CancelToken,
+    /// The index a live smart folder reads from, kept current while it is shown.
+    live_index: Option<LiveIndex>,
+    /// Showing the most frecent paths instead of `cwd`.
+    in_recents: bool,
+    /// Where opens, navigations and previews are recorded; `None` if it could not be opened.
+    frecency: Option<Arc<Frecency>>,
+    /// Frecency as of when the search box was opened, for ranking its matches.
+    frecency_scores: Scores,
+    /// Most frecent directory matching the search box's text, jumped to with Ctrl/Cmd+J.
+    jump_target: Option<PathBuf>,
+    /// Destructive action waiting for the user to confirm it.
+    pending_confirm: Option<PendingConfirm>,
+    /// Entry to select once the listing it belongs to has loaded.
+    reveal: Option<PathBuf>,
     search_query: String,
+    /// Why the search box's query does not parse.
+    filter_error: Option<String>,
     search_visible: bool,
     search_input: Entity<InputState>,
     resizable: Entity<ResizableState>,
     list: Option<Entity<List<FileListDelegate>>>,
     subs: Vec<gpui::Subscription>,
-    preview_path: Option<String>,
+    preview_path: Option<PathBuf>,
+    /// A note shown in place of the file's contents, e.g. why there is no preview.
     preview_text: Option<String>,
+    /// The previewed file's text, highlighted as its lines scroll into view.
+    preview_code: Option<CodePreview>,
+    /// Only the start of the previewed file's text was read.
+    preview_truncated: bool,
+    /// Set as well as `preview_code` for Markdown files, which are shown rendered.
+    preview_markdown: Option<MarkdownDocument>,
+    /// Show Markdown files as source instead.
+    show_markdown_source: bool,
+    /// Thumbnail shown for an image, once it has been made.
+    preview_image: Option<PathBuf>,
+    /// Dump of a file that is neither text nor an image.
+    preview_hex: Option<HexPreview>,
+    preview_thumbnail: Option<ThumbnailStream>,
+    preview_thumbnail_task: Option<gpui::Task<()>>,
+    /// Reads and prepares the previewed file in the background.
+    preview_task: Option<gpui::Task<()>>,
+    code_options: CodeOptions,
     selected_index: Option<usize>,
     virtual_scroll_handle: VirtualListScrollHandle,
     item_sizes: Rc<Vec<gpui::Size<gpui::Pixels>>>,
@@ -77,6 +182,26 @@ struct ResizingColumn {
     start_x: gpui::Point<gpui::Pixels>,
 }
 
+struct Clipboard {
+    paths: Vec<PathBuf>,
+    cut: bool,
+}
+
+enum PendingConfirm {
+    /// Delete these paths permanently, bypassing the trash.
+    Delete(Vec<PathBuf>),
+    /// Purge these trash ids.
+    Purge(Vec<String>),
+    EmptyTrash,
+}
+
+struct LiveIndex {
+    index: Arc<SearchIndex>,
+    _updater: Arc<IndexUpdater>,
+    /// Last commit the shown results reflect.
+    last_update: Option<SystemTime>,
+}
+
 struct LastClickInfo {
     row: usize,
     timestamp: Instant,
@@ -84,6 +209,17 @@ struct LastClickInfo {
 }
 
 const CONFIRM_SUPPRESS_WINDOW: Duration = Duration::from_millis(300);
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(16);
+const WATCH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SIZE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+const SMART_POLL_INTERVAL: Duration = Duration::from_millis(500);
+const THUMBNAIL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Fits the grid's cards.
+const GRID_THUMBNAIL_SIZE: ThumbnailSize = ThumbnailSize::Normal;
+/// Stays sharp in a wide preview pane on high-density displays.
+const PREVIEW_THUMBNAIL_SIZE: ThumbnailSize = ThumbnailSize::XLarge;
+/// Paths listed under Recents.
+const RECENTS_LIMIT: usize = 100;
 
 impl ExplorerPage {
     pub fn new(
@@ -92,16 +228,62 @@ impl ExplorerPage {
         focus_handle: FocusHandle,
     ) -> Self {
         Self {
-            cwd: std::env::current_dir()
-                .map(|p| p.to_string_lossy().to_string())
-                .unwrap_or_else(|_| ".".into()),
+            cwd: std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
             history: Vec::new(),
             history_index: 0,
             entries: Vec::new(),
             filtered_entries: Vec::new(),
             sort_key: SortKey::Name,
             sort_asc: true,
+            show_hidden: true,
+            needs_reload: true,
+            stream: None,
+            stream_sort: SortOptions::default(),
+            listing_errors: Vec::new(),
+            load_task: None,
+            watcher: None,
+            watch_task: None,
+            dir_sizes: HashMap::new(),
+            size_cache: Arc::new(SizeCache::new()),
+            sizes: None,
+            size_task: None,
+            thumbnails: HashMap::new(),
+            thumbnail_cache: Arc::new(ThumbnailCache::open_default()),
+            thumbnail_stream: None,
+            thumbnail_task: None,
+            clipboard: None,
+            job: None,
+            job_op: None,
+            job_label: String::new(),
+            job_progress: None,
+            job_task: None,
+            job_conflict: None,
+            job_apply_all: false,
+            job_message: None,
+            journal: Journal::open_default()
+                .map_err(|err| tracing::warn!(%err, "undo history unavailable"))
+                .ok()
+                .map(|j| Arc::new(Mutex::new(j))),
+            journal_task: None,
+            in_trash: false,
+            trash_entries: Vec::new(),
+            trash_task: None,
+            saved_searches: SavedSearches::open_default()
+                .map_err(|err| tracing::warn!(%err, "saved searches unavailable"))
+                .ok(),
+            smart_folder: None,
+            smart_cancel: CancelToken::new(),
+            live_index: None,
+            in_recents: false,
+            frecency: Frecency::shared()
+                .map_err(|err| tracing::warn!(%err, "frecency unavailable"))
+                .ok(),
+            frecency_scores: Scores::default(),
+            jump_target: None,
+            pending_confirm: None,
+            reveal: None,
             search_query: String::new(),
+            filter_error: None,
             search_visible: false,
             search_input,
             resizable,
@@ -109,6 +291,16 @@ impl ExplorerPage {
             subs: Vec::new(),
             preview_path: None,
             preview_text: None,
+            preview_code: None,
+            preview_truncated: false,
+            preview_markdown: None,
+            show_markdown_source: false,
+            preview_image: None,
+            preview_hex: None,
+            preview_thumbnail: None,
+            preview_thumbnail_task: None,
+            preview_task: None,
+            code_options: CodeOptions::default(),
             selected_index: None,
             virtual_scroll_handle: VirtualListScrollHandle::new(),
             item_sizes: Rc::new(Vec::new()),
@@ -126,26 +318,364 @@ impl ExplorerPage {
         }
     }
 
-    fn ensure_loaded(&mut self) {
-        if self.entries.is_empty() {
-            self.reload();
+    fn ensure_loaded(&mut self, cx: &mut Context<Self>) {
+        if self.needs_reload {
+            self.reload(cx);
+        }
+    }
+
+    fn reload(&mut self, cx: &mut Context<Self>) {
+        self.needs_reload = false;
+        self.entries.clear();
+        self.listing_errors.clear();
+        self.apply_filter();
+        self.clear_preview();
+        self.watcher = None;
+        self.watch_task = None;
+        self.live_index = None;
+        self.dir_sizes.clear();
+        self.sizes = None;
+        self.size_task = None;
+        self.thumbnails.clear();
+        self.thumbnail_stream = None;
+        self.thumbnail_task = None;
+        if self.in_trash {
+            self.load_trash(cx);
+            return;
+        }
+        if self.in_recents {
+            self.load_recents(cx);
+            return;
+        }
+        if let Some(search) = self.smart_folder.clone() {
+            self.stream = None;
+            if search.live {
+                self.start_smart_live(&search, cx);
+            }
+            self.run_smart_folder(search, cx);
+            return;
+        }
+        self.start_watching(cx);
+
+        // Stream the directory so the first rows show up before the whole read finishes;
+        // the fully sorted listing replaces the batches once it is ready.
+        let sort = self.sort_options();
+        self.stream = Some(list_dir_stream(StreamParams {
+            path: self.cwd.clone(),
+            sort: Some(sort),
+            show_hidden: self.show_hidden,
+            ..Default::default()
+        }));
+        self.stream_sort = sort;
+        self.load_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(STREAM_POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_stream(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+    }
+
+    /// Drain available stream events. Returns whether the stream is still running.
+    fn poll_stream(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(stream) = self.stream.as_ref() else {
+            return false;
+        };
+        let mut changed = false;
+        let mut pending = true;
+        loop {
+            match stream.try_next() {
+                Ok(StreamEvent::Batch(batch)) => {
+                    self.entries.extend(batch);
+                    changed = true;
+                }
+                Ok(StreamEvent::Sorted(sorted)) => {
+                    self.entries = sorted;
+                    let current = self.sort_options();
+                    if current != self.stream_sort {
+                        sort::sort_entries(&mut self.entries, &current);
+                    }
+                    changed = true;
+                }
+                Ok(StreamEvent::EntryError(err)) => {
+                    self.listing_errors.push(err.to_string());
+                    changed = true;
+                }
+                Ok(StreamEvent::Error(err)) => {
+                    tracing::warn!(
+                        path = %self.cwd.display(),
+                        %err,
+                        "directory listing stopped early"
+                    );
+                    self.listing_errors.push(err.to_string());
+                    changed = true;
+                }
+                Ok(StreamEvent::Done { .. }) | Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if changed {
+            self.apply_filter();
+            cx.notify();
         }
+        if !pending {
+            self.stream = None;
+            self.select_revealed(cx);
+            self.start_sizes(cx);
+            self.start_thumbnails(cx);
+        }
+        pending
     }
 
-    fn reload(&mut self) {
-        if let Ok(res) = list_dir_sync(ListParams {
-            path: &self.cwd,
-            limit: 1000,
-            cursor: None,
+    /// Watch `cwd` so files added or removed behind our back show up without a reload.
+    /// Started before the listing so nothing that happens while it streams is missed.
+    fn start_watching(&mut self, cx: &mut Context<Self>) {
+        match watch::watch(WatchParams {
+            path: self.cwd.clone(),
+            ..Default::default()
         }) {
-            let mut e = res.entries;
-            self.sort_entries(&mut e);
-            self.entries = e;
+            Ok(watcher) => self.watcher = Some(watcher),
+            Err(err) => {
+                tracing::warn!(path = %self.cwd.display(), %err, "live updates unavailable");
+                return;
+            }
+        }
+        self.watch_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(WATCH_POLL_INTERVAL).await;
+            let watching = this
+                .update(cx, |this, cx| this.poll_watch(cx))
+                .unwrap_or(false);
+            if !watching {
+                break;
+            }
+        }));
+    }
+
+    /// Patch the listing with pending changes. Returns whether the watch is still active.
+    fn poll_watch(&mut self, cx: &mut Context<Self>) -> bool {
+        // Changes wait until the listing they apply to is complete.
+        if self.stream.is_some() {
+            return true;
+        }
+        let Some(watcher) = self.watcher.as_ref() else {
+            return false;
+        };
+        let mut changes = Vec::new();
+        loop {
+            match watcher.try_next() {
+                Ok(WatchEvent::Changes(batch)) => changes.extend(batch),
+                Ok(WatchEvent::Rescan) => {
+                    self.reload(cx);
+                    return false;
+                }
+                Ok(WatchEvent::Error(err)) => {
+                    tracing::debug!(path = %self.cwd.display(), %err, "watch error");
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    self.watcher = None;
+                    return false;
+                }
+            }
+        }
+        if changes.is_empty() {
+            return true;
+        }
+
+        for change in &changes {
+            if let Change::Renamed { from, .. } = change {
+                self.size_cache.invalidate(from);
+                self.dir_sizes.remove(from);
+            }
+            self.size_cache.invalidate(change.path());
+            self.dir_sizes.remove(change.path());
+            self.thumbnails.remove(change.path());
+            if let Change::Removed(path) = change {
+                self.thumbnail_cache.remove(path);
+            }
+        }
+        let selected = self.selected_entry().and_then(|e| e.fs_path().ok());
+        let sort = self.sort_options();
+        for err in watch::apply_changes(
+            &mut self.entries,
+            &self.cwd,
+            &changes,
+            &sort,
+            self.show_hidden,
+            None,
+        ) {
+            tracing::debug!(%err, "changed entry could not be read");
+        }
+        self.apply_filter();
+        self.selected_index = selected.and_then(|path| {
+            self.filtered_entries
+                .iter()
+                .position(|e| e.fs_path().is_ok_and(|p| p == path))
+        });
+        if let Some(preview) = self.preview_path.clone() {
+            for change in &changes {
+                match change {
+                    Change::Removed(p) | Change::Renamed { from: p, .. } if *p == preview => {
+                        self.clear_preview();
+                    }
+                    c if c.path() == preview => self.open_preview(preview.clone(), cx),
+                    _ => {}
+                }
+            }
+        }
+        self.start_sizes(cx);
+        self.start_thumbnails(cx);
+        cx.notify();
+        true
+    }
+
+    /// Compute the sizes of listed directories that do not have one yet.
+    fn start_sizes(&mut self, cx: &mut Context<Self>) {
+        let dirs: Vec<PathBuf> = self
+            .entries
+            .iter()
+            .filter(|e| e.is_dir())
+            .filter_map(|e| e.fs_path().ok())
+            .filter(|p| !self.dir_sizes.contains_key(p))
+            .collect();
+        if dirs.is_empty() {
+            return;
+        }
+        self.sizes = Some(compute_sizes(dirs, self.size_cache.clone()));
+        self.size_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(SIZE_POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_sizes(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+    }
+
+    /// Make grid thumbnails for the images in the listing that do not have one yet.
+    fn start_thumbnails(&mut self, cx: &mut Context<Self>) {
+        if self.view_mode != ViewMode::Grid || self.in_trash {
+            return;
+        }
+        let images: Vec<PathBuf> = self
+            .entries
+            .iter()
+            .filter(|e| e.is_file())
+            .filter_map(|e| e.fs_path().ok())
+            .filter(|p| thumbnail::is_supported(p) && !self.thumbnails.contains_key(p))
+            .collect();
+        if images.is_empty() {
+            return;
+        }
+        self.thumbnail_stream = Some(generate_thumbnails(
+            images,
+            GRID_THUMBNAIL_SIZE,
+            self.thumbnail_cache.clone(),
+        ));
+        self.thumbnail_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor()
+                .timer(THUMBNAIL_POLL_INTERVAL)
+                .await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_thumbnails(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+    }
+
+    /// Fill in grid thumbnails made so far. Returns whether more are coming.
+    fn poll_thumbnails(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(stream) = self.thumbnail_stream.as_ref() else {
+            return false;
+        };
+        let mut changed = false;
+        let mut pending = true;
+        loop {
+            match stream.try_next() {
+                Ok(result) => {
+                    let thumb = result.thumbnail.unwrap_or_else(|err| {
+                        tracing::debug!(%err, "thumbnail unavailable");
+                        None
+                    });
+                    if let Some(thumb) = &thumb {
+                        reload_image(thumb, cx);
+                    }
+                    self.thumbnails.insert(result.path, thumb);
+                    changed = true;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+            }
+        }
+        if !pending {
+            self.thumbnail_stream = None;
+        }
+        if changed {
+            cx.notify();
+        }
+        pending
+    }
+
+    /// Fill in directory sizes computed so far. Returns whether more are coming.
+    fn poll_sizes(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(sizes) = self.sizes.as_ref() else {
+            return false;
+        };
+        let mut arrived = HashMap::new();
+        let mut pending = true;
+        loop {
+            match sizes.try_next() {
+                Ok(result) => match result.size {
+                    Ok(size) => {
+                        arrived.insert(result.path, size);
+                    }
+                    Err(err) => tracing::debug!(%err, "directory size unavailable"),
+                },
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+            }
+        }
+        if !pending {
+            self.sizes = None;
+        }
+        if arrived.is_empty() {
+            return pending;
+        }
+
+        for entry in self.entries.iter_mut().filter(|e| e.is_dir()) {
+            if let Some(size) = entry.fs_path().ok().and_then(|p| arrived.get(&p)) {
+                entry.size = size.apparent;
+            }
+        }
+        self.dir_sizes.extend(arrived);
+        if self.sort_key == SortKey::Size {
+            let selected = self.selected_entry().and_then(|e| e.fs_path().ok());
+            sort::sort_entries(&mut self.entries, &self.sort_options());
+            self.apply_filter();
+            self.selected_index = selected.and_then(|path| {
+                self.filtered_entries
+                    .iter()
+                    .position(|e| e.fs_path().is_ok_and(|p| p == path))
+            });
+        } else {
             self.apply_filter();
-            self.update_item_sizes();
-            self.preview_text = None;
-            self.preview_path = None;
         }
+        cx.notify();
+        pending
     }
 
     fn update_item_sizes(&mut self) {
@@ -173,68 +703,86 @@ impl ExplorerPage {
             + 48.0
     }
 
+    /// Narrow the listing to the search box's query: its clauses filter and its free text
+    /// ranks fuzzily by name. A query that does not parse leaves the listing as it is.
     fn apply_filter(&mut self) {
-        if self.search_query.is_empty() {
+        let query = match Query::parse(&self.search_query) {
+            Ok(query) => {
+                self.filter_error = None;
+                query
+            }
+            Err(err) => {
+                self.filter_error = Some(err.to_string());
+                Query::default()
+            }
+        };
+        if query.is_empty() {
             self.filtered_entries = self.entries.clone();
         } else {
-            let query = self.search_query.to_lowercase();
-            self.filtered_entries = self
+            let fuzzy = FuzzyQuery::new(&query.text());
+            let now = SystemTime::now();
+            let mut matched: Vec<(i32, &FileEntry)> = self
                 .entries
                 .iter()
-                .filter(|e| e.name.to_lowercase().contains(&query))
-                .cloned()
+                .filter(|e| {
+                    // Matching only needs the display form when the exact path is unknown.
+                    let path = e.fs_path().unwrap_or_else(|_| PathBuf::from(&e.path));
+                    let candidate = Candidate {
+                        path: &path,
+                        name: &e.name,
+                        kind: e.kind,
+                        size: e.size,
+                        modified: Some(UNIX_EPOCH + Duration::new(e.modified, e.modified_nanos)),
+                    };
+                    query.matches(&candidate, now)
+                })
+                .filter_map(|e| {
+                    let m = fuzzy.matches(&e.name)?;
+                    let boost = e.fs_path().map_or(0, |p| self.frecency_scores.boost(&p));
+                    Some((m.score + boost, e))
+                })
                 .collect();
+            // Stable, so equally good matches keep the listing's order.
+            matched.sort_by_key(|&(score, _)| Reverse(score));
+            self.filtered_entries = matched.into_iter().map(|(_, e)| e.clone()).collect();
         }
         self.update_item_sizes();
     }
 
-    fn set_sort_key(&mut self, key: SortKey) {
+    fn set_sort_key(&mut self, key: SortKey, cx: &mut Context<Self>) {
         if self.sort_key == key {
             self.sort_asc = !self.sort_asc;
         } else {
             self.sort_key = key;
             self.sort_asc = true;
         }
-        let mut e = self.entries.clone();
-        self.sort_entries(&mut e);
+        // Entries still streaming in are re-sorted when the final snapshot arrives.
+        let mut e = std::mem::take(&mut self.entries);
+        sort::sort_entries(&mut e, &self.sort_options());
         self.entries = e;
         self.apply_filter();
+        cx.notify();
     }
 
-    fn sort_entries(&self, entries: &mut [FileEntryDto]) {
-        entries.sort_by(|a, b| {
-            // Directories before files
-            let is_dir_a = a.kind == "dir";
-            let is_dir_b = b.kind == "dir";
-
-            match is_dir_b.cmp(&is_dir_a) {
-                std::cmp::Ordering::Equal => {
-                    let order = match self.sort_key {
-                        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
-                        SortKey::Size => a.size.cmp(&b.size),
-                        SortKey::Modified => a.modified.cmp(&b.modified),
-                        SortKey::Type => {
-                            let ext_a = get_extension(&a.name, &a.kind);
-                            let ext_b = get_extension(&b.name, &b.kind);
-                            ext_a.cmp(&ext_b)
-                        }
-                    };
-                    if self.sort_asc {
-                        order
-                    } else {
-                        order.reverse()
-                    }
-                }
-                kind_order => kind_order,
-            }
-        });
+    fn sort_options(&self) -> SortOptions {
+        SortOptions {
+            key: self.sort_key,
+            direction: if self.sort_asc {
+                SortDirection::Asc
+            } else {
+                SortDirection::Desc
+            },
+            dirs_first: true,
+        }
     }
 
-    fn change_dir(&mut self, path: String, window: &mut Window, cx: &mut Context<Self>) {
-        if path == self.cwd {
+    fn change_dir(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        if path == self.cwd && self.shows_cwd() {
             return;
         }
         self.close_search(window, cx);
+        self.leave_views();
+        self.record_access(&path, Access::Navigate, cx);
         if self.history.is_empty() {
             self.history.push(self.cwd.clone());
             self.history_index = 0;
@@ -245,18 +793,51 @@ impl ExplorerPage {
         self.history.push(path.clone());
         self.history_index += 1;
         self.cwd = path;
-        self.entries.clear();
-        self.reload();
+        self.reload(cx);
+    }
+
+    /// Go to the directory containing `path` and select it there.
+    pub fn reveal(&mut self, path: PathBuf, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(dir) = path.parent().map(Path::to_path_buf) else {
+            return;
+        };
+        self.reveal = Some(path);
+        if dir != self.cwd || !self.shows_cwd() {
+            self.change_dir(dir, window, cx);
+        } else if self.stream.is_none() {
+            self.close_search(window, cx);
+            self.select_revealed(cx);
+        }
+        cx.notify();
+    }
+
+    fn select_revealed(&mut self, cx: &mut Context<Self>) {
+        let Some(path) = self.reveal.take() else {
+            return;
+        };
+        self.selected_index = self
+            .filtered_entries
+            .iter()
+            .position(|e| e.fs_path().is_ok_and(|p| p == path));
+        if self.selected_entry().is_some_and(FileEntry::is_file) {
+            self.open_preview(path, cx);
+        }
     }
 
     fn go_back(&mut self, window: &mut Window, cx: &mut Context<Self>) {
-        if self.history_index > 0 {
+        if !self.shows_cwd() {
+            // The trash, Recents and smart folders sit on top of `cwd` rather than in the
+            // history.
+            self.close_search(window, cx);
+            self.leave_views();
+            self.reload(cx);
+        } else if self.history_index > 0 {
             self.history_index -= 1;
             if let Some(p) = self.history.get(self.history_index).cloned() {
+                self.record_access(&p, Access::Navigate, cx);
                 self.cwd = p;
-                self.entries.clear();
                 self.close_search(window, cx);
-                self.reload();
+                self.reload(cx);
             }
         }
     }
@@ -265,10 +846,11 @@ impl ExplorerPage {
         if self.history_index + 1 < self.history.len() {
             self.history_index += 1;
             if let Some(p) = self.history.get(self.history_index).cloned() {
+                self.record_access(&p, Access::Navigate, cx);
                 self.cwd = p;
-                self.entries.clear();
                 self.close_search(window, cx);
-                self.reload();
+                self.leave_views();
+                self.reload(cx);
             }
         }
     }
@@ -313,6 +895,7 @@ impl ExplorerPage {
     fn set_view_mode(&mut self, mode: ViewMode, cx: &mut Context<Self>) {
         if self.view_mode != mode {
             self.view_mode = mode;
+            self.start_thumbnails(cx);
             cx.notify();
         }
     }
@@ -325,11 +908,30 @@ impl ExplorerPage {
         });
     }
 
-    fn activate_entry(&mut self, item: FileEntryDto, window: &mut Window, cx: &mut Context<Self>) {
-        if item.kind == "dir" {
-            self.change_dir(item.path, window, cx);
+    fn activate_entry(&mut self, item: FileEntry, window: &mut Window, cx: &mut Context<Self>) {
+        let Some(path) = self.resolve_entry(&item, cx) else {
+            return;
+        };
+        if item.is_dir() && !self.in_trash {
+            self.change_dir(path, window, cx);
         } else {
-            self.open_preview(item.path);
+            if !self.in_trash {
+                self.record_access(&path, Access::Open, cx);
+            }
+            self.open_preview(path, cx);
+        }
+    }
+
+    /// The exact path of `entry`, or `None` after reporting why it cannot be resolved.
+    fn resolve_entry(&mut self, entry: &FileEntry, cx: &mut Context<Self>) -> Option<PathBuf> {
+        match entry.fs_path() {
+            Ok(path) => Some(path),
+            Err(err) => {
+                tracing::warn!(path = %entry.path, %err, "cannot resolve entry");
+                self.listing_errors.push(err.to_string());
+                cx.notify();
+                None
+            }
         }
     }
 
@@ -345,8 +947,10 @@ impl ExplorerPage {
                     ListEvent::Select(ix) => {
                         this.selected_index = Some(ix.row);
                         if let Some(item) = this.filtered_entries.get(ix.row).cloned() {
-                            if item.kind == "file" {
-                                this.open_preview(item.path);
+                            if item.is_file() {
+                                if let Some(path) = this.resolve_entry(&item, cx) {
+                                    this.preview_entry(path, cx);
+                                }
                             }
                         }
                     }
@@ -384,6 +988,25 @@ impl ExplorerPage {
             return;
         }
         self.search_visible = true;
+        if let Some(frecency) = self.frecency.clone() {
+            cx.spawn(async move |this, cx| {
+                let scores = cx
+                    .background_executor()
+                    .spawn(async move {
+                        frecency
+                            .scores(SystemTime::now())
+                            .map_err(|err| tracing::debug!(%err, "frecency unavailable"))
+                            .unwrap_or_default()
+                    })
+                    .await;
+                let _ = this.update(cx, |this, cx| {
+                    this.frecency_scores = scores;
+                    this.update_jump_target();
+                    cx.notify();
+                });
+            })
+            .detach();
+        }
         self.search_input.update(cx, |input, cx| {
             input.focus(window, cx);
         });
@@ -396,6 +1019,7 @@ impl ExplorerPage {
         }
         self.search_visible = false;
         self.search_query.clear();
+        self.jump_target = None;
         self.apply_filter();
         self.search_input.update(cx, |input, cx| {
             input.set_value("", window, cx);
@@ -411,101 +1035,984 @@ impl ExplorerPage {
         }
     }
 
-    fn open_preview(&mut self, path: String) {
-        if let Ok(md) = std::fs::metadata(&path) {
-            if md.is_file() && md.len() <= 1024 * 1024 * 2 {
-                if let Ok(bytes) = std::fs::read(&path) {
-                    if let Ok(text) = String::from_utf8(bytes) {
-                        self.preview_path = Some(path);
-                        self.preview_text = Some(text);
-                        return;
-                    }
-                }
+    /// Preview a file the user picked, counting it towards its frecency.
+    fn preview_entry(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        if !self.in_trash {
+            self.record_access(&path, Access::Preview, cx);
+        }
+        self.open_preview(path, cx);
+    }
+
+    fn clear_preview(&mut self) {
+        self.preview_path = None;
+        self.preview_text = None;
+        self.preview_code = None;
+        self.preview_truncated = false;
+        self.preview_markdown = None;
+        self.preview_image = None;
+        self.preview_hex = None;
+        self.preview_thumbnail = None;
+        self.preview_thumbnail_task = None;
+        self.preview_task = None;
+    }
+
+    fn open_preview(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        // A file previewed again after it changed keeps showing until it is read again.
+        let reloading = !self.in_trash
+            && self.preview_path.as_ref() == Some(&path)
+            && (self.preview_code.is_some() || self.preview_hex.is_some());
+        if !reloading {
+            self.clear_preview();
+        }
+        if self.in_trash {
+            // Trashed items are previewed by where they came from.
+            if let Some(item) = self.trash_entries.iter().find(|e| Path::new(&e.id) == path) {
+                let parent = item.original_path.parent().unwrap_or(Path::new(""));
+                self.preview_path = Some(item.original_path.clone());
+                self.preview_text = Some(format!("Originally in {}", parent.display()));
             }
+            return;
+        }
+        if thumbnail::is_supported(&path) {
+            self.preview_text = Some("Loading preview…".into());
+            self.start_preview_thumbnail(path.clone(), cx);
+            self.preview_path = Some(path);
+            return;
         }
-        self.preview_path = Some(path);
-        self.preview_text = Some("(Preview not available for this file)".into());
+        if !reloading {
+            self.preview_text = Some("Loading preview…".into());
+        }
+        self.preview_path = Some(path.clone());
+        let options = self.code_options.clone();
+        self.preview_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn({
+                    let path = path.clone();
+                    async move { preview::load(&path, &options) }
+                })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                this.show_preview(&path, result);
+                cx.notify();
+            });
+        }));
     }
 
-    fn shortcuts(&self) -> Vec<(String, String)> {
-        let mut v = Vec::new();
-        let home = std::env::var("HOME").ok();
-        #[cfg(target_os = "windows")]
-        let home = home.or_else(|| std::env::var("USERPROFILE").ok());
-        if let Some(h) = home {
-            let p = |s: &str| {
-                std::path::Path::new(&h)
-                    .join(s)
-                    .to_string_lossy()
-                    .to_string()
-            };
-            v.push(("Home".into(), h.clone()));
-            for (label, sub) in [
-                ("Desktop", "Desktop"),
-                ("Downloads", "Downloads"),
-                ("Documents", "Documents"),
-                ("Pictures", "Pictures"),
-            ] {
-                let path = p(sub);
-                if std::path::Path::new(&path).exists() {
-                    v.push((label.into(), path));
-                }
+    /// Swap in the preview of `path` once it has been read.
+    fn show_preview(&mut self, path: &Path, result: crate::core::errors::Result<Preview>) {
+        self.preview_text = None;
+        self.preview_code = None;
+        self.preview_truncated = false;
+        self.preview_markdown = None;
+        self.preview_hex = None;
+        match result {
+            Ok(Preview::Text {
+                code,
+                markdown,
+                truncated,
+            }) => {
+                self.preview_code = Some(*code);
+                self.preview_markdown = markdown;
+                self.preview_truncated = truncated;
+            }
+            Ok(Preview::Hex(hex)) => self.preview_hex = Some(hex),
+            Ok(Preview::Unavailable) => {
+                self.preview_text = Some("(Preview not available for this file)".into());
+            }
+            Err(err) => {
+                tracing::debug!(path = %path.display(), %err, "cannot preview");
+                self.preview_text = Some(format!("(Cannot preview: {err})"));
             }
         }
-        v
     }
-}
 
-impl Render for ExplorerPage {
-    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
-        self.ensure_loaded();
-        if !self.focus_requested {
-            self.focus_requested = true;
-            cx.focus_self(window);
+    /// Make the thumbnail of the image at `path` for the preview pane in the background.
+    fn start_preview_thumbnail(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        self.preview_thumbnail = Some(generate_thumbnails(
+            vec![path],
+            PREVIEW_THUMBNAIL_SIZE,
+            self.thumbnail_cache.clone(),
+        ));
+        self.preview_thumbnail_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor()
+                .timer(THUMBNAIL_POLL_INTERVAL)
+                .await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_preview_thumbnail(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+    }
+
+    /// Show the preview thumbnail if it is ready. Returns whether it is still coming.
+    fn poll_preview_thumbnail(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(stream) = self.preview_thumbnail.as_ref() else {
+            return false;
+        };
+        let result = match stream.try_next() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return true,
+            Err(TryRecvError::Disconnected) => {
+                self.preview_thumbnail = None;
+                return false;
+            }
+        };
+        self.preview_thumbnail = None;
+        if self.preview_path.as_ref() != Some(&result.path) {
+            return false;
+        }
+        match result.thumbnail {
+            Ok(Some(thumb)) => {
+                reload_image(&thumb, cx);
+                self.preview_image = Some(thumb);
+                self.preview_text = None;
+            }
+            Ok(None) => {
+                self.preview_text = Some("(Preview not available for this file)".into());
+            }
+            Err(err) => {
+                tracing::debug!(path = %result.path.display(), %err, "cannot preview image");
+                self.preview_text = Some(format!("(Cannot preview: {err})"));
+            }
         }
+        cx.notify();
+        false
+    }
 
-        div()
-            .size_full()
-            .flex()
-            .flex_col()
-            .bg(rgb(theme::BG))
-            .relative()
-            .track_focus(&self.focus_handle)
-            .on_key_down(cx.listener(|this, event: &gpui::KeyDownEvent, window, cx| {
-                let key_lc = event.keystroke.key.to_lowercase();
-                let is_f = key_lc == "f" || event.keystroke.key == "KeyF";
-                if is_f && (event.keystroke.modifiers.platform || event.keystroke.modifiers.control)
-                {
-                    this.toggle_search(window, cx);
-                    cx.stop_propagation();
-                } else if key_lc == "escape" && this.search_visible {
-                    this.toggle_search(window, cx);
-                    cx.stop_propagation();
-                }
-            }))
-            .on_mouse_move(
-                cx.listener(|this, event: &gpui::MouseMoveEvent, _window, cx| {
-                    if this.resizing_column.is_some() {
-                        this.update_column_resize(event.position);
-                        cx.notify();
-                    }
-                }),
+    fn selected_entry(&self) -> Option<&FileEntry> {
+        self.selected_index
+            .and_then(|ix| self.filtered_entries.get(ix))
+    }
+
+    fn copy_selection(&mut self, cut: bool, cx: &mut Context<Self>) {
+        if self.in_trash {
+            return;
+        }
+        if let Some(entry) = self.selected_entry().cloned() {
+            let Some(path) = self.resolve_entry(&entry, cx) else {
+                return;
+            };
+            self.clipboard = Some(Clipboard {
+                paths: vec![path],
+                cut,
+            });
+            cx.notify();
+        }
+    }
+
+    fn paste(&mut self, cx: &mut Context<Self>) {
+        // The trash, Recents and smart folders have no directory of their own to paste into.
+        let Some(clip) = self.clipboard.as_ref().filter(|_| self.shows_cwd()) else {
+            return;
+        };
+        let sources = clip.paths.clone();
+        let dest_dir = self.cwd.clone();
+        let (op, verb) = if clip.cut {
+            let on_conflict = ConflictPolicy::Ask;
+            (
+                Operation::Move {
+                    sources,
+                    dest_dir,
+                    on_conflict,
+                },
+                "Moving",
             )
-            .on_mouse_up(
-                gpui::MouseButton::Left,
-                cx.listener(|this, _event, _window, cx| {
-                    if this.resizing_column.is_some() {
-                        this.stop_column_resize();
-                        cx.notify();
-                    }
-                }),
+        } else {
+            // Pasting next to the original keeps both, like duplicating in Finder.
+            let on_conflict = if clip.paths.iter().all(|p| p.parent() == Some(&self.cwd)) {
+                ConflictPolicy::KeepBoth
+            } else {
+                ConflictPolicy::Ask
+            };
+            (
+                Operation::Copy {
+                    sources,
+                    dest_dir,
+                    on_conflict,
+                },
+                "Copying",
             )
-            .child(self.render_header(window, cx))
-            .child(
-                div().flex().flex_row().flex_grow().min_h(px(0.0)).child(
-                    h_resizable("file-explorer", self.resizable.clone())
-                        .child(
-                            resizable_panel()
+        };
+        let label = match clip.paths.as_slice() {
+            [one] => format!("{} {}", verb, path_name(one)),
+            many => format!("{} {} items", verb, many.len()),
+        };
+        if clip.cut {
+            // The originals are gone once moved, so a second paste has nothing to move.
+            self.clipboard = None;
+        }
+        self.run_operation(op, label, cx);
+    }
+
+    fn new_folder(&mut self, cx: &mut Context<Self>) {
+        if !self.shows_cwd() {
+            return;
+        }
+        let mut path = self.cwd.join("untitled folder");
+        let mut n = 2;
+        while std::fs::symlink_metadata(&path).is_ok() {
+            path = self.cwd.join(format!("untitled folder {}", n));
+            n += 1;
+        }
+        self.run_operation(Operation::CreateDir { path }, "Creating folder".into(), cx);
+    }
+
+    /// Start a file operation and poll it until it ends, then reload the listing.
+    fn run_operation(&mut self, op: Operation, label: String, cx: &mut Context<Self>) {
+        if self.job.is_some() {
+            self.job_message = Some("Another operation is still running".into());
+            cx.notify();
+            return;
+        }
+        // Recorded on the job's thread, so the trash listing and journal write stay off the UI.
+        let journal = self.journal.clone();
+        self.job = Some(ops::start_with(op.clone(), move |op, report| {
+            let Some(journal) = journal else {
+                return;
+            };
+            let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+            if let Err(err) = journal.record(op, report) {
+                tracing::warn!(%err, "failed to record operation for undo");
+            }
+        }));
+        self.job_op = Some(op);
+        self.job_label = label;
+        self.job_progress = None;
+        self.job_message = None;
+        self.job_conflict = None;
+        self.job_apply_all = false;
+        self.job_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(STREAM_POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_job(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+        cx.notify();
+    }
+
+    /// Drain available job events. Returns whether the job is still running.
+    fn poll_job(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(job) = self.job.as_ref() else {
+            return false;
+        };
+        let mut pending = true;
+        loop {
+            match job.try_next() {
+                Ok(JobEvent::Progress(p)) => self.job_progress = Some(p),
+                Ok(JobEvent::Conflict(c)) => self.job_conflict = Some(c),
+                Ok(JobEvent::Finished(report)) => {
+                    self.invalidate_sizes(&report);
+                    pending = false;
+                    break;
+                }
+                Ok(JobEvent::Failed(err, report)) => {
+                    self.invalidate_sizes(&report);
+                    self.job_message = Some(err.to_string());
+                    pending = false;
+                    break;
+                }
+                Ok(JobEvent::Cancelled(report)) => {
+                    self.invalidate_sizes(&report);
+                    self.job_message = Some("Cancelled".into());
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if !pending {
+            self.job = None;
+            self.job_op = None;
+            self.job_progress = None;
+            self.job_conflict = None;
+            self.reload(cx);
+        }
+        cx.notify();
+        pending
+    }
+
+    /// Drop cached sizes of everything the job touched; directory mtimes miss changes
+    /// deeper down.
+    fn invalidate_sizes(&self, report: &Report) {
+        for created in &report.created {
+            self.size_cache.invalidate(&created.path);
+            if let Some(source) = &created.source {
+                self.size_cache.invalidate(source);
+            }
+        }
+        for trashed in &report.trashed {
+            self.size_cache.invalidate(&trashed.path);
+        }
+        // A deletion's report does not say how far it got.
+        if let Some(Operation::Delete { paths, .. }) = &self.job_op {
+            for path in paths {
+                self.size_cache.invalidate(path);
+            }
+        }
+    }
+
+    /// Undo (or redo) the most recent journaled operation in the background.
+    fn undo(&mut self, redo: bool, cx: &mut Context<Self>) {
+        let Some(journal) = self.journal.clone() else {
+            return;
+        };
+        if self.job.is_some() || self.journal_task.is_some() {
+            return;
+        }
+        self.journal_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    let mut journal = journal.lock().unwrap_or_else(|e| e.into_inner());
+                    if redo {
+                        journal.redo()
+                    } else {
+                        journal.undo()
+                    }
+                })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                this.journal_task = None;
+                this.size_cache.clear();
+                this.job_message = match result {
+                    Ok(Some(entry)) if redo => Some(format!("Redid {}", entry.label)),
+                    Ok(Some(entry)) => Some(format!("Undid {}", entry.label)),
+                    Ok(None) => None,
+                    Err(err) if redo => Some(format!("Cannot redo: {}", err)),
+                    Err(err) => Some(format!("Cannot undo: {}", err)),
+                };
+                this.reload(cx);
+                cx.notify();
+            });
+        }));
+    }
+
+    fn resolve_conflict(&mut self, action: ConflictAction) {
+        if let (Some(job), Some(_)) = (self.job.as_ref(), self.job_conflict.take()) {
+            job.resolve(Resolution {
+                action,
+                apply_to_all: self.job_apply_all,
+            });
+        }
+    }
+
+    fn cancel_job(&mut self) {
+        if let Some(job) = self.job.as_ref() {
+            job.cancel();
+        }
+    }
+
+    /// Show the trash in place of the current directory.
+    fn open_trash(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.in_trash {
+            return;
+        }
+        self.close_search(window, cx);
+        self.leave_views();
+        self.in_trash = true;
+        self.pending_confirm = None;
+        self.selected_index = None;
+        self.reload(cx);
+    }
+
+    /// Listing `cwd` rather than the trash, Recents or a smart folder.
+    fn shows_cwd(&self) -> bool {
+        !self.in_trash && !self.in_recents && self.smart_folder.is_none()
+    }
+
+    /// Go back to listing `cwd`.
+    fn leave_views(&mut self) {
+        self.leave_trash();
+        self.leave_smart_folder();
+        self.in_recents = false;
+    }
+
+    /// Note a use of `path` in the background; aging may rewrite every record.
+    fn record_access(&self, path: &Path, access: Access, cx: &mut Context<Self>) {
+        let Some(frecency) = self.frecency.clone() else {
+            return;
+        };
+        let path = path.to_path_buf();
+        cx.background_executor()
+            .spawn(async move {
+                if let Err(err) = frecency.record(&path, access) {
+                    tracing::debug!(path = %path.display(), %err, "cannot record access");
+                }
+            })
+            .detach();
+    }
+
+    /// Show the most frecent files and folders in place of the current directory.
+    fn open_recents(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if self.in_recents {
+            return;
+        }
+        self.close_search(window, cx);
+        self.leave_views();
+        self.in_recents = true;
+        self.reload(cx);
+    }
+
+    /// List the most frecent paths, best first.
+    fn load_recents(&mut self, cx: &mut Context<Self>) {
+        self.stream = None;
+        let Some(frecency) = self.frecency.clone() else {
+            self.listing_errors
+                .push("Recents are unavailable".to_string());
+            return;
+        };
+        self.load_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move {
+                    let top = frecency.top(RECENTS_LIMIT, SystemTime::now())?;
+                    let mut errors = Vec::new();
+                    let entries: Vec<FileEntry> = top
+                        .into_iter()
+                        .filter_map(|(path, _)| stat_entry(path_name(&path), &path, &mut errors))
+                        .collect();
+                    Ok::<_, Error>(entries)
+                })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                if !this.in_recents {
+                    return;
+                }
+                match result {
+                    Ok(entries) => {
+                        this.entries = entries;
+                        this.select_revealed(cx);
+                        this.start_sizes(cx);
+                        this.start_thumbnails(cx);
+                    }
+                    Err(err) => {
+                        tracing::warn!(%err, "cannot list recents");
+                        this.listing_errors.push(err.to_string());
+                    }
+                }
+                this.apply_filter();
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Pick the most frecent directory matching the search box's words as the jump target,
+    /// from the scores taken when the search box opened.
+    fn update_jump_target(&mut self) {
+        let keywords: Vec<&str> = self.search_query.split_whitespace().collect();
+        self.jump_target = if keywords.is_empty() {
+            None
+        } else {
+            self.frecency_scores.jump(&keywords, Some(&self.cwd))
+        };
+    }
+
+    /// Go to the jump target, like zoxide's `z`.
+    fn jump(&mut self, window: &mut Window, cx: &mut Context<Self>) {
+        if let Some(target) = self.jump_target.take() {
+            self.change_dir(target, window, cx);
+        }
+    }
+
+    fn leave_trash(&mut self) {
+        self.in_trash = false;
+        self.trash_entries.clear();
+        self.pending_confirm = None;
+        self.selected_index = None;
+    }
+
+    fn load_trash(&mut self, cx: &mut Context<Self>) {
+        self.stream = None;
+        self.load_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async { trash::list() })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                if !this.in_trash {
+                    return;
+                }
+                match result {
+                    Ok(items) => {
+                        this.entries = items.iter().map(trash_row).collect();
+                        sort::sort_entries(&mut this.entries, &this.sort_options());
+                        this.trash_entries = items;
+                    }
+                    Err(err) => {
+                        tracing::warn!(%err, "cannot list the trash");
+                        this.listing_errors.push(err.to_string());
+                    }
+                }
+                this.apply_filter();
+                cx.notify();
+            });
+        }));
+    }
+
+    fn trash_selection(&mut self, cx: &mut Context<Self>) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        let Some(path) = self.resolve_entry(&entry, cx) else {
+            return;
+        };
+        let label = format!("Moving {} to Trash", path_name(&path));
+        self.run_operation(Operation::Trash { paths: vec![path] }, label, cx);
+    }
+
+    /// Ask before deleting the selection permanently, or purging it when in the trash.
+    fn request_delete(&mut self, cx: &mut Context<Self>) {
+        let Some(entry) = self.selected_entry().cloned() else {
+            return;
+        };
+        self.pending_confirm = Some(if self.in_trash {
+            PendingConfirm::Purge(vec![entry.path.clone()])
+        } else {
+            let Some(path) = self.resolve_entry(&entry, cx) else {
+                return;
+            };
+            PendingConfirm::Delete(vec![path])
+        });
+        cx.notify();
+    }
+
+    fn confirm_pending(&mut self, cx: &mut Context<Self>) {
+        let Some(pending) = self.pending_confirm.take() else {
+            return;
+        };
+        match pending {
+            PendingConfirm::Delete(paths) => {
+                let label = match paths.as_slice() {
+                    [one] => format!("Deleting {}", path_name(one)),
+                    many => format!("Deleting {} items", many.len()),
+                };
+                let op = Operation::Delete {
+                    paths,
+                    confirmed: true,
+                };
+                self.run_operation(op, label, cx);
+            }
+            PendingConfirm::Purge(ids) => self.trash_action(
+                move || trash::purge(&ids, true).map(|_| "Deleted permanently".to_string()),
+                cx,
+            ),
+            PendingConfirm::EmptyTrash => self.trash_action(
+                || trash::empty(true).map(|_| "Emptied the trash".to_string()),
+                cx,
+            ),
+        }
+    }
+
+    /// Restore the selected trash item, next to any item now occupying its old place.
+    fn put_back(&mut self, cx: &mut Context<Self>) {
+        let Some(id) = self.selected_entry().map(|e| e.path.clone()) else {
+            return;
+        };
+        self.trash_action(
+            move || {
+                let mut report = trash::restore(&[id], ConflictPolicy::KeepBoth)?;
+                if let Some((_, err)) = report.failed.pop() {
+                    return Err(err);
+                }
+                Ok(match report.restored.as_slice() {
+                    [one] => format!("Put back {}", path_name(one)),
+                    many => format!("Put back {} items", many.len()),
+                })
+            },
+            cx,
+        );
+    }
+
+    /// Run a restore or purge in the background, then show its outcome and refresh.
+    fn trash_action(
+        &mut self,
+        action: impl FnOnce() -> crate::core::errors::Result<String> + Send + 'static,
+        cx: &mut Context<Self>,
+    ) {
+        if self.trash_task.is_some() {
+            return;
+        }
+        self.trash_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move { action() })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                this.trash_task = None;
+                this.job_message = Some(match result {
+                    Ok(message) => message,
+                    Err(err) => err.to_string(),
+                });
+                this.reload(cx);
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Show the current results of the saved search called `name` in place of the
+    /// current directory.
+    fn open_smart_folder(&mut self, name: &str, window: &mut Window, cx: &mut Context<Self>) {
+        if self.smart_folder.as_ref().is_some_and(|s| s.name == name) {
+            return;
+        }
+        let Some(search) = self
+            .saved_searches
+            .as_ref()
+            .and_then(|saved| saved.get(name))
+            .cloned()
+        else {
+            return;
+        };
+        self.close_search(window, cx);
+        self.leave_views();
+        self.smart_folder = Some(search);
+        self.reload(cx);
+    }
+
+    fn leave_smart_folder(&mut self) {
+        self.smart_folder = None;
+        self.smart_cancel.cancel();
+        self.live_index = None;
+        self.pending_confirm = None;
+        self.selected_index = None;
+    }
+
+    /// Run the smart folder's search in the background and show its results, keeping the
+    /// selection. A run still in flight is abandoned.
+    fn run_smart_folder(&mut self, search: SavedSearch, cx: &mut Context<Self>) {
+        self.smart_cancel.cancel();
+        let cancel = CancelToken::new();
+        self.smart_cancel = cancel.clone();
+        self.load_task = Some(cx.spawn(async move |this, cx| {
+            let result = cx
+                .background_executor()
+                .spawn(async move { run_saved_search(&search, &cancel) })
+                .await;
+            let _ = this.update(cx, |this, cx| {
+                if this.smart_folder.is_none() || this.smart_cancel.is_cancelled() {
+                    return;
+                }
+                match result {
+                    Ok(mut entries) => {
+                        let selected = this.selected_entry().and_then(|e| e.fs_path().ok());
+                        sort::sort_entries(&mut entries, &this.sort_options());
+                        this.entries = entries;
+                        this.listing_errors.clear();
+                        this.apply_filter();
+                        this.selected_index = selected.and_then(|path| {
+                            this.filtered_entries
+                                .iter()
+                                .position(|e| e.fs_path().is_ok_and(|p| p == path))
+                        });
+                        this.select_revealed(cx);
+                        this.start_sizes(cx);
+                        this.start_thumbnails(cx);
+                    }
+                    Err(Error::Cancelled) => return,
+                    Err(err) => {
+                        tracing::warn!(%err, "cannot run saved search");
+                        this.listing_errors.push(err.to_string());
+                    }
+                }
+                cx.notify();
+            });
+        }));
+    }
+
+    /// Keep a smart folder current: walked folders watch their root, indexed ones keep
+    /// the index updated and wait for its commits.
+    fn start_smart_live(&mut self, search: &SavedSearch, cx: &mut Context<Self>) {
+        match search.source {
+            ResultSource::Walk => match watch::watch(WatchParams {
+                path: search.root.clone(),
+                recursive: true,
+                debounce: Duration::from_secs(1),
+                ..Default::default()
+            }) {
+                Ok(watcher) => self.watcher = Some(watcher),
+                Err(err) => {
+                    tracing::warn!(root = %search.root.display(), %err, "live updates unavailable");
+                    return;
+                }
+            },
+            ResultSource::Index => match IndexUpdater::shared() {
+                Ok(updater) => {
+                    let index = Arc::clone(updater.index());
+                    self.live_index = Some(LiveIndex {
+                        last_update: index.last_update().ok().flatten(),
+                        _updater: updater,
+                        index,
+                    });
+                }
+                Err(err) => {
+                    tracing::warn!(%err, "live updates unavailable");
+                    return;
+                }
+            },
+        }
+        self.watch_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(SMART_POLL_INTERVAL).await;
+            let live = this
+                .update(cx, |this, cx| this.poll_smart_live(cx))
+                .unwrap_or(false);
+            if !live {
+                break;
+            }
+        }));
+    }
+
+    /// Run the smart folder again if what it searches has changed. Returns whether it is
+    /// still kept current.
+    fn poll_smart_live(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(search) = self.smart_folder.clone() else {
+            return false;
+        };
+        let mut changed = false;
+        if let Some(watcher) = self.watcher.as_ref() {
+            loop {
+                match watcher.try_next() {
+                    Ok(WatchEvent::Changes(_) | WatchEvent::Rescan) => changed = true,
+                    Ok(WatchEvent::Error(err)) => {
+                        tracing::debug!(root = %search.root.display(), %err, "watch error");
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        self.watcher = None;
+                        return false;
+                    }
+                }
+            }
+        } else if let Some(live) = self.live_index.as_mut() {
+            let last_update = live.index.last_update().ok().flatten();
+            if last_update != live.last_update {
+                live.last_update = last_update;
+                changed = true;
+            }
+        } else {
+            return false;
+        }
+        if changed {
+            self.run_smart_folder(search, cx);
+        }
+        true
+    }
+
+    /// Save `search` as a smart folder, replacing the one with the same name.
+    pub fn save_search(&mut self, search: SavedSearch, cx: &mut Context<Self>) {
+        let Some(saved) = self.saved_searches.as_mut() else {
+            self.job_message = Some("Saved searches are unavailable".into());
+            cx.notify();
+            return;
+        };
+        let name = search.name.clone();
+        self.job_message = Some(match saved.save(search) {
+            Ok(()) => format!("Saved search {name}"),
+            Err(err) => err.to_string(),
+        });
+        cx.notify();
+    }
+
+    fn remove_saved_search(&mut self, name: &str, cx: &mut Context<Self>) {
+        let Some(saved) = self.saved_searches.as_mut() else {
+            return;
+        };
+        if let Err(err) = saved.remove(name) {
+            self.job_message = Some(err.to_string());
+        } else if self.smart_folder.as_ref().is_some_and(|s| s.name == name) {
+            self.leave_smart_folder();
+            self.reload(cx);
+        }
+        cx.notify();
+    }
+
+    /// Switch whether the open smart folder is kept current, and remember it.
+    fn toggle_smart_live(&mut self, cx: &mut Context<Self>) {
+        let (Some(saved), Some(search)) =
+            (self.saved_searches.as_mut(), self.smart_folder.as_mut())
+        else {
+            return;
+        };
+        search.live = !search.live;
+        if let Err(err) = saved.save(search.clone()) {
+            self.job_message = Some(err.to_string());
+        }
+        self.reload(cx);
+        cx.notify();
+    }
+
+    /// Ask where to, then write every saved search to a JSON file.
+    fn export_saved_searches(&mut self, cx: &mut Context<Self>) {
+        let rx = cx.prompt_for_new_path(&self.cwd, Some("saved-searches.json"));
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(path))) = rx.await else {
+                return;
+            };
+            let _ = this.update(cx, |this, cx| {
+                let Some(saved) = this.saved_searches.as_ref() else {
+                    return;
+                };
+                this.job_message = Some(match saved.export(&path) {
+                    Ok(()) => format!("Exported {} saved searches", saved.list().len()),
+                    Err(err) => err.to_string(),
+                });
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    /// Ask for a file written by an export and add its saved searches.
+    fn import_saved_searches(&mut self, cx: &mut Context<Self>) {
+        let rx = cx.prompt_for_paths(gpui::PathPromptOptions {
+            files: true,
+            directories: false,
+            multiple: false,
+            prompt: None,
+        });
+        cx.spawn(async move |this, cx| {
+            let Ok(Ok(Some(paths))) = rx.await else {
+                return;
+            };
+            let Some(path) = paths.into_iter().next() else {
+                return;
+            };
+            let _ = this.update(cx, |this, cx| {
+                let Some(saved) = this.saved_searches.as_mut() else {
+                    return;
+                };
+                this.job_message = Some(match saved.import(&path) {
+                    Ok(count) => format!("Imported {count} saved searches"),
+                    Err(err) => err.to_string(),
+                });
+                cx.notify();
+            });
+        })
+        .detach();
+    }
+
+    fn shortcuts(&self) -> Vec<(String, PathBuf)> {
+        let mut v = Vec::new();
+        let home = std::env::var_os("HOME");
+        #[cfg(target_os = "windows")]
+        let home = home.or_else(|| std::env::var_os("USERPROFILE"));
+        if let Some(h) = home.map(PathBuf::from) {
+            v.push(("Home".into(), h.clone()));
+            for (label, sub) in [
+                ("Desktop", "Desktop"),
+                ("Downloads", "Downloads"),
+                ("Documents", "Documents"),
+                ("Pictures", "Pictures"),
+            ] {
+                let path = h.join(sub);
+                if path.exists() {
+                    v.push((label.into(), path));
+                }
+            }
+        }
+        v
+    }
+}
+
+impl Render for ExplorerPage {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.ensure_loaded(cx);
+        if !self.focus_requested {
+            self.focus_requested = true;
+            cx.focus_self(window);
+        }
+
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .bg(rgb(theme::BG))
+            .relative()
+            .track_focus(&self.focus_handle)
+            .on_key_down(cx.listener(|this, event: &gpui::KeyDownEvent, window, cx| {
+                let key_lc = event.keystroke.key.to_lowercase();
+                let is_f = key_lc == "f" || event.keystroke.key == "KeyF";
+                let is_j = key_lc == "j" || event.keystroke.key == "KeyJ";
+                if is_f && (event.keystroke.modifiers.platform || event.keystroke.modifiers.control)
+                {
+                    this.toggle_search(window, cx);
+                    cx.stop_propagation();
+                } else if is_j
+                    && (event.keystroke.modifiers.platform || event.keystroke.modifiers.control)
+                {
+                    // Jump to the most frecent directory matching the search box's words,
+                    // opening the box first to type them.
+                    if this.search_visible {
+                        this.jump(window, cx);
+                    } else {
+                        this.open_search(window, cx);
+                    }
+                    cx.stop_propagation();
+                } else if key_lc == "escape" && this.search_visible {
+                    this.toggle_search(window, cx);
+                    cx.stop_propagation();
+                } else if key_lc == "escape" && this.pending_confirm.is_some() {
+                    this.pending_confirm = None;
+                    cx.notify();
+                    cx.stop_propagation();
+                } else if key_lc == "delete" && !this.search_visible {
+                    // Delete moves to the trash; Shift+Delete (or Delete in the trash) asks
+                    // to delete permanently.
+                    if event.keystroke.modifiers.shift || this.in_trash {
+                        this.request_delete(cx);
+                    } else {
+                        this.trash_selection(cx);
+                    }
+                    cx.stop_propagation();
+                } else if (event.keystroke.modifiers.platform || event.keystroke.modifiers.control)
+                    && !this.search_visible
+                {
+                    match key_lc.as_str() {
+                        "c" => this.copy_selection(false, cx),
+                        "x" => this.copy_selection(true, cx),
+                        "v" => this.paste(cx),
+                        "n" if event.keystroke.modifiers.shift => this.new_folder(cx),
+                        "z" => this.undo(event.keystroke.modifiers.shift, cx),
+                        "y" if event.keystroke.modifiers.control => this.undo(true, cx),
+                        "backspace" if this.in_trash => this.put_back(cx),
+                        "backspace" => this.trash_selection(cx),
+                        _ => return,
+                    }
+                    cx.stop_propagation();
+                }
+            }))
+            .on_mouse_move(
+                cx.listener(|this, event: &gpui::MouseMoveEvent, _window, cx| {
+                    if this.resizing_column.is_some() {
+                        this.update_column_resize(event.position);
+                        cx.notify();
+                    }
+                }),
+            )
+            .on_mouse_up(
+                gpui::MouseButton::Left,
+                cx.listener(|this, _event, _window, cx| {
+                    if this.resizing_column.is_some() {
+                        this.stop_column_resize();
+                        cx.notify();
+                    }
+                }),
+            )
+            .child(self.render_header(window, cx))
+            .child(
+                div().flex().flex_row().flex_grow().min_h(px(0.0)).child(
+                    h_resizable("file-explorer", self.resizable.clone())
+                        .child(
+                            resizable_panel()
                                 .size(px(180.0))
                                 .size_range(px(180.0)..px(360.0))
                                 .child(
@@ -538,7 +2045,7 @@ impl Render for ExplorerPage {
                                         .overflow_hidden()
                                         .border_l_1()
                                         .border_color(rgb(theme::BORDER))
-                                        .child(self.render_preview()),
+                                        .child(self.render_preview(cx)),
                                 ),
                         )
                         .into_any_element(),
@@ -571,34 +2078,8 @@ impl ExplorerPage {
 
         let start_idx = if is_truncated { parts.len() - 5 } else { 0 };
 
-        for (display_i, p) in display_parts.iter().enumerate() {
+        for (display_i, (text, path_here)) in display_parts.into_iter().enumerate() {
             let actual_i = start_idx + display_i;
-            let text = if p.is_empty() {
-                String::from("/")
-            } else {
-                p.clone()
-            };
-
-            let mut path_here = String::new();
-            for (j, part) in parts.iter().enumerate() {
-                if j == 0 {
-                    path_here = if part.is_empty() {
-                        "/".to_string()
-                    } else {
-                        part.clone()
-                    };
-                } else {
-                    path_here.push(std::path::MAIN_SEPARATOR);
-                    path_here.push_str(part);
-                }
-                if j >= actual_i {
-                    break;
-                }
-            }
-            if path_here.is_empty() {
-                path_here = self.cwd.clone();
-            }
-
             bc = bc.item(
                 BreadcrumbItem::new(("bc", actual_i), text).on_click(cx.listener(
                     move |this, _, window, cx| this.change_dir(path_here.clone(), window, cx),
@@ -606,7 +2087,7 @@ impl ExplorerPage {
             );
         }
 
-        let can_go_back = self.history_index > 0;
+        let can_go_back = !self.shows_cwd() || self.history_index > 0;
         let can_go_forward = self.history_index + 1 < self.history.len();
 
         div()
@@ -659,13 +2140,27 @@ impl ExplorerPage {
                             .mx(px(4.0)),
                     ),
             )
-            .child(
-                div()
-                    .flex_1()
-                    .overflow_hidden()
-                    .min_w(px(0.0))
-                    .child(div().flex().items_center().child(bc)),
-            )
+            .child(div().flex_1().overflow_hidden().min_w(px(0.0)).child(
+                div().flex().items_center().map(|this| {
+                    let title = if self.in_trash {
+                        Some("Trash".to_string())
+                    } else if self.in_recents {
+                        Some("Recents".to_string())
+                    } else {
+                        self.smart_folder.as_ref().map(|s| s.name.clone())
+                    };
+                    match title {
+                        Some(title) => this.child(
+                            div()
+                                .text_sm()
+                                .font_weight(gpui::FontWeight::SEMIBOLD)
+                                .text_color(rgb(theme::FG))
+                                .child(title),
+                        ),
+                        None => this.child(bc),
+                    }
+                }),
+            ))
             .child(
                 div()
                     .flex()
@@ -679,6 +2174,33 @@ impl ExplorerPage {
                             .whitespace_nowrap()
                             .child(format!("{} items", self.filtered_entries.len())),
                     )
+                    .when(!self.listing_errors.is_empty(), |this| {
+                        this.child(self.render_error_badge())
+                    })
+                    .when(self.job.is_some(), |this| {
+                        this.child(self.render_job_status(cx))
+                    })
+                    .when_some(self.pending_confirm.as_ref(), |this, pending| {
+                        this.child(self.render_confirm_prompt(pending, cx))
+                    })
+                    .when_some(
+                        self.job_message.clone().filter(|_| self.job.is_none()),
+                        |this, message| {
+                            this.child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(theme::FG_SECONDARY))
+                                    .whitespace_nowrap()
+                                    .child(truncate_middle(&message, 48)),
+                            )
+                        },
+                    )
+                    .when(self.in_trash, |this| {
+                        this.child(self.render_trash_actions(cx))
+                    })
+                    .when_some(self.smart_folder.as_ref(), |this, search| {
+                        this.child(self.render_smart_folder_actions(search, cx))
+                    })
                     .child(self.render_view_mode_toggle(cx))
                     .child(
                         gpui_component::ListItem::new("search-toggle")
@@ -771,10 +2293,29 @@ impl ExplorerPage {
                     .flex_col()
                     .gap_1()
                     .px(px(8.0))
-                    .child(self.sidebar_item(IconName::Folder, "Home", true, cx))
+                    .child(self.sidebar_item(IconName::Folder, "Home", self.shows_cwd(), cx))
                     .child(self.sidebar_item(IconName::Star, "Favorites", false, cx))
-                    .child(self.sidebar_item(IconName::File, "Recent", false, cx))
-                    .child(self.sidebar_item(IconName::Folder, "Trash", false, cx)),
+                    .child(
+                        div()
+                            .id("sidebar-recents")
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_recents(window, cx)),
+                            )
+                            .child(self.sidebar_item(
+                                IconName::File,
+                                "Recent",
+                                self.in_recents,
+                                cx,
+                            )),
+                    )
+                    .child(
+                        div()
+                            .id("sidebar-trash")
+                            .on_click(
+                                cx.listener(|this, _, window, cx| this.open_trash(window, cx)),
+                            )
+                            .child(self.sidebar_item(IconName::Folder, "Trash", self.in_trash, cx)),
+                    ),
             )
             .child(
                 div()
@@ -792,13 +2333,129 @@ impl ExplorerPage {
                     )
                     .child(self.render_shortcuts(cx)),
             )
+            .when(self.saved_searches.is_some(), |this| {
+                this.child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .mt(px(16.0))
+                        .child(
+                            div()
+                                .flex()
+                                .items_center()
+                                .px(px(12.0))
+                                .py(px(8.0))
+                                .child(
+                                    div()
+                                        .flex_1()
+                                        .text_xs()
+                                        .font_weight(gpui::FontWeight::SEMIBOLD)
+                                        .text_color(rgb(theme::FG_SECONDARY))
+                                        .child("Smart Folders"),
+                                )
+                                .child(self.sidebar_action(
+                                    "smart-folders-import",
+                                    "Import",
+                                    Self::import_saved_searches,
+                                    cx,
+                                ))
+                                .child(self.sidebar_action(
+                                    "smart-folders-export",
+                                    "Export",
+                                    Self::export_saved_searches,
+                                    cx,
+                                )),
+                        )
+                        .child(self.render_smart_folders(cx)),
+                )
+            })
+    }
+
+    /// Small text button in a sidebar section header.
+    fn sidebar_action(
+        &self,
+        id: &'static str,
+        label: &'static str,
+        action: fn(&mut Self, &mut Context<Self>),
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        div()
+            .id(id)
+            .px(px(4.0))
+            .rounded(px(4.0))
+            .cursor_pointer()
+            .hover(|this| this.bg(rgb(theme::BG_HOVER)))
+            .text_xs()
+            .text_color(rgb(theme::FG_SECONDARY))
+            .on_click(cx.listener(move |this, _, _, cx| action(this, cx)))
+            .child(label)
+    }
+
+    fn render_smart_folders(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut folders_el = div().flex().flex_col().gap_1().px(px(8.0));
+        let Some(saved) = self.saved_searches.as_ref() else {
+            return folders_el;
+        };
+
+        for (i, search) in saved.list().iter().enumerate() {
+            let active = self
+                .smart_folder
+                .as_ref()
+                .is_some_and(|s| s.name == search.name);
+            let open_name = search.name.clone();
+            let remove_name = search.name.clone();
+
+            folders_el = folders_el.child(
+                gpui_component::ListItem::new(("smart-folder", i))
+                    .when(active, |this| this.bg(rgb(theme::BG_HOVER)))
+                    .on_click(cx.listener(move |this, _, window, cx| {
+                        this.open_smart_folder(&open_name, window, cx)
+                    }))
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .child(
+                                Icon::new(IconName::Search)
+                                    .size_4()
+                                    .text_color(rgb(theme::GRAY_600)),
+                            )
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .overflow_hidden()
+                                    .text_sm()
+                                    .text_color(rgb(theme::FG))
+                                    .whitespace_nowrap()
+                                    .child(search.name.clone()),
+                            )
+                            .child(
+                                div()
+                                    .id(("smart-folder-remove", i))
+                                    .px(px(4.0))
+                                    .rounded(px(4.0))
+                                    .hover(|this| this.bg(rgb(theme::BORDER)))
+                                    .text_xs()
+                                    .text_color(rgb(theme::FG_SECONDARY))
+                                    .on_click(cx.listener(move |this, _, _, cx| {
+                                        cx.stop_propagation();
+                                        this.remove_saved_search(&remove_name, cx);
+                                    }))
+                                    .child("×"),
+                            ),
+                    ),
+            );
+        }
+
+        folders_el
     }
 
     fn sidebar_item(
         &self,
         icon: IconName,
         label: &str,
-        _active: bool,
+        active: bool,
         _cx: &mut Context<Self>,
     ) -> impl IntoElement {
         let label = label.to_string();
@@ -811,6 +2468,7 @@ impl ExplorerPage {
             .py(px(8.0))
             .rounded(px(6.0))
             .cursor_pointer()
+            .when(active, |this| this.bg(rgb(theme::BG_HOVER)))
             .hover(|this| this.bg(rgb(theme::BG_HOVER)))
             .child(Icon::new(icon).size_4().text_color(rgb(theme::GRAY_600)))
             .child(div().text_sm().text_color(rgb(theme::FG)).child(label))
@@ -916,7 +2574,7 @@ impl ExplorerPage {
 
     fn render_grid_item(
         &mut self,
-        item: FileEntryDto,
+        item: FileEntry,
         ix: usize,
         selected: bool,
         _window: &mut Window,
@@ -924,19 +2582,23 @@ impl ExplorerPage {
     ) -> AnyElement {
         use crate::ui::components::file_list::{format_date, get_file_type, human_bytes};
 
-        let icon_name = match item.kind.as_str() {
-            "dir" => IconName::Folder,
+        let icon_name = match item.kind {
+            FileKind::Dir => IconName::Folder,
             _ => IconName::File,
         };
 
         let name = truncate_middle(&item.name, 28);
-        let file_type = get_file_type(&item.name, &item.kind);
-        let size_text = match item.kind.as_str() {
-            "file" => human_bytes(item.size),
-            "dir" => file_type.clone(),
+        let file_type = get_file_type(&item);
+        let size_text = match item.kind {
+            FileKind::File => human_bytes(item.size),
             _ => file_type.clone(),
         };
         let modified_text = format_date(&item.modified);
+        let thumbnail = item
+            .fs_path()
+            .ok()
+            .and_then(|p| self.thumbnails.get(&p).cloned())
+            .flatten();
         let activation_item = item.clone();
         let preview_item = item.clone();
 
@@ -971,19 +2633,35 @@ impl ExplorerPage {
                 cx.listener(move |this, event: &gpui::MouseDownEvent, window, cx| {
                     this.record_click(ix, event.click_count);
                     this.selected_index = Some(ix);
-                    if preview_item.kind == "file" {
-                        this.open_preview(preview_item.path.clone());
+                    if preview_item.is_file() {
+                        if let Some(path) = this.resolve_entry(&preview_item, cx) {
+                            this.preview_entry(path, cx);
+                        }
                     }
                     if event.click_count >= 2 {
                         this.activate_entry(activation_item.clone(), window, cx);
                     }
                 }),
             )
-            .child(
-                Icon::new(icon_name)
+            .child(match thumbnail {
+                Some(thumb) => div()
+                    .w_full()
+                    .h(px(96.0))
+                    .flex()
+                    .items_center()
+                    .justify_center()
+                    .child(
+                        gpui::img(thumb)
+                            .max_w_full()
+                            .max_h_full()
+                            .object_fit(gpui::ObjectFit::Contain),
+                    )
+                    .into_any_element(),
+                None => Icon::new(icon_name)
                     .size_6()
-                    .text_color(rgb(theme::GRAY_600)),
-            )
+                    .text_color(rgb(theme::GRAY_600))
+                    .into_any_element(),
+            })
             .child(
                 div()
                     .text_sm()
@@ -1024,6 +2702,7 @@ impl ExplorerPage {
         if current_text != self.search_query {
             self.search_query = current_text;
             self.apply_filter();
+            self.update_jump_target();
         }
 
         let is_empty = self.search_query.is_empty();
@@ -1086,11 +2765,32 @@ impl ExplorerPage {
                                     div()
                                         .text_xs()
                                         .text_color(rgb(theme::FG_SECONDARY))
-                                        .when(!is_empty, |this| {
+                                        .when_some(self.filter_error.clone(), |this, err| {
+                                            this.child(err)
+                                        })
+                                        .when(!is_empty && self.filter_error.is_none(), |this| {
                                             this.child(format!("{} matches", match_count))
                                         }),
                                 ),
-                            ),
+                            )
+                            .when_some(self.jump_target.clone(), |this, target| {
+                                this.child(
+                                    div()
+                                        .id("search-jump")
+                                        .cursor_pointer()
+                                        .text_xs()
+                                        .text_color(rgb(theme::ACCENT))
+                                        .on_click(
+                                            cx.listener(|view, _, window, cx| {
+                                                view.jump(window, cx)
+                                            }),
+                                        )
+                                        .child(format!(
+                                            "Jump to {}  (Ctrl+J)",
+                                            truncate_middle(&target.to_string_lossy(), 40)
+                                        )),
+                                )
+                            }),
                     )
                     .child(
                         gpui_component::ListItem::new("close-search")
@@ -1252,15 +2952,15 @@ impl ExplorerPage {
 
     fn render_file_row(
         &self,
-        item: &FileEntryDto,
+        item: &FileEntry,
         ix: usize,
         cx: &mut Context<Self>,
     ) -> impl IntoElement {
         use crate::ui::components::file_list::{format_date, get_file_type, human_bytes};
         use gpui_component::ListItem;
 
-        let icon_name = match item.kind.as_str() {
-            "dir" => IconName::Folder,
+        let icon_name = match item.kind {
+            FileKind::Dir => IconName::Folder,
             _ => IconName::File,
         };
 
@@ -1270,7 +2970,7 @@ impl ExplorerPage {
             theme::GRAY_50
         };
 
-        let file_type = get_file_type(&item.name, &item.kind);
+        let file_type = get_file_type(item);
 
         let max_chars = (self.col_name_width / 8.0) as usize;
         let display_name = truncate_middle(&item.name, max_chars.max(20));
@@ -1290,8 +2990,10 @@ impl ExplorerPage {
                         if mouse.up.button == gpui::MouseButton::Left {
                             this.record_click(ix, mouse.up.click_count);
                             this.selected_index = Some(ix);
-                            if item_for_preview.kind == "file" {
-                                this.open_preview(item_for_preview.path.clone());
+                            if item_for_preview.is_file() {
+                                if let Some(path) = this.resolve_entry(&item_for_preview, cx) {
+                                    this.preview_entry(path, cx);
+                                }
                             }
                             if mouse.up.click_count >= 2 {
                                 this.activate_entry(item_for_activate.clone(), window, cx);
@@ -1345,170 +3047,1038 @@ impl ExplorerPage {
                     )
                     .child(
                         div()
-                            .w(px(self.col_size_width))
-                            .flex_shrink_0()
-                            .text_sm()
-                            .text_color(rgb(theme::FG_SECONDARY))
-                            .child(match item.kind.as_str() {
-                                "file" => human_bytes(item.size),
-                                "dir" => "-".to_string(),
-                                other => other.to_string(),
+                            .w(px(self.col_size_width))
+                            .flex_shrink_0()
+                            .text_sm()
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .child(match item.kind {
+                                FileKind::File => human_bytes(item.size),
+                                FileKind::Dir => item
+                                    .fs_path()
+                                    .ok()
+                                    .and_then(|p| self.dir_sizes.get(&p))
+                                    .map_or_else(|| "-".to_string(), |s| human_bytes(s.apparent)),
+                                other => other.as_str().to_string(),
+                            }),
+                    )
+                    .child(
+                        div()
+                            .w(px(self.col_modified_width))
+                            .flex_shrink_0()
+                            .text_sm()
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .overflow_hidden()
+                            .text_ellipsis()
+                            .whitespace_nowrap()
+                            .child(format_date(&item.modified)),
+                    )
+                    .child(
+                        div()
+                            .w(px(self.col_action_width))
+                            .flex_shrink_0()
+                            .flex()
+                            .justify_end()
+                            .child(
+                                Icon::new(IconName::File)
+                                    .size_4()
+                                    .text_color(rgb(theme::MUTED)),
+                            ),
+                    ),
+            )
+    }
+
+    fn render_column_header(
+        &self,
+        label: &str,
+        key: SortKey,
+        key_idx: usize,
+        flex: bool,
+        cx: &mut Context<Self>,
+    ) -> gpui::Div {
+        let is_active = self.sort_key == key;
+        let label_str = label.to_string();
+        let sort_icon = if is_active {
+            Some(if self.sort_asc { "↑" } else { "↓" })
+        } else {
+            None
+        };
+
+        let mut wrapper = div();
+
+        if flex {
+            wrapper = wrapper.flex_1();
+        }
+
+        wrapper.child(
+            gpui_component::ListItem::new(("sort-header", key_idx))
+                .on_click(cx.listener(move |this, _, _, cx| {
+                    this.set_sort_key(key, cx);
+                }))
+                .child(
+                    div()
+                        .flex()
+                        .items_center()
+                        .gap_1()
+                        .child(
+                            div()
+                                .text_xs()
+                                .font_weight(gpui::FontWeight::SEMIBOLD)
+                                .text_color(if is_active {
+                                    rgb(theme::FG)
+                                } else {
+                                    rgb(theme::FG_SECONDARY)
+                                })
+                                .child(label_str),
+                        )
+                        .when(is_active, |this| {
+                            this.child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(theme::FG))
+                                    .child(sort_icon.unwrap_or("")),
+                            )
+                        }),
+                ),
+        )
+    }
+
+    fn render_preview(&mut self, cx: &mut Context<Self>) -> impl IntoElement {
+        let title = self
+            .preview_path
+            .as_ref()
+            .map(|p| path_name(p))
+            .unwrap_or_else(|| "Preview".to_string());
+
+        let show_source = self.show_markdown_source;
+        let markdown = self.preview_markdown.as_mut().filter(|_| !show_source);
+        let body = match (&self.preview_code, markdown) {
+            _ if self.preview_image.is_some() => div()
+                .size_full()
+                .flex()
+                .items_center()
+                .justify_center()
+                .p(px(16.0))
+                .children(self.preview_image.clone().map(|thumb| {
+                    gpui::img(thumb)
+                        .max_w_full()
+                        .max_h_full()
+                        .object_fit(gpui::ObjectFit::Contain)
+                }))
+                .into_any_element(),
+            _ if self.preview_hex.is_some() => self.render_hex(cx).into_any_element(),
+            (_, Some(document)) => div()
+                .id("preview-markdown")
+                .size_full()
+                .overflow_y_scroll()
+                .px(px(16.0))
+                .py(px(16.0))
+                .child(
+                    div()
+                        .flex()
+                        .flex_col()
+                        .gap_3()
+                        .children(render_markdown_blocks(&mut document.blocks)),
+                )
+                .into_any_element(),
+            (Some(code), None) => div()
+                .size_full()
+                .bg(rgb(code.background()))
+                .py(px(8.0))
+                .child(
+                    uniform_list(
+                        "preview-code",
+                        code.line_count(),
+                        cx.processor(|this, range: Range<usize>, _window, _cx| {
+                            this.render_code_lines(range)
+                        }),
+                    )
+                    .size_full(),
+                )
+                .into_any_element(),
+            (None, None) => div()
+                .size_full()
+                .px(px(16.0))
+                .py(px(16.0))
+                .child(
+                    div()
+                        .text_sm()
+                        .text_color(rgb(theme::FG_SECONDARY))
+                        .line_height(px(20.0))
+                        .child(
+                            self.preview_text
+                                .clone()
+                                .unwrap_or_else(|| "Select a file to see a preview".into()),
+                        ),
+                )
+                .into_any_element(),
+        };
+
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .bg(rgb(theme::BG))
+            .child(
+                div()
+                    .px(px(16.0))
+                    .py(px(12.0))
+                    .border_b_1()
+                    .border_color(rgb(theme::BORDER))
+                    .child(
+                        div()
+                            .flex()
+                            .items_center()
+                            .gap_2()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .text_sm()
+                                    .font_weight(gpui::FontWeight::SEMIBOLD)
+                                    .text_color(rgb(theme::FG))
+                                    .child(title),
+                            )
+                            .when_some(
+                                self.preview_code.as_ref().and_then(CodePreview::language),
+                                |this, language| {
+                                    this.child(
+                                        div()
+                                            .text_xs()
+                                            .text_color(rgb(theme::FG_SECONDARY))
+                                            .child(language),
+                                    )
+                                },
+                            )
+                            .when(self.preview_truncated, |this| {
+                                this.child(
+                                    div().text_xs().text_color(rgb(theme::FG_SECONDARY)).child(
+                                        format!(
+                                            "First {} shown",
+                                            crate::ui::components::file_list::human_bytes(
+                                                MAX_TEXT_SIZE
+                                            )
+                                        ),
+                                    ),
+                                )
+                            })
+                            .when(self.preview_markdown.is_some(), |this| {
+                                this.child(self.render_markdown_toggle(cx))
+                            }),
+                    )
+                    .children(self.render_entry_details()),
+            )
+            .child(div().flex_1().min_h(px(0.0)).overflow_hidden().child(body))
+    }
+
+    /// A binary file's type, if known, above a dump of its bytes.
+    fn render_hex(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let (summary, rows) = match &self.preview_hex {
+            Some(hex) => {
+                let size = crate::ui::components::file_list::human_bytes(hex.len());
+                let summary = match hex.summary() {
+                    Some(kind) => format!("{kind} · {size}"),
+                    None => format!("Binary data · {size}"),
+                };
+                let rows = usize::try_from(hex.row_count()).unwrap_or(usize::MAX);
+                (summary, rows)
+            }
+            None => (String::new(), 0),
+        };
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .child(
+                div()
+                    .px(px(12.0))
+                    .py(px(8.0))
+                    .border_b_1()
+                    .border_color(rgb(theme::BORDER))
+                    .text_xs()
+                    .text_color(rgb(theme::FG_SECONDARY))
+                    .child(summary),
+            )
+            .child(
+                div().flex_1().min_h(px(0.0)).py(px(8.0)).child(
+                    uniform_list(
+                        "preview-hex",
+                        rows,
+                        cx.processor(|this, range: Range<usize>, _window, _cx| {
+                            this.render_hex_rows(range)
+                        }),
+                    )
+                    .size_full(),
+                ),
+            )
+    }
+
+    /// Offset, hex and ASCII columns for the dump rows in `range`, read as they scroll
+    /// into view.
+    fn render_hex_rows(&mut self, range: Range<usize>) -> Vec<AnyElement> {
+        let Some(hex) = self.preview_hex.as_mut() else {
+            return Vec::new();
+        };
+        let width = hex.offset_width();
+        range
+            .map(|ix| {
+                let offset = ix as u64 * BYTES_PER_ROW as u64;
+                // A row that cannot be read, e.g. because the file shrank since it was
+                // opened, keeps its place so the rows after it stay at their offsets.
+                let (bytes, ascii, color) = match hex.row(ix as u64) {
+                    Some(row) => (row.hex(), row.ascii(), theme::FG),
+                    None => ("(unreadable)".to_string(), String::new(), theme::MUTED),
+                };
+                div()
+                    .flex()
+                    .h(px(20.0))
+                    .px(px(12.0))
+                    .gap_3()
+                    .font_family("monospace")
+                    .text_xs()
+                    .whitespace_nowrap()
+                    .child(
+                        div()
+                            .flex_shrink_0()
+                            .text_color(rgb(theme::MUTED))
+                            .child(format!("{offset:0width$x}")),
+                    )
+                    .child(div().text_color(rgb(color)).child(bytes))
+                    .child(div().text_color(rgb(theme::FG_SECONDARY)).child(ascii))
+                    .into_any_element()
+            })
+            .collect()
+    }
+
+    /// Switches a Markdown preview between rendered and source.
+    fn render_markdown_toggle(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let label = if self.show_markdown_source {
+            "Rendered"
+        } else {
+            "Source"
+        };
+        gpui_component::ListItem::new("preview-markdown-toggle")
+            .px(px(8.0))
+            .py(px(4.0))
+            .rounded(px(6.0))
+            .on_click(cx.listener(|view, _, _, cx| {
+                view.show_markdown_source = !view.show_markdown_source;
+                cx.notify();
+            }))
+            .child(div().text_xs().text_color(rgb(theme::FG)).child(label))
+    }
+
+    /// Numbered, highlighted rows for the preview lines in `range`. Only the rows on
+    /// screen are asked for, so a large file is highlighted as it is scrolled through.
+    fn render_code_lines(&mut self, range: Range<usize>) -> Vec<AnyElement> {
+        let Some(code) = self.preview_code.as_mut() else {
+            return Vec::new();
+        };
+        let gutter = code.line_count().to_string().len();
+        let foreground = code.foreground();
+        range
+            .filter_map(|ix| {
+                let (line, spans) = code.line(ix)?;
+                let highlights: Vec<_> = spans
+                    .iter()
+                    .map(|span| (span.range.clone(), highlight_style(&span.style)))
+                    .collect();
+                Some(
+                    div()
+                        .flex()
+                        .h(px(20.0))
+                        .px(px(12.0))
+                        .gap_3()
+                        .font_family("monospace")
+                        .text_xs()
+                        .whitespace_nowrap()
+                        .child(
+                            div()
+                                .flex_shrink_0()
+                                .text_color(rgb(theme::MUTED))
+                                .child(format!("{:>gutter$}", ix + 1)),
+                        )
+                        .child(
+                            div().text_color(rgb(foreground)).child(
+                                StyledText::new(line.to_string()).with_highlights(highlights),
+                            ),
+                        )
+                        .into_any_element(),
+                )
+            })
+            .collect()
+    }
+}
+
+impl ExplorerPage {
+    /// Badge counting entries that could not be read while listing the directory.
+    fn render_error_badge(&self) -> impl IntoElement {
+        let count = self.listing_errors.len();
+        let label = if count == 1 {
+            "1 error".to_string()
+        } else {
+            format!("{} errors", count)
+        };
+        div()
+            .px(px(6.0))
+            .py(px(2.0))
+            .rounded(px(4.0))
+            .bg(rgb(theme::ACCENT_LIGHT))
+            .text_xs()
+            .text_color(rgb(theme::FG))
+            .whitespace_nowrap()
+            .child(label)
+    }
+
+    /// Label, percentage and time left of the running file operation, with a cancel button.
+    /// While the job waits on a conflict, shows the choices instead.
+    fn render_job_status(&self, cx: &mut Context<Self>) -> AnyElement {
+        if let Some(conflict) = &self.job_conflict {
+            return self.render_conflict_prompt(conflict, cx).into_any_element();
+        }
+        let mut text = self.job_label.clone();
+        if let Some(p) = &self.job_progress {
+            let (done, total) = if p.bytes_total > 0 {
+                (p.bytes_done, p.bytes_total)
+            } else {
+                (p.files_done, p.files_total)
+            };
+            if total > 0 {
+                text.push_str(&format!(" · {}%", done * 100 / total));
+            }
+            if let Some(eta) = p.eta {
+                text.push_str(&format!(" · {} left", format_eta(eta)));
+            }
+        }
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px(px(6.0))
+            .py(px(2.0))
+            .rounded(px(4.0))
+            .bg(rgb(theme::ACCENT_LIGHT))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(theme::FG))
+                    .whitespace_nowrap()
+                    .child(text),
+            )
+            .child(
+                gpui_component::ListItem::new("job-cancel")
+                    .px(px(4.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(|view, _, _, cx| {
+                        view.cancel_job();
+                        cx.notify();
+                    }))
+                    .child(div().text_xs().text_color(rgb(theme::GRAY_600)).child("✕")),
+            )
+            .into_any_element()
+    }
+
+    fn render_conflict_prompt(
+        &self,
+        conflict: &Conflict,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let choice = |id: &'static str, label: &'static str, action: ConflictAction| {
+            gpui_component::ListItem::new(id)
+                .px(px(6.0))
+                .rounded(px(4.0))
+                .on_click(cx.listener(move |view, _, _, cx| {
+                    view.resolve_conflict(action);
+                    cx.notify();
+                }))
+                .child(div().text_xs().text_color(rgb(theme::ACCENT)).child(label))
+        };
+        let replace = choice("conflict-replace", "Replace", ConflictAction::Overwrite);
+        let skip = choice("conflict-skip", "Skip", ConflictAction::Skip);
+        let keep = choice("conflict-keep", "Keep both", ConflictAction::KeepBoth);
+        let cancel = choice("conflict-cancel", "Cancel", ConflictAction::Cancel);
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px(px(6.0))
+            .py(px(2.0))
+            .rounded(px(4.0))
+            .bg(rgb(theme::ACCENT_LIGHT))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(theme::FG))
+                    .whitespace_nowrap()
+                    .child(format!(
+                        "“{}” already exists",
+                        truncate_middle(&path_name(&conflict.dest), 32)
+                    )),
+            )
+            .child(replace)
+            .child(skip)
+            .child(keep)
+            .child(
+                gpui_component::ListItem::new("conflict-all")
+                    .px(px(6.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(|view, _, _, cx| {
+                        view.job_apply_all = !view.job_apply_all;
+                        cx.notify();
+                    }))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(if self.job_apply_all {
+                                rgb(theme::ACCENT)
+                            } else {
+                                rgb(theme::GRAY_600)
+                            })
+                            .child(if self.job_apply_all {
+                                "☑ All"
+                            } else {
+                                "☐ All"
                             }),
-                    )
+                    ),
+            )
+            .child(cancel)
+    }
+
+    /// Asks whether to go ahead with a permanent deletion.
+    fn render_confirm_prompt(
+        &self,
+        pending: &PendingConfirm,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let question = match pending {
+            PendingConfirm::Delete(paths) => match paths.as_slice() {
+                [one] => format!(
+                    "Delete “{}” permanently?",
+                    truncate_middle(&path_name(one), 32)
+                ),
+                many => format!("Delete {} items permanently?", many.len()),
+            },
+            PendingConfirm::Purge(ids) => {
+                let name = match ids.as_slice() {
+                    [id] => self.trash_entries.iter().find(|e| &e.id == id),
+                    _ => None,
+                };
+                match name {
+                    Some(item) => {
+                        format!("Delete “{}” permanently?", truncate_middle(&item.name, 32))
+                    }
+                    None => format!("Delete {} items permanently?", ids.len()),
+                }
+            }
+            PendingConfirm::EmptyTrash => format!(
+                "Permanently delete all {} items in the Trash?",
+                self.trash_entries.len()
+            ),
+        };
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px(px(6.0))
+            .py(px(2.0))
+            .rounded(px(4.0))
+            .bg(rgb(theme::ACCENT_LIGHT))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(theme::FG))
+                    .whitespace_nowrap()
+                    .child(question),
+            )
+            .child(
+                gpui_component::ListItem::new("confirm-delete")
+                    .px(px(6.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(|view, _, _, cx| {
+                        view.confirm_pending(cx);
+                        cx.notify();
+                    }))
                     .child(
                         div()
-                            .w(px(self.col_modified_width))
-                            .flex_shrink_0()
-                            .text_sm()
-                            .text_color(rgb(theme::FG_SECONDARY))
-                            .overflow_hidden()
-                            .text_ellipsis()
-                            .whitespace_nowrap()
-                            .child(format_date(&item.modified)),
-                    )
+                            .text_xs()
+                            .text_color(rgb(theme::ACCENT))
+                            .child("Delete"),
+                    ),
+            )
+            .child(
+                gpui_component::ListItem::new("confirm-cancel")
+                    .px(px(6.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(|view, _, _, cx| {
+                        view.pending_confirm = None;
+                        cx.notify();
+                    }))
                     .child(
                         div()
-                            .w(px(self.col_action_width))
-                            .flex_shrink_0()
-                            .flex()
-                            .justify_end()
-                            .child(
-                                Icon::new(IconName::File)
-                                    .size_4()
-                                    .text_color(rgb(theme::MUTED)),
-                            ),
+                            .text_xs()
+                            .text_color(rgb(theme::GRAY_600))
+                            .child("Cancel"),
                     ),
             )
     }
 
-    fn render_column_header(
+    /// Put Back and Empty Trash buttons shown while viewing the trash.
+    fn render_trash_actions(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let can_put_back = self.selected_entry().is_some() && self.trash_task.is_none();
+        let can_empty = !self.trash_entries.is_empty() && self.trash_task.is_none();
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(
+                gpui_component::ListItem::new("trash-put-back")
+                    .px(px(8.0))
+                    .py(px(6.0))
+                    .rounded(px(6.0))
+                    .when(!can_put_back, |this| this.opacity(0.3))
+                    .when(can_put_back, |this| {
+                        this.on_click(cx.listener(|view, _, _, cx| view.put_back(cx)))
+                    })
+                    .child(div().text_xs().text_color(rgb(theme::FG)).child("Put Back")),
+            )
+            .child(
+                gpui_component::ListItem::new("trash-empty")
+                    .px(px(8.0))
+                    .py(px(6.0))
+                    .rounded(px(6.0))
+                    .when(!can_empty, |this| this.opacity(0.3))
+                    .when(can_empty, |this| {
+                        this.on_click(cx.listener(|view, _, _, cx| {
+                            view.pending_confirm = Some(PendingConfirm::EmptyTrash);
+                            cx.notify();
+                        }))
+                    })
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::FG))
+                            .child("Empty Trash"),
+                    ),
+            )
+    }
+
+    /// Live toggle shown while viewing a smart folder: the query it runs, and whether its
+    /// results follow changes.
+    fn render_smart_folder_actions(
         &self,
-        label: &str,
-        key: SortKey,
-        key_idx: usize,
-        flex: bool,
+        search: &SavedSearch,
         cx: &mut Context<Self>,
-    ) -> gpui::Div {
-        let is_active = self.sort_key == key;
-        let label_str = label.to_string();
-        let sort_icon = if is_active {
-            Some(if self.sort_asc { "↑" } else { "↓" })
-        } else {
-            None
-        };
+    ) -> impl IntoElement {
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(theme::FG_SECONDARY))
+                    .whitespace_nowrap()
+                    .child(truncate_middle(&search.query, 40)),
+            )
+            .child(
+                gpui_component::ListItem::new("smart-folder-live")
+                    .px(px(8.0))
+                    .py(px(6.0))
+                    .rounded(px(6.0))
+                    .when(search.live, |this| this.bg(rgb(theme::BG_HOVER)))
+                    .on_click(cx.listener(|view, _, _, cx| view.toggle_smart_live(cx)))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(if search.live {
+                                rgb(theme::ACCENT)
+                            } else {
+                                rgb(theme::FG)
+                            })
+                            .child("Live"),
+                    ),
+            )
+    }
 
-        let mut wrapper = div();
+    /// Metadata rows for the previewed entry (permissions, owner, link target, ...).
+    fn render_entry_details(&self) -> Option<impl IntoElement> {
+        use crate::ui::components::file_list::{format_date, format_mode};
 
-        if flex {
-            wrapper = wrapper.flex_1();
+        let entry = self
+            .selected_index
+            .and_then(|ix| self.filtered_entries.get(ix))
+            .filter(|e| e.fs_path().ok().as_ref() == self.preview_path.as_ref())?;
+
+        let mut rows: Vec<(&'static str, String)> = Vec::new();
+        if let Some(mode) = entry.mode {
+            rows.push(("Permissions", format_mode(mode)));
+        }
+        if entry.uid.is_some() || entry.gid.is_some() {
+            let owner = entry
+                .owner
+                .clone()
+                .or_else(|| entry.uid.map(|u| u.to_string()))
+                .unwrap_or_default();
+            let group = entry
+                .group
+                .clone()
+                .or_else(|| entry.gid.map(|g| g.to_string()))
+                .unwrap_or_default();
+            rows.push(("Owner", format!("{}:{}", owner, group)));
+        }
+        if let Some(created) = entry.created {
+            rows.push(("Created", format_date(&(created.secs.max(0) as u64))));
+        }
+        rows.push(("Modified", format_date(&entry.modified)));
+        if let Some(nlink) = entry.nlink {
+            rows.push(("Links", nlink.to_string()));
+        }
+        if let Some(inode) = entry.inode {
+            rows.push(("Inode", inode.to_string()));
+        }
+        if let Some(target) = &entry.link_target {
+            let target = if entry.link_broken {
+                format!("{} (broken)", target)
+            } else {
+                target.clone()
+            };
+            rows.push(("Target", target));
         }
 
-        wrapper.child(
-            gpui_component::ListItem::new(("sort-header", key_idx))
-                .on_click(cx.listener(move |this, _, _, _| {
-                    this.set_sort_key(key);
-                }))
-                .child(
+        Some(
+            div()
+                .mt(px(8.0))
+                .flex()
+                .flex_col()
+                .gap_1()
+                .children(rows.into_iter().map(|(label, value)| {
                     div()
                         .flex()
-                        .items_center()
-                        .gap_1()
+                        .gap_2()
+                        .text_xs()
                         .child(
                             div()
-                                .text_xs()
-                                .font_weight(gpui::FontWeight::SEMIBOLD)
-                                .text_color(if is_active {
-                                    rgb(theme::FG)
-                                } else {
-                                    rgb(theme::FG_SECONDARY)
-                                })
-                                .child(label_str),
+                                .w(px(72.0))
+                                .flex_shrink_0()
+                                .text_color(rgb(theme::MUTED))
+                                .child(label),
                         )
-                        .when(is_active, |this| {
-                            this.child(
-                                div()
-                                    .text_xs()
-                                    .text_color(rgb(theme::FG))
-                                    .child(sort_icon.unwrap_or("")),
-                            )
-                        }),
-                ),
+                        .child(
+                            div()
+                                .text_color(rgb(theme::FG_SECONDARY))
+                                .overflow_hidden()
+                                .text_ellipsis()
+                                .whitespace_nowrap()
+                                .child(value),
+                        )
+                })),
         )
     }
+}
 
-    fn render_preview(&mut self) -> impl IntoElement {
-        let title = self
-            .preview_path
-            .as_ref()
-            .map(|p| path_name(p))
-            .unwrap_or_else(|| "Preview".to_string());
+impl crate::pages::Page for ExplorerPage {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> AnyElement {
+        <Self as Render>::render(self, window, cx).into_any_element()
+    }
+}
 
-        let body: String = self
-            .preview_text
-            .as_ref()
-            .map(|s| s.clone())
-            .unwrap_or_else(|| "Select a file to see a preview".into());
+/// A trash item as a listing row. The trash id stands in for the path and the deletion
+/// time for the modification time.
+fn trash_row(item: &TrashEntry) -> FileEntry {
+    FileEntry {
+        name: item.name.clone(),
+        path: item.id.clone(),
+        kind: item.kind,
+        size: item.size.unwrap_or(0),
+        modified: item.deleted_at.map_or(0, |t| t.secs.max(0) as u64),
+        hidden: item.name.starts_with('.'),
+        ..Default::default()
+    }
+}
 
-        div()
-            .size_full()
+/// Make gpui read the image at `path` again the next time it is drawn. It caches images
+/// by path, and a thumbnail is rewritten in place when its original changes, so a stale
+/// copy would otherwise be shown.
+fn reload_image(path: &Path, cx: &mut gpui::App) {
+    gpui::ImageSource::from(path.to_path_buf()).remove_asset(cx);
+}
+
+fn highlight_style(style: &SpanStyle) -> HighlightStyle {
+    HighlightStyle {
+        color: Some(rgb(style.color).into()),
+        font_weight: style.bold.then_some(gpui::FontWeight::BOLD),
+        font_style: style.italic.then_some(gpui::FontStyle::Italic),
+        underline: style.underline.then(|| gpui::UnderlineStyle {
+            thickness: px(1.0),
+            ..Default::default()
+        }),
+        ..Default::default()
+    }
+}
+
+fn render_markdown_blocks(blocks: &mut [Block]) -> Vec<AnyElement> {
+    blocks.iter_mut().map(render_markdown_block).collect()
+}
+
+/// Lay out `block`. Code blocks are highlighted the first time they are shown.
+fn render_markdown_block(block: &mut Block) -> AnyElement {
+    match block {
+        Block::Heading { level, text } => {
+            let heading = div()
+                .font_weight(gpui::FontWeight::SEMIBOLD)
+                .text_color(rgb(theme::FG))
+                .child(rich_text(text));
+            match level {
+                1 => heading.text_xl(),
+                2 => heading.text_lg(),
+                3 => heading.text_base(),
+                _ => heading.text_sm(),
+            }
+            .into_any_element()
+        }
+        Block::Paragraph(text) => div()
+            .text_sm()
+            .line_height(px(20.0))
+            .text_color(rgb(theme::FG))
+            .child(rich_text(text))
+            .into_any_element(),
+        Block::Image { source, alt } => {
+            let path = match source {
+                ImageSource::File(path) => path.clone(),
+                // Remote images are not fetched, so previewing a file does not tell a
+                // server that it was opened; the alt text and address stand in for them.
+                ImageSource::Url(url) => {
+                    let label = if url.starts_with("data:") {
+                        format!("[{alt}]")
+                    } else {
+                        format!("[{alt}] {url}")
+                    };
+                    return div()
+                        .text_xs()
+                        .text_color(rgb(theme::MUTED))
+                        .overflow_hidden()
+                        .text_ellipsis()
+                        .whitespace_nowrap()
+                        .child(label)
+                        .into_any_element();
+                }
+            };
+            let alt = alt.clone();
+            gpui::img(path)
+                .max_w_full()
+                .object_fit(gpui::ObjectFit::Contain)
+                .with_fallback(move || {
+                    div()
+                        .text_xs()
+                        .text_color(rgb(theme::MUTED))
+                        .child(format!("[{alt}]"))
+                        .into_any_element()
+                })
+                .into_any_element()
+        }
+        Block::Code { code, .. } => {
+            let (foreground, background) = (code.foreground(), code.background());
+            let lines: Vec<_> = (0..code.line_count())
+                .filter_map(|ix| {
+                    let (line, spans) = code.line(ix)?;
+                    let highlights: Vec<_> = spans
+                        .iter()
+                        .map(|span| (span.range.clone(), highlight_style(&span.style)))
+                        .collect();
+                    Some(
+                        div()
+                            .min_h(px(18.0))
+                            .whitespace_nowrap()
+                            .child(StyledText::new(line.to_string()).with_highlights(highlights)),
+                    )
+                })
+                .collect();
+            div()
+                .flex()
+                .flex_col()
+                .px(px(12.0))
+                .py(px(8.0))
+                .rounded(px(6.0))
+                .bg(rgb(background))
+                .border_1()
+                .border_color(rgb(theme::BORDER))
+                .overflow_hidden()
+                .font_family("monospace")
+                .text_xs()
+                .text_color(rgb(foreground))
+                .children(lines)
+                .into_any_element()
+        }
+        Block::Quote(blocks) => div()
             .flex()
             .flex_col()
-            .bg(rgb(theme::BG))
-            .child(
+            .gap_2()
+            .pl(px(12.0))
+            .border_l_1()
+            .border_color(rgb(theme::BORDER_HOVER))
+            .text_color(rgb(theme::FG_SECONDARY))
+            .children(render_markdown_blocks(blocks))
+            .into_any_element(),
+        Block::List { start, items } => div()
+            .flex()
+            .flex_col()
+            .gap_1()
+            .children(items.iter_mut().enumerate().map(|(ix, item)| {
+                let marker = match (item.task, *start) {
+                    (Some(true), _) => "☑".to_string(),
+                    (Some(false), _) => "☐".to_string(),
+                    (None, Some(start)) => format!("{}.", start + ix),
+                    (None, None) => "•".to_string(),
+                };
                 div()
-                    .px(px(16.0))
-                    .py(px(12.0))
-                    .border_b_1()
-                    .border_color(rgb(theme::BORDER))
+                    .flex()
+                    .gap_2()
                     .child(
                         div()
+                            .flex_shrink_0()
+                            .min_w(px(16.0))
                             .text_sm()
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .child(marker),
+                    )
+                    .child(
+                        div()
+                            .flex_1()
+                            .flex()
+                            .flex_col()
+                            .gap_1()
+                            .children(render_markdown_blocks(&mut item.blocks)),
+                    )
+            }))
+            .into_any_element(),
+        Block::Table {
+            alignments,
+            header,
+            rows,
+        } => {
+            let row = |cells: &[RichText], is_header: bool| {
+                div()
+                    .flex()
+                    .when(is_header, |this| {
+                        this.bg(rgb(theme::BG_SECONDARY))
                             .font_weight(gpui::FontWeight::SEMIBOLD)
-                            .text_color(rgb(theme::FG))
-                            .child(title),
-                    ),
-            )
+                    })
+                    .children(cells.iter().enumerate().map(|(ix, cell)| {
+                        let cell_div = div()
+                            .flex_1()
+                            .flex()
+                            .px(px(8.0))
+                            .py(px(4.0))
+                            .border_1()
+                            .border_color(rgb(theme::BORDER))
+                            .text_sm()
+                            .child(rich_text(cell));
+                        match alignments.get(ix) {
+                            Some(Alignment::Center) => cell_div.justify_center(),
+                            Some(Alignment::Right) => cell_div.justify_end(),
+                            _ => cell_div,
+                        }
+                    }))
+            };
+            div()
+                .flex()
+                .flex_col()
+                .text_color(rgb(theme::FG))
+                .child(row(header, true))
+                .children(rows.iter().map(|cells| row(cells, false)))
+                .into_any_element()
+        }
+        Block::Rule => div().h(px(1.0)).bg(rgb(theme::BORDER)).into_any_element(),
+        Block::Html(html) => div()
+            .font_family("monospace")
+            .text_xs()
+            .text_color(rgb(theme::FG_SECONDARY))
+            .child(html.clone())
+            .into_any_element(),
+        Block::Footnote { number, blocks } => div()
+            .flex()
+            .gap_2()
+            .text_xs()
+            .text_color(rgb(theme::FG_SECONDARY))
+            .child(div().flex_shrink_0().child(format!("{number}.")))
             .child(
                 div()
                     .flex_1()
-                    .overflow_hidden()
-                    .px(px(16.0))
-                    .py(px(16.0))
-                    .child(
-                        div()
-                            .text_sm()
-                            .text_color(rgb(theme::FG_SECONDARY))
-                            .line_height(px(20.0))
-                            .child(body),
-                    ),
+                    .flex()
+                    .flex_col()
+                    .gap_1()
+                    .children(render_markdown_blocks(blocks)),
             )
+            .into_any_element(),
     }
 }
 
-impl crate::pages::Page for ExplorerPage {
-    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> AnyElement {
-        <Self as Render>::render(self, window, cx).into_any_element()
+fn rich_text(text: &RichText) -> StyledText {
+    let highlights: Vec<_> = text
+        .styles
+        .iter()
+        .map(|(range, style)| (range.clone(), text_highlight(style)))
+        .collect();
+    StyledText::new(text.text.clone()).with_highlights(highlights)
+}
+
+fn text_highlight(style: &TextStyle) -> HighlightStyle {
+    let mut highlight = HighlightStyle {
+        font_weight: style.bold.then_some(gpui::FontWeight::BOLD),
+        font_style: style.italic.then_some(gpui::FontStyle::Italic),
+        strikethrough: style.strikethrough.then(|| gpui::StrikethroughStyle {
+            thickness: px(1.0),
+            color: None,
+        }),
+        ..Default::default()
+    };
+    if style.code {
+        highlight.background_color = Some(rgb(theme::BG_HOVER).into());
+        highlight.color = Some(rgb(theme::GRAY_700).into());
+    }
+    if style.link.is_some() {
+        highlight.color = Some(rgb(theme::ACCENT_HOVER).into());
+        highlight.underline = Some(gpui::UnderlineStyle {
+            thickness: px(1.0),
+            ..Default::default()
+        });
     }
+    highlight
 }
 
-fn path_name(p: &str) -> String {
-    std::path::Path::new(p)
-        .file_name()
+fn path_name(p: &Path) -> String {
+    p.file_name()
         .map(|s| s.to_string_lossy().to_string())
-        .unwrap_or_else(|| p.to_string())
+        .unwrap_or_else(|| p.to_string_lossy().to_string())
 }
 
-fn path_parts(path: &str) -> Vec<String> {
-    let mut parts: Vec<String> = Vec::new();
-    for c in std::path::Path::new(path).components() {
-        parts.push(c.as_os_str().to_string_lossy().to_string());
+/// Breadcrumb segments: display label and the full path up to that component.
+fn path_parts(path: &Path) -> Vec<(String, PathBuf)> {
+    let mut parts: Vec<(String, PathBuf)> = Vec::new();
+    let mut here = PathBuf::new();
+    for c in path.components() {
+        here.push(c.as_os_str());
+        let label = match c {
+            Component::RootDir => "/".to_string(),
+            _ => c.as_os_str().to_string_lossy().to_string(),
+        };
+        parts.push((label, here.clone()));
     }
     if parts.is_empty() {
-        parts.push(path.to_string());
+        parts.push((path.to_string_lossy().to_string(), path.to_path_buf()));
     }
     parts
 }
 
+fn format_eta(d: Duration) -> String {
+    let secs = d.as_secs();
+    if secs >= 3600 {
+        format!("{}h {}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs.max(1))
+    }
+}
+
 fn truncate_middle(text: &str, max_len: usize) -> String {
     let char_count = text.chars().count();
 
@@ -1544,15 +4114,3 @@ fn truncate_middle(text: &str, max_len: usize) -> String {
         format!("{}...{}", start_part, end_part)
     }
 }
-
-fn get_extension(name: &str, kind: &str) -> String {
-    match kind {
-        "dir" => "0_dir".to_string(),
-        "file" => std::path::Path::new(name)
-            .extension()
-            .and_then(|e| e.to_str())
-            .map(|s| s.to_lowercase())
-            .unwrap_or_else(|| "zzz_noext".to_string()),
-        other => other.to_string(),
-    }
-}
@@ -6,12 +6,14 @@ pub mod git;
 pub mod s3;
 pub mod search;
 pub mod settings;
+pub mod usage;
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
 pub enum PageKind {
     Explorer,
     Search,
     Git,
+    Usage,
     S3,
     Extensions,
     Settings,
@@ -23,6 +25,7 @@ impl PageKind {
             PageKind::Explorer => "Explorer",
             PageKind::Search => "Search",
             PageKind::Git => "Git",
+            PageKind::Usage => "Disk Usage",
             PageKind::S3 => "S3",
             PageKind::Extensions => "Extensions",
             PageKind::Settings => "Settings",
@@ -34,6 +37,7 @@ impl PageKind {
             PageKind::Explorer => "icons/folder.svg",
             PageKind::Search => "icons/search.svg",
             PageKind::Git => "icons/github.svg",
+            PageKind::Usage => "icons/hard-drive.svg",
             PageKind::S3 => "icons/database.svg",
             PageKind::Extensions => "icons/layout-dashboard.svg",
             PageKind::Settings => "icons/settings.svg",
@@ -45,6 +49,7 @@ impl PageKind {
             PageKind::Explorer,
             PageKind::Search,
             PageKind::Git,
+            PageKind::Usage,
             PageKind::S3,
             PageKind::Extensions,
             PageKind::Settings,
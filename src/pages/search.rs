@@ -1,36 +1,910 @@
+use crate::models::file_entry::FileKind;
+use crate::services::frecency::current_scores;
+use crate::services::search::content::{
+    search_content, CaseMode, ContentQuery, ContentSearch, ContentSearchEvent, ContentSearchParams,
+    ContentSearchStats, FileMatches, LineMatch, PatternKind,
+};
+use crate::services::search::index::{
+    search_index, start_rebuild, IndexHit, IndexSearch, IndexSearchEvent, IndexSearchParams,
+    IndexStatus, IndexUpdater, Rebuild, RebuildEvent, SearchIndex,
+};
+use crate::services::search::names::{
+    search_names, NameHit, NameSearch, NameSearchEvent, NameSearchParams,
+};
+use crate::services::search::query::Query;
+use crate::services::search::saved::{ResultSource, SavedSearch};
+use crate::ui::components::file_list::{format_date, human_bytes};
 use crate::ui::theme::theme;
-use gpui::{div, prelude::*, px, rgb, AnyElement, Context, Render, Window};
+use gpui::{
+    div, prelude::*, px, rgb, AnyElement, Context, Entity, EventEmitter, HighlightStyle, Render,
+    StyledText, Window,
+};
+use gpui_component::input::{InputState, TextInput};
+use gpui_component::{Icon, IconName};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::TryRecvError;
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
 
-pub struct SearchPage;
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// Pause in typing after which the query is run.
+const SEARCH_DEBOUNCE: Duration = Duration::from_millis(150);
+/// Hits asked of the index.
+const INDEX_LIMIT: usize = 500;
+/// Matching lines rendered at most; the rest are summarized.
+const MAX_SHOWN_LINES: usize = 2000;
+
+/// Asks the app to show a search result in the explorer.
+pub struct RevealInExplorer(pub PathBuf);
+
+/// Asks the app to keep a search as a smart folder in the explorer.
+pub struct SaveSearch(pub SavedSearch);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SearchMode {
+    /// Fuzzy match file names and paths.
+    Names,
+    /// Search inside files. The query is the pattern itself, not in the query language.
+    Contents,
+    /// Query the persistent index instead of walking the tree.
+    Index,
+}
+
+/// Fuzzy search over file names and paths, full-text search of file contents, or a query
+/// of the persistent index, below a root. Results stream in while a search runs, and
+/// editing the query restarts it.
+pub struct SearchPage {
+    root_input: Entity<InputState>,
+    query_input: Entity<InputState>,
+    mode: SearchMode,
+    /// Content search options; the pattern comes from the query field.
+    content_query: ContentQuery,
+    /// Root and query the current results are for.
+    root: PathBuf,
+    query: String,
+    /// The mode or an option changed, so the results are out of date.
+    stale: bool,
+    /// Waits for typing to pause before searching.
+    debounce_task: Option<gpui::Task<()>>,
+    names: Option<NameSearch>,
+    contents: Option<ContentSearch>,
+    index_search: Option<IndexSearch>,
+    search_task: Option<gpui::Task<()>>,
+    hits: Vec<NameHit>,
+    files: Vec<FileMatches>,
+    /// Entries looked at by a finished name search.
+    scanned: Option<u64>,
+    content_stats: Option<ContentSearchStats>,
+    /// Files and directories that could not be read.
+    errors: usize,
+    /// Why the query could not be run, e.g. a malformed regex.
+    message: Option<String>,
+    /// Selected name hit, or file and line of the selected content match.
+    selected: Option<usize>,
+    selected_line: Option<(usize, usize)>,
+    /// Opened the first time the index mode is used, and kept current from then on.
+    index: Option<Arc<SearchIndex>>,
+    _updater: Option<Arc<IndexUpdater>>,
+    index_hits: Vec<IndexHit>,
+    index_status: Option<IndexStatus>,
+    rebuild: Option<Rebuild>,
+    rebuild_task: Option<gpui::Task<()>>,
+    /// Documents added by the running rebuild.
+    rebuilt: u64,
+}
+
+impl EventEmitter<RevealInExplorer> for SearchPage {}
+impl EventEmitter<SaveSearch> for SearchPage {}
 
 impl SearchPage {
-    pub fn new() -> Self {
-        Self
+    pub fn new(root_input: Entity<InputState>, query_input: Entity<InputState>) -> Self {
+        Self {
+            root_input,
+            query_input,
+            mode: SearchMode::Names,
+            content_query: ContentQuery::default(),
+            root: PathBuf::new(),
+            query: String::new(),
+            stale: false,
+            debounce_task: None,
+            names: None,
+            contents: None,
+            index_search: None,
+            search_task: None,
+            hits: Vec::new(),
+            files: Vec::new(),
+            scanned: None,
+            content_stats: None,
+            errors: 0,
+            message: None,
+            selected: None,
+            selected_line: None,
+            index: None,
+            _updater: None,
+            index_hits: Vec::new(),
+            index_status: None,
+            rebuild: None,
+            rebuild_task: None,
+            rebuilt: 0,
+        }
+    }
+
+    /// Restart the search once typing pauses, when the root, the query, the mode or an
+    /// option changed.
+    fn sync_inputs(&mut self, cx: &mut Context<Self>) {
+        let typed_root = self.root_input.read(cx).text().to_string();
+        let root = match typed_root.trim() {
+            "" => std::env::current_dir().unwrap_or_else(|_| PathBuf::from(".")),
+            path => PathBuf::from(path),
+        };
+        let query = self.query_input.read(cx).text().to_string();
+        if root == self.root && query == self.query && !self.stale {
+            return;
+        }
+        self.root = root;
+        self.query = query;
+        self.stale = false;
+        // Replacing the task drops the previous wait, so only the last change searches.
+        self.debounce_task = Some(cx.spawn(async move |this, cx| {
+            cx.background_executor().timer(SEARCH_DEBOUNCE).await;
+            let _ = this.update(cx, |this, cx| {
+                this.start_search(cx);
+                cx.notify();
+            });
+        }));
+    }
+
+    fn set_mode(&mut self, mode: SearchMode, cx: &mut Context<Self>) {
+        if self.mode != mode {
+            self.mode = mode;
+            self.stale = true;
+            cx.notify();
+        }
+    }
+
+    fn update_options(&mut self, update: impl FnOnce(&mut ContentQuery), cx: &mut Context<Self>) {
+        update(&mut self.content_query);
+        self.stale = true;
+        cx.notify();
+    }
+
+    /// Start over with the current inputs. Dropping the previous search cancels it.
+    fn start_search(&mut self, cx: &mut Context<Self>) {
+        self.names = None;
+        self.contents = None;
+        self.index_search = None;
+        self.search_task = None;
+        self.hits.clear();
+        self.files.clear();
+        self.index_hits.clear();
+        self.scanned = None;
+        self.content_stats = None;
+        self.errors = 0;
+        self.message = None;
+        self.selected = None;
+        self.selected_line = None;
+        if self.query.trim().is_empty() {
+            return;
+        }
+        match self.mode {
+            SearchMode::Names => {
+                let query = match Query::parse(&self.query) {
+                    Ok(query) => query,
+                    Err(err) => {
+                        self.message = Some(err.to_string());
+                        return;
+                    }
+                };
+                self.names = Some(search_names(NameSearchParams {
+                    root: self.root.clone(),
+                    query,
+                    frecency: current_scores(),
+                    ..Default::default()
+                }));
+            }
+            SearchMode::Contents => {
+                let query = ContentQuery {
+                    pattern: self.query.clone(),
+                    ..self.content_query.clone()
+                };
+                match search_content(ContentSearchParams {
+                    root: self.root.clone(),
+                    query,
+                    ..Default::default()
+                }) {
+                    Ok(search) => self.contents = Some(search),
+                    Err(err) => {
+                        self.message = Some(err.to_string());
+                        return;
+                    }
+                }
+            }
+            SearchMode::Index => {
+                let query = match Query::parse(&self.query) {
+                    Ok(query) => query,
+                    Err(err) => {
+                        self.message = Some(err.to_string());
+                        return;
+                    }
+                };
+                self.index_search = Some(search_index(IndexSearchParams {
+                    query,
+                    under: Some(self.root.clone()),
+                    limit: INDEX_LIMIT,
+                    frecency: true,
+                }));
+            }
+        }
+        self.search_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_search(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+    }
+
+    /// Drain available search events. Returns whether the search is still running.
+    fn poll_search(&mut self, cx: &mut Context<Self>) -> bool {
+        let pending = if self.names.is_some() {
+            self.poll_names()
+        } else if self.contents.is_some() {
+            self.poll_contents()
+        } else if self.index_search.is_some() {
+            self.poll_index()
+        } else {
+            false
+        };
+        cx.notify();
+        pending
+    }
+
+    fn poll_names(&mut self) -> bool {
+        let Some(search) = self.names.as_ref() else {
+            return false;
+        };
+        loop {
+            match search.try_next() {
+                Ok(NameSearchEvent::Ranked(hits)) => {
+                    let selected = self
+                        .selected
+                        .and_then(|ix| self.hits.get(ix))
+                        .map(|h| h.path.clone());
+                    self.hits = hits;
+                    self.selected =
+                        selected.and_then(|path| self.hits.iter().position(|h| h.path == path));
+                }
+                Ok(NameSearchEvent::EntryError(err)) => {
+                    tracing::debug!(%err, "search skipped an entry");
+                    self.errors += 1;
+                }
+                Ok(NameSearchEvent::Done { scanned, .. }) => {
+                    self.scanned = Some(scanned);
+                    self.names = None;
+                    return false;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.names = None;
+                    return false;
+                }
+                Err(TryRecvError::Empty) => return true,
+            }
+        }
+    }
+
+    fn poll_contents(&mut self) -> bool {
+        let Some(search) = self.contents.as_ref() else {
+            return false;
+        };
+        loop {
+            match search.try_next() {
+                Ok(ContentSearchEvent::File(file)) => self.files.push(file),
+                Ok(ContentSearchEvent::EntryError(err)) => {
+                    tracing::debug!(%err, "search skipped an entry");
+                    self.errors += 1;
+                }
+                Ok(ContentSearchEvent::Done(stats)) => {
+                    self.content_stats = Some(stats);
+                    self.contents = None;
+                    return false;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.contents = None;
+                    return false;
+                }
+                Err(TryRecvError::Empty) => return true,
+            }
+        }
+    }
+
+    fn poll_index(&mut self) -> bool {
+        let Some(search) = self.index_search.as_ref() else {
+            return false;
+        };
+        loop {
+            match search.try_next() {
+                Ok(IndexSearchEvent::Opened { updater, status }) => {
+                    if self.index.is_none() {
+                        self.index = Some(Arc::clone(updater.index()));
+                        self._updater = Some(updater);
+                        self.index_status = status;
+                    }
+                }
+                Ok(IndexSearchEvent::Hits(hits)) => {
+                    self.index_hits = hits;
+                    self.index_search = None;
+                    return false;
+                }
+                Ok(IndexSearchEvent::Failed(err)) => {
+                    self.message = Some(err.to_string());
+                    self.index_search = None;
+                    return false;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    self.index_search = None;
+                    return false;
+                }
+                Err(TryRecvError::Empty) => return true,
+            }
+        }
+    }
+
+    /// The index, opened and watched on first use.
+    fn open_index(&mut self) -> crate::core::errors::Result<Arc<SearchIndex>> {
+        if let Some(index) = &self.index {
+            return Ok(Arc::clone(index));
+        }
+        let updater = IndexUpdater::shared()?;
+        let index = Arc::clone(updater.index());
+        self._updater = Some(updater);
+        self.index_status = index.status().ok();
+        self.index = Some(Arc::clone(&index));
+        Ok(index)
+    }
+
+    fn start_rebuild(&mut self, cx: &mut Context<Self>) {
+        let index = match self.open_index() {
+            Ok(index) => index,
+            Err(err) => {
+                self.message = Some(err.to_string());
+                cx.notify();
+                return;
+            }
+        };
+        self.rebuilt = 0;
+        self.rebuild = Some(start_rebuild(index));
+        self.rebuild_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_rebuild(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+        cx.notify();
+    }
+
+    /// Drain rebuild progress. Returns whether the rebuild is still running.
+    fn poll_rebuild(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(rebuild) = self.rebuild.as_ref() else {
+            return false;
+        };
+        let mut pending = true;
+        loop {
+            match rebuild.try_next() {
+                Ok(RebuildEvent::Progress(added)) => self.rebuilt = added,
+                Ok(RebuildEvent::Done { .. }) => {
+                    // Run the query again against the new contents.
+                    self.stale = true;
+                    pending = false;
+                    break;
+                }
+                Ok(RebuildEvent::Failed(err)) => {
+                    self.message = Some(err.to_string());
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if !pending {
+            self.rebuild = None;
+            self.index_status = self.index.as_ref().and_then(|index| index.status().ok());
+        }
+        cx.notify();
+        pending
+    }
+
+    fn reveal(&mut self, path: PathBuf, cx: &mut Context<Self>) {
+        cx.emit(RevealInExplorer(path));
+    }
+
+    /// Offer the current query as a smart folder, named after it. Content searches use
+    /// their own pattern syntax and cannot be saved.
+    fn save_search(&mut self, cx: &mut Context<Self>) {
+        let source = match self.mode {
+            SearchMode::Names => ResultSource::Walk,
+            SearchMode::Index => ResultSource::Index,
+            SearchMode::Contents => return,
+        };
+        let query = self.query.trim();
+        if query.is_empty() {
+            return;
+        }
+        cx.emit(SaveSearch(SavedSearch {
+            name: query.to_string(),
+            query: query.to_string(),
+            root: self.root.clone(),
+            source,
+            live: false,
+        }));
+    }
+
+    fn status(&self) -> String {
+        if let Some(message) = &self.message {
+            return message.clone();
+        }
+        if self.mode == SearchMode::Index {
+            return self.index_summary();
+        }
+        if self.query.trim().is_empty() {
+            return String::new();
+        }
+        let running = self.names.is_some() || self.contents.is_some();
+        let mut status = match self.mode {
+            SearchMode::Names => match self.scanned {
+                _ if running => format!("{} matches so far…", self.hits.len()),
+                Some(scanned) => format!("{} matches in {} items", self.hits.len(), scanned),
+                None => String::new(),
+            },
+            SearchMode::Contents => {
+                let lines: usize = self.files.iter().map(|f| f.matches.len()).sum();
+                match self.content_stats {
+                    _ if running => {
+                        format!("{lines} lines in {} files so far…", self.files.len())
+                    }
+                    Some(stats) => format!(
+                        "{lines} lines in {} of {} files · {:.2}s",
+                        self.files.len(),
+                        stats.files_searched,
+                        stats.elapsed.as_secs_f32()
+                    ),
+                    None => String::new(),
+                }
+            }
+        };
+        if self.errors > 0 {
+            status.push_str(&format!(" · {} unreadable", self.errors));
+        }
+        status
+    }
+
+    /// Hits for the query, then what the index holds and when it last changed.
+    fn index_summary(&self) -> String {
+        if self.rebuild.is_some() {
+            return format!("Indexing… {} items", self.rebuilt);
+        }
+        let mut parts = Vec::new();
+        if self.index_search.is_some() {
+            parts.push("Searching…".to_string());
+        } else if !self.query.trim().is_empty() {
+            parts.push(format!("{} matches", self.index_hits.len()));
+        }
+        if let Some(status) = &self.index_status {
+            parts.push(format!("{} indexed", status.documents));
+            parts.push(human_bytes(status.size_on_disk));
+            match status
+                .last_update
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            {
+                Some(at) => parts.push(format!("updated {}", format_date(&at.as_secs()))),
+                None => parts.push("never built".to_string()),
+            }
+        }
+        parts.join(" · ")
     }
 }
 
 impl Render for SearchPage {
-    fn render(&mut self, _window: &mut Window, _cx: &mut Context<Self>) -> impl IntoElement {
+    fn render(&mut self, _window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        self.sync_inputs(cx);
+
+        let results = match self.mode {
+            SearchMode::Names => {
+                let mut rows = div().flex().flex_col();
+                for ix in 0..self.hits.len() {
+                    rows = rows.child(self.render_hit(ix, cx));
+                }
+                rows
+            }
+            SearchMode::Contents => self.render_content_results(cx),
+            SearchMode::Index => {
+                let mut rows = div().flex().flex_col();
+                for ix in 0..self.index_hits.len() {
+                    rows = rows.child(self.render_index_hit(ix, cx));
+                }
+                rows
+            }
+        };
+
         div()
             .size_full()
             .flex()
             .flex_col()
-            .items_center()
-            .justify_center()
             .bg(rgb(theme::BG))
             .child(
                 div()
-                    .text_2xl()
-                    .font_weight(gpui::FontWeight::BOLD)
+                    .flex()
+                    .items_center()
+                    .gap_3()
+                    .px(px(16.0))
+                    .py(px(12.0))
+                    .border_b_1()
+                    .border_color(rgb(theme::BORDER))
+                    .child(
+                        div()
+                            .text_lg()
+                            .font_weight(gpui::FontWeight::BOLD)
+                            .text_color(rgb(theme::FG))
+                            .child("Search"),
+                    )
+                    .child(self.render_modes(cx))
+                    .child(div().w(px(280.0)).child(TextInput::new(&self.root_input)))
+                    .child(div().flex_1().child(TextInput::new(&self.query_input)))
+                    .when(self.mode == SearchMode::Contents, |this| {
+                        this.child(self.render_content_options(cx))
+                    })
+                    .when(self.mode == SearchMode::Index, |this| {
+                        this.child(
+                            toggle("index-rebuild", "Rebuild", self.rebuild.is_some()).on_click(
+                                cx.listener(|view, _, _, cx| {
+                                    if view.rebuild.is_none() {
+                                        view.start_rebuild(cx);
+                                    }
+                                }),
+                            ),
+                        )
+                    })
+                    .when(
+                        self.mode != SearchMode::Contents && !self.query.trim().is_empty(),
+                        |this| {
+                            this.child(
+                                toggle("search-save", "Save", false)
+                                    .on_click(cx.listener(|view, _, _, cx| view.save_search(cx))),
+                            )
+                        },
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .whitespace_nowrap()
+                            .child(self.status()),
+                    ),
+            )
+            .child(
+                div()
+                    .id("search-results")
+                    .flex_1()
+                    .min_h(px(0.0))
+                    .overflow_y_scroll()
+                    .px(px(8.0))
+                    .py(px(8.0))
+                    .child(results),
+            )
+    }
+}
+
+impl SearchPage {
+    fn render_modes(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let mode = |id: &'static str, label: &'static str, target: SearchMode| {
+            toggle(id, label, self.mode == target)
+                .on_click(cx.listener(move |view, _, _, cx| view.set_mode(target, cx)))
+        };
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(mode("search-mode-names", "Names", SearchMode::Names))
+            .child(mode(
+                "search-mode-contents",
+                "Contents",
+                SearchMode::Contents,
+            ))
+            .child(mode("search-mode-index", "Index", SearchMode::Index))
+    }
+
+    /// Regex, case and whole-word switches for content search.
+    fn render_content_options(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let q = &self.content_query;
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .child(
+                toggle("search-regex", ".*", q.kind == PatternKind::Regex).on_click(cx.listener(
+                    |view, _, _, cx| {
+                        view.update_options(
+                            |q| {
+                                q.kind = match q.kind {
+                                    PatternKind::Literal => PatternKind::Regex,
+                                    PatternKind::Regex => PatternKind::Literal,
+                                }
+                            },
+                            cx,
+                        )
+                    },
+                )),
+            )
+            .child(
+                toggle("search-case", "Aa", q.case == CaseMode::Sensitive).on_click(cx.listener(
+                    |view, _, _, cx| {
+                        view.update_options(
+                            |q| {
+                                q.case = match q.case {
+                                    CaseMode::Sensitive => CaseMode::Smart,
+                                    _ => CaseMode::Sensitive,
+                                }
+                            },
+                            cx,
+                        )
+                    },
+                )),
+            )
+            .child(
+                toggle("search-word", "W", q.whole_word).on_click(cx.listener(|view, _, _, cx| {
+                    view.update_options(|q| q.whole_word = !q.whole_word, cx)
+                })),
+            )
+    }
+
+    /// One result: its relative path with the matched characters highlighted. Double-click
+    /// shows it in the explorer.
+    fn render_hit(&self, ix: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let hit = &self.hits[ix];
+        let icon = if hit.kind == FileKind::Dir {
+            IconName::Folder
+        } else {
+            IconName::File
+        };
+        let path = hit.path.clone();
+        div()
+            .flex()
+            .items_center()
+            .gap_2()
+            .px(px(8.0))
+            .py(px(4.0))
+            .rounded(px(4.0))
+            .cursor_pointer()
+            .when(self.selected == Some(ix), |this| {
+                this.bg(rgb(theme::BG_HOVER))
+            })
+            .hover(|this| this.bg(rgb(theme::BG_HOVER)))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |this, event: &gpui::MouseDownEvent, _window, cx| {
+                    this.selected = Some(ix);
+                    if event.click_count >= 2 {
+                        this.reveal(path.clone(), cx);
+                    }
+                    cx.notify();
+                }),
+            )
+            .child(
+                Icon::new(icon)
+                    .size_4()
+                    .text_color(rgb(theme::FG_SECONDARY)),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_sm()
+                    .text_color(rgb(theme::FG))
+                    .child(highlighted(
+                        &hit.rel_path,
+                        char_ranges(&hit.rel_path, &hit.positions),
+                    )),
+            )
+    }
+
+    /// An indexed entry: its path below the root, size and modification date.
+    fn render_index_hit(&self, ix: usize, cx: &mut Context<Self>) -> impl IntoElement {
+        let hit = &self.index_hits[ix];
+        let icon = if hit.kind == FileKind::Dir {
+            IconName::Folder
+        } else {
+            IconName::File
+        };
+        let rel = hit.path.strip_prefix(&self.root).unwrap_or(&hit.path);
+        let modified = hit
+            .modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| format_date(&d.as_secs()))
+            .unwrap_or_default();
+        let path = hit.path.clone();
+        div()
+            .flex()
+            .items_center()
+            .gap_2()
+            .px(px(8.0))
+            .py(px(4.0))
+            .rounded(px(4.0))
+            .cursor_pointer()
+            .when(self.selected == Some(ix), |this| {
+                this.bg(rgb(theme::BG_HOVER))
+            })
+            .hover(|this| this.bg(rgb(theme::BG_HOVER)))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |this, event: &gpui::MouseDownEvent, _window, cx| {
+                    this.selected = Some(ix);
+                    if event.click_count >= 2 {
+                        this.reveal(path.clone(), cx);
+                    }
+                    cx.notify();
+                }),
+            )
+            .child(
+                Icon::new(icon)
+                    .size_4()
+                    .text_color(rgb(theme::FG_SECONDARY)),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_sm()
                     .text_color(rgb(theme::FG))
-                    .child("🔍 Search"),
+                    .child(rel.display().to_string()),
             )
             .child(
                 div()
-                    .mt(px(16.0))
-                    .text_base()
+                    .w(px(80.0))
+                    .text_xs()
                     .text_color(rgb(theme::FG_SECONDARY))
-                    .child("Full-text search feature to be implemented"),
+                    .when(hit.kind != FileKind::Dir, |this| {
+                        this.child(human_bytes(hit.size))
+                    }),
+            )
+            .child(
+                div()
+                    .w(px(90.0))
+                    .text_xs()
+                    .text_color(rgb(theme::FG_SECONDARY))
+                    .child(modified),
+            )
+    }
+
+    /// Files with matches, each with its matching lines and their context. Context shared
+    /// by neighbouring matches is shown once.
+    fn render_content_results(&self, cx: &mut Context<Self>) -> gpui::Div {
+        let mut rows = div().flex().flex_col();
+        let mut shown = 0;
+        for (file_ix, file) in self.files.iter().enumerate() {
+            if shown >= MAX_SHOWN_LINES {
+                let remaining: usize = self.files[file_ix..].iter().map(|f| f.matches.len()).sum();
+                rows = rows.child(
+                    div()
+                        .px(px(8.0))
+                        .py(px(8.0))
+                        .text_xs()
+                        .text_color(rgb(theme::FG_SECONDARY))
+                        .child(format!("{remaining} more matching lines not shown")),
+                );
+                break;
+            }
+            let rel = file.path.strip_prefix(&self.root).unwrap_or(&file.path);
+            rows = rows.child(
+                div()
+                    .flex()
+                    .items_center()
+                    .gap_2()
+                    .px(px(8.0))
+                    .pt(px(10.0))
+                    .pb(px(2.0))
+                    .child(
+                        Icon::new(IconName::File)
+                            .size_4()
+                            .text_color(rgb(theme::FG_SECONDARY)),
+                    )
+                    .child(
+                        div()
+                            .text_sm()
+                            .font_weight(gpui::FontWeight::BOLD)
+                            .text_color(rgb(theme::FG))
+                            .child(rel.display().to_string()),
+                    )
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .child(file.matches.len().to_string()),
+                    ),
+            );
+            let mut last_shown = 0;
+            for (line_ix, m) in file.matches.iter().enumerate() {
+                let first_before = m.line_number - m.before.len() as u64;
+                if last_shown > 0 && first_before > last_shown + 1 {
+                    rows = rows.child(context_row(None, "⋯"));
+                }
+                for (k, text) in m.before.iter().enumerate() {
+                    let number = first_before + k as u64;
+                    if number > last_shown {
+                        rows = rows.child(context_row(Some(number), text));
+                    }
+                }
+                rows = rows.child(self.render_line_match(file_ix, line_ix, &file.path, m, cx));
+                last_shown = m.line_number;
+                // Context after a match that the next match's context covers is left to it.
+                let next = file.matches.get(line_ix + 1).map(|n| n.line_number);
+                for (k, text) in m.after.iter().enumerate() {
+                    let number = m.line_number + 1 + k as u64;
+                    if next.is_some_and(|n| number >= n) {
+                        break;
+                    }
+                    rows = rows.child(context_row(Some(number), text));
+                    last_shown = number;
+                }
+                shown += 1;
+            }
+        }
+        rows
+    }
+
+    fn render_line_match(
+        &self,
+        file_ix: usize,
+        line_ix: usize,
+        path: &Path,
+        m: &LineMatch,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let path = path.to_path_buf();
+        div()
+            .flex()
+            .gap_2()
+            .px(px(8.0))
+            .py(px(1.0))
+            .rounded(px(4.0))
+            .cursor_pointer()
+            .when(self.selected_line == Some((file_ix, line_ix)), |this| {
+                this.bg(rgb(theme::BG_HOVER))
+            })
+            .hover(|this| this.bg(rgb(theme::BG_HOVER)))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |this, event: &gpui::MouseDownEvent, _window, cx| {
+                    this.selected_line = Some((file_ix, line_ix));
+                    if event.click_count >= 2 {
+                        this.reveal(path.clone(), cx);
+                    }
+                    cx.notify();
+                }),
+            )
+            .child(line_number(Some(m.line_number)))
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_sm()
+                    .font_family("monospace")
+                    .text_color(rgb(theme::FG))
+                    .child(highlighted(&m.line, m.ranges.clone())),
             )
     }
 }
@@ -40,3 +914,79 @@ impl crate::pages::Page for SearchPage {
         <Self as Render>::render(self, window, cx).into_any_element()
     }
 }
+
+/// A small switch button, accented while `active`.
+fn toggle(id: &'static str, label: &'static str, active: bool) -> gpui_component::ListItem {
+    gpui_component::ListItem::new(id)
+        .px(px(6.0))
+        .py(px(4.0))
+        .rounded(px(4.0))
+        .when(active, |this| this.bg(rgb(theme::BG_HOVER)))
+        .child(
+            div()
+                .text_xs()
+                .text_color(rgb(if active {
+                    theme::ACCENT
+                } else {
+                    theme::GRAY_600
+                }))
+                .child(label),
+        )
+}
+
+fn line_number(number: Option<u64>) -> impl IntoElement {
+    div()
+        .w(px(48.0))
+        .flex_none()
+        .text_xs()
+        .text_color(rgb(theme::MUTED))
+        .child(number.map(|n| n.to_string()).unwrap_or_default())
+}
+
+fn context_row(number: Option<u64>, text: &str) -> impl IntoElement {
+    div()
+        .flex()
+        .gap_2()
+        .px(px(8.0))
+        .py(px(1.0))
+        .child(line_number(number))
+        .child(
+            div()
+                .flex_1()
+                .min_w(px(0.0))
+                .overflow_hidden()
+                .whitespace_nowrap()
+                .text_sm()
+                .font_family("monospace")
+                .text_color(rgb(theme::FG_SECONDARY))
+                .child(text.to_string()),
+        )
+}
+
+/// `text` with the byte `ranges` emphasized.
+fn highlighted(text: &str, ranges: Vec<Range<usize>>) -> StyledText {
+    let style = HighlightStyle {
+        color: Some(rgb(theme::ACCENT).into()),
+        font_weight: Some(gpui::FontWeight::BOLD),
+        ..Default::default()
+    };
+    StyledText::new(text.to_string()).with_highlights(ranges.into_iter().map(move |r| (r, style)))
+}
+
+/// Byte ranges of `text` covering the characters at `positions`, merging neighbours.
+fn char_ranges(text: &str, positions: &[usize]) -> Vec<Range<usize>> {
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    let mut wanted = positions.iter().peekable();
+    for (ix, (byte, c)) in text.char_indices().enumerate() {
+        if wanted.peek() != Some(&&ix) {
+            continue;
+        }
+        wanted.next();
+        let end = byte + c.len_utf8();
+        match ranges.last_mut() {
+            Some(last) if last.end == byte => last.end = end,
+            _ => ranges.push(byte..end),
+        }
+    }
+    ranges
+}
@@ -0,0 +1,756 @@
+use crate::models::file_entry::FileKind;
+use crate::services::fs::ops::{self, JobEvent, JobHandle, Operation};
+use crate::services::fs::usage::{
+    self, Rect, ScanEvent, ScanHandle, ScanProgress, UsageNode, UsageScan,
+};
+use crate::services::fs::walk::WalkOptions;
+use crate::ui::components::file_list::human_bytes;
+use crate::ui::theme::theme;
+use gpui::{
+    div, prelude::*, px, rgb, AnyElement, Context, Entity, FocusHandle, Focusable, IntoElement,
+    Render, Window,
+};
+use gpui_component::input::{InputState, TextInput};
+use gpui_component::{Icon, IconName};
+use std::path::PathBuf;
+use std::sync::mpsc::TryRecvError;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// Width of the ranked list next to the treemap.
+const LIST_WIDTH: f32 = 420.0;
+/// Space around the treemap taken by the navigation bar, toolbar, header and footer.
+const TREEMAP_CHROME: (f32, f32) = (64.0 + LIST_WIDTH + 32.0, 36.0 + 96.0 + 28.0 + 32.0);
+/// Tiles smaller than this in either direction are drawn without a label.
+const MIN_LABEL_SIZE: (f32, f32) = (56.0, 28.0);
+const LARGEST_FILES: usize = 10;
+const TILE_COLORS: [u32; 6] = [0xDEA584, 0x93C5FD, 0x86EFAC, 0xFCD34D, 0xC4B5FD, 0xF9A8D4];
+
+/// ncdu-style disk usage: scan a tree, drill into it as a ranked list and a treemap, and
+/// trash or delete what takes up the space.
+pub struct DiskUsagePage {
+    root_input: Entity<InputState>,
+    scan: Option<UsageScan>,
+    /// Running scan and its latest progress.
+    scanning: Option<ScanHandle>,
+    progress: Option<ScanProgress>,
+    scan_task: Option<gpui::Task<()>>,
+    /// Child indices from the scan's root to the directory being shown.
+    focus: Vec<usize>,
+    /// Selected child of the focused directory.
+    selected: Option<usize>,
+    /// Running trash or delete, and the node it removes.
+    job: Option<JobHandle>,
+    job_target: Vec<usize>,
+    job_task: Option<gpui::Task<()>>,
+    /// Node waiting for the user to confirm its permanent deletion.
+    pending_delete: Option<Vec<usize>>,
+    message: Option<String>,
+    focus_handle: FocusHandle,
+}
+
+impl Focusable for DiskUsagePage {
+    fn focus_handle(&self, _cx: &gpui::App) -> FocusHandle {
+        self.focus_handle.clone()
+    }
+}
+
+impl DiskUsagePage {
+    pub fn new(root_input: Entity<InputState>, focus_handle: FocusHandle) -> Self {
+        Self {
+            root_input,
+            scan: None,
+            scanning: None,
+            progress: None,
+            scan_task: None,
+            focus: Vec::new(),
+            selected: None,
+            job: None,
+            job_target: Vec::new(),
+            job_task: None,
+            pending_delete: None,
+            message: None,
+            focus_handle,
+        }
+    }
+
+    /// Scan the directory typed into the root field, or the home directory.
+    fn start_scan(&mut self, cx: &mut Context<Self>) {
+        let typed = self.root_input.read(cx).text().to_string();
+        let root = match typed.trim() {
+            "" => std::env::var_os("HOME")
+                .or_else(|| std::env::var_os("USERPROFILE"))
+                .map_or_else(|| PathBuf::from("."), PathBuf::from),
+            path => PathBuf::from(path),
+        };
+        let opts = WalkOptions {
+            same_file_system: true,
+            ..Default::default()
+        };
+        self.scanning = Some(usage::start_scan(root, opts));
+        self.progress = Some(ScanProgress::default());
+        self.message = None;
+        // A removal still running refers to the old tree; leave it out of the new one.
+        self.job_target.clear();
+        self.scan_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_scan(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+        cx.notify();
+    }
+
+    /// Drain available scan events. Returns whether the scan is still running.
+    fn poll_scan(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(handle) = self.scanning.as_ref() else {
+            return false;
+        };
+        let mut pending = true;
+        loop {
+            match handle.try_next() {
+                Ok(ScanEvent::Progress(p)) => self.progress = Some(p),
+                Ok(ScanEvent::Finished(scan)) => {
+                    if scan.unreadable > 0 {
+                        self.message = Some(format!("{} items could not be read", scan.unreadable));
+                    }
+                    self.scan = Some(*scan);
+                    self.focus.clear();
+                    self.selected = None;
+                    pending = false;
+                    break;
+                }
+                Ok(ScanEvent::Failed(err)) => {
+                    self.message = Some(err.to_string());
+                    pending = false;
+                    break;
+                }
+                Ok(ScanEvent::Cancelled) | Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if !pending {
+            self.scanning = None;
+            self.progress = None;
+        }
+        cx.notify();
+        pending
+    }
+
+    fn cancel_scan(&mut self, cx: &mut Context<Self>) {
+        if let Some(handle) = self.scanning.take() {
+            handle.cancel();
+        }
+        self.progress = None;
+        self.scan_task = None;
+        cx.notify();
+    }
+
+    fn focused(&self) -> Option<&UsageNode> {
+        self.scan.as_ref()?.tree.get(&self.focus)
+    }
+
+    /// Select child `ix` of the focused directory, entering it if it is a directory and
+    /// `enter` is set.
+    fn select(&mut self, ix: usize, enter: bool, cx: &mut Context<Self>) {
+        let is_dir = self
+            .focused()
+            .and_then(|node| node.children.get(ix))
+            .is_some_and(|child| child.kind == FileKind::Dir);
+        if enter && is_dir {
+            self.focus.push(ix);
+            self.selected = None;
+        } else {
+            self.selected = Some(ix);
+        }
+        cx.notify();
+    }
+
+    fn go_up(&mut self, cx: &mut Context<Self>) {
+        self.selected = self.focus.pop();
+        cx.notify();
+    }
+
+    fn selected_path(&self) -> Option<Vec<usize>> {
+        let ix = self.selected?;
+        let mut at = self.focus.clone();
+        at.push(ix);
+        Some(at)
+    }
+
+    /// Trash the selected item, or with `permanently`, ask to delete it.
+    fn remove_selected(&mut self, permanently: bool, cx: &mut Context<Self>) {
+        let Some(at) = self.selected_path() else {
+            return;
+        };
+        if permanently {
+            self.pending_delete = Some(at);
+            cx.notify();
+        } else {
+            self.run_removal(at, false, cx);
+        }
+    }
+
+    fn run_removal(&mut self, at: Vec<usize>, delete: bool, cx: &mut Context<Self>) {
+        if self.job.is_some() {
+            self.message = Some("Another operation is still running".into());
+            cx.notify();
+            return;
+        }
+        let Some(path) = self
+            .scan
+            .as_ref()
+            .and_then(|s| s.tree.path_of(&s.root, &at))
+        else {
+            return;
+        };
+        let paths = vec![path];
+        let op = if delete {
+            Operation::Delete {
+                paths,
+                confirmed: true,
+            }
+        } else {
+            Operation::Trash { paths }
+        };
+        self.job = Some(ops::start(op));
+        self.job_target = at;
+        self.message = None;
+        self.job_task = Some(cx.spawn(async move |this, cx| loop {
+            cx.background_executor().timer(POLL_INTERVAL).await;
+            let pending = this
+                .update(cx, |this, cx| this.poll_job(cx))
+                .unwrap_or(false);
+            if !pending {
+                break;
+            }
+        }));
+        cx.notify();
+    }
+
+    /// Keep the focus and selection pointing at the same nodes after the node at `removed`
+    /// was taken out of the scan.
+    fn forget(&mut self, removed: &[usize]) {
+        let Some((&gone, parent)) = removed.split_last() else {
+            return;
+        };
+        if self.focus.starts_with(removed) {
+            self.focus.truncate(parent.len());
+            self.selected = None;
+        } else if self.focus.starts_with(parent) && self.focus.len() > parent.len() {
+            if self.focus[parent.len()] > gone {
+                self.focus[parent.len()] -= 1;
+            }
+        } else if self.focus == parent {
+            self.selected = match self.selected {
+                Some(ix) if ix == gone => None,
+                Some(ix) if ix > gone => Some(ix - 1),
+                other => other,
+            };
+        }
+    }
+
+    /// Drain available job events. Returns whether the job is still running. A finished
+    /// removal is taken out of the scan rather than scanning again.
+    fn poll_job(&mut self, cx: &mut Context<Self>) -> bool {
+        let Some(job) = self.job.as_ref() else {
+            return false;
+        };
+        let mut pending = true;
+        loop {
+            match job.try_next() {
+                Ok(JobEvent::Finished(_)) => {
+                    let target = std::mem::take(&mut self.job_target);
+                    let removed = self.scan.as_mut().and_then(|s| s.tree.remove(&target));
+                    if let Some(removed) = removed {
+                        self.message = Some(format!("Freed {}", human_bytes(removed.on_disk)));
+                        self.forget(&target);
+                    }
+                    pending = false;
+                    break;
+                }
+                Ok(JobEvent::Failed(err, _)) => {
+                    self.message = Some(err.to_string());
+                    pending = false;
+                    break;
+                }
+                Ok(JobEvent::Cancelled(_)) | Err(TryRecvError::Disconnected) => {
+                    pending = false;
+                    break;
+                }
+                Ok(JobEvent::Progress(_) | JobEvent::Conflict(_)) => {}
+                Err(TryRecvError::Empty) => break,
+            }
+        }
+        if !pending {
+            self.job = None;
+        }
+        cx.notify();
+        pending
+    }
+}
+
+impl Render for DiskUsagePage {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        div()
+            .size_full()
+            .flex()
+            .flex_col()
+            .bg(rgb(theme::BG))
+            .track_focus(&self.focus_handle)
+            .on_key_down(
+                cx.listener(|this, event: &gpui::KeyDownEvent, _window, cx| {
+                    match event.keystroke.key.to_lowercase().as_str() {
+                        "escape" if this.pending_delete.is_some() => {
+                            this.pending_delete = None;
+                            cx.notify();
+                        }
+                        "delete" => this.remove_selected(event.keystroke.modifiers.shift, cx),
+                        "backspace" => this.go_up(cx),
+                        "enter" => {
+                            if let Some(ix) = this.selected {
+                                this.select(ix, true, cx);
+                            }
+                        }
+                        _ => return,
+                    }
+                    cx.stop_propagation();
+                }),
+            )
+            .child(self.render_header(cx))
+            .child(self.render_path_bar(cx))
+            .child(
+                div()
+                    .flex()
+                    .flex_row()
+                    .flex_grow()
+                    .min_h(px(0.0))
+                    .gap_4()
+                    .px(px(16.0))
+                    .pb(px(16.0))
+                    .child(self.render_ranking(cx))
+                    .child(self.render_treemap(window, cx)),
+            )
+    }
+}
+
+impl DiskUsagePage {
+    fn render_header(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let status = match (&self.progress, &self.scan) {
+            (Some(p), _) => format!(
+                "Scanning… {} files, {}{}",
+                p.files,
+                human_bytes(p.bytes),
+                p.current
+                    .as_ref()
+                    .map(|c| format!(" · {}", c.display()))
+                    .unwrap_or_default()
+            ),
+            (None, Some(scan)) => format!(
+                "{} in {} files · scanned in {:.1}s",
+                human_bytes(scan.tree.on_disk),
+                scan.tree.files,
+                scan.elapsed.as_secs_f32()
+            ),
+            (None, None) => "Pick a folder to see what takes up its space".into(),
+        };
+        let scanning = self.scanning.is_some();
+        div()
+            .flex()
+            .items_center()
+            .gap_3()
+            .px(px(16.0))
+            .py(px(12.0))
+            .border_b_1()
+            .border_color(rgb(theme::BORDER))
+            .child(
+                div()
+                    .text_lg()
+                    .font_weight(gpui::FontWeight::BOLD)
+                    .text_color(rgb(theme::FG))
+                    .child("Disk Usage"),
+            )
+            .child(div().w(px(320.0)).child(TextInput::new(&self.root_input)))
+            .child(
+                gpui_component::ListItem::new("usage-scan")
+                    .px(px(8.0))
+                    .py(px(6.0))
+                    .rounded(px(6.0))
+                    .on_click(cx.listener(move |view, _, _, cx| {
+                        if scanning {
+                            view.cancel_scan(cx);
+                        } else {
+                            view.start_scan(cx);
+                        }
+                    }))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::ACCENT))
+                            .child(if scanning { "Cancel" } else { "Scan" }),
+                    ),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_xs()
+                    .text_color(rgb(theme::FG_SECONDARY))
+                    .child(status),
+            )
+    }
+
+    /// Where the view is in the scanned tree, and what can be done with the selection.
+    fn render_path_bar(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let here = self
+            .scan
+            .as_ref()
+            .and_then(|s| s.tree.path_of(&s.root, &self.focus))
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        let can_go_up = !self.focus.is_empty();
+        let can_remove = self.selected.is_some() && self.job.is_none();
+        let action = |id: &'static str, label: &'static str, enabled: bool| {
+            gpui_component::ListItem::new(id)
+                .px(px(8.0))
+                .py(px(4.0))
+                .rounded(px(6.0))
+                .when(!enabled, |this| this.opacity(0.3))
+                .child(div().text_xs().text_color(rgb(theme::FG)).child(label))
+        };
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px(px(16.0))
+            .py(px(8.0))
+            .child(
+                action("usage-up", "↑ Up", can_go_up).when(can_go_up, |this| {
+                    this.on_click(cx.listener(|view, _, _, cx| view.go_up(cx)))
+                }),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_sm()
+                    .text_color(rgb(theme::FG))
+                    .child(here),
+            )
+            .when_some(self.message.clone(), |this, message| {
+                this.child(
+                    div()
+                        .text_xs()
+                        .text_color(rgb(theme::FG_SECONDARY))
+                        .child(message),
+                )
+            })
+            .map(|this| match &self.pending_delete {
+                Some(at) => this.child(self.render_confirm_prompt(at, cx)),
+                None => {
+                    this.child(action("usage-trash", "Move to Trash", can_remove).when(
+                        can_remove,
+                        |this| {
+                            this.on_click(
+                                cx.listener(|view, _, _, cx| view.remove_selected(false, cx)),
+                            )
+                        },
+                    ))
+                    .child(
+                        action("usage-delete", "Delete…", can_remove).when(can_remove, |this| {
+                            this.on_click(
+                                cx.listener(|view, _, _, cx| view.remove_selected(true, cx)),
+                            )
+                        }),
+                    )
+                }
+            })
+    }
+
+    fn render_confirm_prompt(&self, at: &[usize], cx: &mut Context<Self>) -> impl IntoElement {
+        let name = self
+            .scan
+            .as_ref()
+            .and_then(|s| s.tree.get(at))
+            .map(|node| node.name.clone())
+            .unwrap_or_default();
+        let target = at.to_vec();
+        div()
+            .flex()
+            .items_center()
+            .gap_1()
+            .px(px(6.0))
+            .py(px(2.0))
+            .rounded(px(4.0))
+            .bg(rgb(theme::ACCENT_LIGHT))
+            .child(
+                div()
+                    .text_xs()
+                    .text_color(rgb(theme::FG))
+                    .whitespace_nowrap()
+                    .child(format!("Delete “{name}” permanently?")),
+            )
+            .child(
+                gpui_component::ListItem::new("usage-confirm-delete")
+                    .px(px(6.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(move |view, _, _, cx| {
+                        view.pending_delete = None;
+                        view.run_removal(target.clone(), true, cx);
+                    }))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::ACCENT))
+                            .child("Delete"),
+                    ),
+            )
+            .child(
+                gpui_component::ListItem::new("usage-confirm-cancel")
+                    .px(px(6.0))
+                    .rounded(px(4.0))
+                    .on_click(cx.listener(|view, _, _, cx| {
+                        view.pending_delete = None;
+                        cx.notify();
+                    }))
+                    .child(
+                        div()
+                            .text_xs()
+                            .text_color(rgb(theme::GRAY_600))
+                            .child("Cancel"),
+                    ),
+            )
+    }
+
+    /// Children of the focused directory, largest first, followed by the largest files
+    /// anywhere below it.
+    fn render_ranking(&self, cx: &mut Context<Self>) -> impl IntoElement {
+        let mut list = div().flex().flex_col();
+        if let Some(node) = self.focused() {
+            let total = node.on_disk.max(1);
+            for (ix, child) in node.children.iter().enumerate() {
+                list = list.child(self.render_rank_row(ix, child, total, cx));
+            }
+            if node.children.is_empty() {
+                list = list.child(
+                    div()
+                        .p(px(8.0))
+                        .text_sm()
+                        .text_color(rgb(theme::FG_SECONDARY))
+                        .child("Empty"),
+                );
+            }
+            if let Some(scan) = &self.scan {
+                let base = scan
+                    .tree
+                    .path_of(&scan.root, &self.focus)
+                    .unwrap_or_default();
+                let largest = node.largest_files(&base, LARGEST_FILES);
+                if !largest.is_empty() {
+                    list = list.child(
+                        div()
+                            .mt(px(16.0))
+                            .px(px(8.0))
+                            .pb(px(4.0))
+                            .text_xs()
+                            .font_weight(gpui::FontWeight::BOLD)
+                            .text_color(rgb(theme::FG_SECONDARY))
+                            .child("Largest files"),
+                    );
+                }
+                for (path, file) in largest {
+                    let rel = path.strip_prefix(&base).unwrap_or(&path);
+                    list = list.child(
+                        div()
+                            .flex()
+                            .gap_2()
+                            .px(px(8.0))
+                            .py(px(2.0))
+                            .text_xs()
+                            .child(
+                                div()
+                                    .flex_1()
+                                    .min_w(px(0.0))
+                                    .overflow_hidden()
+                                    .whitespace_nowrap()
+                                    .text_color(rgb(theme::FG))
+                                    .child(rel.display().to_string()),
+                            )
+                            .child(
+                                div()
+                                    .text_color(rgb(theme::FG_SECONDARY))
+                                    .child(human_bytes(file.on_disk)),
+                            ),
+                    );
+                }
+            }
+        }
+        div()
+            .id("usage-ranking")
+            .w(px(LIST_WIDTH))
+            .h_full()
+            .flex_none()
+            .overflow_y_scroll()
+            .child(list)
+    }
+
+    fn render_rank_row(
+        &self,
+        ix: usize,
+        child: &UsageNode,
+        total: u64,
+        cx: &mut Context<Self>,
+    ) -> impl IntoElement {
+        let share = child.on_disk as f32 / total as f32;
+        let selected = self.selected == Some(ix);
+        let icon = if child.kind == FileKind::Dir {
+            IconName::Folder
+        } else {
+            IconName::File
+        };
+        div()
+            .flex()
+            .items_center()
+            .gap_2()
+            .px(px(8.0))
+            .py(px(4.0))
+            .rounded(px(4.0))
+            .cursor_pointer()
+            .when(selected, |this| this.bg(rgb(theme::BG_HOVER)))
+            .hover(|this| this.bg(rgb(theme::BG_HOVER)))
+            .on_mouse_down(
+                gpui::MouseButton::Left,
+                cx.listener(move |this, event: &gpui::MouseDownEvent, _window, cx| {
+                    this.select(ix, event.click_count >= 2, cx);
+                }),
+            )
+            .child(
+                Icon::new(icon)
+                    .size_4()
+                    .text_color(rgb(theme::FG_SECONDARY)),
+            )
+            .child(
+                div()
+                    .flex_1()
+                    .min_w(px(0.0))
+                    .overflow_hidden()
+                    .whitespace_nowrap()
+                    .text_sm()
+                    .text_color(rgb(theme::FG))
+                    .child(child.name.clone()),
+            )
+            .child(
+                div()
+                    .w(px(80.0))
+                    .h(px(6.0))
+                    .rounded(px(3.0))
+                    .bg(rgb(theme::GRAY_100))
+                    .child(
+                        div()
+                            .w(px(80.0 * share))
+                            .h_full()
+                            .rounded(px(3.0))
+                            .bg(rgb(theme::ACCENT)),
+                    ),
+            )
+            .child(
+                div()
+                    .w(px(40.0))
+                    .text_xs()
+                    .text_color(rgb(theme::FG_SECONDARY))
+                    .child(format!("{:.0}%", share * 100.0)),
+            )
+            .child(
+                div()
+                    .w(px(72.0))
+                    .text_xs()
+                    .text_color(rgb(theme::FG))
+                    .child(human_bytes(child.on_disk)),
+            )
+    }
+
+    /// One tile per child of the focused directory. Double-clicking a directory drills into
+    /// it.
+    fn render_treemap(&self, window: &mut Window, cx: &mut Context<Self>) -> impl IntoElement {
+        let viewport = window.viewport_size();
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            w: (f32::from(viewport.width) - TREEMAP_CHROME.0).max(0.0),
+            h: (f32::from(viewport.height) - TREEMAP_CHROME.1).max(0.0),
+        };
+        let mut map = div()
+            .relative()
+            .w(px(bounds.w))
+            .h(px(bounds.h))
+            .bg(rgb(theme::BG_SECONDARY));
+        if let Some(node) = self.focused() {
+            let sizes: Vec<u64> = node.children.iter().map(|c| c.on_disk).collect();
+            let tiles = usage::squarify(&sizes, bounds);
+            for (ix, (child, tile)) in node.children.iter().zip(tiles).enumerate() {
+                if tile.w < 1.0 || tile.h < 1.0 {
+                    continue;
+                }
+                let selected = self.selected == Some(ix);
+                let labelled = tile.w >= MIN_LABEL_SIZE.0 && tile.h >= MIN_LABEL_SIZE.1;
+                let color = if child.kind == FileKind::Dir {
+                    TILE_COLORS[ix % TILE_COLORS.len()]
+                } else {
+                    theme::GRAY_300
+                };
+                map = map.child(
+                    div()
+                        .absolute()
+                        .left(px(tile.x))
+                        .top(px(tile.y))
+                        .w(px(tile.w))
+                        .h(px(tile.h))
+                        .p(px(4.0))
+                        .overflow_hidden()
+                        .border_1()
+                        .border_color(rgb(if selected { theme::FG } else { theme::WHITE }))
+                        .bg(rgb(color))
+                        .cursor_pointer()
+                        .on_mouse_down(
+                            gpui::MouseButton::Left,
+                            cx.listener(move |this, event: &gpui::MouseDownEvent, _window, cx| {
+                                this.select(ix, event.click_count >= 2, cx);
+                            }),
+                        )
+                        .when(labelled, |this| {
+                            this.child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(theme::FG))
+                                    .whitespace_nowrap()
+                                    .child(child.name.clone()),
+                            )
+                            .child(
+                                div()
+                                    .text_xs()
+                                    .text_color(rgb(theme::GRAY_700))
+                                    .child(human_bytes(child.on_disk)),
+                            )
+                        }),
+                );
+            }
+        }
+        div().flex_1().min_w(px(0.0)).child(map)
+    }
+}
+
+impl crate::pages::Page for DiskUsagePage {
+    fn render(&mut self, window: &mut Window, cx: &mut Context<Self>) -> AnyElement {
+        <Self as Render>::render(self, window, cx).into_any_element()
+    }
+}
@@ -0,0 +1,690 @@
+//! Frecency: how often and how recently paths were used.
+//!
+//! Every open, navigation and preview adds to the path's rank, and its score is that rank
+//! weighted by how long ago it was last used, as in zoxide: four times within the hour,
+//! twice within the day, half within the week and a quarter after that. Once the ranks
+//! add up to more than [`Scoring::max_total_rank`], all of them are scaled down and those
+//! that drop below one are forgotten, so old habits fade.
+//!
+//! Records live in a sled database in the data directory. The scoring itself is plain
+//! functions on [`Scoring`] and [`Record`], so it can be checked and tuned without one.
+
+use crate::core::errors::{Error, Result};
+use crate::core::paths;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const HOUR: u64 = 60 * 60;
+const DAY: u64 = 24 * HOUR;
+const WEEK: u64 = 7 * DAY;
+
+/// A use of a path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    /// Opened, e.g. by double-clicking a file.
+    Open,
+    /// Browsed into as the current directory.
+    Navigate,
+    /// Shown in the preview pane.
+    Preview,
+}
+
+/// Tunables of the score.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scoring {
+    /// Rank added by each kind of access.
+    pub open_weight: f64,
+    pub navigate_weight: f64,
+    pub preview_weight: f64,
+    /// Total rank above which every rank is scaled down.
+    pub max_total_rank: f64,
+    /// Fuzzy match points a score of `e - 1` is worth; grows with the log of the score.
+    pub boost_scale: f64,
+}
+
+impl Default for Scoring {
+    fn default() -> Self {
+        Self {
+            open_weight: 1.0,
+            navigate_weight: 1.0,
+            // Selecting a file previews it, so previews come cheap and count for less.
+            preview_weight: 0.25,
+            max_total_rank: 10_000.0,
+            boost_scale: 8.0,
+        }
+    }
+}
+
+/// What is remembered about a path.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub rank: f64,
+    /// Seconds since the Unix epoch.
+    pub last_access: u64,
+}
+
+impl Scoring {
+    pub fn weight(&self, access: Access) -> f64 {
+        match access {
+            Access::Open => self.open_weight,
+            Access::Navigate => self.navigate_weight,
+            Access::Preview => self.preview_weight,
+        }
+    }
+
+    /// `record` after one more `access` at `now`.
+    pub fn accessed(&self, record: Option<Record>, access: Access, now: SystemTime) -> Record {
+        Record {
+            rank: record.map_or(0.0, |r| r.rank) + self.weight(access),
+            last_access: unix_secs(now),
+        }
+    }
+
+    /// The frecency of `record` as of `now`.
+    pub fn score(&self, record: &Record, now: SystemTime) -> f64 {
+        let age = unix_secs(now).saturating_sub(record.last_access);
+        record.rank * recency_factor(Duration::from_secs(age))
+    }
+
+    /// What every rank has to be multiplied by once they add up to `total_rank`, or `None`
+    /// while it is within bounds. Scales to 90% of the bound so aging is not redone on
+    /// every access.
+    pub fn aging_factor(&self, total_rank: f64) -> Option<f64> {
+        (total_rank > self.max_total_rank).then(|| 0.9 * self.max_total_rank / total_rank)
+    }
+
+    /// Points added to a fuzzy match score for a path with frecency `score`.
+    pub fn boost(&self, score: f64) -> i32 {
+        (score.max(0.0).ln_1p() * self.boost_scale).round() as i32
+    }
+
+    /// What a relevance score, such as the index's, is multiplied by for a path with
+    /// frecency `score`. One for paths never used.
+    pub fn relevance_factor(&self, score: f64) -> f64 {
+        1.0 + score.max(0.0).ln_1p()
+    }
+}
+
+/// zoxide's recency weights.
+pub fn recency_factor(age: Duration) -> f64 {
+    match age.as_secs() {
+        a if a < HOUR => 4.0,
+        a if a < DAY => 2.0,
+        a if a < WEEK => 0.5,
+        _ => 0.25,
+    }
+}
+
+/// Whether `path` matches jump `keywords` the way zoxide does: case-insensitively, each
+/// keyword occurs in the path after the previous one, and the last one occurs in the
+/// last component. No keywords match everything.
+pub fn matches_keywords(path: &Path, keywords: &[&str]) -> bool {
+    let path = path.to_string_lossy().to_lowercase();
+    let Some(last) = keywords.last() else {
+        return true;
+    };
+    let last_component = path
+        .trim_end_matches(std::path::MAIN_SEPARATOR)
+        .rsplit(std::path::MAIN_SEPARATOR)
+        .next()
+        .unwrap_or_default();
+    if !last_component.contains(&last.to_lowercase()) {
+        return false;
+    }
+    let mut rest = path.as_str();
+    for keyword in keywords {
+        let keyword = keyword.to_lowercase();
+        match rest.find(&keyword) {
+            Some(at) => rest = &rest[at + keyword.len()..],
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Frecency scores of every recorded path at one moment, for ranking many candidates.
+#[derive(Debug, Clone, Default)]
+pub struct Scores {
+    /// Score and last access of each path.
+    scores: Arc<HashMap<PathBuf, (f64, u64)>>,
+    scoring: Scoring,
+}
+
+impl Scores {
+    /// The score of `path`; zero if it was never used.
+    pub fn get(&self, path: &Path) -> f64 {
+        self.scores.get(path).map_or(0.0, |&(score, _)| score)
+    }
+
+    /// Points to add to `path`'s fuzzy match score.
+    pub fn boost(&self, path: &Path) -> i32 {
+        self.scoring.boost(self.get(path))
+    }
+
+    /// What `path`'s relevance score is multiplied by.
+    pub fn relevance_factor(&self, path: &Path) -> f64 {
+        self.scoring.relevance_factor(self.get(path))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+
+    /// The highest-scoring existing directory matching `keywords` (see
+    /// [`matches_keywords`]), other than `exclude`, e.g. the current directory. Equal
+    /// scores go to the path used last, then in path order.
+    pub fn jump(&self, keywords: &[&str], exclude: Option<&Path>) -> Option<PathBuf> {
+        let mut ranked: Vec<(&PathBuf, &(f64, u64))> = self
+            .scores
+            .iter()
+            .filter(|(path, _)| Some(path.as_path()) != exclude && matches_keywords(path, keywords))
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.1 .0
+                .total_cmp(&a.1 .0)
+                .then(b.1 .1.cmp(&a.1 .1))
+                .then_with(|| a.0.cmp(b.0))
+        });
+        ranked
+            .into_iter()
+            .map(|(path, _)| path)
+            .find(|path| path.is_dir())
+            .cloned()
+    }
+}
+
+/// The frecency database.
+pub struct Frecency {
+    db: sled::Db,
+    scoring: Scoring,
+    /// Sum of all ranks, kept to know when to age. Also serializes updates.
+    total_rank: Mutex<f64>,
+}
+
+impl Frecency {
+    /// Open the database at `path`, creating it if needed.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let db = sled::open(path.as_ref()).map_err(db_error)?;
+        let frecency = Self {
+            db,
+            scoring: Scoring::default(),
+            total_rank: Mutex::new(0.0),
+        };
+        let total = frecency.records()?.iter().map(|(_, r)| r.rank).sum();
+        *frecency.lock_total() = total;
+        Ok(frecency)
+    }
+
+    /// Open the database in the nohrs data directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(paths::data_dir().join("frecency"))
+    }
+
+    /// The database in the data directory, opened once and shared while anyone holds it.
+    /// sled locks its directory, so opening it twice would fail.
+    pub fn shared() -> Result<Arc<Self>> {
+        static SHARED: Mutex<Weak<Frecency>> = Mutex::new(Weak::new());
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(frecency) = shared.upgrade() {
+            return Ok(frecency);
+        }
+        let frecency = Arc::new(Self::open_default()?);
+        *shared = Arc::downgrade(&frecency);
+        Ok(frecency)
+    }
+
+    /// Score with `scoring` instead of the defaults.
+    pub fn with_scoring(mut self, scoring: Scoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    pub fn scoring(&self) -> &Scoring {
+        &self.scoring
+    }
+
+    /// Note a use of `path`.
+    pub fn record(&self, path: &Path, access: Access) -> Result<()> {
+        self.record_at(path, access, SystemTime::now())
+    }
+
+    /// Note a use of `path` at `now`.
+    pub fn record_at(&self, path: &Path, access: Access, now: SystemTime) -> Result<()> {
+        let mut total = self.lock_total();
+        let key = path_key(path);
+        let record = self.db.get(&key).map_err(db_error)?.and_then(decode);
+        let updated = self.scoring.accessed(record, access, now);
+        self.db.insert(&key, encode(&updated)?).map_err(db_error)?;
+        *total += updated.rank - record.map_or(0.0, |r| r.rank);
+        if let Some(factor) = self.scoring.aging_factor(*total) {
+            *total = self.age(factor)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, path: &Path) -> Result<Option<Record>> {
+        Ok(self
+            .db
+            .get(path_key(path))
+            .map_err(db_error)?
+            .and_then(decode))
+    }
+
+    /// Forget `path`. Returns whether it was known.
+    pub fn remove(&self, path: &Path) -> Result<bool> {
+        let mut total = self.lock_total();
+        let Some(record) = self.db.remove(path_key(path)).map_err(db_error)? else {
+            return Ok(false);
+        };
+        if let Some(record) = decode(record) {
+            *total -= record.rank;
+        }
+        Ok(true)
+    }
+
+    /// Scores of every recorded path as of `now`.
+    pub fn scores(&self, now: SystemTime) -> Result<Scores> {
+        let scores = self
+            .records()?
+            .into_iter()
+            .map(|(path, record)| {
+                let score = self.scoring.score(&record, now);
+                (path, (score, record.last_access))
+            })
+            .collect();
+        Ok(Scores {
+            scores: Arc::new(scores),
+            scoring: self.scoring,
+        })
+    }
+
+    /// The `limit` highest-scoring paths that still exist, best first.
+    pub fn top(&self, limit: usize, now: SystemTime) -> Result<Vec<(PathBuf, f64)>> {
+        Ok(self
+            .ranked(now, |_| true)?
+            .into_iter()
+            .filter(|(path, _)| path.symlink_metadata().is_ok())
+            .take(limit)
+            .collect())
+    }
+
+    /// [`Scores::jump`] as of `now`.
+    pub fn jump(
+        &self,
+        keywords: &[&str],
+        exclude: Option<&Path>,
+        now: SystemTime,
+    ) -> Result<Option<PathBuf>> {
+        Ok(self.scores(now)?.jump(keywords, exclude))
+    }
+
+    /// The recorded paths `keep` accepts with their scores as of `now`, best first.
+    /// Equal scores go to the path used last, then in path order.
+    fn ranked(&self, now: SystemTime, keep: impl Fn(&Path) -> bool) -> Result<Vec<(PathBuf, f64)>> {
+        let mut ranked: Vec<(PathBuf, Record, f64)> = self
+            .records()?
+            .into_iter()
+            .filter(|(path, _)| keep(path))
+            .map(|(path, record)| {
+                let score = self.scoring.score(&record, now);
+                (path, record, score)
+            })
+            .collect();
+        ranked.sort_by(|a, b| {
+            b.2.total_cmp(&a.2)
+                .then(b.1.last_access.cmp(&a.1.last_access))
+                .then_with(|| a.0.cmp(&b.0))
+        });
+        Ok(ranked
+            .into_iter()
+            .map(|(path, _, score)| (path, score))
+            .collect())
+    }
+
+    fn records(&self) -> Result<Vec<(PathBuf, Record)>> {
+        let mut records = Vec::new();
+        for item in self.db.iter() {
+            let (key, value) = item.map_err(db_error)?;
+            if let Some(record) = decode(value) {
+                records.push((key_path(&key), record));
+            }
+        }
+        Ok(records)
+    }
+
+    /// Multiply every rank by `factor` and drop those falling below one. Returns the new
+    /// total.
+    fn age(&self, factor: f64) -> Result<f64> {
+        let mut total = 0.0;
+        for (path, mut record) in self.records()? {
+            record.rank *= factor;
+            let key = path_key(&path);
+            if record.rank < 1.0 {
+                self.db.remove(&key).map_err(db_error)?;
+            } else {
+                self.db.insert(&key, encode(&record)?).map_err(db_error)?;
+                total += record.rank;
+            }
+        }
+        Ok(total)
+    }
+
+    fn lock_total(&self) -> std::sync::MutexGuard<'_, f64> {
+        self.total_rank.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Scores from the [shared](Frecency::shared) database as of now. Empty if it cannot be
+/// read, so ranking goes on without it.
+pub fn current_scores() -> Scores {
+    Frecency::shared()
+        .and_then(|frecency| frecency.scores(SystemTime::now()))
+        .unwrap_or_else(|err| {
+            tracing::warn!(%err, "frecency unavailable");
+            Scores::default()
+        })
+}
+
+fn unix_secs(t: SystemTime) -> u64 {
+    t.duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+fn encode(record: &Record) -> Result<Vec<u8>> {
+    serde_json::to_vec(record).map_err(|e| Error::Other(format!("cannot encode record: {e}")))
+}
+
+/// A stored record, or `None` for a value this version cannot read.
+fn decode(value: impl AsRef<[u8]>) -> Option<Record> {
+    serde_json::from_slice(value.as_ref())
+        .map_err(|err| tracing::debug!(%err, "skipping unreadable frecency record"))
+        .ok()
+}
+
+/// Paths are stored by their raw bytes, so names that are not UTF-8 survive.
+#[cfg(unix)]
+fn path_key(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_key(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+#[cfg(unix)]
+fn key_path(key: &[u8]) -> PathBuf {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(OsString::from_vec(key.to_vec()))
+}
+
+#[cfg(not(unix))]
+fn key_path(key: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(key).into_owned())
+}
+
+fn db_error(err: impl std::fmt::Display) -> Error {
+    Error::Database(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn open(tmp: &TempDir) -> Frecency {
+        Frecency::open(tmp.path().join("db")).unwrap()
+    }
+
+    #[test]
+    fn accesses_add_their_weight() {
+        let scoring = Scoring::default();
+        assert_eq!(scoring.weight(Access::Open), 1.0);
+        assert_eq!(scoring.weight(Access::Navigate), 1.0);
+        assert_eq!(scoring.weight(Access::Preview), 0.25);
+
+        let first = scoring.accessed(None, Access::Preview, at(100));
+        assert_eq!(
+            first,
+            Record {
+                rank: 0.25,
+                last_access: 100
+            }
+        );
+        let second = scoring.accessed(Some(first), Access::Open, at(200));
+        assert_eq!(
+            second,
+            Record {
+                rank: 1.25,
+                last_access: 200
+            }
+        );
+    }
+
+    #[test]
+    fn recency_buckets() {
+        let factor = |secs| recency_factor(Duration::from_secs(secs));
+        assert_eq!(factor(0), 4.0);
+        assert_eq!(factor(HOUR - 1), 4.0);
+        assert_eq!(factor(HOUR), 2.0);
+        assert_eq!(factor(DAY - 1), 2.0);
+        assert_eq!(factor(DAY), 0.5);
+        assert_eq!(factor(WEEK - 1), 0.5);
+        assert_eq!(factor(WEEK), 0.25);
+        assert_eq!(factor(100 * WEEK), 0.25);
+    }
+
+    #[test]
+    fn scores_weight_rank_by_recency() {
+        let scoring = Scoring::default();
+        let record = Record {
+            rank: 3.0,
+            last_access: DAY,
+        };
+        assert_eq!(scoring.score(&record, at(DAY + 60)), 12.0);
+        assert_eq!(scoring.score(&record, at(DAY + 2 * HOUR)), 6.0);
+        assert_eq!(scoring.score(&record, at(3 * DAY)), 1.5);
+        assert_eq!(scoring.score(&record, at(9 * DAY)), 0.75);
+        // A last access in the future, after the clock moved back, counts as now.
+        assert_eq!(scoring.score(&record, at(0)), 12.0);
+    }
+
+    #[test]
+    fn boosts_grow_with_the_log_of_the_score() {
+        let scoring = Scoring::default();
+        assert_eq!(scoring.boost(0.0), 0);
+        assert_eq!(scoring.boost(-5.0), 0);
+        assert_eq!(scoring.boost(std::f64::consts::E - 1.0), 8);
+        assert!(scoring.boost(100.0) > scoring.boost(10.0));
+        assert_eq!(scoring.relevance_factor(0.0), 1.0);
+        assert!((scoring.relevance_factor(std::f64::consts::E - 1.0) - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn aging_starts_above_the_bound() {
+        let scoring = Scoring::default();
+        assert_eq!(scoring.aging_factor(10_000.0), None);
+        assert_eq!(scoring.aging_factor(20_000.0), Some(0.45));
+    }
+
+    #[test]
+    fn keywords_match_in_order_and_end_in_the_last_component() {
+        let path = Path::new("/home/me/Projects/nohrs/src");
+        assert!(matches_keywords(path, &[]));
+        assert!(matches_keywords(path, &["src"]));
+        assert!(matches_keywords(path, &["proj", "src"]));
+        assert!(matches_keywords(path, &["PROJ", "Sr"]));
+        // The last keyword has to be in the last component.
+        assert!(!matches_keywords(path, &["nohrs"]));
+        assert!(!matches_keywords(path, &["src", "proj"]));
+        // In order, and each after the end of the previous one.
+        assert!(!matches_keywords(path, &["src", "src"]));
+        assert!(matches_keywords(Path::new("/a/src/src"), &["src", "src"]));
+        assert!(matches_keywords(Path::new("/a/docs/"), &["docs"]));
+    }
+
+    #[test]
+    fn records_are_kept_and_forgotten() {
+        let tmp = TempDir::new();
+        let frecency = open(&tmp);
+        let path = Path::new("/some/where");
+        assert_eq!(frecency.get(path).unwrap(), None);
+        frecency.record_at(path, Access::Open, at(10)).unwrap();
+        frecency.record_at(path, Access::Navigate, at(20)).unwrap();
+        assert_eq!(
+            frecency.get(path).unwrap(),
+            Some(Record {
+                rank: 2.0,
+                last_access: 20
+            })
+        );
+
+        let scores = frecency.scores(at(20)).unwrap();
+        assert!(!scores.is_empty());
+        assert_eq!(scores.get(path), 8.0);
+        assert_eq!(scores.get(Path::new("/never")), 0.0);
+        assert!(scores.boost(path) > 0);
+        assert_eq!(scores.relevance_factor(Path::new("/never")), 1.0);
+
+        assert!(frecency.remove(path).unwrap());
+        assert!(!frecency.remove(path).unwrap());
+        assert!(frecency.scores(at(20)).unwrap().is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_that_are_not_utf8_survive() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+        let tmp = TempDir::new();
+        let frecency = open(&tmp);
+        let path = Path::new(OsStr::from_bytes(b"/odd/\xff\xfe"));
+        frecency.record_at(path, Access::Open, at(1)).unwrap();
+        assert_eq!(frecency.records().unwrap()[0].0, path);
+    }
+
+    #[test]
+    fn aging_scales_ranks_and_prunes_the_faded() {
+        let tmp = TempDir::new();
+        let frecency = open(&tmp).with_scoring(Scoring {
+            max_total_rank: 10.0,
+            ..Scoring::default()
+        });
+        let (a, b, c) = (Path::new("/a"), Path::new("/b"), Path::new("/c"));
+        for _ in 0..8 {
+            frecency.record_at(a, Access::Open, at(1)).unwrap();
+        }
+        frecency.record_at(b, Access::Open, at(1)).unwrap();
+        frecency.record_at(b, Access::Open, at(1)).unwrap();
+        // At the bound: nothing happens yet.
+        assert_eq!(frecency.get(a).unwrap().unwrap().rank, 8.0);
+
+        // Now over it: every rank is scaled to 90% of the bound in total.
+        frecency.record_at(c, Access::Open, at(1)).unwrap();
+        let factor = 9.0 / 11.0;
+        let rank = |p| frecency.get(p).unwrap().map(|r| r.rank);
+        assert!((rank(a).unwrap() - 8.0 * factor).abs() < 1e-9);
+        assert!((rank(b).unwrap() - 2.0 * factor).abs() < 1e-9);
+        assert_eq!(rank(c), None);
+        assert_eq!(*frecency.lock_total(), rank(a).unwrap() + rank(b).unwrap());
+    }
+
+    #[test]
+    fn top_lists_existing_paths_best_first() {
+        let tmp = TempDir::new();
+        let frecency = open(&tmp);
+        let (x, y) = (tmp.mkdir("x"), tmp.write("y.txt", ""));
+        let gone = tmp.path().join("gone");
+        frecency.record_at(&x, Access::Open, at(DAY)).unwrap();
+        frecency.record_at(&y, Access::Open, at(DAY)).unwrap();
+        frecency.record_at(&y, Access::Open, at(DAY)).unwrap();
+        for _ in 0..5 {
+            frecency.record_at(&gone, Access::Open, at(DAY)).unwrap();
+        }
+        let top = frecency.top(10, at(DAY)).unwrap();
+        assert_eq!(top, [(y.clone(), 8.0), (x, 4.0)]);
+        assert_eq!(frecency.top(1, at(DAY)).unwrap(), [(y, 8.0)]);
+    }
+
+    #[test]
+    fn jump_picks_the_best_matching_directory() {
+        let tmp = TempDir::new();
+        let frecency = open(&tmp);
+        let docs = tmp.mkdir("work/docs");
+        let other_docs = tmp.mkdir("home/docs");
+        let file = tmp.write("notes/docs", "");
+        let now = at(10 * DAY);
+        for _ in 0..3 {
+            frecency.record_at(&docs, Access::Navigate, now).unwrap();
+        }
+        frecency
+            .record_at(&other_docs, Access::Navigate, now)
+            .unwrap();
+        for _ in 0..9 {
+            frecency.record_at(&file, Access::Open, now).unwrap();
+        }
+
+        // Files never win, however often they were used.
+        assert_eq!(
+            frecency.jump(&["docs"], None, now).unwrap(),
+            Some(docs.clone())
+        );
+        assert_eq!(
+            frecency.jump(&["docs"], Some(&docs), now).unwrap(),
+            Some(other_docs.clone())
+        );
+        assert_eq!(
+            frecency.jump(&["home", "docs"], None, now).unwrap(),
+            Some(other_docs)
+        );
+        assert_eq!(frecency.jump(&["nothing"], None, now).unwrap(), None);
+
+        // A snapshot answers the same without going back to the database.
+        let scores = frecency.scores(now).unwrap();
+        drop(frecency);
+        assert_eq!(scores.jump(&["docs"], None), Some(docs));
+    }
+
+    #[test]
+    fn jump_ties_go_to_the_latest_use_then_the_path() {
+        let tmp = TempDir::new();
+        let frecency = open(&tmp);
+        let alpha = tmp.mkdir("proj-alpha");
+        let beta = tmp.mkdir("proj-beta");
+        let gamma = tmp.mkdir("proj-gamma");
+        let now = at(DAY);
+        // All used once within the hour, so all score the same.
+        frecency
+            .record_at(&alpha, Access::Navigate, at(DAY - 100))
+            .unwrap();
+        frecency
+            .record_at(&beta, Access::Navigate, at(DAY - 10))
+            .unwrap();
+        frecency
+            .record_at(&gamma, Access::Navigate, at(DAY - 10))
+            .unwrap();
+        assert_eq!(
+            frecency.jump(&["proj"], None, now).unwrap(),
+            Some(beta.clone())
+        );
+        assert_eq!(
+            frecency.jump(&["proj"], Some(&beta), now).unwrap(),
+            Some(gamma)
+        );
+        let top: Vec<PathBuf> = frecency
+            .top(3, now)
+            .unwrap()
+            .into_iter()
+            .map(|(p, _)| p)
+            .collect();
+        assert_eq!(top[2], alpha);
+    }
+}
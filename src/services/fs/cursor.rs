@@ -0,0 +1,202 @@
+//! Opaque pagination cursors for directory listings.
+//!
+//! A cursor records the sort key of the last entry handed out instead of an offset, so the
+//! next page resumes strictly after that entry even if files were created or removed in
+//! between. It also carries a fingerprint of the directory so callers can be told when the
+//! listing changed underneath them, and the filter settings so it cannot be used to page
+//! through a differently filtered listing.
+
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::{FileEntry, FileKind};
+use crate::services::fs::sort::SortOptions;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+const CURSOR_VERSION: u8 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Cursor {
+    v: u8,
+    pub sort: SortOptions,
+    pub filter: FilterKey,
+    pub last: LastSeen,
+    pub dir: DirFingerprint,
+}
+
+/// The listing options besides the sort that decide which entries are paged through.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct FilterKey {
+    pub show_hidden: bool,
+    /// The name filter, lowercased since it matches case-insensitively.
+    pub pattern: Option<String>,
+}
+
+impl FilterKey {
+    pub fn new(show_hidden: bool, pattern: Option<&str>) -> Self {
+        Self {
+            show_hidden,
+            pattern: pattern.filter(|p| !p.is_empty()).map(str::to_lowercase),
+        }
+    }
+}
+
+/// The fields of the last returned entry that take part in ordering.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct LastSeen {
+    name: String,
+    path: String,
+    /// Raw path bytes, which break ties between colliding lossy paths.
+    #[serde(default)]
+    raw_path: Option<String>,
+    kind: FileKind,
+    size: u64,
+    modified: u64,
+}
+
+impl LastSeen {
+    pub fn from_entry(e: &FileEntry) -> Self {
+        Self {
+            name: e.name.clone(),
+            path: e.path.clone(),
+            raw_path: e.raw_path.clone(),
+            kind: e.kind,
+            size: e.size,
+            modified: e.modified,
+        }
+    }
+
+    /// Rebuild an entry that compares equal to the last returned one.
+    pub fn to_entry(&self) -> FileEntry {
+        FileEntry {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            raw_path: self.raw_path.clone(),
+            kind: self.kind,
+            size: self.size,
+            modified: self.modified,
+            ..Default::default()
+        }
+    }
+}
+
+/// Cheap identity of a directory's contents: any create, delete or rename bumps the mtime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct DirFingerprint {
+    mtime_ns: u128,
+    ino: u64,
+    dev: u64,
+}
+
+impl DirFingerprint {
+    pub fn of(dir: &Path) -> Result<Self> {
+        let md = fs::metadata(dir).map_err(|e| Error::from_io(dir, e))?;
+        let mtime_ns = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or(0);
+        #[cfg(unix)]
+        let (ino, dev) = {
+            use std::os::unix::fs::MetadataExt;
+            (md.ino(), md.dev())
+        };
+        #[cfg(not(unix))]
+        let (ino, dev) = (0, 0);
+        Ok(Self { mtime_ns, ino, dev })
+    }
+}
+
+impl Cursor {
+    pub fn new(
+        sort: SortOptions,
+        filter: FilterKey,
+        last: &FileEntry,
+        dir: DirFingerprint,
+    ) -> Self {
+        Self {
+            v: CURSOR_VERSION,
+            sort,
+            filter,
+            last: LastSeen::from_entry(last),
+            dir,
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        // Serializing plain data cannot fail.
+        let json = serde_json::to_vec(self).expect("serialize cursor");
+        URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(s: &str) -> Result<Self> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(s)
+            .map_err(|e| Error::InvalidCursor(e.to_string()))?;
+        let cursor: Cursor =
+            serde_json::from_slice(&bytes).map_err(|e| Error::InvalidCursor(e.to_string()))?;
+        if cursor.v != CURSOR_VERSION {
+            return Err(Error::InvalidCursor(format!(
+                "unsupported cursor version {}",
+                cursor.v
+            )));
+        }
+        Ok(cursor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn cursor(dir: &Path) -> Cursor {
+        let last = FileEntry {
+            name: "b".into(),
+            path: dir.join("b").to_string_lossy().into_owned(),
+            size: 3,
+            ..Default::default()
+        };
+        Cursor::new(
+            SortOptions::default(),
+            FilterKey::new(true, Some("*.TXT")),
+            &last,
+            DirFingerprint::of(dir).unwrap(),
+        )
+    }
+
+    #[test]
+    fn round_trips() {
+        let tmp = TempDir::new();
+        let c = cursor(tmp.path());
+        let decoded = Cursor::decode(&c.encode()).unwrap();
+        assert_eq!(decoded.sort, c.sort);
+        assert_eq!(decoded.filter, FilterKey::new(true, Some("*.txt")));
+        assert_eq!(decoded.dir, c.dir);
+        assert_eq!(decoded.last.to_entry().size, 3);
+    }
+
+    #[test]
+    fn empty_filter_is_no_filter() {
+        assert_eq!(FilterKey::new(false, Some("")), FilterKey::new(false, None));
+    }
+
+    #[test]
+    fn rejects_garbage_and_other_versions() {
+        assert!(matches!(
+            Cursor::decode("not a cursor!"),
+            Err(Error::InvalidCursor(_))
+        ));
+        let tmp = TempDir::new();
+        let mut c = cursor(tmp.path());
+        c.v = CURSOR_VERSION + 1;
+        assert!(matches!(
+            Cursor::decode(&c.encode()),
+            Err(Error::InvalidCursor(_))
+        ));
+    }
+}
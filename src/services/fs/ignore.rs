@@ -0,0 +1,396 @@
+//! `.gitignore`-style ignore rules.
+//!
+//! Supports the gitignore pattern syntax: `#` comments, `!` negation, a trailing `/` for
+//! directories only, patterns anchored by a `/`, and the `*`, `?`, `[...]` and `**`
+//! wildcards. Each directory may hold several ignore files; later files in
+//! [`IGNORE_FILES`] take precedence over earlier ones, and within a file the last
+//! matching pattern wins.
+
+use std::fs;
+use std::path::Path;
+
+/// Ignore files read from each directory, lowest precedence first.
+pub const IGNORE_FILES: [&str; 3] = [".gitignore", ".ignore", ".nohrsignore"];
+
+/// Outcome of matching a path against a set of rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    Ignore,
+    /// Re-included by a `!` pattern, overriding rules from further up.
+    Include,
+}
+
+/// The ignore rules of one directory.
+#[derive(Debug, Clone, Default)]
+pub struct IgnoreRules {
+    rules: Vec<Rule>,
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    tokens: Vec<Token>,
+    negated: bool,
+    dir_only: bool,
+    /// Matched against the whole relative path rather than just the file name.
+    anchored: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    /// `?`: one character other than `/`.
+    Any,
+    /// `*`: any run of characters other than `/`.
+    Star,
+    /// `**/`: nothing, or any run of whole directories.
+    AnyDirs,
+    /// Trailing `/**`'s `**`: everything below.
+    GlobStar,
+    Class {
+        negated: bool,
+        ranges: Vec<(char, char)>,
+    },
+}
+
+impl IgnoreRules {
+    /// Read the ignore files in `dir`. Returns `None` when it has none with any rules.
+    pub fn load(dir: &Path) -> Option<Self> {
+        let mut rules = Self::default();
+        for name in IGNORE_FILES {
+            let path = dir.join(name);
+            match fs::read(&path) {
+                Ok(data) => rules.add_patterns(&String::from_utf8_lossy(&data)),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    tracing::debug!(path = %path.display(), %err, "skipping unreadable ignore file");
+                }
+            }
+        }
+        (!rules.rules.is_empty()).then_some(rules)
+    }
+
+    /// Add the patterns in `text`, one per line, with higher precedence than existing ones.
+    pub fn add_patterns(&mut self, text: &str) {
+        self.rules.extend(text.lines().filter_map(parse_rule));
+    }
+
+    /// Match `rel`, a `/`-separated path relative to the directory the rules came from.
+    pub fn matched(&self, rel: &str, is_dir: bool) -> Option<Verdict> {
+        let full: Vec<char> = rel.chars().collect();
+        let name_start = full.iter().rposition(|&c| c == '/').map_or(0, |i| i + 1);
+        self.rules.iter().rev().find_map(|rule| {
+            if rule.dir_only && !is_dir {
+                return None;
+            }
+            let text = if rule.anchored {
+                &full[..]
+            } else {
+                &full[name_start..]
+            };
+            glob(&rule.tokens, text).then_some(if rule.negated {
+                Verdict::Include
+            } else {
+                Verdict::Ignore
+            })
+        })
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    let mut pattern = line.trim_end_matches('\r');
+    // Trailing spaces are dropped unless escaped.
+    while pattern.ends_with(' ') && !pattern.ends_with("\\ ") {
+        pattern = &pattern[..pattern.len() - 1];
+    }
+    if pattern.is_empty() || pattern.starts_with('#') {
+        return None;
+    }
+    let negated = pattern.starts_with('!');
+    if negated {
+        pattern = &pattern[1..];
+    }
+    let dir_only = pattern.ends_with('/');
+    let pattern = pattern.trim_end_matches('/');
+    let anchored = pattern.contains('/');
+    let pattern = pattern.strip_prefix('/').unwrap_or(pattern);
+    if pattern.is_empty() {
+        return None;
+    }
+    Some(Rule {
+        tokens: tokenize(pattern),
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+fn tokenize(pattern: &str) -> Vec<Token> {
+    let chars: Vec<char> = pattern.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' if i + 1 < chars.len() => {
+                tokens.push(Token::Char(chars[i + 1]));
+                i += 2;
+                continue;
+            }
+            '?' => tokens.push(Token::Any),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                let at_segment_start = i == 0 || chars[i - 1] == '/';
+                i += 2;
+                while chars.get(i) == Some(&'*') {
+                    i += 1;
+                }
+                match chars.get(i) {
+                    Some('/') if at_segment_start => {
+                        tokens.push(Token::AnyDirs);
+                        i += 1;
+                    }
+                    None if at_segment_start => tokens.push(Token::GlobStar),
+                    _ => tokens.push(Token::Star),
+                }
+                continue;
+            }
+            '*' => {
+                if tokens.last() != Some(&Token::Star) {
+                    tokens.push(Token::Star);
+                }
+            }
+            '[' => {
+                if let Some((class, len)) = parse_class(&chars[i..]) {
+                    tokens.push(class);
+                    i += len;
+                    continue;
+                }
+                tokens.push(Token::Char('['));
+            }
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+/// Parse a `[...]` class at the start of `chars`, returning it and its length.
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 1;
+    let negated = matches!(chars.get(i), Some('!' | '^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let mut first = true;
+    loop {
+        let c = *chars.get(i)?;
+        if c == ']' && !first {
+            return Some((Token::Class { negated, ranges }, i + 1));
+        }
+        first = false;
+        let c = if c == '\\' {
+            i += 1;
+            *chars.get(i)?
+        } else {
+            c
+        };
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&e| e != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+/// A pattern in which only `*` and `?` are special, matched like ignore rules. The
+/// listing's name filter uses it.
+#[derive(Debug, Clone)]
+pub(crate) struct Wildcards(Vec<Token>);
+
+impl Wildcards {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let mut tokens = Vec::new();
+        for c in pattern.chars() {
+            match c {
+                '*' if tokens.last() == Some(&Token::Star) => {}
+                '*' => tokens.push(Token::Star),
+                '?' => tokens.push(Token::Any),
+                c => tokens.push(Token::Char(c)),
+            }
+        }
+        Self(tokens)
+    }
+
+    /// Whether the pattern matches the whole of `name`.
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        glob(&self.0, &name.chars().collect::<Vec<_>>())
+    }
+}
+
+/// Match `tokens` against the whole of `text`.
+///
+/// Every star can try every split of the rest of the text, so the outcome for each pair of
+/// token and text position is memoised; otherwise patterns like `*a*a*a*b` take
+/// exponential time on long names.
+fn glob(tokens: &[Token], text: &[char]) -> bool {
+    let mut memo = vec![None; (tokens.len() + 1) * (text.len() + 1)];
+    glob_from(tokens, text, 0, 0, &mut memo)
+}
+
+/// Whether `tokens[t..]` matches `text[p..]`.
+fn glob_from(
+    tokens: &[Token],
+    text: &[char],
+    t: usize,
+    p: usize,
+    memo: &mut [Option<bool>],
+) -> bool {
+    let key = t * (text.len() + 1) + p;
+    if let Some(known) = memo[key] {
+        return known;
+    }
+    let rest = &text[p..];
+    let matched = match tokens.get(t) {
+        None => rest.is_empty(),
+        Some(Token::Char(c)) => {
+            rest.first() == Some(c) && glob_from(tokens, text, t + 1, p + 1, memo)
+        }
+        Some(Token::Any) => {
+            rest.first().is_some_and(|&c| c != '/') && glob_from(tokens, text, t + 1, p + 1, memo)
+        }
+        Some(Token::Class { negated, ranges }) => {
+            rest.first().is_some_and(|&c| {
+                c != '/' && ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated
+            }) && glob_from(tokens, text, t + 1, p + 1, memo)
+        }
+        Some(Token::Star) => {
+            let end = rest.iter().position(|&c| c == '/').unwrap_or(rest.len());
+            (p..=p + end).any(|i| glob_from(tokens, text, t + 1, i, memo))
+        }
+        Some(Token::GlobStar) => (p..=text.len()).any(|i| glob_from(tokens, text, t + 1, i, memo)),
+        Some(Token::AnyDirs) => {
+            glob_from(tokens, text, t + 1, p, memo)
+                || (p..text.len())
+                    .any(|i| text[i] == '/' && glob_from(tokens, text, t + 1, i + 1, memo))
+        }
+    };
+    memo[key] = Some(matched);
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn rules(text: &str) -> IgnoreRules {
+        let mut rules = IgnoreRules::default();
+        rules.add_patterns(text);
+        rules
+    }
+
+    #[test]
+    fn unanchored_patterns_match_the_name_at_any_depth() {
+        let rules = rules("*.log\n# a comment\n\nbuild\n");
+        assert_eq!(rules.matched("app.log", false), Some(Verdict::Ignore));
+        assert_eq!(
+            rules.matched("src/deep/app.log", false),
+            Some(Verdict::Ignore)
+        );
+        assert_eq!(rules.matched("src/build", true), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("app.logs", false), None);
+        assert_eq!(rules.matched("# a comment", false), None);
+    }
+
+    #[test]
+    fn negation_and_last_match_wins() {
+        let rules = rules("*.log\n!keep.log\n");
+        assert_eq!(rules.matched("drop.log", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("keep.log", false), Some(Verdict::Include));
+
+        let rules = self::rules("!keep.log\n*.log\n");
+        assert_eq!(rules.matched("keep.log", false), Some(Verdict::Ignore));
+    }
+
+    #[test]
+    fn trailing_slash_matches_directories_only() {
+        let rules = rules("target/\n");
+        assert_eq!(rules.matched("target", true), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("target", false), None);
+    }
+
+    #[test]
+    fn slash_anchors_to_the_rules_directory() {
+        let rules = rules("/root.txt\ndocs/*.md\n");
+        assert_eq!(rules.matched("root.txt", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("sub/root.txt", false), None);
+        assert_eq!(rules.matched("docs/a.md", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("src/docs/a.md", false), None);
+        // `*` does not cross directories.
+        assert_eq!(rules.matched("docs/x/a.md", false), None);
+    }
+
+    #[test]
+    fn double_star_spans_directories() {
+        let rules = rules("**/cache\nlogs/**\na/**/z\n");
+        assert_eq!(rules.matched("cache", true), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("x/y/cache", true), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("logs/a/b.txt", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("logs", true), None);
+        assert_eq!(rules.matched("a/z", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("a/b/c/z", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("b/z", false), None);
+    }
+
+    #[test]
+    fn wildcards_and_classes() {
+        let rules = rules("file?.txt\n[abc]x\n[!0-9]y\n[a-c-]z\n");
+        assert_eq!(rules.matched("file1.txt", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("file10.txt", false), None);
+        assert_eq!(rules.matched("bx", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("dx", false), None);
+        assert_eq!(rules.matched("qy", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("5y", false), None);
+        assert_eq!(rules.matched("-z", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("dz", false), None);
+    }
+
+    #[test]
+    fn many_stars_do_not_backtrack_exponentially() {
+        let rules = rules("*a*a*a*a*a*a*a*a*b\n");
+        let name = "a".repeat(200);
+        assert_eq!(rules.matched(&name, false), None);
+        assert_eq!(
+            rules.matched(&format!("{name}b"), false),
+            Some(Verdict::Ignore)
+        );
+    }
+
+    #[test]
+    fn escapes_and_trailing_spaces() {
+        let rules = rules("\\#hash\n\\!bang\nspaced   \n");
+        assert_eq!(rules.matched("#hash", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("!bang", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("spaced", false), Some(Verdict::Ignore));
+        // An unclosed class is a literal `[`.
+        assert_eq!(
+            self::rules("a[b\n").matched("a[b", false),
+            Some(Verdict::Ignore)
+        );
+    }
+
+    #[test]
+    fn load_reads_every_ignore_file_in_precedence_order() {
+        let tmp = TempDir::new();
+        assert!(IgnoreRules::load(tmp.path()).is_none());
+        tmp.write(".gitignore", "# only comments\n");
+        assert!(IgnoreRules::load(tmp.path()).is_none());
+
+        tmp.write(".gitignore", "*.tmp\n");
+        tmp.write(".nohrsignore", "!keep.tmp\n");
+        let rules = IgnoreRules::load(tmp.path()).unwrap();
+        assert_eq!(rules.matched("a.tmp", false), Some(Verdict::Ignore));
+        assert_eq!(rules.matched("keep.tmp", false), Some(Verdict::Include));
+    }
+}
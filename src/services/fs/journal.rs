@@ -0,0 +1,895 @@
+//! Persistent undo/redo journal for file operations.
+//!
+//! Each operation is recorded as the effects it had on disk (items created, moved or
+//! trashed), including the part of a failed or cancelled one that completed. Undo reverts
+//! those effects in reverse order and redo replays them. Every effect carries a fingerprint
+//! of the item it produced, and undo/redo refuse to touch an item whose fingerprint no
+//! longer matches, so edits made since are never destroyed. The fingerprint only covers the
+//! item itself, not what is inside a directory, so undoing a copy or creation moves the
+//! item to the trash rather than deleting it.
+//!
+//! If undo or redo fails part way, the entry is split: the effects already applied move to
+//! the other stack and the rest stay where they were, so the journal always matches disk.
+//!
+//! The journal is a small JSON file that is re-read before every change under an advisory
+//! lock, which keeps several nohrs processes (windows, or the GUI and the API server) from
+//! overwriting each other's entries.
+
+use crate::core::errors::{Error, Result};
+use crate::core::paths;
+use crate::models::file_entry::{FileKind, Timestamp};
+use crate::services::fs::ops::{self, ensure_absent, ConflictPolicy, Operation, Report};
+use crate::services::trash::{self, TrashBatch};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::SystemTime;
+
+/// Entries kept on the undo stack; older ones are dropped.
+const MAX_ENTRIES: usize = 100;
+const VERSION: u32 = 1;
+
+/// State of an item as the journal last saw it.
+///
+/// Deliberately excludes the inode: moves across filesystems copy the item, which keeps
+/// size and modification time but not identity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+    pub kind: FileKind,
+    pub size: u64,
+    pub modified: Option<Timestamp>,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path) -> Result<Self> {
+        let md = fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+        Ok(Self {
+            kind: FileKind::from_file_type(md.file_type()),
+            size: md.len(),
+            modified: md.modified().ok().map(Timestamp::from_system_time),
+        })
+    }
+}
+
+/// One change an operation made on disk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Effect {
+    /// `path` was made as a copy of `source`, or empty when there is no source.
+    Created {
+        source: Option<PathBuf>,
+        path: PathBuf,
+        dir: bool,
+        fingerprint: Fingerprint,
+    },
+    /// An item was moved or renamed. `fingerprint` describes it at its current location:
+    /// `to` while the effect is applied, `from` after it has been undone.
+    Moved {
+        from: PathBuf,
+        to: PathBuf,
+        fingerprint: Fingerprint,
+    },
+    /// `original` was moved to the trash, where it has the given trash id. `fingerprint`
+    /// describes the item once undo has put it back.
+    Trashed {
+        original: PathBuf,
+        id: String,
+        fingerprint: Option<Fingerprint>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: u64,
+    pub at: Timestamp,
+    /// Short description for menus, e.g. "Copy 3 items".
+    pub label: String,
+    pub effects: Vec<Effect>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    version: u32,
+    next_id: u64,
+    undo: Vec<JournalEntry>,
+    redo: Vec<JournalEntry>,
+}
+
+pub struct Journal {
+    path: PathBuf,
+    state: State,
+}
+
+impl Journal {
+    /// Open the journal stored at `path`, starting empty if it does not exist yet.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let mut journal = Self {
+            path: path.into(),
+            state: State::default(),
+        };
+        journal.reload()?;
+        Ok(journal)
+    }
+
+    /// Open the journal in the nohrs data directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(paths::data_dir().join("journal.json"))
+    }
+
+    /// Entries that can be undone, most recent last.
+    pub fn undo_stack(&self) -> &[JournalEntry] {
+        &self.state.undo
+    }
+
+    /// Entries that can be redone, most recently undone last.
+    pub fn redo_stack(&self) -> &[JournalEntry] {
+        &self.state.redo
+    }
+
+    /// Record what an operation did, including the part of it that completed before it
+    /// failed or was cancelled. Clears the redo stack.
+    ///
+    /// Deletions are not recorded, and neither are overwrites whose replaced item could not
+    /// be tracked in the trash, since what they removed cannot be brought back.
+    pub fn record(&mut self, op: &Operation, report: &Report) -> Result<()> {
+        let Some(effects) = effects_of(op, report) else {
+            return Ok(());
+        };
+        if effects.is_empty() {
+            return Ok(());
+        }
+        let _lock = self.lock()?;
+        self.reload()?;
+        self.state.next_id += 1;
+        self.state.undo.push(JournalEntry {
+            id: self.state.next_id,
+            at: Timestamp::from_system_time(SystemTime::now()),
+            label: label_of(op, report),
+            effects,
+        });
+        let excess = self.state.undo.len().saturating_sub(MAX_ENTRIES);
+        self.state.undo.drain(..excess);
+        self.state.redo.clear();
+        self.save()
+    }
+
+    /// Revert the most recent entry. Returns `None` when there is nothing to undo.
+    ///
+    /// Fails with `Error::Changed` without touching anything if an item was modified
+    /// since the operation; the entry then stays on the undo stack. If reverting fails
+    /// part way, the reverted effects move to the redo stack and the error is returned.
+    pub fn undo(&mut self) -> Result<Option<JournalEntry>> {
+        let _lock = self.lock()?;
+        self.reload()?;
+        let Some(entry) = self.state.undo.pop() else {
+            return Ok(None);
+        };
+        let applied = revert(&entry);
+        self.settle(entry, applied, true)
+    }
+
+    /// Re-apply the most recently undone entry. Returns `None` when there is nothing to redo.
+    ///
+    /// Fails without touching anything if an item changed since the undo. If replaying
+    /// fails part way, the replayed effects move to the undo stack and the error is
+    /// returned.
+    pub fn redo(&mut self) -> Result<Option<JournalEntry>> {
+        let _lock = self.lock()?;
+        self.reload()?;
+        let Some(entry) = self.state.redo.pop() else {
+            return Ok(None);
+        };
+        let applied = replay(&entry);
+        self.settle(entry, applied, false)
+    }
+
+    /// Move what was applied of `entry` to the other stack and put the rest back.
+    fn settle(
+        &mut self,
+        entry: JournalEntry,
+        applied: Result<Applied>,
+        undo: bool,
+    ) -> Result<Option<JournalEntry>> {
+        let (from, to) = if undo {
+            (&mut self.state.undo, &mut self.state.redo)
+        } else {
+            (&mut self.state.redo, &mut self.state.undo)
+        };
+        let applied = match applied {
+            Ok(applied) => applied,
+            Err(err) => {
+                from.push(entry);
+                return Err(err);
+            }
+        };
+        let done = JournalEntry {
+            effects: applied.done,
+            ..entry.clone()
+        };
+        let Some((left, err)) = applied.failed else {
+            to.push(done.clone());
+            self.save()?;
+            return Ok(Some(done));
+        };
+        if !done.effects.is_empty() {
+            to.push(done);
+        }
+        from.push(JournalEntry {
+            effects: left,
+            ..entry
+        });
+        if let Err(save_err) = self.save() {
+            tracing::warn!(%save_err, "cannot record a partly applied entry");
+        }
+        Err(err)
+    }
+
+    /// Take the advisory lock that serializes changes to the journal between processes,
+    /// such as two nohrs windows. It is released when the returned file is dropped.
+    fn lock(&self) -> Result<File> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| Error::from_io(dir, e))?;
+        }
+        let path = self.path.with_extension("json.lock");
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::from_io(&path, e))?;
+        file.lock().map_err(|e| Error::from_io(&path, e))?;
+        Ok(file)
+    }
+
+    fn reload(&mut self) -> Result<()> {
+        let data = match fs::read(&self.path) {
+            Ok(d) => d,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.state = State::default();
+                return Ok(());
+            }
+            Err(e) => return Err(Error::from_io(&self.path, e)),
+        };
+        self.state = match serde_json::from_slice::<State>(&data) {
+            Ok(state) if state.version == VERSION => state,
+            Ok(state) => {
+                tracing::warn!(
+                    version = state.version,
+                    "ignoring journal with unknown version"
+                );
+                State::default()
+            }
+            Err(err) => {
+                tracing::warn!(path = %self.path.display(), %err, "ignoring unreadable journal");
+                State::default()
+            }
+        };
+        Ok(())
+    }
+
+    /// Write the journal atomically via a temporary file. Callers hold [`Journal::lock`].
+    fn save(&mut self) -> Result<()> {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        self.state.version = VERSION;
+        let data = serde_json::to_vec_pretty(&self.state)
+            .map_err(|e| Error::Other(format!("cannot encode journal: {e}")))?;
+        let tmp = self.path.with_extension(format!(
+            "json.{}-{}.tmp",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        fs::write(&tmp, data).map_err(|e| Error::from_io(&tmp, e))?;
+        fs::rename(&tmp, &self.path).map_err(|e| Error::from_io(&self.path, e))
+    }
+}
+
+/// Effects of an operation, or `None` if it cannot be undone.
+fn effects_of(op: &Operation, report: &Report) -> Option<Vec<Effect>> {
+    if matches!(op, Operation::Delete { .. })
+        || report
+            .created
+            .iter()
+            .any(|c| c.replaced && c.replaced_id.is_none())
+    {
+        return None;
+    }
+    let mut effects = Vec::with_capacity(report.created.len() + report.trashed.len());
+    for trashed in &report.trashed {
+        match &trashed.id {
+            Some(id) => effects.push(Effect::Trashed {
+                original: trashed.path.clone(),
+                id: id.clone(),
+                fingerprint: None,
+            }),
+            None => tracing::debug!(
+                path = %trashed.path.display(),
+                "trashed item cannot be tracked for undo"
+            ),
+        }
+    }
+    for created in &report.created {
+        // The item it replaced went to the trash first.
+        if let Some(id) = &created.replaced_id {
+            effects.push(Effect::Trashed {
+                original: created.path.clone(),
+                id: id.clone(),
+                fingerprint: None,
+            });
+        }
+        let fingerprint = match Fingerprint::of(&created.path) {
+            Ok(fp) => fp,
+            Err(err) => {
+                tracing::warn!(%err, "operation result vanished before it was journaled");
+                continue;
+            }
+        };
+        let effect = match op {
+            Operation::Move { .. } | Operation::Rename { .. } => Effect::Moved {
+                from: created.source.clone()?,
+                to: created.path.clone(),
+                fingerprint,
+            },
+            _ => Effect::Created {
+                source: created.source.clone(),
+                path: created.path.clone(),
+                dir: fingerprint.kind == FileKind::Dir,
+                fingerprint,
+            },
+        };
+        effects.push(effect);
+    }
+    Some(effects)
+}
+
+fn label_of(op: &Operation, report: &Report) -> String {
+    let verb = match op {
+        Operation::Copy { .. } => "Copy",
+        Operation::Move { .. } => "Move",
+        Operation::Rename { .. } => "Rename",
+        Operation::CreateDir { .. } => "New Folder",
+        Operation::CreateFile { .. } => "New File",
+        Operation::Trash { .. } => "Move to Trash",
+        Operation::Delete { .. } => "Delete",
+    };
+    let names: Vec<&Path> = match op {
+        Operation::Trash { .. } => report.trashed.iter().map(|t| t.path.as_path()).collect(),
+        Operation::CreateDir { .. } | Operation::CreateFile { .. } => return verb.to_string(),
+        _ => report
+            .created
+            .iter()
+            .filter_map(|c| c.source.as_deref())
+            .collect(),
+    };
+    match names.as_slice() {
+        [one] => {
+            let name = one.file_name().unwrap_or_default().to_string_lossy();
+            format!("{verb} \u{201c}{name}\u{201d}")
+        }
+        many => format!("{verb} {} items", many.len()),
+    }
+}
+
+/// Effects of an entry after undoing or redoing it.
+struct Applied {
+    /// The effects that were applied, updated to their new state, in entry order.
+    done: Vec<Effect>,
+    /// The effects that were not, unchanged, and the error that stopped them.
+    failed: Option<(Vec<Effect>, Error)>,
+}
+
+/// Undo `entry`'s effects, newest first, after checking that all of them are still safe.
+fn revert(entry: &JournalEntry) -> Result<Applied> {
+    let in_trash = trash_ids(entry)?;
+    // Paths that reverting the later effects frees up for the earlier ones, as when an
+    // overwrite's copy is trashed before the item it replaced is restored.
+    let mut freed: HashSet<&Path> = HashSet::new();
+    for effect in entry.effects.iter().rev() {
+        match effect {
+            Effect::Created {
+                path, fingerprint, ..
+            } => {
+                ensure_unchanged(path, fingerprint)?;
+                freed.insert(path.as_path());
+            }
+            Effect::Moved {
+                from,
+                to,
+                fingerprint,
+            } => {
+                ensure_unchanged(to, fingerprint)?;
+                ensure_vacant(from, &freed)?;
+                freed.remove(from.as_path());
+                freed.insert(to.as_path());
+            }
+            Effect::Trashed { original, id, .. } => {
+                if !in_trash.contains(id) {
+                    return Err(Error::Vanished {
+                        path: original.clone(),
+                    });
+                }
+                ensure_vacant(original, &freed)?;
+                freed.remove(original.as_path());
+            }
+        }
+    }
+
+    let mut effects = entry.effects.clone();
+    for ix in (0..effects.len()).rev() {
+        if let Err(err) = revert_effect(&mut effects[ix]) {
+            let done = effects.split_off(ix + 1);
+            return Ok(Applied {
+                done,
+                failed: Some((effects, err)),
+            });
+        }
+    }
+    Ok(Applied {
+        done: effects,
+        failed: None,
+    })
+}
+
+fn revert_effect(effect: &mut Effect) -> Result<()> {
+    match effect {
+        // Trashed rather than deleted, since what is inside may have changed.
+        Effect::Created { path, .. } => {
+            trash::move_to_trash(path)?;
+        }
+        Effect::Moved {
+            from,
+            to,
+            fingerprint,
+        } => {
+            ops::move_exact(to, from)?;
+            *fingerprint = Fingerprint::of(from)?;
+        }
+        Effect::Trashed {
+            original,
+            id,
+            fingerprint,
+        } => {
+            let mut report = trash::restore(std::slice::from_ref(id), ConflictPolicy::Error)?;
+            if let Some((_, err)) = report.failed.pop() {
+                return Err(err);
+            }
+            *fingerprint = Some(Fingerprint::of(original)?);
+        }
+    }
+    Ok(())
+}
+
+/// Re-apply `entry`'s effects, oldest first, after checking that all of them are possible.
+fn replay(entry: &JournalEntry) -> Result<Applied> {
+    // Paths that replaying the earlier effects frees up for the later ones, as when an
+    // overwrite trashes the item it replaces before copying over it.
+    let mut freed: HashSet<&Path> = HashSet::new();
+    for effect in &entry.effects {
+        match effect {
+            Effect::Created { source, path, .. } => {
+                ensure_vacant(path, &freed)?;
+                freed.remove(path.as_path());
+                if let Some(src) = source {
+                    fs::symlink_metadata(src).map_err(|e| Error::from_io(src, e))?;
+                }
+            }
+            Effect::Moved {
+                from,
+                to,
+                fingerprint,
+            } => {
+                ensure_unchanged(from, fingerprint)?;
+                ensure_vacant(to, &freed)?;
+                freed.remove(to.as_path());
+                freed.insert(from.as_path());
+            }
+            Effect::Trashed {
+                original,
+                fingerprint,
+                ..
+            } => match fingerprint {
+                Some(fp) => {
+                    ensure_unchanged(original, fp)?;
+                    freed.insert(original.as_path());
+                }
+                None => {
+                    return Err(Error::Vanished {
+                        path: original.clone(),
+                    })
+                }
+            },
+        }
+    }
+
+    let mut effects = entry.effects.clone();
+    let mut batch = TrashBatch::new();
+    // Effects that trashed an item, with its index in `batch`.
+    let mut trashed = Vec::new();
+    let mut failed = None;
+    for ix in 0..effects.len() {
+        match replay_effect(&mut effects[ix], &mut batch) {
+            Ok(Some(batch_ix)) => trashed.push((ix, batch_ix)),
+            Ok(None) => {}
+            Err(err) => {
+                failed = Some((effects.split_off(ix), err));
+                break;
+            }
+        }
+    }
+    fill_trash_ids(&mut effects, &batch, &trashed);
+    Ok(Applied {
+        done: effects,
+        failed,
+    })
+}
+
+/// Give the effects that trashed items through `batch` the ids the items got, listing
+/// the trash once for all of them. An item whose id cannot be found is in the trash but
+/// cannot be tracked there, so its effect is dropped.
+fn fill_trash_ids(effects: &mut Vec<Effect>, batch: &TrashBatch, trashed: &[(usize, usize)]) {
+    if trashed.is_empty() {
+        return;
+    }
+    let mut ids = batch.ids();
+    let mut untracked = HashSet::new();
+    for &(ix, batch_ix) in trashed {
+        let Effect::Trashed { original, id, .. } = &mut effects[ix] else {
+            continue;
+        };
+        match ids[batch_ix].take() {
+            Some(new) => *id = new,
+            None => {
+                tracing::warn!(path = %original.display(), "trashed item cannot be tracked for undo");
+                untracked.insert(ix);
+            }
+        }
+    }
+    let mut ix = 0;
+    effects.retain(|_| {
+        ix += 1;
+        !untracked.contains(&(ix - 1))
+    });
+}
+
+/// Re-apply one effect. Returns the index in `batch` of the item it trashed, whose id is
+/// only known once the batch is done.
+fn replay_effect(effect: &mut Effect, batch: &mut TrashBatch) -> Result<Option<usize>> {
+    match effect {
+        Effect::Created {
+            source,
+            path,
+            dir,
+            fingerprint,
+        } => {
+            match source {
+                Some(src) => ops::copy_exact(src, path)?,
+                None if *dir => fs::create_dir(&*path).map_err(|e| Error::from_io(&*path, e))?,
+                None => fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .open(&*path)
+                    .map(drop)
+                    .map_err(|e| Error::from_io(&*path, e))?,
+            }
+            *fingerprint = Fingerprint::of(path)?;
+        }
+        Effect::Moved {
+            from,
+            to,
+            fingerprint,
+        } => {
+            ops::move_exact(from, to)?;
+            *fingerprint = Fingerprint::of(to)?;
+        }
+        Effect::Trashed {
+            original,
+            fingerprint,
+            ..
+        } => {
+            // The trash assigns a fresh id each time.
+            let ix = batch.trash(original)?;
+            *fingerprint = None;
+            return Ok(Some(ix));
+        }
+    }
+    Ok(None)
+}
+
+/// Ids currently in the trash, fetched only when `entry` has trashed items.
+fn trash_ids(entry: &JournalEntry) -> Result<HashSet<String>> {
+    if !entry
+        .effects
+        .iter()
+        .any(|e| matches!(e, Effect::Trashed { .. }))
+    {
+        return Ok(HashSet::new());
+    }
+    Ok(trash::list()?.into_iter().map(|e| e.id).collect())
+}
+
+/// Like [`ensure_absent`], but also accepts paths an earlier effect will have vacated.
+fn ensure_vacant(path: &Path, freed: &HashSet<&Path>) -> Result<()> {
+    if freed.contains(path) {
+        return Ok(());
+    }
+    ensure_absent(path)
+}
+
+fn ensure_unchanged(path: &Path, expected: &Fingerprint) -> Result<()> {
+    if Fingerprint::of(path)? != *expected {
+        return Err(Error::Changed {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::cancel::CancelToken;
+    use crate::core::test_support::TempDir;
+    use crate::services::fs::ops::{ConflictAction, JobEvent, Resolution};
+
+    fn run_and_record(journal: &mut Journal, op: Operation) {
+        let report = ops::run(
+            &op,
+            &CancelToken::new(),
+            |_| {},
+            |_| Resolution::once(ConflictAction::Cancel),
+        )
+        .unwrap();
+        journal.record(&op, &report).unwrap();
+    }
+
+    /// Names in `dir`.
+    fn names(dir: &Path) -> Vec<String> {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn open(tmp: &TempDir) -> Journal {
+        Journal::open(tmp.path().join("state/journal.json")).unwrap()
+    }
+
+    #[test]
+    fn undoes_and_redoes_a_rename() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a.txt", "a");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Rename {
+                path: file.clone(),
+                new_name: "b.txt".into(),
+            },
+        );
+        assert_eq!(
+            journal.undo_stack()[0].label,
+            "Rename \u{201c}a.txt\u{201d}"
+        );
+
+        journal.undo().unwrap().unwrap();
+        assert!(file.exists());
+        assert!(!tmp.path().join("b.txt").exists());
+        assert!(journal.undo_stack().is_empty());
+
+        // Another process sees the same state.
+        let mut other = open(&tmp);
+        assert_eq!(other.redo_stack().len(), 1);
+        other.redo().unwrap().unwrap();
+        assert!(tmp.path().join("b.txt").exists());
+        assert!(journal.undo().unwrap().is_some());
+        assert!(journal.undo().unwrap().is_none());
+    }
+
+    #[test]
+    fn refuses_to_undo_changed_items() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a", "a");
+        let dest = tmp.mkdir("dest");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Move {
+                sources: vec![file],
+                dest_dir: dest.clone(),
+                on_conflict: ConflictPolicy::Error,
+            },
+        );
+        fs::write(dest.join("a"), "edited").unwrap();
+        assert!(matches!(journal.undo(), Err(Error::Changed { .. })));
+        assert_eq!(journal.undo_stack().len(), 1);
+        assert_eq!(fs::read_to_string(dest.join("a")).unwrap(), "edited");
+    }
+
+    #[test]
+    fn undoing_a_copy_trashes_it() {
+        let tmp = TempDir::new();
+        tmp.write("dir/a", "a");
+        let dest = tmp.mkdir("dest");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Copy {
+                sources: vec![tmp.path().join("dir")],
+                dest_dir: dest.clone(),
+                on_conflict: ConflictPolicy::Error,
+            },
+        );
+        // Not visible in the copy's own fingerprint.
+        fs::write(dest.join("dir/a"), "b").unwrap();
+
+        journal.undo().unwrap().unwrap();
+        assert!(!dest.join("dir").exists());
+        let trashed = trash::find_latest(&dest.join("dir")).unwrap().unwrap();
+        trash::purge(&[trashed.id], true).unwrap();
+
+        journal.redo().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(dest.join("dir/a")).unwrap(), "a");
+    }
+
+    #[test]
+    fn partial_undo_splits_the_entry() {
+        let tmp = TempDir::new();
+        let a = tmp.write("one/a", "a");
+        let b = tmp.write("two/b", "b");
+        let dest = tmp.mkdir("dest");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Move {
+                sources: vec![a.clone(), b.clone()],
+                dest_dir: dest.clone(),
+                on_conflict: ConflictPolicy::Error,
+            },
+        );
+        // `b` goes back first; `a` then has nowhere to go.
+        fs::remove_dir(tmp.path().join("one")).unwrap();
+        assert!(journal.undo().is_err());
+        assert!(b.exists());
+        assert!(dest.join("a").exists());
+        let moved = |entry: &JournalEntry| -> Vec<PathBuf> {
+            entry
+                .effects
+                .iter()
+                .map(|e| match e {
+                    Effect::Moved { from, .. } => from.clone(),
+                    other => panic!("unexpected {other:?}"),
+                })
+                .collect()
+        };
+        assert_eq!(moved(&journal.undo_stack()[0]), vec![a.clone()]);
+        assert_eq!(moved(&journal.redo_stack()[0]), vec![b.clone()]);
+
+        tmp.mkdir("one");
+        journal.undo().unwrap().unwrap();
+        assert!(a.exists());
+        assert_eq!(journal.redo_stack().len(), 2);
+    }
+
+    #[test]
+    fn undoes_and_redoes_trashing_the_right_item() {
+        let tmp = TempDir::new();
+        let a = tmp.write("a", "older");
+        // An older item from the same path is already in the trash.
+        let mut batch = TrashBatch::new();
+        batch.trash(&a).unwrap();
+        let older = batch.ids().pop().flatten().unwrap();
+        tmp.write("a", "a");
+        let b = tmp.write("b", "b");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Trash {
+                paths: vec![a.clone(), b.clone()],
+            },
+        );
+        assert_eq!(journal.undo_stack()[0].label, "Move to Trash 2 items");
+
+        journal.undo().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+        assert!(trash::list().unwrap().iter().any(|e| e.id == older));
+
+        journal.redo().unwrap().unwrap();
+        assert!(!a.exists() && !b.exists());
+        journal.undo().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a");
+        trash::purge(&[older], true).unwrap();
+    }
+
+    #[test]
+    fn undoes_and_redoes_an_overwrite() {
+        let tmp = TempDir::new();
+        tmp.write("a", "new");
+        let dest = tmp.write("dest/a", "old");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Move {
+                sources: vec![tmp.path().join("a")],
+                dest_dir: tmp.path().join("dest"),
+                on_conflict: ConflictPolicy::Overwrite,
+            },
+        );
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "new");
+
+        journal.undo().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "old");
+        assert_eq!(fs::read_to_string(tmp.path().join("a")).unwrap(), "new");
+
+        journal.redo().unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "new");
+        assert!(!tmp.path().join("a").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn records_the_finished_part_of_a_failed_operation() {
+        let tmp = TempDir::new();
+        let a = tmp.write("a", "a");
+        tmp.mkdir("d");
+        let fifo = std::ffi::CString::new(tmp.path().join("d/fifo").to_str().unwrap()).unwrap();
+        // SAFETY: `fifo` is a valid NUL-terminated path.
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+        let dest = tmp.mkdir("dest");
+        let op = Operation::Copy {
+            sources: vec![a, tmp.path().join("d")],
+            dest_dir: dest.clone(),
+            on_conflict: ConflictPolicy::Error,
+        };
+        let mut journal = open(&tmp);
+        let handle = ops::start_with(op, {
+            let path = journal.path.clone();
+            move |op, report| Journal::open(path).unwrap().record(op, report).unwrap()
+        });
+        assert!(matches!(handle.last(), Some(JobEvent::Failed(..))));
+
+        journal.undo().unwrap().unwrap();
+        assert!(names(&dest).is_empty());
+    }
+
+    #[test]
+    fn concurrent_journals_keep_each_others_entries() {
+        let tmp = TempDir::new();
+        let threads: Vec<_> = (0..2)
+            .map(|t| {
+                let mut journal = open(&tmp);
+                let dir = tmp.path().to_path_buf();
+                std::thread::spawn(move || {
+                    for i in 0..20 {
+                        let path = dir.join(format!("dir-{t}-{i}"));
+                        run_and_record(&mut journal, Operation::CreateDir { path });
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(open(&tmp).undo_stack().len(), 40);
+        let mut names = names(&tmp.path().join("state"));
+        names.sort();
+        assert_eq!(names, ["journal.json", "journal.json.lock"]);
+    }
+
+    #[test]
+    fn skips_deletions() {
+        let tmp = TempDir::new();
+        tmp.write("a", "a");
+        let mut journal = open(&tmp);
+        run_and_record(
+            &mut journal,
+            Operation::Delete {
+                paths: vec![tmp.path().join("a")],
+                confirmed: true,
+            },
+        );
+        assert!(journal.undo_stack().is_empty());
+    }
+}
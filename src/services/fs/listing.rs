@@ -1,113 +1,582 @@
-use crate::core::errors::Result;
-use serde::Serialize;
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::{FileEntry, FileKind, Timestamp};
+use crate::models::os_path;
+use crate::services::fs::cursor::{Cursor, DirFingerprint, FilterKey};
+use crate::services::fs::ignore::Wildcards;
+use crate::services::fs::metadata;
+use crate::services::fs::sort::{compare_entries, sort_entries, SortOptions};
 use std::ffi::OsStr;
 use std::fs;
-use std::path::{Path, PathBuf};
-use std::time::UNIX_EPOCH;
+use std::path::Path;
 use tokio::task;
 
-#[derive(Debug, Serialize, Clone)]
-pub struct FileEntryDto {
-    pub name: String,
-    pub path: String,
-    pub kind: String,
-    pub size: u64,
-    pub modified: u64,
-}
-
+#[derive(Debug, Clone)]
 pub struct ListParams<'a> {
-    pub path: &'a str,
+    pub path: &'a Path,
     pub limit: usize,
+    /// Opaque cursor from a previous `ListResult::next_cursor`.
     pub cursor: Option<&'a str>,
+    pub sort: SortOptions,
+    /// Include dot-files.
+    pub show_hidden: bool,
+    /// Case-insensitive name filter. Treated as a glob when it contains `*` or `?`,
+    /// otherwise as a substring.
+    pub filter: Option<&'a str>,
+}
+
+impl Default for ListParams<'_> {
+    fn default() -> Self {
+        Self {
+            path: Path::new(""),
+            limit: 0,
+            cursor: None,
+            sort: SortOptions::default(),
+            show_hidden: false,
+            filter: None,
+        }
+    }
 }
 
 pub struct ListResult {
-    pub entries: Vec<FileEntryDto>,
+    pub entries: Vec<FileEntry>,
+    /// Problems with individual entries. The listing itself still succeeded; entries that
+    /// could not be inspected are either omitted (vanished) or listed with an unknown kind.
+    pub errors: Vec<Error>,
     pub next_cursor: Option<String>,
+    /// The directory was modified since the cursor passed in was issued. Paging still
+    /// resumes after the last returned entry, but earlier pages may be stale.
+    pub dir_changed: bool,
+}
+
+/// Owned copy of the listing options, so they can cross into a blocking task.
+#[derive(Debug, Clone)]
+struct ListOptions {
+    limit: usize,
+    cursor: Option<String>,
+    sort: SortOptions,
+    show_hidden: bool,
+    filter: Option<NameFilter>,
+    /// `show_hidden` and `filter` as recorded in cursors.
+    filter_key: FilterKey,
+}
+
+impl From<&ListParams<'_>> for ListOptions {
+    fn from(params: &ListParams<'_>) -> Self {
+        Self {
+            limit: params.limit,
+            cursor: params.cursor.map(|s| s.to_string()),
+            sort: params.sort,
+            show_hidden: params.show_hidden,
+            filter: params.filter.filter(|f| !f.is_empty()).map(NameFilter::new),
+            filter_key: FilterKey::new(params.show_hidden, params.filter),
+        }
+    }
 }
 
 pub async fn list_dir(params: ListParams<'_>) -> Result<ListResult> {
     // Use a blocking task for filesystem IO to avoid blocking async executors.
-    let path = params.path.to_string();
-    let limit = params.limit;
-    let cursor = params.cursor.map(|s| s.to_string());
+    let path = params.path.to_path_buf();
+    let opts = ListOptions::from(&params);
 
-    task::spawn_blocking(move || list_dir_impl(&path, limit, cursor.as_deref()))
+    task::spawn_blocking(move || list_dir_impl(&path, &opts))
         .await
         .unwrap()
 }
 
 /// Synchronous variant for UI contexts where an async runtime is not available.
 pub fn list_dir_sync(params: ListParams<'_>) -> Result<ListResult> {
-    list_dir_impl(params.path, params.limit, params.cursor)
-}
-
-fn list_dir_impl(path: &str, limit: usize, cursor: Option<&str>) -> Result<ListResult> {
-    let dir = Path::new(path);
-    let mut names: Vec<(String, PathBuf)> = Vec::new();
-
-    // Read directory entries: collect names and paths only (cheap), then sort by name for stable paging.
-    for entry in fs::read_dir(dir)? {
-        let entry = entry?;
-        let file_name = os_str_to_string(entry.file_name());
-        names.push((file_name, entry.path()));
-    }
-    names.sort_by(|a, b| a.0.to_lowercase().cmp(&b.0.to_lowercase()));
-
-    let total = names.len();
-    let offset = cursor
-        .and_then(|s| s.parse::<usize>().ok())
-        .unwrap_or(0)
-        .min(total);
-
-    let end = (offset + limit).min(total);
-    let slice = &names[offset..end];
-
-    let mut entries = Vec::with_capacity(slice.len());
-    for (name, path) in slice.iter() {
-        let md = fs::symlink_metadata(path);
-        let (kind, size, modified) = match md {
-            Ok(md) => {
-                let modified = md
-                    .modified()
-                    .ok()
-                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
-                    .map(|d| d.as_secs())
-                    .unwrap_or(0);
-                if md.file_type().is_dir() {
-                    ("dir".to_string(), 0, modified)
-                } else if md.file_type().is_file() {
-                    ("file".to_string(), md.len(), modified)
-                } else if md.file_type().is_symlink() {
-                    ("symlink".to_string(), 0, modified)
-                } else {
-                    ("other".to_string(), 0, modified)
-                }
+    list_dir_impl(params.path, &ListOptions::from(&params))
+}
+
+fn list_dir_impl(dir: &Path, opts: &ListOptions) -> Result<ListResult> {
+    let cursor = opts.cursor.as_deref().map(Cursor::decode).transpose()?;
+    if let Some(c) = &cursor {
+        if c.sort != opts.sort {
+            return Err(Error::InvalidCursor(
+                "cursor was issued for a different sort order".to_string(),
+            ));
+        }
+        if c.filter != opts.filter_key {
+            return Err(Error::InvalidCursor(
+                "cursor was issued for a different filter".to_string(),
+            ));
+        }
+    }
+    let fingerprint = DirFingerprint::of(dir)?;
+    let mut entries: Vec<FileEntry> = Vec::new();
+    let mut errors: Vec<Error> = Vec::new();
+
+    // Read directory entries. The kind comes from the dirent type (cheap); full metadata is
+    // only fetched up front when the sort key needs it, otherwise just for the returned page.
+    for entry in fs::read_dir(dir).map_err(|e| Error::from_io(dir, e))? {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                errors.push(Error::from_io(dir, e));
+                continue;
             }
-            Err(_) => ("unknown".to_string(), 0, 0),
         };
-
-        entries.push(FileEntryDto {
-            name: name.clone(),
-            path: path.to_string_lossy().to_string(),
-            kind,
-            size,
-            modified,
-        });
+        let name = entry_name(&entry, &mut errors);
+        if !opts.show_hidden && is_hidden(&name) {
+            continue;
+        }
+        if let Some(filter) = &opts.filter {
+            if !filter.matches(&name) {
+                continue;
+            }
+        }
+        let path = entry.path();
+        let e = if opts.sort.key.needs_metadata() {
+            match stat_entry(name, &path, &mut errors) {
+                Some(e) => e,
+                None => continue,
+            }
+        } else {
+            FileEntry {
+                name,
+                path: path.to_string_lossy().to_string(),
+                raw_path: os_path::to_raw(&path),
+                kind: entry
+                    .file_type()
+                    .map(FileKind::from_file_type)
+                    .unwrap_or(FileKind::Unknown),
+                ..Default::default()
+            }
+        };
+        entries.push(e);
     }
+    sort_entries(&mut entries, &opts.sort);
 
-    let next_cursor = if end < total {
-        Some(end.to_string())
-    } else {
-        None
+    let total = entries.len();
+    let offset = match &cursor {
+        // Resume strictly after the last entry handed out, wherever it now sorts.
+        Some(c) => {
+            let last = c.last.to_entry();
+            entries.partition_point(|e| compare_entries(e, &last, &opts.sort).is_le())
+        }
+        None => 0,
+    };
+
+    let end = (offset + opts.limit).min(total);
+    let mut page: Vec<FileEntry> = entries.drain(offset..end).collect();
+
+    // Capture the sort-time view of the last entry before metadata is filled in below.
+    let next_cursor = match page.last() {
+        Some(last) if end < total => {
+            Some(Cursor::new(opts.sort, opts.filter_key.clone(), last, fingerprint).encode())
+        }
+        _ => None,
     };
 
+    if !opts.sort.key.needs_metadata() {
+        page = page
+            .into_iter()
+            .filter_map(|e| match e.fs_path() {
+                Ok(path) => stat_entry(e.name, &path, &mut errors),
+                Err(err) => {
+                    errors.push(err);
+                    None
+                }
+            })
+            .collect();
+    }
+
     Ok(ListResult {
-        entries,
+        entries: page,
+        errors,
         next_cursor,
+        dir_changed: cursor.is_some_and(|c| c.dir != fingerprint),
     })
 }
 
+/// Stat `path` and build its entry, recording any failure in `errors`.
+///
+/// Entries that disappeared since `read_dir` are dropped; others that cannot be inspected
+/// are kept with an unknown kind so they still show up.
+pub(crate) fn stat_entry(name: String, path: &Path, errors: &mut Vec<Error>) -> Option<FileEntry> {
+    match fs::symlink_metadata(path) {
+        Ok(md) => Some(entry_from_metadata(name, path, &md)),
+        Err(e) => {
+            let err = Error::from_io(path, e);
+            let vanished = matches!(err, Error::Vanished { .. });
+            errors.push(err);
+            if vanished {
+                return None;
+            }
+            Some(FileEntry {
+                hidden: is_hidden(&name),
+                name,
+                path: path.to_string_lossy().to_string(),
+                raw_path: os_path::to_raw(path),
+                kind: FileKind::Unknown,
+                ..Default::default()
+            })
+        }
+    }
+}
+
+/// Build an entry from the result of `symlink_metadata`.
+pub(crate) fn entry_from_metadata(name: String, path: &Path, md: &fs::Metadata) -> FileEntry {
+    let hidden = is_hidden(&name);
+    let ft = md.file_type();
+    let modified = md.modified().ok().map(Timestamp::from_system_time);
+    let unix = metadata::unix_meta(md);
+    let (link_target, link_broken) = if ft.is_symlink() {
+        metadata::link_info(path)
+    } else {
+        (None, false)
+    };
+
+    FileEntry {
+        name,
+        path: path.to_string_lossy().to_string(),
+        raw_path: os_path::to_raw(path),
+        kind: FileKind::from_file_type(ft),
+        size: if ft.is_file() { md.len() } else { 0 },
+        modified: modified.map(|t| t.secs.max(0) as u64).unwrap_or(0),
+        modified_nanos: modified.map(|t| t.nanos).unwrap_or(0),
+        accessed: md.accessed().ok().map(Timestamp::from_system_time),
+        created: md.created().ok().map(Timestamp::from_system_time),
+        mode: unix.mode,
+        uid: unix.uid,
+        gid: unix.gid,
+        owner: unix.uid.and_then(metadata::user_name),
+        group: unix.gid.and_then(metadata::group_name),
+        inode: unix.inode,
+        dev: unix.dev,
+        nlink: unix.nlink,
+        link_target,
+        link_broken,
+        hidden,
+    }
+}
+
+pub(crate) fn is_hidden(name: &str) -> bool {
+    name.starts_with('.')
+}
+
+/// Case-insensitive name filter: glob when it contains wildcards, substring otherwise.
+#[derive(Debug, Clone)]
+pub(crate) enum NameFilter {
+    Substring(String),
+    Glob(Wildcards),
+}
+
+impl NameFilter {
+    pub(crate) fn new(pattern: &str) -> Self {
+        let pattern = pattern.to_lowercase();
+        if pattern.contains(['*', '?']) {
+            NameFilter::Glob(Wildcards::new(&pattern))
+        } else {
+            NameFilter::Substring(pattern)
+        }
+    }
+
+    pub(crate) fn matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        match self {
+            NameFilter::Substring(s) => name.contains(s.as_str()),
+            NameFilter::Glob(p) => p.matches(&name),
+        }
+    }
+}
+
+/// Display name of a dirent, recording an error when it is not valid UTF-8. The name is
+/// lossy then; the exact bytes are kept in the entry's `raw_path`.
+pub(crate) fn entry_name(entry: &fs::DirEntry, errors: &mut Vec<Error>) -> String {
+    let name = entry.file_name();
+    if name.to_str().is_none() {
+        errors.push(Error::InvalidName { path: entry.path() });
+    }
+    os_str_to_string(name)
+}
+
 fn os_str_to_string(s: impl AsRef<OsStr>) -> String {
     s.as_ref().to_string_lossy().into_owned()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn names(result: &ListResult) -> Vec<&str> {
+        result.entries.iter().map(|e| e.name.as_str()).collect()
+    }
+
+    fn list_all(dir: &Path, limit: usize, filter: Option<&str>) -> Vec<String> {
+        let mut out = Vec::new();
+        let mut cursor = None;
+        loop {
+            let result = list_dir_sync(ListParams {
+                path: dir,
+                limit,
+                cursor: cursor.as_deref(),
+                filter,
+                ..Default::default()
+            })
+            .unwrap();
+            out.extend(result.entries.iter().map(|e| e.name.clone()));
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => return out,
+            }
+        }
+    }
+
+    #[test]
+    fn pages_cover_every_entry_once_in_order() {
+        let tmp = TempDir::new();
+        for name in ["b.txt", "a.txt", "d.rs", "c.txt", "e.md"] {
+            tmp.write(name, name);
+        }
+        tmp.mkdir("zdir");
+        assert_eq!(
+            list_all(tmp.path(), 2, None),
+            ["zdir", "a.txt", "b.txt", "c.txt", "d.rs", "e.md"]
+        );
+    }
+
+    #[test]
+    fn hides_dot_files_unless_asked() {
+        let tmp = TempDir::new();
+        tmp.write(".hidden", "");
+        tmp.write("shown", "");
+        let params = ListParams {
+            path: tmp.path(),
+            limit: 10,
+            ..Default::default()
+        };
+        let result = list_dir_sync(params.clone()).unwrap();
+        assert_eq!(names(&result), ["shown"]);
+        let result = list_dir_sync(ListParams {
+            show_hidden: true,
+            ..params
+        })
+        .unwrap();
+        assert_eq!(names(&result), [".hidden", "shown"]);
+    }
+
+    #[test]
+    fn filters_by_substring_and_glob() {
+        let tmp = TempDir::new();
+        for name in ["Readme.md", "main.rs", "lib.rs", "notes.txt"] {
+            tmp.write(name, "");
+        }
+        assert_eq!(list_all(tmp.path(), 10, Some("README")), ["Readme.md"]);
+        assert_eq!(
+            list_all(tmp.path(), 10, Some("*.RS")),
+            ["lib.rs", "main.rs"]
+        );
+        assert_eq!(list_all(tmp.path(), 1, Some("?ain*")), ["main.rs"]);
+        assert_eq!(list_all(tmp.path(), 10, Some("")).len(), 4);
+    }
+
+    #[test]
+    fn stat_failures_become_entry_errors() {
+        let tmp = TempDir::new();
+        let mut errors = Vec::new();
+        let gone = tmp.path().join("gone");
+        assert!(stat_entry("gone".into(), &gone, &mut errors).is_none());
+        assert!(matches!(&errors[..], [Error::Vanished { path }] if *path == gone));
+
+        // Kept with an unknown kind when it failed for another reason.
+        errors.clear();
+        let long = tmp.path().join("x".repeat(300));
+        let entry = stat_entry("long".into(), &long, &mut errors).unwrap();
+        assert_eq!(entry.kind, FileKind::Unknown);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path(), Some(long.as_path()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn non_utf8_names_are_listed_with_raw_path_and_reported() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new();
+        let path = tmp.path().join(OsStr::from_bytes(b"bad\xff"));
+        fs::write(&path, "x").unwrap();
+        let result = list_dir_sync(ListParams {
+            path: tmp.path(),
+            limit: 10,
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(names(&result), ["bad\u{fffd}"]);
+        let entry = &result.entries[0];
+        assert_eq!(entry.fs_path().unwrap(), path);
+        assert_eq!(entry.size, 1);
+        assert!(matches!(&result.errors[..], [Error::InvalidName { path: p }] if *p == path));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn pages_through_names_with_colliding_lossy_forms() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new();
+        let paths: Vec<_> = [&b"x\xfe"[..], b"x\xff", b"y"]
+            .iter()
+            .map(|name| tmp.path().join(OsStr::from_bytes(name)))
+            .collect();
+        for path in &paths {
+            fs::write(path, "").unwrap();
+        }
+
+        let mut seen = Vec::new();
+        let mut cursor = None;
+        loop {
+            let result = list_dir_sync(ListParams {
+                path: tmp.path(),
+                limit: 1,
+                cursor: cursor.as_deref(),
+                ..Default::default()
+            })
+            .unwrap();
+            seen.extend(result.entries.iter().map(|e| e.fs_path().unwrap()));
+            match result.next_cursor {
+                Some(next) => cursor = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, paths);
+    }
+
+    #[test]
+    fn glob_matches_wildcards() {
+        let glob = |pattern: &str, name: &str| NameFilter::new(pattern).matches(name);
+        assert!(glob("*", ""));
+        assert!(glob("a*c", "abbbc"));
+        assert!(glob("a?c", "abc"));
+        assert!(!glob("a?c", "ac"));
+        assert!(glob("*.tar.*", "x.tar.gz"));
+        assert!(!glob("*.rs", "main.rsx"));
+        assert!(glob("[a]?", "[A]b"));
+        let name = "a".repeat(200);
+        assert!(!glob("*a*a*a*a*a*a*a*a*b", &name));
+        assert!(glob("*a*a*a*a*a*a*a*a*b", &format!("{name}b")));
+    }
+
+    #[test]
+    fn cursor_resumes_after_last_entry_when_dir_changes() {
+        let tmp = TempDir::new();
+        for name in ["b", "d", "f"] {
+            tmp.write(name, "");
+        }
+        let params = ListParams {
+            path: tmp.path(),
+            limit: 2,
+            ..Default::default()
+        };
+        let first = list_dir_sync(params.clone()).unwrap();
+        assert_eq!(names(&first), ["b", "d"]);
+        assert!(!first.dir_changed);
+
+        // An entry sorting before the cursor would shift an offset-based page. The mtime is
+        // set explicitly since coarse filesystem timestamps may not move on their own.
+        tmp.write("a", "");
+        tmp.write("e", "");
+        let later = fs::metadata(tmp.path()).unwrap().modified().unwrap()
+            + std::time::Duration::from_secs(5);
+        fs::File::open(tmp.path())
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+        let cursor = first.next_cursor.unwrap();
+        let second = list_dir_sync(ListParams {
+            cursor: Some(&cursor),
+            ..params
+        })
+        .unwrap();
+        assert_eq!(names(&second), ["e", "f"]);
+        assert!(second.dir_changed);
+        assert!(second.next_cursor.is_none());
+    }
+
+    #[test]
+    fn cursor_survives_removal_of_last_entry() {
+        let tmp = TempDir::new();
+        for name in ["a", "b", "c"] {
+            tmp.write(name, "");
+        }
+        let params = ListParams {
+            path: tmp.path(),
+            limit: 2,
+            ..Default::default()
+        };
+        let cursor = list_dir_sync(params.clone()).unwrap().next_cursor.unwrap();
+        fs::remove_file(tmp.path().join("b")).unwrap();
+        let result = list_dir_sync(ListParams {
+            cursor: Some(&cursor),
+            ..params
+        })
+        .unwrap();
+        assert_eq!(names(&result), ["c"]);
+    }
+
+    #[test]
+    fn rejects_cursor_for_other_sort_or_filter() {
+        let tmp = TempDir::new();
+        for name in ["a", "b", "c", ".d"] {
+            tmp.write(name, "");
+        }
+        let params = ListParams {
+            path: tmp.path(),
+            limit: 1,
+            ..Default::default()
+        };
+        let cursor = list_dir_sync(params.clone()).unwrap().next_cursor.unwrap();
+        let with_cursor = ListParams {
+            cursor: Some(&cursor),
+            ..params
+        };
+        assert!(list_dir_sync(with_cursor.clone()).is_ok());
+
+        let other_sort = ListParams {
+            sort: SortOptions {
+                dirs_first: false,
+                ..Default::default()
+            },
+            ..with_cursor.clone()
+        };
+        let hidden = ListParams {
+            show_hidden: true,
+            ..with_cursor.clone()
+        };
+        let filtered = ListParams {
+            filter: Some("a"),
+            ..with_cursor
+        };
+        for params in [other_sort, hidden, filtered] {
+            assert!(matches!(
+                list_dir_sync(params),
+                Err(Error::InvalidCursor(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn filter_key_ignores_case() {
+        let tmp = TempDir::new();
+        for name in ["a1", "a2", "b"] {
+            tmp.write(name, "");
+        }
+        let params = ListParams {
+            path: tmp.path(),
+            limit: 1,
+            filter: Some("A"),
+            ..Default::default()
+        };
+        let cursor = list_dir_sync(params.clone()).unwrap().next_cursor.unwrap();
+        let result = list_dir_sync(ListParams {
+            cursor: Some(&cursor),
+            filter: Some("a"),
+            ..params
+        })
+        .unwrap();
+        assert_eq!(names(&result), ["a2"]);
+    }
+}
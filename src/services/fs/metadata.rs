@@ -0,0 +1,212 @@
+//! Platform metadata beyond what `std::fs::Metadata` exposes portably: Unix mode bits,
+//! ownership with resolved user/group names, inode/device and link targets.
+
+use std::fs;
+use std::path::Path;
+
+/// Ownership and identity fields. All are `None` on platforms without Unix metadata.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct UnixMeta {
+    pub mode: Option<u32>,
+    pub uid: Option<u32>,
+    pub gid: Option<u32>,
+    pub inode: Option<u64>,
+    pub dev: Option<u64>,
+    pub nlink: Option<u64>,
+}
+
+#[cfg(unix)]
+pub(crate) fn unix_meta(md: &fs::Metadata) -> UnixMeta {
+    use std::os::unix::fs::MetadataExt;
+    UnixMeta {
+        mode: Some(md.mode()),
+        uid: Some(md.uid()),
+        gid: Some(md.gid()),
+        inode: Some(md.ino()),
+        dev: Some(md.dev()),
+        nlink: Some(md.nlink()),
+    }
+}
+
+#[cfg(not(unix))]
+pub(crate) fn unix_meta(_md: &fs::Metadata) -> UnixMeta {
+    UnixMeta::default()
+}
+
+/// Symlink target and whether it resolves. Only meaningful for symlinks.
+pub(crate) fn link_info(path: &Path) -> (Option<String>, bool) {
+    let target = fs::read_link(path)
+        .ok()
+        .map(|t| t.to_string_lossy().into_owned());
+    let broken = fs::metadata(path).is_err();
+    (target, broken)
+}
+
+#[cfg(unix)]
+mod names {
+    use std::collections::HashMap;
+    use std::ffi::CStr;
+    use std::sync::{Mutex, OnceLock};
+
+    type Cache = Mutex<HashMap<u32, Option<String>>>;
+
+    fn users() -> &'static Cache {
+        static USERS: OnceLock<Cache> = OnceLock::new();
+        USERS.get_or_init(Default::default)
+    }
+
+    fn groups() -> &'static Cache {
+        static GROUPS: OnceLock<Cache> = OnceLock::new();
+        GROUPS.get_or_init(Default::default)
+    }
+
+    fn cached(
+        cache: &Cache,
+        id: u32,
+        lookup: impl FnOnce(u32) -> Option<String>,
+    ) -> Option<String> {
+        let mut map = cache.lock().unwrap_or_else(|e| e.into_inner());
+        map.entry(id).or_insert_with(|| lookup(id)).clone()
+    }
+
+    /// Largest buffer offered to the `*_r` lookups before giving up on an entry.
+    const MAX_BUFFER: usize = 1 << 20;
+
+    /// Run a reentrant passwd/group lookup, doubling the buffer while it reports `ERANGE`.
+    /// `lookup` returns the error code and whether an entry was found; on success the
+    /// buffer the entry's strings point into is returned.
+    fn with_buffer(
+        size_hint: libc::c_int,
+        mut lookup: impl FnMut(&mut [u8]) -> (libc::c_int, bool),
+    ) -> Option<Vec<u8>> {
+        // SAFETY: `sysconf` has no preconditions; it returns -1 when there is no limit.
+        let hint = unsafe { libc::sysconf(size_hint) };
+        let mut buf = vec![0u8; usize::try_from(hint).unwrap_or(0).max(1024)];
+        loop {
+            match lookup(&mut buf) {
+                (0, true) => return Some(buf),
+                (libc::ERANGE, _) if buf.len() < MAX_BUFFER => {
+                    let len = buf.len() * 2;
+                    buf.resize(len, 0);
+                }
+                _ => return None,
+            }
+        }
+    }
+
+    pub fn user_name(uid: u32) -> Option<String> {
+        cached(users(), uid, |uid| {
+            let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut result: *mut libc::passwd = std::ptr::null_mut();
+            let _buf = with_buffer(libc::_SC_GETPW_R_SIZE_MAX, |buf| {
+                // SAFETY: all pointers reference live locals and `buf` outlives the call.
+                let rc = unsafe {
+                    libc::getpwuid_r(
+                        uid,
+                        &mut pwd,
+                        buf.as_mut_ptr().cast(),
+                        buf.len(),
+                        &mut result,
+                    )
+                };
+                (rc, !result.is_null())
+            })?;
+            // SAFETY: on success `pw_name` points into `_buf` as a NUL-terminated string.
+            let name = unsafe { CStr::from_ptr(pwd.pw_name) };
+            Some(name.to_string_lossy().into_owned())
+        })
+    }
+
+    pub fn group_name(gid: u32) -> Option<String> {
+        cached(groups(), gid, |gid| {
+            let mut grp: libc::group = unsafe { std::mem::zeroed() };
+            let mut result: *mut libc::group = std::ptr::null_mut();
+            let _buf = with_buffer(libc::_SC_GETGR_R_SIZE_MAX, |buf| {
+                // SAFETY: all pointers reference live locals and `buf` outlives the call.
+                let rc = unsafe {
+                    libc::getgrgid_r(
+                        gid,
+                        &mut grp,
+                        buf.as_mut_ptr().cast(),
+                        buf.len(),
+                        &mut result,
+                    )
+                };
+                (rc, !result.is_null())
+            })?;
+            // SAFETY: on success `gr_name` points into `_buf` as a NUL-terminated string.
+            let name = unsafe { CStr::from_ptr(grp.gr_name) };
+            Some(name.to_string_lossy().into_owned())
+        })
+    }
+}
+
+/// Resolve a uid to a user name. Lookups are cached for the life of the process.
+#[cfg(unix)]
+pub fn user_name(uid: u32) -> Option<String> {
+    names::user_name(uid)
+}
+
+/// Resolve a gid to a group name. Lookups are cached for the life of the process.
+#[cfg(unix)]
+pub fn group_name(gid: u32) -> Option<String> {
+    names::group_name(gid)
+}
+
+#[cfg(not(unix))]
+pub fn user_name(_uid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(not(unix))]
+pub fn group_name(_gid: u32) -> Option<String> {
+    None
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use crate::services::fs::listing::entry_from_metadata;
+    use std::os::unix::fs::{symlink, MetadataExt, PermissionsExt};
+
+    #[test]
+    fn reads_mode_ownership_and_identity() {
+        let tmp = TempDir::new();
+        let path = tmp.write("file", "data");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+        fs::hard_link(&path, tmp.path().join("link")).unwrap();
+        let md = fs::metadata(&path).unwrap();
+        let meta = unix_meta(&md);
+        assert_eq!(meta.mode.map(|m| m & 0o777), Some(0o640));
+        assert_eq!(meta.uid, Some(md.uid()));
+        assert_eq!(meta.inode, Some(md.ino()));
+        assert_eq!(meta.nlink, Some(2));
+    }
+
+    #[test]
+    fn reports_link_targets_and_broken_links() {
+        let tmp = TempDir::new();
+        tmp.write("target", "");
+        let good = tmp.path().join("good");
+        let bad = tmp.path().join("bad");
+        symlink("target", &good).unwrap();
+        symlink("missing", &bad).unwrap();
+        assert_eq!(link_info(&good), (Some("target".into()), false));
+        assert_eq!(link_info(&bad), (Some("missing".into()), true));
+
+        let md = fs::symlink_metadata(&bad).unwrap();
+        let entry = entry_from_metadata("bad".into(), &bad, &md);
+        assert_eq!(entry.link_target.as_deref(), Some("missing"));
+        assert!(entry.link_broken);
+    }
+
+    #[test]
+    fn resolves_root_user_and_group_names() {
+        // uid and gid 0 resolve everywhere, unlike the uid tests may run as in containers.
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        // Served from the cache the second time.
+        assert_eq!(user_name(0).as_deref(), Some("root"));
+        assert!(group_name(0).is_some_and(|n| !n.is_empty()));
+    }
+}
@@ -1 +1,13 @@
+pub mod cursor;
+pub mod ignore;
+pub mod journal;
 pub mod listing;
+pub mod metadata;
+pub mod ops;
+pub mod size;
+pub mod sort;
+pub mod stream;
+pub mod tags;
+pub mod usage;
+pub mod walk;
+pub mod watch;
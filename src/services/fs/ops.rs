@@ -0,0 +1,1583 @@
+//! File operations: copy, move, rename, create and delete.
+//!
+//! Every operation runs through the same job engine. [`start`] runs it on a background
+//! thread and reports progress over a channel, which is what the explorer polls; [`run`]
+//! is the synchronous core for callers that already own a thread. Both honour a
+//! [`CancelToken`] between files and between chunks of a large file.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::Timestamp;
+use crate::services::trash::{self, TrashBatch};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bytes copied between cancellation checks and progress updates.
+const CHUNK_SIZE: usize = 1024 * 1024;
+/// Minimum time between two progress events.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Operation {
+    /// Copy each source, recursively, into `dest_dir`.
+    Copy {
+        sources: Vec<PathBuf>,
+        dest_dir: PathBuf,
+        #[serde(default)]
+        on_conflict: ConflictPolicy,
+    },
+    /// Move each source into `dest_dir`, copying and deleting when a rename would cross
+    /// filesystems.
+    Move {
+        sources: Vec<PathBuf>,
+        dest_dir: PathBuf,
+        #[serde(default)]
+        on_conflict: ConflictPolicy,
+    },
+    /// Give `path` a new name in the same directory.
+    Rename {
+        path: PathBuf,
+        new_name: String,
+    },
+    CreateDir {
+        path: PathBuf,
+    },
+    /// Create an empty file.
+    CreateFile {
+        path: PathBuf,
+    },
+    /// Move each path to the desktop trash.
+    Trash {
+        paths: Vec<PathBuf>,
+    },
+    /// Delete each path permanently, recursively for directories. Fails with
+    /// `Error::ConfirmationRequired` unless `confirmed` is set.
+    Delete {
+        paths: Vec<PathBuf>,
+        #[serde(default)]
+        confirmed: bool,
+    },
+}
+
+/// What a copy or move does when the destination name is already taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// Stop the job with `Error::AlreadyExists`.
+    #[default]
+    Error,
+    /// Replace the existing item. A directory is replaced as a whole, not merged. The
+    /// existing item is moved to the trash, and put back if the copy or move fails.
+    Overwrite,
+    Skip,
+    /// Keep the existing item and give the new one a " (2)"-style suffix.
+    KeepBoth,
+    /// Overwrite when the source was modified more recently than the existing item,
+    /// otherwise skip.
+    OverwriteIfNewer,
+    /// Ask for each conflict; see [`JobEvent::Conflict`] and the `ask` callback of [`run`].
+    Ask,
+}
+
+/// A destination that already exists, as presented to the `Ask` policy.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Conflict {
+    pub source: PathBuf,
+    pub dest: PathBuf,
+    pub source_is_dir: bool,
+    pub dest_is_dir: bool,
+    pub source_size: u64,
+    pub dest_size: u64,
+    pub source_modified: Option<Timestamp>,
+    pub dest_modified: Option<Timestamp>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictAction {
+    Overwrite,
+    Skip,
+    KeepBoth,
+    /// Stop the whole job.
+    Cancel,
+}
+
+/// Answer to a [`Conflict`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Resolution {
+    pub action: ConflictAction,
+    /// Use the same action for every later conflict of this job without asking.
+    pub apply_to_all: bool,
+}
+
+impl Resolution {
+    pub fn once(action: ConflictAction) -> Self {
+        Self {
+            action,
+            apply_to_all: false,
+        }
+    }
+}
+
+/// What a dry run expects to happen to one top-level item.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// `dest` will be created (copied, moved, renamed or made).
+    Create,
+    /// `dest` exists and will be replaced.
+    Replace,
+    /// `dest` exists and the source will be left alone.
+    Skip,
+    /// `dest` will be moved to the trash.
+    Trash,
+    Delete,
+    /// `dest` exists and the `Ask` policy will prompt for it.
+    Ask,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlanItem {
+    /// The item being copied, moved or renamed; `None` for creations and deletions.
+    pub source: Option<PathBuf>,
+    pub dest: PathBuf,
+    pub action: PlannedAction,
+}
+
+/// Snapshot of a running job. Files count every item visited, directories included.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Progress {
+    pub bytes_done: u64,
+    pub bytes_total: u64,
+    pub files_done: u64,
+    pub files_total: u64,
+    /// Item currently being processed.
+    pub current: Option<PathBuf>,
+    /// Estimated time left, once enough work has been done to tell.
+    pub eta: Option<Duration>,
+}
+
+/// Outcome of a completed operation.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub files: u64,
+    pub bytes: u64,
+    /// Top-level items the operation produced, e.g. the copies or the renamed file.
+    pub created: Vec<Created>,
+    /// Sources left alone because of a conflict.
+    pub skipped: Vec<PathBuf>,
+    /// Items a trash operation moved to the trash.
+    #[serde(default)]
+    pub trashed: Vec<Trashed>,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Created {
+    /// The item that was copied, moved or renamed; `None` for new files and directories.
+    pub source: Option<PathBuf>,
+    pub path: PathBuf,
+    /// An existing item at `path` was overwritten.
+    pub replaced: bool,
+    /// Trash id of the overwritten item, when it went to a trash that can be listed.
+    #[serde(default)]
+    pub replaced_id: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trashed {
+    pub path: PathBuf,
+    /// The item's id in the trash, where the platform can list the trash.
+    pub id: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum JobEvent {
+    Progress(Progress),
+    /// The job is paused until [`JobHandle::resolve`] answers this conflict.
+    Conflict(Conflict),
+    Finished(Report),
+    /// The operation stopped on an error. Work done before the error is kept and described
+    /// by the report.
+    Failed(Error, Report),
+    /// The operation stopped at the caller's request. Items finished before are kept and
+    /// described by the report; the partial copy of the item in progress is removed.
+    Cancelled(Report),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct JobId(pub u64);
+
+impl JobId {
+    fn next() -> Self {
+        static NEXT: AtomicU64 = AtomicU64::new(1);
+        JobId(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl std::fmt::Display for JobId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "job-{}", self.0)
+    }
+}
+
+/// Receiving end of a background job.
+///
+/// Dropping the handle does not stop the job; call [`JobHandle::cancel`] for that.
+pub struct JobHandle {
+    pub id: JobId,
+    rx: Receiver<JobEvent>,
+    resolve_tx: Sender<Resolution>,
+    cancel: CancelToken,
+}
+
+impl JobHandle {
+    /// Non-blocking poll for UI loops. `Disconnected` means the job has ended.
+    pub fn try_next(&self) -> std::result::Result<JobEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    /// Answer the pending [`JobEvent::Conflict`].
+    pub fn resolve(&self, resolution: Resolution) {
+        let _ = self.resolve_tx.send(resolution);
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+        // Wake the job if it is waiting on a conflict.
+        self.resolve(Resolution::once(ConflictAction::Cancel));
+    }
+
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+}
+
+impl Iterator for JobHandle {
+    type Item = JobEvent;
+
+    fn next(&mut self) -> Option<JobEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Start `op` on a background thread.
+pub fn start(op: Operation) -> JobHandle {
+    start_with(op, |_, _| {})
+}
+
+/// Start `op` on a background thread and call `on_end` there with what it did, whether it
+/// finished, failed or was cancelled, before the final event is sent.
+pub fn start_with(
+    op: Operation,
+    on_end: impl FnOnce(&Operation, &Report) + Send + 'static,
+) -> JobHandle {
+    let (tx, rx) = mpsc::channel();
+    let (resolve_tx, resolve_rx) = mpsc::channel();
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+
+    thread::spawn(move || {
+        let progress_tx = tx.clone();
+        let conflict_tx = tx.clone();
+        let (report, result) = run_partial(
+            &op,
+            &token,
+            |p| {
+                let _ = progress_tx.send(JobEvent::Progress(p.clone()));
+            },
+            |c| {
+                if conflict_tx.send(JobEvent::Conflict(c.clone())).is_err() {
+                    return Resolution::once(ConflictAction::Cancel);
+                }
+                resolve_rx
+                    .recv()
+                    .unwrap_or(Resolution::once(ConflictAction::Cancel))
+            },
+        );
+        on_end(&op, &report);
+        let event = match result {
+            Ok(()) => JobEvent::Finished(report),
+            Err(Error::Cancelled) => JobEvent::Cancelled(report),
+            Err(err) => {
+                tracing::warn!(?op, %err, "file operation failed");
+                JobEvent::Failed(err, report)
+            }
+        };
+        let _ = tx.send(event);
+    });
+
+    JobHandle {
+        id: JobId::next(),
+        rx,
+        resolve_tx,
+        cancel,
+    }
+}
+
+/// Run `op` on the current thread, calling `on_progress` as work completes and `ask`
+/// for each conflict under [`ConflictPolicy::Ask`].
+///
+/// Returns `Error::Cancelled` if `cancel` fires or `ask` answers
+/// [`ConflictAction::Cancel`] before the operation finishes.
+pub fn run(
+    op: &Operation,
+    cancel: &CancelToken,
+    on_progress: impl FnMut(&Progress),
+    ask: impl FnMut(&Conflict) -> Resolution,
+) -> Result<Report> {
+    let (report, result) = run_partial(op, cancel, on_progress, ask);
+    result.map(|()| report)
+}
+
+/// Like [`run`], but also reports the work done when the operation stops early.
+fn run_partial(
+    op: &Operation,
+    cancel: &CancelToken,
+    mut on_progress: impl FnMut(&Progress),
+    mut ask: impl FnMut(&Conflict) -> Resolution,
+) -> (Report, Result<()>) {
+    let mut job = Job::new(cancel, &mut on_progress, &mut ask, false);
+    let result = job.execute(op);
+    job.fill_trash_ids();
+    if result.is_ok() {
+        job.progress.current = None;
+        job.emit(true);
+    }
+    let report = Report {
+        files: job.progress.files_done,
+        bytes: job.progress.bytes_done,
+        created: job.created,
+        skipped: job.skipped,
+        trashed: job.trashed,
+        elapsed: job.started.elapsed(),
+    };
+    (report, result)
+}
+
+/// Work out what `op` would do without touching the filesystem.
+///
+/// Fails with the same errors a real run would hit up front, such as a missing source or
+/// an `Error` policy conflict. Under `Ask`, conflicts are reported as
+/// [`PlannedAction::Ask`].
+pub fn dry_run(op: &Operation) -> Result<Vec<PlanItem>> {
+    with_job(true, |job| {
+        job.execute(op)?;
+        Ok(std::mem::take(&mut job.plan))
+    })
+}
+
+/// Copy `src` to exactly `dest`, which must not exist. Used to replay journal entries.
+pub(crate) fn copy_exact(src: &Path, dest: &Path) -> Result<()> {
+    with_job(false, |job| job.copy_new(src, dest))
+}
+
+/// Move `src` to exactly `dest`, which must not exist. Used to replay journal entries.
+pub(crate) fn move_exact(src: &Path, dest: &Path) -> Result<()> {
+    ensure_absent(dest)?;
+    with_job(false, |job| job.move_item(src, dest))
+}
+
+/// Run `f` against a job without progress reporting or conflict prompts.
+fn with_job<T>(dry_run: bool, f: impl FnOnce(&mut Job<'_>) -> Result<T>) -> Result<T> {
+    let cancel = CancelToken::new();
+    let mut on_progress = |_: &Progress| {};
+    let mut ask = |_: &Conflict| Resolution::once(ConflictAction::Cancel);
+    let mut job = Job::new(&cancel, &mut on_progress, &mut ask, dry_run);
+    f(&mut job)
+}
+
+struct Job<'a> {
+    cancel: &'a CancelToken,
+    on_progress: &'a mut dyn FnMut(&Progress),
+    ask: &'a mut dyn FnMut(&Conflict) -> Resolution,
+    /// Effective conflict policy; an "apply to all" answer replaces `Ask`.
+    policy: ConflictPolicy,
+    dry_run: bool,
+    plan: Vec<PlanItem>,
+    /// Destinations claimed earlier in this job, so later items do not collide with them.
+    claimed: HashSet<PathBuf>,
+    progress: Progress,
+    created: Vec<Created>,
+    skipped: Vec<PathBuf>,
+    trashed: Vec<Trashed>,
+    /// Everything this job moved to the trash, whose ids are looked up once it ends.
+    trash: TrashBatch,
+    /// The entry each item in `trash` belongs to, by its index there.
+    trash_slots: Vec<(usize, TrashSlot)>,
+    started: Instant,
+    last_emit: Option<Instant>,
+}
+
+/// An entry of the report waiting for a trash id.
+enum TrashSlot {
+    /// An index into `Job::trashed`.
+    Trashed(usize),
+    /// An index into `Job::created`, for the item it replaced.
+    Replaced(usize),
+}
+
+/// Where a copied or moved item ends up after conflict resolution.
+enum Target {
+    Fresh(PathBuf),
+    Replace(PathBuf),
+    Skip(PathBuf),
+    Ask(PathBuf),
+}
+
+impl<'a> Job<'a> {
+    fn new(
+        cancel: &'a CancelToken,
+        on_progress: &'a mut dyn FnMut(&Progress),
+        ask: &'a mut dyn FnMut(&Conflict) -> Resolution,
+        dry_run: bool,
+    ) -> Self {
+        Self {
+            cancel,
+            on_progress,
+            ask,
+            policy: ConflictPolicy::Error,
+            dry_run,
+            plan: Vec::new(),
+            claimed: HashSet::new(),
+            progress: Progress::default(),
+            created: Vec::new(),
+            skipped: Vec::new(),
+            trashed: Vec::new(),
+            trash: TrashBatch::new(),
+            trash_slots: Vec::new(),
+            started: Instant::now(),
+            last_emit: None,
+        }
+    }
+
+    /// Put the ids of what this job trashed into its report, listing the trash only once.
+    fn fill_trash_ids(&mut self) {
+        if self.trash_slots.is_empty() {
+            return;
+        }
+        let mut ids = self.trash.ids();
+        for (ix, slot) in self.trash_slots.drain(..) {
+            let id = ids[ix].take();
+            match slot {
+                TrashSlot::Trashed(i) => self.trashed[i].id = id,
+                TrashSlot::Replaced(i) => self.created[i].replaced_id = id,
+            }
+        }
+    }
+
+    fn execute(&mut self, op: &Operation) -> Result<()> {
+        match op {
+            Operation::Copy {
+                sources,
+                dest_dir,
+                on_conflict,
+            } => {
+                self.policy = *on_conflict;
+                self.copy(sources, dest_dir)
+            }
+            Operation::Move {
+                sources,
+                dest_dir,
+                on_conflict,
+            } => {
+                self.policy = *on_conflict;
+                self.move_to(sources, dest_dir)
+            }
+            Operation::Rename { path, new_name } => self.rename(path, new_name),
+            Operation::CreateDir { path } => self.create(path, true),
+            Operation::CreateFile { path } => self.create(path, false),
+            Operation::Trash { paths } => self.trash(paths),
+            Operation::Delete { paths, confirmed } => self.delete(paths, *confirmed),
+        }
+    }
+
+    fn copy(&mut self, sources: &[PathBuf], dest_dir: &Path) -> Result<()> {
+        let targets = targets(sources, dest_dir)?;
+        let mut sizes = Vec::with_capacity(sources.len());
+        if !self.dry_run {
+            for src in sources {
+                let size = plan(std::slice::from_ref(src))?;
+                self.progress.files_total += size.0;
+                self.progress.bytes_total += size.1;
+                sizes.push(size);
+            }
+            self.emit(true);
+        }
+
+        for (i, (src, dest)) in targets.into_iter().enumerate() {
+            self.check()?;
+            let target = self.resolve(&src, dest)?;
+            if self.record(&src, &target) {
+                continue;
+            }
+            let (dest, replaced) = match target {
+                Target::Fresh(dest) => {
+                    self.copy_new(&src, &dest)?;
+                    (dest, None)
+                }
+                Target::Replace(dest) => {
+                    let trashed = self.replace(&dest, |job| job.copy_new(&src, &dest))?;
+                    (dest, Some(trashed))
+                }
+                Target::Skip(_) | Target::Ask(_) => {
+                    let (files, bytes) = sizes[i];
+                    self.progress.files_total -= files;
+                    self.progress.bytes_total -= bytes;
+                    self.skipped.push(src);
+                    continue;
+                }
+            };
+            if let Some(Some(ix)) = replaced {
+                let slot = TrashSlot::Replaced(self.created.len());
+                self.trash_slots.push((ix, slot));
+            }
+            self.created.push(Created {
+                source: Some(src),
+                path: dest,
+                replaced: replaced.is_some(),
+                replaced_id: None,
+            });
+        }
+        Ok(())
+    }
+
+    fn move_to(&mut self, sources: &[PathBuf], dest_dir: &Path) -> Result<()> {
+        let targets = targets(sources, dest_dir)?;
+        // Renames are instant, so each counts as one item until it has to fall back.
+        self.progress.files_total = targets.len() as u64;
+        self.emit(true);
+
+        for (src, dest) in targets {
+            self.check()?;
+            let target = self.resolve(&src, dest)?;
+            if self.record(&src, &target) {
+                continue;
+            }
+            let (dest, replaced) = match target {
+                Target::Fresh(dest) => {
+                    self.move_item(&src, &dest)?;
+                    (dest, None)
+                }
+                Target::Replace(dest) => {
+                    let trashed = self.replace(&dest, |job| job.move_item(&src, &dest))?;
+                    (dest, Some(trashed))
+                }
+                Target::Skip(_) | Target::Ask(_) => {
+                    self.progress.files_total -= 1;
+                    self.skipped.push(src);
+                    continue;
+                }
+            };
+            if let Some(Some(ix)) = replaced {
+                let slot = TrashSlot::Replaced(self.created.len());
+                self.trash_slots.push((ix, slot));
+            }
+            self.created.push(Created {
+                source: Some(src),
+                path: dest,
+                replaced: replaced.is_some(),
+                replaced_id: None,
+            });
+        }
+        Ok(())
+    }
+
+    /// Rename `src` to `dest`, falling back to copy and delete across filesystems.
+    fn move_item(&mut self, src: &Path, dest: &Path) -> Result<()> {
+        self.set_current(src);
+        match fs::rename(src, dest) {
+            Ok(()) => self.item_done(),
+            Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                let (files, bytes) = plan(&[src.to_path_buf()])?;
+                self.progress.files_total += files.saturating_sub(1);
+                self.progress.bytes_total += bytes;
+                self.copy_new(src, dest)?;
+                remove_tree(src)?;
+            }
+            Err(e) => return Err(Error::from_io(src, e)),
+        }
+        Ok(())
+    }
+
+    fn rename(&mut self, path: &Path, new_name: &str) -> Result<()> {
+        validate_name(new_name)?;
+        let dest = path.with_file_name(new_name);
+        fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+        if dest != path {
+            ensure_absent(&dest)?;
+        }
+        if self.dry_run {
+            self.plan.push(PlanItem {
+                source: Some(path.to_path_buf()),
+                dest,
+                action: PlannedAction::Create,
+            });
+            return Ok(());
+        }
+        self.progress.files_total = 1;
+        self.set_current(path);
+        fs::rename(path, &dest).map_err(|e| Error::from_io(path, e))?;
+        self.item_done();
+        self.created.push(Created {
+            source: Some(path.to_path_buf()),
+            path: dest,
+            replaced: false,
+            replaced_id: None,
+        });
+        Ok(())
+    }
+
+    fn create(&mut self, path: &Path, dir: bool) -> Result<()> {
+        if let Some(name) = path.file_name() {
+            validate_name(&name.to_string_lossy())?;
+        }
+        if self.dry_run {
+            ensure_absent(path)?;
+            self.plan.push(PlanItem {
+                source: None,
+                dest: path.to_path_buf(),
+                action: PlannedAction::Create,
+            });
+            return Ok(());
+        }
+        self.progress.files_total = 1;
+        self.set_current(path);
+        let result = if dir {
+            fs::create_dir(path)
+        } else {
+            OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(path)
+                .map(drop)
+        };
+        result.map_err(|e| Error::from_io(path, e))?;
+        self.item_done();
+        self.created.push(Created {
+            source: None,
+            path: path.to_path_buf(),
+            replaced: false,
+            replaced_id: None,
+        });
+        Ok(())
+    }
+
+    fn trash(&mut self, paths: &[PathBuf]) -> Result<()> {
+        if self.dry_run {
+            for path in paths {
+                fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+                self.plan.push(PlanItem {
+                    source: None,
+                    dest: path.clone(),
+                    action: PlannedAction::Trash,
+                });
+            }
+            return Ok(());
+        }
+        self.progress.files_total = paths.len() as u64;
+        self.emit(true);
+        for path in paths {
+            self.check()?;
+            self.set_current(path);
+            let ix = self.trash.trash(path)?;
+            let slot = TrashSlot::Trashed(self.trashed.len());
+            self.trash_slots.push((ix, slot));
+            self.trashed.push(Trashed {
+                path: path.clone(),
+                id: None,
+            });
+            self.item_done();
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, paths: &[PathBuf], confirmed: bool) -> Result<()> {
+        if self.dry_run {
+            for path in paths {
+                fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+                self.plan.push(PlanItem {
+                    source: None,
+                    dest: path.clone(),
+                    action: PlannedAction::Delete,
+                });
+            }
+            return Ok(());
+        }
+        if !confirmed {
+            return Err(Error::ConfirmationRequired("permanent deletion"));
+        }
+        let (files, _) = plan(paths)?;
+        self.progress.files_total = files;
+        self.emit(true);
+        for path in paths {
+            self.delete_item(path)?;
+        }
+        Ok(())
+    }
+
+    /// Apply the conflict policy to the destination of `src`.
+    fn resolve(&mut self, src: &Path, dest: PathBuf) -> Result<Target> {
+        let src_md = fs::symlink_metadata(src).map_err(|e| Error::from_io(src, e))?;
+        let dest_md = fs::symlink_metadata(&dest).ok();
+        if dest_md.is_none() && !self.claimed.contains(&dest) {
+            self.claimed.insert(dest.clone());
+            return Ok(Target::Fresh(dest));
+        }
+
+        let action = match self.policy {
+            ConflictPolicy::Error => return Err(Error::AlreadyExists { path: dest }),
+            ConflictPolicy::Overwrite => ConflictAction::Overwrite,
+            ConflictPolicy::Skip => ConflictAction::Skip,
+            ConflictPolicy::KeepBoth => ConflictAction::KeepBoth,
+            ConflictPolicy::OverwriteIfNewer => {
+                let src_time = src_md.modified().ok();
+                let dest_time = dest_md.as_ref().and_then(|m| m.modified().ok());
+                match (src_time, dest_time) {
+                    (Some(s), Some(d)) if s > d => ConflictAction::Overwrite,
+                    _ => ConflictAction::Skip,
+                }
+            }
+            ConflictPolicy::Ask if self.dry_run => return Ok(Target::Ask(dest)),
+            ConflictPolicy::Ask => {
+                let conflict = Conflict {
+                    source: src.to_path_buf(),
+                    dest: dest.clone(),
+                    source_is_dir: src_md.is_dir(),
+                    dest_is_dir: dest_md.as_ref().is_some_and(|m| m.is_dir()),
+                    source_size: src_md.len(),
+                    dest_size: dest_md.as_ref().map_or(0, |m| m.len()),
+                    source_modified: src_md.modified().ok().map(Timestamp::from_system_time),
+                    dest_modified: dest_md
+                        .as_ref()
+                        .and_then(|m| m.modified().ok())
+                        .map(Timestamp::from_system_time),
+                };
+                let answer = (self.ask)(&conflict);
+                if answer.apply_to_all {
+                    self.policy = match answer.action {
+                        ConflictAction::Overwrite => ConflictPolicy::Overwrite,
+                        ConflictAction::Skip => ConflictPolicy::Skip,
+                        ConflictAction::KeepBoth => ConflictPolicy::KeepBoth,
+                        ConflictAction::Cancel => self.policy,
+                    };
+                }
+                answer.action
+            }
+        };
+
+        match action {
+            ConflictAction::Cancel => Err(Error::Cancelled),
+            ConflictAction::Skip => Ok(Target::Skip(dest)),
+            ConflictAction::KeepBoth => {
+                let dest = free_name(&dest, src_md.is_dir(), &self.claimed);
+                self.claimed.insert(dest.clone());
+                Ok(Target::Fresh(dest))
+            }
+            ConflictAction::Overwrite => {
+                if is_same_file(src, &dest) {
+                    // Replacing an item with itself would only destroy it.
+                    return Ok(Target::Skip(dest));
+                }
+                if let (Ok(s), Ok(d)) = (fs::canonicalize(src), fs::canonicalize(&dest)) {
+                    if s.starts_with(&d) {
+                        return Err(Error::Other(format!(
+                            "cannot replace {} with an item inside it",
+                            dest.display()
+                        )));
+                    }
+                }
+                self.claimed.insert(dest.clone());
+                Ok(Target::Replace(dest))
+            }
+        }
+    }
+
+    /// Record `target` in the dry-run plan. Returns whether this is a dry run.
+    fn record(&mut self, src: &Path, target: &Target) -> bool {
+        if !self.dry_run {
+            return false;
+        }
+        let (dest, action) = match target {
+            Target::Fresh(d) => (d, PlannedAction::Create),
+            Target::Replace(d) => (d, PlannedAction::Replace),
+            Target::Skip(d) => (d, PlannedAction::Skip),
+            Target::Ask(d) => (d, PlannedAction::Ask),
+        };
+        self.plan.push(PlanItem {
+            source: Some(src.to_path_buf()),
+            dest: dest.clone(),
+            action,
+        });
+        true
+    }
+
+    /// Put a new item at the occupied `dest` with `make`. The occupant is moved to the trash
+    /// first and restored if `make` fails. Returns its index in the job's trash batch.
+    ///
+    /// Where the occupant cannot be trashed, e.g. on a volume without a trash, it is set
+    /// aside under a temporary name instead and deleted once `make` succeeds.
+    fn replace(
+        &mut self,
+        dest: &Path,
+        make: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<Option<usize>> {
+        let ix = match self.trash.trash(dest) {
+            Ok(ix) => ix,
+            Err(err) => {
+                tracing::warn!(%err, "cannot trash the item being replaced, deleting it instead");
+                self.replace_permanently(dest, make)?;
+                return Ok(None);
+            }
+        };
+        if let Err(err) = make(self) {
+            // `make` cleans up after itself, unless a move already left the item at `dest`.
+            let id = self.trash.ids().swap_remove(ix);
+            let restored = match id {
+                Some(id) if fs::symlink_metadata(dest).is_err() => {
+                    trash::restore(&[id], ConflictPolicy::Error)
+                        .is_ok_and(|report| report.failed.is_empty())
+                }
+                _ => false,
+            };
+            if !restored {
+                tracing::warn!(
+                    path = %dest.display(),
+                    "could not put back the item being replaced; it is in the trash"
+                );
+            }
+            return Err(err);
+        }
+        Ok(Some(ix))
+    }
+
+    /// [`Job::replace`] for items that cannot go to the trash. The occupant is set aside
+    /// under a temporary name and only removed once `make` succeeds; if it fails, the
+    /// occupant is put back.
+    fn replace_permanently(
+        &mut self,
+        dest: &Path,
+        make: impl FnOnce(&mut Self) -> Result<()>,
+    ) -> Result<()> {
+        let aside = temp_sibling(dest);
+        fs::rename(dest, &aside).map_err(|e| Error::from_io(dest, e))?;
+        if let Err(err) = make(self) {
+            // `make` cleans up after itself, unless a move already left the item at `dest`.
+            if fs::symlink_metadata(dest).is_ok() || fs::rename(&aside, dest).is_err() {
+                tracing::warn!(
+                    path = %dest.display(),
+                    kept = %aside.display(),
+                    "could not put back the item being replaced"
+                );
+            }
+            return Err(err);
+        }
+        if let Err(err) = remove_tree(&aside) {
+            tracing::warn!(%err, "could not remove the replaced item");
+        }
+        Ok(())
+    }
+
+    /// Copy `src` to `dest`, which must not exist, removing whatever was written of
+    /// `dest` if the copy fails or is cancelled.
+    fn copy_new(&mut self, src: &Path, dest: &Path) -> Result<()> {
+        let result = self.copy_item(src, dest);
+        if let Err(err) = &result {
+            // Anything else at `dest` is not ours to remove.
+            let occupied = matches!(err, Error::AlreadyExists { path } if path == dest);
+            if !occupied && fs::symlink_metadata(dest).is_ok() {
+                if let Err(err) = remove_tree(dest) {
+                    tracing::warn!(%err, "could not remove a partial copy");
+                }
+            }
+        }
+        result
+    }
+
+    fn copy_item(&mut self, src: &Path, dest: &Path) -> Result<()> {
+        self.check()?;
+        let md = fs::symlink_metadata(src).map_err(|e| Error::from_io(src, e))?;
+        self.set_current(src);
+        let ft = md.file_type();
+
+        if ft.is_dir() {
+            fs::create_dir(dest).map_err(|e| Error::from_io(dest, e))?;
+            self.item_done();
+            let read = fs::read_dir(src).map_err(|e| Error::from_io(src, e))?;
+            for entry in read {
+                let entry = entry.map_err(|e| Error::from_io(src, e))?;
+                self.copy_item(&entry.path(), &dest.join(entry.file_name()))?;
+            }
+            // Applied last so a read-only source directory can still be filled and the
+            // modification time is not bumped by the children.
+            fs::set_permissions(dest, md.permissions()).map_err(|e| Error::from_io(dest, e))?;
+            if let (Ok(modified), Ok(dir)) = (md.modified(), File::open(dest)) {
+                let _ = dir.set_modified(modified);
+            }
+        } else if ft.is_symlink() {
+            copy_symlink(src, dest)?;
+            self.item_done();
+        } else if ft.is_file() {
+            if let Err(err) = self.copy_file(src, dest, &md) {
+                let _ = fs::remove_file(dest);
+                return Err(err);
+            }
+            self.item_done();
+        } else {
+            return Err(Error::Other(format!(
+                "cannot copy special file {}",
+                src.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn copy_file(&mut self, src: &Path, dest: &Path, md: &fs::Metadata) -> Result<()> {
+        let mut input = File::open(src).map_err(|e| Error::from_io(src, e))?;
+        let mut output = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(dest)
+            .map_err(|e| Error::from_io(dest, e))?;
+
+        let mut buf = vec![0u8; CHUNK_SIZE.min(md.len().max(1) as usize)];
+        loop {
+            self.check()?;
+            let n = match input.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(Error::from_io(src, e)),
+            };
+            output
+                .write_all(&buf[..n])
+                .map_err(|e| Error::from_io(dest, e))?;
+            self.progress.bytes_done += n as u64;
+            self.emit(false);
+        }
+
+        output
+            .set_permissions(md.permissions())
+            .map_err(|e| Error::from_io(dest, e))?;
+        if let Ok(modified) = md.modified() {
+            let _ = output.set_modified(modified);
+        }
+        Ok(())
+    }
+
+    fn delete_item(&mut self, path: &Path) -> Result<()> {
+        self.check()?;
+        let md = fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+        self.set_current(path);
+        if md.is_dir() {
+            let read = fs::read_dir(path).map_err(|e| Error::from_io(path, e))?;
+            for entry in read {
+                let entry = entry.map_err(|e| Error::from_io(path, e))?;
+                self.delete_item(&entry.path())?;
+            }
+            fs::remove_dir(path).map_err(|e| Error::from_io(path, e))?;
+        } else {
+            fs::remove_file(path).map_err(|e| Error::from_io(path, e))?;
+        }
+        self.item_done();
+        Ok(())
+    }
+
+    fn check(&self) -> Result<()> {
+        if self.cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+
+    fn set_current(&mut self, path: &Path) {
+        self.progress.current = Some(path.to_path_buf());
+        self.emit(false);
+    }
+
+    fn item_done(&mut self) {
+        self.progress.files_done += 1;
+        self.emit(false);
+    }
+
+    /// Report progress, throttled to `PROGRESS_INTERVAL` unless forced.
+    fn emit(&mut self, force: bool) {
+        let now = Instant::now();
+        if !force
+            && self
+                .last_emit
+                .is_some_and(|t| now.duration_since(t) < PROGRESS_INTERVAL)
+        {
+            return;
+        }
+        self.last_emit = Some(now);
+        self.progress.eta = eta(&self.progress, now.duration_since(self.started));
+        (self.on_progress)(&self.progress);
+    }
+}
+
+/// Extrapolate the time left from the rate so far, by bytes when there are any.
+fn eta(p: &Progress, elapsed: Duration) -> Option<Duration> {
+    let (done, total) = if p.bytes_total > 0 {
+        (p.bytes_done, p.bytes_total)
+    } else {
+        (p.files_done, p.files_total)
+    };
+    if done == 0 || done >= total || elapsed.is_zero() {
+        return None;
+    }
+    let rate = done as f64 / elapsed.as_secs_f64();
+    Duration::try_from_secs_f64((total - done) as f64 / rate).ok()
+}
+
+/// Item count and total file size under `paths`, without following links.
+fn plan(paths: &[PathBuf]) -> Result<(u64, u64)> {
+    let mut files = 0;
+    let mut bytes = 0;
+    for path in paths {
+        for entry in walkdir::WalkDir::new(path) {
+            let entry = entry.map_err(walk_error)?;
+            files += 1;
+            if entry.file_type().is_file() {
+                bytes += entry.metadata().map_err(walk_error)?.len();
+            }
+        }
+    }
+    Ok((files, bytes))
+}
+
+fn walk_error(err: walkdir::Error) -> Error {
+    let path = err.path().map(Path::to_path_buf).unwrap_or_default();
+    let msg = err.to_string();
+    match err.into_io_error() {
+        Some(io) => Error::from_io(path, io),
+        None => Error::Other(msg),
+    }
+}
+
+/// Pair each source with its destination inside `dest_dir`, rejecting copies of a
+/// directory into itself.
+fn targets(sources: &[PathBuf], dest_dir: &Path) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let dest_real = fs::canonicalize(dest_dir).map_err(|e| Error::from_io(dest_dir, e))?;
+    sources
+        .iter()
+        .map(|src| {
+            let name = src
+                .file_name()
+                .ok_or_else(|| Error::Other(format!("cannot copy {}", src.display())))?;
+            let md = fs::symlink_metadata(src).map_err(|e| Error::from_io(src, e))?;
+            let src_real = fs::canonicalize(src).map_err(|e| Error::from_io(src, e));
+            if md.is_dir() && dest_real.starts_with(src_real?) {
+                return Err(Error::Other(format!(
+                    "cannot copy {} into itself",
+                    src.display()
+                )));
+            }
+            Ok((src.clone(), dest_dir.join(name)))
+        })
+        .collect()
+}
+
+pub(crate) fn ensure_absent(path: &Path) -> Result<()> {
+    if fs::symlink_metadata(path).is_ok() {
+        return Err(Error::AlreadyExists {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(())
+}
+
+/// First free "name (n).ext" next to `dest`, starting at 2, avoiding `claimed` paths too.
+pub(crate) fn free_name(dest: &Path, is_dir: bool, claimed: &HashSet<PathBuf>) -> PathBuf {
+    let (stem, ext) = if is_dir {
+        (dest.file_name().unwrap_or_default(), None)
+    } else {
+        (dest.file_stem().unwrap_or_default(), dest.extension())
+    };
+    (2..)
+        .map(|n| {
+            let mut name = stem.to_os_string();
+            name.push(format!(" ({})", n));
+            if let Some(ext) = ext {
+                name.push(".");
+                name.push(ext);
+            }
+            dest.with_file_name(name)
+        })
+        .find(|p| fs::symlink_metadata(p).is_err() && !claimed.contains(p))
+        .expect("unbounded candidate range")
+}
+
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || name == "."
+        || name == ".."
+        || name.contains('/')
+        || name.contains(std::path::MAIN_SEPARATOR)
+        || name.contains('\0')
+    {
+        return Err(Error::Other(format!("invalid file name: {name:?}")));
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn copy_symlink(src: &Path, dest: &Path) -> Result<()> {
+    let target = fs::read_link(src).map_err(|e| Error::from_io(src, e))?;
+    std::os::unix::fs::symlink(target, dest).map_err(|e| Error::from_io(dest, e))
+}
+
+#[cfg(not(unix))]
+fn copy_symlink(_src: &Path, _dest: &Path) -> Result<()> {
+    Err(Error::NotImplemented("copying symlinks"))
+}
+
+/// An unused hidden name next to `path` to keep an item under for a while.
+fn temp_sibling(path: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(
+        ".nohrs-{}-{}",
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    path.with_file_name(name)
+}
+
+/// Remove a file or directory tree without progress reporting.
+pub(crate) fn remove_tree(path: &Path) -> Result<()> {
+    let md = fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+    let result = if md.is_dir() {
+        fs::remove_dir_all(path)
+    } else {
+        fs::remove_file(path)
+    };
+    result.map_err(|e| Error::from_io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn run_op(op: &Operation) -> Result<Report> {
+        run(
+            op,
+            &CancelToken::new(),
+            |_| {},
+            |_| Resolution::once(ConflictAction::Cancel),
+        )
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn copies_trees_and_reports_progress() {
+        let tmp = TempDir::new();
+        tmp.write("src/a.txt", "aaa");
+        tmp.write("src/sub/b.txt", "bb");
+        let dest = tmp.mkdir("dest");
+        let op = Operation::Copy {
+            sources: vec![tmp.path().join("src")],
+            dest_dir: dest.clone(),
+            on_conflict: ConflictPolicy::Error,
+        };
+        let mut last = Progress::default();
+        let report = run(
+            &op,
+            &CancelToken::new(),
+            |p| last = p.clone(),
+            |_| unreachable!(),
+        )
+        .unwrap();
+
+        assert_eq!(read(&dest.join("src/a.txt")), "aaa");
+        assert_eq!(read(&dest.join("src/sub/b.txt")), "bb");
+        assert!(tmp.path().join("src/a.txt").exists());
+        assert_eq!((report.files, report.bytes), (4, 5));
+        assert_eq!((last.files_done, last.files_total), (4, 4));
+        assert_eq!((last.bytes_done, last.bytes_total), (5, 5));
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.created[0].path, dest.join("src"));
+    }
+
+    #[test]
+    fn moves_renames_and_creates() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a.txt", "a");
+        let dest = tmp.mkdir("dest");
+        run_op(&Operation::Move {
+            sources: vec![file.clone()],
+            dest_dir: dest.clone(),
+            on_conflict: ConflictPolicy::Error,
+        })
+        .unwrap();
+        assert!(!file.exists());
+        assert_eq!(read(&dest.join("a.txt")), "a");
+
+        run_op(&Operation::Rename {
+            path: dest.join("a.txt"),
+            new_name: "b.txt".into(),
+        })
+        .unwrap();
+        assert_eq!(read(&dest.join("b.txt")), "a");
+
+        run_op(&Operation::CreateDir {
+            path: dest.join("new"),
+        })
+        .unwrap();
+        run_op(&Operation::CreateFile {
+            path: dest.join("new/empty"),
+        })
+        .unwrap();
+        assert_eq!(read(&dest.join("new/empty")), "");
+        assert!(matches!(
+            run_op(&Operation::CreateFile {
+                path: dest.join("new/empty"),
+            }),
+            Err(Error::AlreadyExists { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_names_and_copies_into_self() {
+        let tmp = TempDir::new();
+        let dir = tmp.mkdir("dir");
+        for name in ["", "..", "a/b"] {
+            assert!(run_op(&Operation::Rename {
+                path: dir.clone(),
+                new_name: name.into(),
+            })
+            .is_err());
+        }
+        let inner = tmp.mkdir("dir/inner");
+        assert!(run_op(&Operation::Copy {
+            sources: vec![dir.clone()],
+            dest_dir: inner,
+            on_conflict: ConflictPolicy::Error,
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn delete_needs_confirmation() {
+        let tmp = TempDir::new();
+        tmp.write("dir/a", "a");
+        let dir = tmp.path().join("dir");
+        assert!(matches!(
+            run_op(&Operation::Delete {
+                paths: vec![dir.clone()],
+                confirmed: false,
+            }),
+            Err(Error::ConfirmationRequired(_))
+        ));
+        assert!(dir.exists());
+        let report = run_op(&Operation::Delete {
+            paths: vec![dir.clone()],
+            confirmed: true,
+        })
+        .unwrap();
+        assert_eq!(report.files, 2);
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn cancelled_job_reports_cancelled() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a", "a");
+        let dest = tmp.mkdir("dest");
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let op = Operation::Copy {
+            sources: vec![file],
+            dest_dir: dest.clone(),
+            on_conflict: ConflictPolicy::Error,
+        };
+        let result = run(&op, &cancel, |_| {}, |_| unreachable!());
+        assert!(matches!(result, Err(Error::Cancelled)));
+        assert!(!dest.join("a").exists());
+    }
+
+    #[test]
+    fn background_job_finishes() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a", "a");
+        let dest = tmp.mkdir("dest");
+        let handle = start(Operation::Copy {
+            sources: vec![file],
+            dest_dir: dest.clone(),
+            on_conflict: ConflictPolicy::Error,
+        });
+        let last = handle.last();
+        assert!(matches!(last, Some(JobEvent::Finished(report)) if report.files == 1));
+        assert!(dest.join("a").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_background_job_reports_finished_items() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a", "a");
+        uncopyable_dir(&tmp);
+        let dest = tmp.mkdir("dest");
+        let (tx, rx) = mpsc::channel();
+        let handle = start_with(
+            Operation::Copy {
+                sources: vec![file, tmp.path().join("d")],
+                dest_dir: dest.clone(),
+                on_conflict: ConflictPolicy::Error,
+            },
+            move |_, report| tx.send(report.clone()).unwrap(),
+        );
+        let Some(JobEvent::Failed(_, report)) = handle.last() else {
+            panic!("copy of a FIFO should fail");
+        };
+        let created: Vec<_> = report.created.iter().map(|c| c.path.clone()).collect();
+        assert_eq!(created, [dest.join("a")]);
+        assert_eq!(rx.recv().unwrap(), report);
+    }
+
+    fn copy_into(tmp: &TempDir, source: &str, on_conflict: ConflictPolicy) -> Result<Report> {
+        run_op(&Operation::Copy {
+            sources: vec![tmp.path().join(source)],
+            dest_dir: tmp.path().join("dest"),
+            on_conflict,
+        })
+    }
+
+    /// Names in `dir`, sorted.
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn applies_conflict_policies() {
+        let tmp = TempDir::new();
+        tmp.write("a.txt", "new");
+        tmp.write("dest/a.txt", "old");
+        let dest = tmp.path().join("dest/a.txt");
+
+        assert!(matches!(
+            copy_into(&tmp, "a.txt", ConflictPolicy::Error),
+            Err(Error::AlreadyExists { .. })
+        ));
+        let report = copy_into(&tmp, "a.txt", ConflictPolicy::Skip).unwrap();
+        assert_eq!(report.skipped, [tmp.path().join("a.txt")]);
+        assert_eq!(read(&dest), "old");
+
+        copy_into(&tmp, "a.txt", ConflictPolicy::KeepBoth).unwrap();
+        copy_into(&tmp, "a.txt", ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(read(&tmp.path().join("dest/a (2).txt")), "new");
+        assert_eq!(read(&tmp.path().join("dest/a (3).txt")), "new");
+
+        let report = copy_into(&tmp, "a.txt", ConflictPolicy::Overwrite).unwrap();
+        assert!(report.created[0].replaced);
+        assert_eq!(read(&dest), "new");
+        let id = report.created[0].replaced_id.clone().unwrap();
+        trash::restore(&[id], ConflictPolicy::KeepBoth).unwrap();
+        assert_eq!(read(&tmp.path().join("dest/a (4).txt")), "old");
+        fs::remove_file(tmp.path().join("dest/a (4).txt")).unwrap();
+        assert_eq!(
+            names(&tmp.path().join("dest")),
+            ["a (2).txt", "a (3).txt", "a.txt"]
+        );
+    }
+
+    #[test]
+    fn overwrites_only_older_items_when_asked() {
+        let tmp = TempDir::new();
+        let src = tmp.write("a", "new");
+        let dest = tmp.write("dest/a", "old");
+        let hour = Duration::from_secs(3600);
+        let now = std::time::SystemTime::now();
+        File::options()
+            .write(true)
+            .open(&dest)
+            .unwrap()
+            .set_modified(now)
+            .unwrap();
+        File::options()
+            .write(true)
+            .open(&src)
+            .unwrap()
+            .set_modified(now - hour)
+            .unwrap();
+        copy_into(&tmp, "a", ConflictPolicy::OverwriteIfNewer).unwrap();
+        assert_eq!(read(&dest), "old");
+
+        File::options()
+            .write(true)
+            .open(&src)
+            .unwrap()
+            .set_modified(now + hour)
+            .unwrap();
+        copy_into(&tmp, "a", ConflictPolicy::OverwriteIfNewer).unwrap();
+        assert_eq!(read(&dest), "new");
+    }
+
+    #[test]
+    fn asks_until_told_to_apply_to_all() {
+        let tmp = TempDir::new();
+        let sources: Vec<PathBuf> = ["a", "b", "c"]
+            .iter()
+            .map(|name| {
+                tmp.write(&format!("dest/{name}"), "old");
+                tmp.write(name, "new")
+            })
+            .collect();
+        let op = Operation::Copy {
+            sources,
+            dest_dir: tmp.path().join("dest"),
+            on_conflict: ConflictPolicy::Ask,
+        };
+        let mut asked = Vec::new();
+        let report = run(
+            &op,
+            &CancelToken::new(),
+            |_| {},
+            |conflict| {
+                asked.push(conflict.dest.clone());
+                Resolution {
+                    action: ConflictAction::Skip,
+                    apply_to_all: asked.len() == 2,
+                }
+            },
+        )
+        .unwrap();
+        assert_eq!(asked.len(), 2);
+        assert_eq!(report.skipped.len(), 3);
+
+        let answer = |_: &Conflict| Resolution::once(ConflictAction::Cancel);
+        let result = run(&op, &CancelToken::new(), |_| {}, answer);
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn dry_run_plans_without_touching_anything() {
+        let tmp = TempDir::new();
+        tmp.write("a", "new");
+        tmp.write("b", "new");
+        tmp.write("dest/a", "old");
+        let op = Operation::Move {
+            sources: vec![tmp.path().join("a"), tmp.path().join("b")],
+            dest_dir: tmp.path().join("dest"),
+            on_conflict: ConflictPolicy::Overwrite,
+        };
+        let plan = dry_run(&op).unwrap();
+        let actions: Vec<_> = plan.iter().map(|item| item.action).collect();
+        assert_eq!(actions, [PlannedAction::Replace, PlannedAction::Create]);
+        assert_eq!(plan[1].dest, tmp.path().join("dest/b"));
+        assert_eq!(names(tmp.path()), ["a", "b", "dest"]);
+        assert_eq!(names(&tmp.path().join("dest")), ["a"]);
+    }
+
+    #[test]
+    fn overwriting_an_item_with_itself_skips_it() {
+        let tmp = TempDir::new();
+        tmp.write("dest/a", "keep");
+        let op = Operation::Copy {
+            sources: vec![tmp.path().join("dest/a")],
+            dest_dir: tmp.path().join("dest"),
+            on_conflict: ConflictPolicy::Overwrite,
+        };
+        let report = run_op(&op).unwrap();
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(read(&tmp.path().join("dest/a")), "keep");
+    }
+
+    /// A directory whose copy fails part way, on a FIFO that cannot be copied.
+    #[cfg(unix)]
+    fn uncopyable_dir(tmp: &TempDir) {
+        tmp.write("d/a", "a");
+        tmp.write("d/sub/b", "b");
+        let fifo = std::ffi::CString::new(tmp.path().join("d/sub/fifo").to_str().unwrap()).unwrap();
+        // SAFETY: `fifo` is a valid NUL-terminated path.
+        assert_eq!(unsafe { libc::mkfifo(fifo.as_ptr(), 0o600) }, 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_copy_leaves_no_partial_tree() {
+        let tmp = TempDir::new();
+        uncopyable_dir(&tmp);
+        tmp.mkdir("dest");
+        assert!(copy_into(&tmp, "d", ConflictPolicy::Error).is_err());
+        assert!(names(&tmp.path().join("dest")).is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_overwrite_keeps_the_original() {
+        let tmp = TempDir::new();
+        uncopyable_dir(&tmp);
+        tmp.write("dest/d/keep", "original");
+        assert!(copy_into(&tmp, "d", ConflictPolicy::Overwrite).is_err());
+        assert_eq!(names(&tmp.path().join("dest")), ["d"]);
+        assert_eq!(names(&tmp.path().join("dest/d")), ["keep"]);
+        assert_eq!(read(&tmp.path().join("dest/d/keep")), "original");
+    }
+
+    #[test]
+    fn overwrite_replaces_directories_whole() {
+        let tmp = TempDir::new();
+        tmp.write("d/new", "new");
+        tmp.write("dest/d/old", "old");
+        run_op(&Operation::Move {
+            sources: vec![tmp.path().join("d")],
+            dest_dir: tmp.path().join("dest"),
+            on_conflict: ConflictPolicy::Overwrite,
+        })
+        .unwrap();
+        assert_eq!(names(&tmp.path().join("dest")), ["d"]);
+        assert_eq!(names(&tmp.path().join("dest/d")), ["new"]);
+        assert!(!tmp.path().join("d").exists());
+    }
+
+    #[test]
+    fn eta_extrapolates_the_rate() {
+        let progress = Progress {
+            bytes_done: 25,
+            bytes_total: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            eta(&progress, Duration::from_secs(1)),
+            Some(Duration::from_secs(3))
+        );
+        assert_eq!(eta(&Progress::default(), Duration::from_secs(1)), None);
+    }
+
+    #[test]
+    fn eta_is_none_when_the_rate_is_too_low_to_extrapolate() {
+        // One byte in the longest representable time leaves far more than `Duration::MAX`.
+        let progress = Progress {
+            bytes_done: 1,
+            bytes_total: u64::MAX,
+            ..Default::default()
+        };
+        assert_eq!(eta(&progress, Duration::MAX), None);
+    }
+}
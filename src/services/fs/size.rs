@@ -0,0 +1,350 @@
+//! Recursive directory sizes, computed in the background and cached.
+//!
+//! A cached size is reused while the directory itself is unmodified, which is a single
+//! stat. Changes deeper in the tree, and files changed in place, do not touch it, so
+//! callers watch the tree and [`SizeCache::invalidate`] the paths the watcher reports.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::services::fs::walk::{walk, WalkOptions};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::SystemTime;
+
+/// Totals for everything below a directory.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DirSize {
+    /// Sum of file lengths.
+    pub apparent: u64,
+    /// Space allocated on disk, including directories themselves. Hard-linked files are
+    /// counted once. Equal to `apparent` where the platform does not report blocks.
+    pub on_disk: u64,
+    pub files: u64,
+    pub dirs: u64,
+    /// Entries that could not be read; the totals leave them out.
+    pub unreadable: u64,
+}
+
+/// Compute the size of `path` on the calling thread. Stays on `path`'s filesystem and
+/// does not follow symlinks. Fails with `Error::Cancelled` once `cancel` is set.
+pub fn dir_size(path: &Path, cancel: &CancelToken) -> Result<DirSize> {
+    measure(path, cancel).map(|(size, _)| size)
+}
+
+/// The size of `path` and its modification time, read before its contents so a change
+/// made during the walk shows up as a newer time.
+fn measure(path: &Path, cancel: &CancelToken) -> Result<(DirSize, Option<SystemTime>)> {
+    let root = fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+    let mut size = DirSize {
+        on_disk: allocated(&root),
+        ..Default::default()
+    };
+    let mut seen = HashSet::new();
+    let opts = WalkOptions {
+        same_file_system: true,
+        ..Default::default()
+    };
+    for entry in walk(path, &opts) {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let Ok(md) = entry.and_then(|e| e.metadata()) else {
+            size.unreadable += 1;
+            continue;
+        };
+        if md.is_dir() {
+            size.dirs += 1;
+        } else {
+            size.files += 1;
+            if let Some(id) = hard_link_id(&md) {
+                if !seen.insert(id) {
+                    continue;
+                }
+            }
+            size.apparent += md.len();
+        }
+        size.on_disk += allocated(&md);
+    }
+    Ok((size, root.modified().ok()))
+}
+
+/// Directory sizes keyed by path, each valid while its directory is unmodified and not
+/// invalidated. Safe to share between threads.
+#[derive(Default)]
+pub struct SizeCache {
+    entries: Mutex<HashMap<PathBuf, Cached>>,
+}
+
+struct Cached {
+    /// Of the directory, when it was measured.
+    modified: SystemTime,
+    size: DirSize,
+}
+
+impl SizeCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cached size of `path`, if the directory has not been modified since.
+    pub fn get(&self, path: &Path) -> Option<DirSize> {
+        let (time, size) = {
+            let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            let cached = entries.get(path)?;
+            (cached.modified, cached.size)
+        };
+        (modified(path) == Some(time)).then_some(size)
+    }
+
+    /// Compute the size of `path`, or reuse the cached one.
+    pub fn size(&self, path: &Path, cancel: &CancelToken) -> Result<DirSize> {
+        if let Some(size) = self.get(path) {
+            return Ok(size);
+        }
+        let (size, modified) = measure(path, cancel)?;
+        if let Some(modified) = modified {
+            let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+            entries.insert(path.to_path_buf(), Cached { modified, size });
+        }
+        Ok(size)
+    }
+
+    /// Forget `path`, the directories containing it and everything below it.
+    pub fn invalidate(&self, path: &Path) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|key, _| !key.starts_with(path) && !path.starts_with(key));
+    }
+
+    pub fn clear(&self) {
+        self.entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clear();
+    }
+}
+
+/// Result for one directory of a [`SizeStream`].
+#[derive(Debug)]
+pub struct SizeResult {
+    pub path: PathBuf,
+    pub size: Result<DirSize>,
+}
+
+/// Receiving end of a background size calculation. Dropping it cancels the calculation.
+pub struct SizeStream {
+    rx: Receiver<SizeResult>,
+    cancelled: CancelToken,
+}
+
+impl SizeStream {
+    /// Non-blocking poll for UI loops. `Disconnected` means every size has been sent.
+    pub fn try_next(&self) -> std::result::Result<SizeResult, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for SizeStream {
+    type Item = SizeResult;
+
+    fn next(&mut self) -> Option<SizeResult> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for SizeStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Compute the sizes of `paths` one after another on a background thread, serving cached
+/// ones first.
+pub fn compute_sizes(paths: Vec<PathBuf>, cache: Arc<SizeCache>) -> SizeStream {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let mut pending = Vec::new();
+        for path in paths {
+            match cache.get(&path) {
+                Some(size) => {
+                    let size = Ok(size);
+                    if tx.send(SizeResult { path, size }).is_err() {
+                        return;
+                    }
+                }
+                None => pending.push(path),
+            }
+        }
+        for path in pending {
+            let size = cache.size(&path, &flag);
+            if matches!(size, Err(Error::Cancelled)) || tx.send(SizeResult { path, size }).is_err()
+            {
+                return;
+            }
+        }
+    });
+
+    SizeStream { rx, cancelled }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::symlink_metadata(path).ok()?.modified().ok()
+}
+
+#[cfg(unix)]
+pub(crate) fn allocated(md: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    md.blocks() * 512
+}
+
+#[cfg(not(unix))]
+pub(crate) fn allocated(md: &fs::Metadata) -> u64 {
+    if md.is_dir() {
+        0
+    } else {
+        md.len()
+    }
+}
+
+/// Identity of a file with several hard links, so it is only counted once.
+#[cfg(unix)]
+pub(crate) fn hard_link_id(md: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    (md.nlink() > 1).then(|| (md.dev(), md.ino()))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn hard_link_id(_md: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    /// Give `dir` a modification time far from now, as a change would.
+    fn set_modified(dir: &Path, secs: u64) {
+        fs::File::open(dir)
+            .unwrap()
+            .set_modified(UNIX_EPOCH + Duration::from_secs(secs))
+            .unwrap();
+    }
+
+    fn tree() -> TempDir {
+        let tmp = TempDir::new();
+        tmp.write("top/a.bin", [0u8; 100]);
+        tmp.write("top/sub/b.bin", [0u8; 20]);
+        tmp.write("top/sub/deep/c.bin", [0u8; 3]);
+        tmp
+    }
+
+    #[test]
+    fn dir_size_totals_everything_below() {
+        let tmp = tree();
+        let size = dir_size(&tmp.path().join("top"), &CancelToken::new()).unwrap();
+        assert_eq!(size.apparent, 123);
+        assert_eq!(size.files, 3);
+        assert_eq!(size.dirs, 2);
+        assert_eq!(size.unreadable, 0);
+        assert!(size.on_disk > 0);
+
+        assert!(dir_size(&tmp.path().join("missing"), &CancelToken::new()).is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_are_counted_once() {
+        let tmp = tree();
+        fs::hard_link(
+            tmp.path().join("top/a.bin"),
+            tmp.path().join("top/sub/link.bin"),
+        )
+        .unwrap();
+        let size = dir_size(&tmp.path().join("top"), &CancelToken::new()).unwrap();
+        assert_eq!(size.apparent, 123);
+        assert_eq!(size.files, 4);
+    }
+
+    #[test]
+    fn cancelled_walks_fail() {
+        let tmp = tree();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(matches!(
+            dir_size(&tmp.path().join("top"), &cancel),
+            Err(Error::Cancelled)
+        ));
+    }
+
+    #[test]
+    fn cached_size_is_reused_until_the_tree_changes() {
+        let tmp = tree();
+        let top = tmp.path().join("top");
+        let cache = SizeCache::new();
+        assert_eq!(cache.get(&top), None);
+        let size = cache.size(&top, &CancelToken::new()).unwrap();
+        assert_eq!(cache.get(&top), Some(size));
+
+        tmp.write("top/new.bin", [0u8; 7]);
+        set_modified(&top, 1_000_000);
+        assert_eq!(cache.get(&top), None);
+        let size = cache.size(&top, &CancelToken::new()).unwrap();
+        assert_eq!(size.apparent, 130);
+        assert_eq!(cache.get(&top), Some(size));
+
+        // A file added further down does not touch `top`; the watcher reports it instead.
+        let deep = tmp.path().join("top/sub/deep/new.bin");
+        tmp.write("top/sub/deep/new.bin", [0u8; 7]);
+        assert_eq!(cache.get(&top), Some(size));
+        cache.invalidate(&deep);
+        assert_eq!(cache.size(&top, &CancelToken::new()).unwrap().apparent, 137);
+    }
+
+    #[test]
+    fn invalidate_forgets_ancestors_and_descendants() {
+        let tmp = tree();
+        tmp.write("other/x.bin", [0u8; 1]);
+        let cache = SizeCache::new();
+        let cancel = CancelToken::new();
+        let dirs = ["top", "top/sub", "top/sub/deep", "other"].map(|d| tmp.path().join(d));
+        for dir in &dirs {
+            cache.size(dir, &cancel).unwrap();
+        }
+
+        cache.invalidate(&dirs[1]);
+        let cached: Vec<bool> = dirs.iter().map(|d| cache.get(d).is_some()).collect();
+        assert_eq!(cached, [false, false, false, true]);
+
+        cache.clear();
+        assert_eq!(cache.get(&dirs[3]), None);
+    }
+
+    #[test]
+    fn compute_sizes_reports_every_path() {
+        let tmp = tree();
+        let cache = Arc::new(SizeCache::new());
+        let top = tmp.path().join("top");
+        let missing = tmp.path().join("missing");
+        let mut results: Vec<SizeResult> =
+            compute_sizes(vec![top.clone(), missing.clone()], cache.clone()).collect();
+        results.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(results[0].path, missing);
+        assert!(results[0].size.is_err());
+        assert_eq!(results[1].path, top);
+        assert_eq!(results[1].size.as_ref().unwrap().apparent, 123);
+        assert!(cache.get(&top).is_some());
+    }
+}
@@ -0,0 +1,245 @@
+use crate::models::file_entry::{FileEntry, FileKind};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+
+/// Column a directory listing is ordered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortKey {
+    /// Case-insensitive name.
+    #[default]
+    Name,
+    /// Case-insensitive name where digit runs compare numerically ("file2" < "file10").
+    Natural,
+    Size,
+    Modified,
+    /// Extension, with directories first and extension-less files last.
+    Type,
+}
+
+impl SortKey {
+    /// Whether ordering by this key requires stat-ing every entry up front.
+    pub fn needs_metadata(self) -> bool {
+        matches!(self, SortKey::Size | SortKey::Modified)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortDirection {
+    #[default]
+    Asc,
+    Desc,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SortOptions {
+    pub key: SortKey,
+    pub direction: SortDirection,
+    /// Keep directories above everything else regardless of direction.
+    pub dirs_first: bool,
+}
+
+impl Default for SortOptions {
+    fn default() -> Self {
+        Self {
+            key: SortKey::Name,
+            direction: SortDirection::Asc,
+            dirs_first: true,
+        }
+    }
+}
+
+/// Sort entries in place. This is the single ordering used by `list_dir` and the UI.
+pub fn sort_entries(entries: &mut [FileEntry], opts: &SortOptions) {
+    entries.sort_by(|a, b| compare_entries(a, b, opts));
+}
+
+/// Total order over entries for the given options.
+///
+/// Ties on the sort key fall back to the name and finally the path so that the order
+/// (and therefore every page boundary) is deterministic. Lossy display paths that collide
+/// are told apart by their raw bytes.
+pub fn compare_entries(a: &FileEntry, b: &FileEntry, opts: &SortOptions) -> Ordering {
+    if opts.dirs_first {
+        let kind_order = b.is_dir().cmp(&a.is_dir());
+        if kind_order != Ordering::Equal {
+            return kind_order;
+        }
+    }
+
+    let order = match opts.key {
+        SortKey::Name => cmp_lowercase(&a.name, &b.name),
+        SortKey::Natural => natural_cmp(&a.name, &b.name),
+        SortKey::Size => a.size.cmp(&b.size),
+        SortKey::Modified => a.modified.cmp(&b.modified),
+        SortKey::Type => type_rank(a).cmp(&type_rank(b)),
+    }
+    .then_with(|| cmp_lowercase(&a.name, &b.name))
+    .then_with(|| a.name.cmp(&b.name))
+    .then_with(|| a.path.cmp(&b.path))
+    .then_with(|| raw_path_order(a, b));
+
+    match opts.direction {
+        SortDirection::Asc => order,
+        SortDirection::Desc => order.reverse(),
+    }
+}
+
+/// Order by the exact OS path; only reached when the display paths are equal, which
+/// happens only for distinct non-UTF-8 names.
+fn raw_path_order(a: &FileEntry, b: &FileEntry) -> Ordering {
+    if a.raw_path == b.raw_path {
+        return Ordering::Equal;
+    }
+    let exact = |e: &FileEntry| e.fs_path().ok().map(PathBuf::into_os_string);
+    exact(a).cmp(&exact(b))
+}
+
+/// Compare strings case-insensitively, treating runs of ASCII digits as numbers.
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut ai = a.chars().peekable();
+    let mut bi = b.chars().peekable();
+
+    loop {
+        match (ai.peek().copied(), bi.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(ca), Some(cb)) if ca.is_ascii_digit() && cb.is_ascii_digit() => {
+                let na = take_digits(&mut ai);
+                let nb = take_digits(&mut bi);
+                // Strip leading zeros, then longer runs are larger numbers.
+                let ta = na.trim_start_matches('0');
+                let tb = nb.trim_start_matches('0');
+                let order = ta
+                    .len()
+                    .cmp(&tb.len())
+                    .then_with(|| ta.cmp(tb))
+                    .then_with(|| na.len().cmp(&nb.len()));
+                if order != Ordering::Equal {
+                    return order;
+                }
+            }
+            (Some(ca), Some(cb)) => {
+                let order = ca.to_lowercase().cmp(cb.to_lowercase());
+                if order != Ordering::Equal {
+                    return order;
+                }
+                ai.next();
+                bi.next();
+            }
+        }
+    }
+}
+
+fn take_digits(it: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut s = String::new();
+    while let Some(c) = it.peek().copied() {
+        if !c.is_ascii_digit() {
+            break;
+        }
+        s.push(c);
+        it.next();
+    }
+    s
+}
+
+fn cmp_lowercase(a: &str, b: &str) -> Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase())
+}
+
+/// Sort rank for `SortKey::Type`: directories, then files by extension, then the rest.
+fn type_rank(e: &FileEntry) -> (u8, String) {
+    match e.kind {
+        FileKind::Dir => (0, String::new()),
+        FileKind::File => match e.extension() {
+            Some(ext) => (1, ext),
+            None => (2, String::new()),
+        },
+        other => (3, other.as_str().to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, kind: FileKind, size: u64) -> FileEntry {
+        FileEntry {
+            name: name.into(),
+            path: format!("/d/{name}"),
+            kind,
+            size,
+            ..Default::default()
+        }
+    }
+
+    fn sorted(entries: &[FileEntry], opts: SortOptions) -> Vec<String> {
+        let mut entries = entries.to_vec();
+        sort_entries(&mut entries, &opts);
+        entries.into_iter().map(|e| e.name).collect()
+    }
+
+    fn sample() -> Vec<FileEntry> {
+        vec![
+            entry("b.txt", FileKind::File, 30),
+            entry("A.rs", FileKind::File, 10),
+            entry("docs", FileKind::Dir, 0),
+            entry("Makefile", FileKind::File, 20),
+            entry("a.txt", FileKind::File, 20),
+        ]
+    }
+
+    #[test]
+    fn name_order_ignores_case_and_keeps_dirs_first() {
+        let order = sorted(&sample(), SortOptions::default());
+        assert_eq!(order, ["docs", "A.rs", "a.txt", "b.txt", "Makefile"]);
+
+        let desc = SortOptions {
+            direction: SortDirection::Desc,
+            ..Default::default()
+        };
+        let order = sorted(&sample(), desc);
+        assert_eq!(order, ["docs", "Makefile", "b.txt", "a.txt", "A.rs"]);
+    }
+
+    #[test]
+    fn size_ties_fall_back_to_name() {
+        let opts = SortOptions {
+            key: SortKey::Size,
+            dirs_first: false,
+            ..Default::default()
+        };
+        let order = sorted(&sample(), opts);
+        assert_eq!(order, ["docs", "A.rs", "a.txt", "Makefile", "b.txt"]);
+    }
+
+    #[test]
+    fn type_order_groups_by_extension() {
+        let opts = SortOptions {
+            key: SortKey::Type,
+            ..Default::default()
+        };
+        let order = sorted(&sample(), opts);
+        assert_eq!(order, ["docs", "A.rs", "a.txt", "b.txt", "Makefile"]);
+    }
+
+    #[test]
+    fn natural_order_compares_digit_runs_as_numbers() {
+        assert_eq!(natural_cmp("file2", "file10"), Ordering::Less);
+        assert_eq!(natural_cmp("File10", "file9"), Ordering::Greater);
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Greater);
+        assert_eq!(natural_cmp("a1", "a1b"), Ordering::Less);
+        assert_eq!(natural_cmp("ABC", "abc"), Ordering::Equal);
+    }
+
+    #[test]
+    fn only_size_and_modified_need_metadata() {
+        assert!(SortKey::Size.needs_metadata());
+        assert!(SortKey::Modified.needs_metadata());
+        assert!(!SortKey::Name.needs_metadata());
+        assert!(!SortKey::Type.needs_metadata());
+    }
+}
@@ -0,0 +1,264 @@
+//! Streaming directory listing for very large directories.
+//!
+//! Unlike `list_dir`, which reads every name before returning a page, the stream stats and
+//! yields entries in batches as `read_dir` produces them, so the first rows are available
+//! immediately. An optional sort runs on the same background thread once reading finishes
+//! and is delivered as a single `StreamEvent::Sorted` that replaces the streamed batches.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::Error;
+use crate::models::file_entry::FileEntry;
+use crate::services::fs::listing::{entry_name, is_hidden, stat_entry, NameFilter};
+use crate::services::fs::sort::{sort_entries, SortOptions};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+#[derive(Debug, Clone)]
+pub struct StreamParams {
+    pub path: PathBuf,
+    /// Number of entries per `StreamEvent::Batch`.
+    pub batch_size: usize,
+    /// When set, all entries are sorted in the background after the last batch.
+    pub sort: Option<SortOptions>,
+    pub show_hidden: bool,
+    /// Same semantics as `ListParams::filter`.
+    pub filter: Option<String>,
+}
+
+impl Default for StreamParams {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            batch_size: 256,
+            sort: None,
+            show_hidden: false,
+            filter: None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum StreamEvent {
+    /// Entries in directory order, as read.
+    Batch(Vec<FileEntry>),
+    /// Every streamed entry, ordered by `StreamParams::sort`. Sent after the last batch.
+    Sorted(Vec<FileEntry>),
+    /// A single entry could not be read or inspected; streaming continues.
+    EntryError(Error),
+    /// Reading stopped early. Entries already streamed remain valid.
+    Error(Error),
+    /// The stream is complete; no further events follow.
+    Done { total: usize },
+}
+
+/// Receiving end of a streaming listing. Dropping it cancels the background reader.
+pub struct ListStream {
+    rx: Receiver<StreamEvent>,
+    cancelled: CancelToken,
+}
+
+impl ListStream {
+    /// Non-blocking poll for UI loops. `Disconnected` means the stream has ended.
+    pub fn try_next(&self) -> std::result::Result<StreamEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for ListStream {
+    type Item = StreamEvent;
+
+    fn next(&mut self) -> Option<StreamEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for ListStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Start listing `params.path` on a background thread.
+pub fn list_dir_stream(params: StreamParams) -> ListStream {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let total = stream_impl(&params, &tx, &flag);
+        let _ = tx.send(StreamEvent::Done { total });
+    });
+
+    ListStream { rx, cancelled }
+}
+
+fn stream_impl(params: &StreamParams, tx: &Sender<StreamEvent>, cancelled: &CancelToken) -> usize {
+    let filter = params
+        .filter
+        .as_deref()
+        .filter(|f| !f.is_empty())
+        .map(NameFilter::new);
+    let batch_size = params.batch_size.max(1);
+    // Entries are only retained when a final sorted snapshot was requested.
+    let mut all: Vec<FileEntry> = Vec::new();
+    let mut batch: Vec<FileEntry> = Vec::with_capacity(batch_size);
+    let mut total = 0;
+
+    let dir = params.path.as_path();
+    let read = match fs::read_dir(dir) {
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tx.send(StreamEvent::Error(Error::from_io(dir, e)));
+            return 0;
+        }
+    };
+
+    let mut errors = Vec::new();
+    for entry in read {
+        if cancelled.is_cancelled() {
+            return total;
+        }
+        for err in errors.drain(..) {
+            let _ = tx.send(StreamEvent::EntryError(err));
+        }
+        let entry = match entry {
+            Ok(e) => e,
+            Err(e) => {
+                errors.push(Error::from_io(dir, e));
+                continue;
+            }
+        };
+        let name = entry_name(&entry, &mut errors);
+        if !params.show_hidden && is_hidden(&name) {
+            continue;
+        }
+        if let Some(f) = &filter {
+            if !f.matches(&name) {
+                continue;
+            }
+        }
+        let Some(e) = stat_entry(name, &entry.path(), &mut errors) else {
+            continue;
+        };
+        batch.push(e);
+        total += 1;
+
+        if batch.len() >= batch_size && !flush(&mut batch, &mut all, params, tx) {
+            return total;
+        }
+    }
+    for err in errors.drain(..) {
+        let _ = tx.send(StreamEvent::EntryError(err));
+    }
+    if !flush(&mut batch, &mut all, params, tx) {
+        return total;
+    }
+
+    if let Some(sort) = &params.sort {
+        if cancelled.is_cancelled() {
+            return total;
+        }
+        sort_entries(&mut all, sort);
+        let _ = tx.send(StreamEvent::Sorted(all));
+    }
+    total
+}
+
+/// Send the pending batch. Returns false once the receiver is gone.
+fn flush(
+    batch: &mut Vec<FileEntry>,
+    all: &mut Vec<FileEntry>,
+    params: &StreamParams,
+    tx: &Sender<StreamEvent>,
+) -> bool {
+    if batch.is_empty() {
+        return true;
+    }
+    let out = std::mem::replace(batch, Vec::with_capacity(params.batch_size.max(1)));
+    if params.sort.is_some() {
+        all.extend(out.iter().cloned());
+    }
+    tx.send(StreamEvent::Batch(out)).is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use crate::services::fs::sort::SortKey;
+
+    #[test]
+    fn streams_batches_then_sorted_snapshot() {
+        let tmp = TempDir::new();
+        for ix in 0..7 {
+            tmp.write(&format!("f{ix}"), "x".repeat(ix));
+        }
+        tmp.write(".hidden", "");
+        let stream = list_dir_stream(StreamParams {
+            path: tmp.path().to_path_buf(),
+            batch_size: 3,
+            sort: Some(SortOptions {
+                key: SortKey::Size,
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let mut batches = Vec::new();
+        let mut sorted = None;
+        let mut done = None;
+        for event in stream {
+            match event {
+                StreamEvent::Batch(batch) => batches.push(batch.len()),
+                StreamEvent::Sorted(all) => sorted = Some(all),
+                StreamEvent::Done { total } => done = Some(total),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        assert_eq!(batches, [3, 3, 1]);
+        assert_eq!(done, Some(7));
+        let sizes: Vec<u64> = sorted.unwrap().iter().map(|e| e.size).collect();
+        assert_eq!(sizes, [0, 1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn applies_filter_without_sorting() {
+        let tmp = TempDir::new();
+        for name in ["a.rs", "b.rs", "c.txt"] {
+            tmp.write(name, "");
+        }
+        let stream = list_dir_stream(StreamParams {
+            path: tmp.path().to_path_buf(),
+            filter: Some("*.rs".into()),
+            ..Default::default()
+        });
+        let mut names = Vec::new();
+        for event in stream {
+            match event {
+                StreamEvent::Batch(batch) => names.extend(batch.into_iter().map(|e| e.name)),
+                StreamEvent::Done { total } => assert_eq!(total, 2),
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+        names.sort();
+        assert_eq!(names, ["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn reports_unreadable_directory() {
+        let tmp = TempDir::new();
+        let events: Vec<_> = list_dir_stream(StreamParams {
+            path: tmp.path().join("missing"),
+            ..Default::default()
+        })
+        .collect();
+        assert!(matches!(events[0], StreamEvent::Error(_)));
+        assert!(matches!(events[1], StreamEvent::Done { total: 0 }));
+    }
+}
@@ -0,0 +1,58 @@
+//! Freedesktop file tags.
+//!
+//! Tags live in the `user.xdg.tags` extended attribute as a comma-separated list, which
+//! is what file managers on Linux read and write. Other platforms report no tags.
+
+use std::path::Path;
+
+/// Extended attribute holding the tags.
+pub const TAGS_XATTR: &str = "user.xdg.tags";
+
+/// Tags of `path`, trimmed, in the order stored. Empty when it has none or they cannot
+/// be read.
+pub fn read_tags(path: &Path) -> Vec<String> {
+    let Some(data) = read_xattr(path, TAGS_XATTR) else {
+        return Vec::new();
+    };
+    String::from_utf8_lossy(&data)
+        .split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(target_os = "linux")]
+fn read_xattr(path: &Path, name: &str) -> Option<Vec<u8>> {
+    use std::ffi::CString;
+    use std::os::unix::ffi::OsStrExt;
+
+    let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    let c_name = CString::new(name).ok()?;
+    let mut buf = vec![0u8; 256];
+    loop {
+        // SAFETY: both strings are NUL-terminated and `buf` is valid for `buf.len()` bytes.
+        let len = unsafe {
+            libc::getxattr(
+                c_path.as_ptr(),
+                c_name.as_ptr(),
+                buf.as_mut_ptr().cast(),
+                buf.len(),
+            )
+        };
+        if len >= 0 {
+            buf.truncate(len as usize);
+            return Some(buf);
+        }
+        let too_small = std::io::Error::last_os_error().raw_os_error() == Some(libc::ERANGE);
+        if !too_small || buf.len() >= 64 * 1024 {
+            return None;
+        }
+        buf.resize(buf.len() * 4, 0);
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_xattr(_path: &Path, _name: &str) -> Option<Vec<u8>> {
+    None
+}
@@ -0,0 +1,655 @@
+//! Disk usage analysis: scan a tree into per-node totals, rank it and lay it out as a
+//! treemap.
+//!
+//! A scan keeps one node per file and directory below the root, each child list sorted
+//! largest first by space on disk, so the result can be drilled into without rescanning.
+//! Scans serialize to JSON for headless use.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::{FileKind, Timestamp};
+use crate::models::os_path;
+use crate::services::fs::size::{allocated, hard_link_id};
+use crate::services::fs::walk::{walk, WalkOptions};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageNode {
+    /// Display name. Lossy when the name is not valid UTF-8.
+    pub name: String,
+    /// Base64 of the raw name bytes, present only when the name is not valid UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<String>,
+    pub kind: FileKind,
+    /// Sum of file lengths at or below this node.
+    pub apparent: u64,
+    /// Space allocated on disk at or below this node. Hard-linked files are counted at
+    /// their first occurrence only.
+    pub on_disk: u64,
+    /// Files at or below this node.
+    pub files: u64,
+    /// Largest on disk first. Empty for files.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<UsageNode>,
+}
+
+impl UsageNode {
+    /// The node reached by following child indices from this one.
+    pub fn get(&self, at: &[usize]) -> Option<&UsageNode> {
+        at.iter().try_fold(self, |node, &i| node.children.get(i))
+    }
+
+    /// Path of the node at `at`, given this node's own path.
+    pub fn path_of(&self, base: &Path, at: &[usize]) -> Option<PathBuf> {
+        let mut path = base.to_path_buf();
+        let mut node = self;
+        for &i in at {
+            node = node.children.get(i)?;
+            path.push(node.os_name()?);
+        }
+        Some(path)
+    }
+
+    /// The exact file name, honoring `raw_name`. `None` when `raw_name` does not decode.
+    pub fn os_name(&self) -> Option<PathBuf> {
+        os_path::resolve(&self.name, self.raw_name.as_deref()).ok()
+    }
+
+    /// Detach the node at `at`, for example after deleting it, and subtract its totals
+    /// from every node above it.
+    pub fn remove(&mut self, at: &[usize]) -> Option<UsageNode> {
+        let (&first, rest) = at.split_first()?;
+        let removed = if rest.is_empty() {
+            (first < self.children.len()).then(|| self.children.remove(first))?
+        } else {
+            self.children.get_mut(first)?.remove(rest)?
+        };
+        self.apparent -= removed.apparent;
+        self.on_disk -= removed.on_disk;
+        self.files -= removed.files;
+        Some(removed)
+    }
+
+    /// The `limit` largest files anywhere below this node, by space on disk, with their
+    /// paths relative to `base`, this node's own path.
+    pub fn largest_files(&self, base: &Path, limit: usize) -> Vec<(PathBuf, &UsageNode)> {
+        let mut found = Vec::new();
+        let mut stack = vec![(base.to_path_buf(), self)];
+        while let Some((path, node)) = stack.pop() {
+            for child in &node.children {
+                let Some(name) = child.os_name() else {
+                    continue;
+                };
+                let child_path = path.join(name);
+                if child.kind == FileKind::Dir {
+                    stack.push((child_path, child));
+                } else {
+                    found.push((child_path, child));
+                }
+            }
+        }
+        found.sort_by_key(|(_, node)| Reverse(node.on_disk));
+        found.truncate(limit);
+        found
+    }
+
+    fn sort(&mut self) {
+        self.children.sort_by(|a, b| {
+            b.on_disk
+                .cmp(&a.on_disk)
+                .then_with(|| a.name.cmp(&b.name))
+                .then_with(|| a.raw_name.cmp(&b.raw_name))
+        });
+    }
+
+    fn add(&mut self, child: UsageNode) {
+        self.apparent += child.apparent;
+        self.on_disk += child.on_disk;
+        self.files += child.files;
+        self.children.push(child);
+    }
+}
+
+/// Result of [`scan`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UsageScan {
+    pub root: PathBuf,
+    pub tree: UsageNode,
+    pub scanned_at: Timestamp,
+    pub elapsed: Duration,
+    /// Entries that could not be read; the totals leave them out.
+    pub unreadable: u64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanProgress {
+    pub files: u64,
+    pub bytes: u64,
+    /// Directory currently being read.
+    pub current: Option<PathBuf>,
+}
+
+/// Scan everything below `root` on the calling thread.
+///
+/// `opts` picks what the walk visits; disk usage normally sets `same_file_system` so
+/// mounted volumes are not counted. Fails with `Error::Cancelled` once `cancel` is set.
+pub fn scan(
+    root: &Path,
+    opts: &WalkOptions,
+    cancel: &CancelToken,
+    mut on_progress: impl FnMut(&ScanProgress),
+) -> Result<UsageScan> {
+    let started = Instant::now();
+    let md = fs::symlink_metadata(root).map_err(|e| Error::from_io(root, e))?;
+    let mut stack = vec![UsageNode {
+        name: root.to_string_lossy().into_owned(),
+        raw_name: os_path::to_raw(root),
+        kind: FileKind::from_file_type(md.file_type()),
+        on_disk: allocated(&md),
+        ..Default::default()
+    }];
+    let mut seen = HashSet::new();
+    let mut unreadable = 0;
+    let mut progress = ScanProgress::default();
+    let mut last_emit = started;
+
+    let mut entries = walk(root, opts);
+    while let Some(entry) = entries.next() {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::debug!(%err, "skipping unreadable entry");
+                unreadable += 1;
+                continue;
+            }
+        };
+        while stack.len() > entry.depth {
+            close(&mut stack);
+        }
+        let md = match entry.metadata() {
+            Ok(md) => md,
+            Err(err) => {
+                tracing::debug!(%err, "skipping unreadable entry");
+                unreadable += 1;
+                if entry.is_dir() {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+        };
+        let file_name = Path::new(entry.path.file_name().unwrap_or_default());
+        let name = file_name.to_string_lossy().into_owned();
+        let raw_name = os_path::to_raw(file_name);
+        if entry.is_dir() {
+            stack.push(UsageNode {
+                name,
+                raw_name,
+                kind: FileKind::Dir,
+                on_disk: allocated(&md),
+                ..Default::default()
+            });
+            progress.current = Some(entry.path);
+        } else {
+            let duplicate = hard_link_id(&md).is_some_and(|id| !seen.insert(id));
+            let (apparent, on_disk) = if duplicate {
+                (0, 0)
+            } else {
+                (md.len(), allocated(&md))
+            };
+            progress.files += 1;
+            progress.bytes += on_disk;
+            let top = stack.last_mut().expect("scan stack holds the root");
+            top.add(UsageNode {
+                name,
+                raw_name,
+                kind: entry.kind,
+                apparent,
+                on_disk,
+                files: 1,
+                children: Vec::new(),
+            });
+        }
+        if last_emit.elapsed() >= PROGRESS_INTERVAL {
+            last_emit = Instant::now();
+            on_progress(&progress);
+        }
+    }
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+    let mut tree = stack.pop().expect("scan stack holds the root");
+    tree.sort();
+    progress.current = None;
+    on_progress(&progress);
+
+    Ok(UsageScan {
+        root: root.to_path_buf(),
+        tree,
+        scanned_at: Timestamp::from_system_time(SystemTime::now()),
+        elapsed: started.elapsed(),
+        unreadable,
+    })
+}
+
+/// Finish the directory on top of the stack and add it to its parent.
+fn close(stack: &mut Vec<UsageNode>) {
+    let mut done = stack.pop().expect("closing below the root");
+    done.sort();
+    stack
+        .last_mut()
+        .expect("scan stack holds the root")
+        .add(done);
+}
+
+/// Write `scan` as pretty-printed JSON.
+pub fn write_json(scan: &UsageScan, out: impl io::Write) -> Result<()> {
+    serde_json::to_writer_pretty(out, scan)
+        .map_err(|e| Error::Other(format!("cannot encode scan: {e}")))
+}
+
+/// Write `scan` as JSON to the file at `path`.
+pub fn export_json(scan: &UsageScan, path: &Path) -> Result<()> {
+    let file = fs::File::create(path).map_err(|e| Error::from_io(path, e))?;
+    write_json(scan, io::BufWriter::new(file))
+}
+
+#[derive(Debug)]
+pub enum ScanEvent {
+    Progress(ScanProgress),
+    Finished(Box<UsageScan>),
+    Failed(Error),
+    Cancelled,
+}
+
+/// Receiving end of a background scan. Dropping it cancels the scan.
+pub struct ScanHandle {
+    rx: Receiver<ScanEvent>,
+    cancel: CancelToken,
+}
+
+impl ScanHandle {
+    /// Non-blocking poll for UI loops. `Disconnected` means the scan has ended.
+    pub fn try_next(&self) -> std::result::Result<ScanEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancel.cancel();
+    }
+}
+
+impl Iterator for ScanHandle {
+    type Item = ScanEvent;
+
+    fn next(&mut self) -> Option<ScanEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for ScanHandle {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Start scanning `root` on a background thread.
+pub fn start_scan(root: PathBuf, opts: WalkOptions) -> ScanHandle {
+    let (tx, rx) = mpsc::channel();
+    let cancel = CancelToken::new();
+    let token = cancel.clone();
+
+    thread::spawn(move || {
+        let progress_tx = tx.clone();
+        let result = scan(&root, &opts, &token, |p| {
+            let _ = progress_tx.send(ScanEvent::Progress(p.clone()));
+        });
+        let event = match result {
+            Ok(scan) => ScanEvent::Finished(Box::new(scan)),
+            Err(Error::Cancelled) => ScanEvent::Cancelled,
+            Err(err) => ScanEvent::Failed(err),
+        };
+        let _ = tx.send(event);
+    });
+
+    ScanHandle { rx, cancel }
+}
+
+/// An axis-aligned rectangle in treemap coordinates.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Rect {
+    pub x: f32,
+    pub y: f32,
+    pub w: f32,
+    pub h: f32,
+}
+
+/// Lay out `sizes` inside `bounds` as a squarified treemap (Bruls, Huizing and van Wijk),
+/// keeping tiles as close to square as the proportions allow.
+///
+/// `sizes` should be sorted largest first. Returns one rectangle per size, in the same
+/// order; zero sizes get empty rectangles.
+pub fn squarify(sizes: &[u64], bounds: Rect) -> Vec<Rect> {
+    let mut out = vec![
+        Rect {
+            x: bounds.x,
+            y: bounds.y,
+            w: 0.0,
+            h: 0.0,
+        };
+        sizes.len()
+    ];
+    let total: f64 = sizes.iter().map(|&s| s as f64).sum();
+    let (mut x, mut y) = (bounds.x as f64, bounds.y as f64);
+    let (mut w, mut h) = (bounds.w.max(0.0) as f64, bounds.h.max(0.0) as f64);
+    if total <= 0.0 || w * h <= 0.0 {
+        return out;
+    }
+    let scale = w * h / total;
+    let areas: Vec<f64> = sizes.iter().map(|&s| s as f64 * scale).collect();
+    let count = areas.iter().take_while(|&&a| a > 0.0).count();
+
+    let mut i = 0;
+    while i < count {
+        let side = w.min(h);
+        let mut j = i + 1;
+        while j < count && worst(&areas[i..=j], side) <= worst(&areas[i..j], side) {
+            j += 1;
+        }
+        let row_area: f64 = areas[i..j].iter().sum();
+        if w >= h {
+            // Column along the left edge.
+            let thick = row_area / h;
+            let mut cy = y;
+            for k in i..j {
+                let len = areas[k] / thick;
+                out[k] = rect(x, cy, thick, len);
+                cy += len;
+            }
+            x += thick;
+            w -= thick;
+        } else {
+            // Row along the top edge.
+            let thick = row_area / w;
+            let mut cx = x;
+            for k in i..j {
+                let len = areas[k] / thick;
+                out[k] = rect(cx, y, len, thick);
+                cx += len;
+            }
+            y += thick;
+            h -= thick;
+        }
+        i = j;
+    }
+    out
+}
+
+/// Worst aspect ratio of a row of `areas` laid along a side of length `side`.
+fn worst(areas: &[f64], side: f64) -> f64 {
+    let sum: f64 = areas.iter().sum();
+    let max = areas.iter().cloned().fold(f64::MIN, f64::max);
+    let min = areas.iter().cloned().fold(f64::MAX, f64::min);
+    let (side2, sum2) = (side * side, sum * sum);
+    (side2 * max / sum2).max(sum2 / (side2 * min))
+}
+
+fn rect(x: f64, y: f64, w: f64, h: f64) -> Rect {
+    Rect {
+        x: x as f32,
+        y: y as f32,
+        w: w as f32,
+        h: h as f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn tree() -> TempDir {
+        let tmp = TempDir::new();
+        tmp.write("big.bin", vec![1u8; 64 * 1024]);
+        tmp.write("dir/mid.bin", vec![1u8; 16 * 1024]);
+        tmp.write("dir/sub/small.bin", vec![1u8; 100]);
+        tmp.mkdir("empty");
+        tmp
+    }
+
+    fn scan_tree(root: &Path) -> UsageScan {
+        scan(root, &WalkOptions::default(), &CancelToken::new(), |_| {}).unwrap()
+    }
+
+    fn names(node: &UsageNode) -> Vec<&str> {
+        node.children.iter().map(|c| c.name.as_str()).collect()
+    }
+
+    #[test]
+    fn scan_totals_and_ranks_the_tree() {
+        let tmp = tree();
+        let scan = scan_tree(tmp.path());
+        let tree = &scan.tree;
+        assert_eq!(scan.unreadable, 0);
+        assert_eq!(tree.kind, FileKind::Dir);
+        assert_eq!(tree.apparent, 64 * 1024 + 16 * 1024 + 100);
+        assert_eq!(tree.files, 3);
+        assert_eq!(names(tree), ["big.bin", "dir", "empty"]);
+
+        let dir = tree.get(&[1]).unwrap();
+        assert_eq!(dir.apparent, 16 * 1024 + 100);
+        assert_eq!(dir.files, 2);
+        assert_eq!(names(dir), ["mid.bin", "sub"]);
+        assert_eq!(tree.get(&[1, 1, 0]).unwrap().name, "small.bin");
+        assert!(tree.get(&[1, 1, 5]).is_none());
+        assert!(tree.get(&[2]).unwrap().children.is_empty());
+
+        let child_sum: u64 = tree.children.iter().map(|c| c.on_disk).sum();
+        assert!(tree.on_disk >= child_sum);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn paths_of_non_utf8_names_are_exact() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let tmp = TempDir::new();
+        let dir = tmp.path().join(OsStr::from_bytes(b"d\xff"));
+        fs::create_dir(&dir).unwrap();
+        fs::write(dir.join(OsStr::from_bytes(b"f\xfe")), "x").unwrap();
+        let scan = scan_tree(tmp.path());
+        assert_eq!(scan.tree.get(&[0]).unwrap().name, "d\u{fffd}");
+        let file = scan.tree.path_of(tmp.path(), &[0, 0]).unwrap();
+        assert_eq!(file, dir.join(OsStr::from_bytes(b"f\xfe")));
+        assert!(file.exists());
+        let largest = scan.tree.largest_files(tmp.path(), 1);
+        assert_eq!(largest[0].0, file);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hard_links_count_once() {
+        let tmp = tree();
+        fs::hard_link(tmp.path().join("big.bin"), tmp.path().join("dir/link.bin")).unwrap();
+        let scan = scan_tree(tmp.path());
+        assert_eq!(scan.tree.apparent, 64 * 1024 + 16 * 1024 + 100);
+        assert_eq!(scan.tree.files, 4);
+    }
+
+    #[test]
+    fn walk_options_apply() {
+        let tmp = tree();
+        let opts = WalkOptions {
+            max_depth: Some(1),
+            ..WalkOptions::default()
+        };
+        let scan = scan(tmp.path(), &opts, &CancelToken::new(), |_| {}).unwrap();
+        assert_eq!(scan.tree.files, 1);
+        assert!(scan.tree.get(&[1]).unwrap().children.is_empty());
+    }
+
+    #[test]
+    fn cancelled_scans_fail() {
+        let tmp = tree();
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let result = scan(tmp.path(), &WalkOptions::default(), &cancel, |_| {});
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn paths_and_largest_files() {
+        let tmp = tree();
+        let scan = scan_tree(tmp.path());
+        let base = Path::new("/base");
+        assert_eq!(
+            scan.tree.path_of(base, &[1, 1, 0]),
+            Some(PathBuf::from("/base/dir/sub/small.bin"))
+        );
+        assert_eq!(scan.tree.path_of(base, &[]), Some(base.to_path_buf()));
+        assert_eq!(scan.tree.path_of(base, &[9]), None);
+
+        let largest: Vec<PathBuf> = scan
+            .tree
+            .largest_files(base, 2)
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            largest,
+            [
+                PathBuf::from("/base/big.bin"),
+                PathBuf::from("/base/dir/mid.bin")
+            ]
+        );
+    }
+
+    #[test]
+    fn remove_subtracts_from_every_ancestor() {
+        let tmp = tree();
+        let mut tree = scan_tree(tmp.path()).tree;
+        let before = (tree.apparent, tree.files, tree.get(&[1]).unwrap().apparent);
+        let removed = tree.remove(&[1, 1]).unwrap();
+        assert_eq!(removed.name, "sub");
+        assert_eq!(tree.apparent, before.0 - 100);
+        assert_eq!(tree.files, before.1 - 1);
+        assert_eq!(tree.get(&[1]).unwrap().apparent, before.2 - 100);
+        assert_eq!(names(tree.get(&[1]).unwrap()), ["mid.bin"]);
+
+        assert!(tree.remove(&[7]).is_none());
+        assert!(tree.remove(&[]).is_none());
+        assert_eq!(tree.files, before.1 - 1);
+    }
+
+    #[test]
+    fn json_round_trips() {
+        let tmp = tree();
+        let scan = scan_tree(tmp.path());
+        let mut out = Vec::new();
+        write_json(&scan, &mut out).unwrap();
+        let back: UsageScan = serde_json::from_slice(&out).unwrap();
+        assert_eq!(back, scan);
+
+        let file = tmp.path().join("scan.json");
+        export_json(&scan, &file).unwrap();
+        let back: UsageScan = serde_json::from_slice(&fs::read(&file).unwrap()).unwrap();
+        assert_eq!(back, scan);
+    }
+
+    #[test]
+    fn background_scan_finishes_or_fails() {
+        let tmp = tree();
+        let last = start_scan(tmp.path().to_path_buf(), WalkOptions::default())
+            .last()
+            .unwrap();
+        match last {
+            ScanEvent::Finished(scan) => assert_eq!(scan.tree.files, 3),
+            other => panic!("unexpected {other:?}"),
+        }
+
+        let last = start_scan(tmp.path().join("missing"), WalkOptions::default())
+            .last()
+            .unwrap();
+        assert!(matches!(last, ScanEvent::Failed(_)));
+    }
+
+    fn area(r: &Rect) -> f32 {
+        r.w * r.h
+    }
+
+    #[test]
+    fn squarify_fills_the_bounds_in_proportion() {
+        let bounds = Rect {
+            x: 10.0,
+            y: 20.0,
+            w: 6.0,
+            h: 4.0,
+        };
+        let sizes = [6, 6, 4, 3, 2, 2, 1];
+        let rects = squarify(&sizes, bounds);
+        assert_eq!(rects.len(), sizes.len());
+        // The first row of the paper's example: two 3x2 tiles down the left edge.
+        assert_eq!(
+            rects[..2],
+            [
+                Rect {
+                    x: 10.0,
+                    y: 20.0,
+                    w: 3.0,
+                    h: 2.0
+                },
+                Rect {
+                    x: 10.0,
+                    y: 22.0,
+                    w: 3.0,
+                    h: 2.0
+                },
+            ]
+        );
+        for (r, &size) in rects.iter().zip(&sizes) {
+            assert!((area(r) - size as f32).abs() < 1e-3, "{r:?} for {size}");
+            assert!(r.x >= bounds.x - 1e-3 && r.x + r.w <= bounds.x + bounds.w + 1e-3);
+            assert!(r.y >= bounds.y - 1e-3 && r.y + r.h <= bounds.y + bounds.h + 1e-3);
+        }
+        for (i, a) in rects.iter().enumerate() {
+            for b in &rects[i + 1..] {
+                let overlap_w = (a.x + a.w).min(b.x + b.w) - a.x.max(b.x);
+                let overlap_h = (a.y + a.h).min(b.y + b.h) - a.y.max(b.y);
+                assert!(
+                    overlap_w <= 1e-3 || overlap_h <= 1e-3,
+                    "{a:?} overlaps {b:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn squarify_degenerate_inputs() {
+        let bounds = Rect {
+            x: 0.0,
+            y: 0.0,
+            w: 100.0,
+            h: 50.0,
+        };
+        assert!(squarify(&[], bounds).is_empty());
+        let rects = squarify(&[10, 0], bounds);
+        assert!((area(&rects[0]) - 5000.0).abs() < 1e-2);
+        assert_eq!(area(&rects[1]), 0.0);
+        assert!(squarify(&[0, 0], bounds).iter().all(|r| area(r) == 0.0));
+        let empty = Rect { w: 0.0, ..bounds };
+        assert!(squarify(&[1, 2], empty).iter().all(|r| area(r) == 0.0));
+    }
+}
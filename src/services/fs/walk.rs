@@ -0,0 +1,424 @@
+//! Recursive directory traversal shared by search, disk usage and bulk operations.
+//!
+//! A thin layer over `walkdir` that adds hidden-file filtering and ignore files. With
+//! `use_ignore_files`, rules from `.gitignore`, `.ignore` and `.nohrsignore` in every
+//! visited directory apply to what lies below it, as do the rules of the directories
+//! between the root and the enclosing git repository, if any.
+
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::FileKind;
+use crate::services::fs::ignore::{IgnoreRules, Verdict};
+use crate::services::fs::listing::is_hidden;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone)]
+pub struct WalkOptions {
+    /// Deepest level to visit; the root's children are at depth 1.
+    pub max_depth: Option<usize>,
+    /// Descend into symlinked directories. Loops are reported as `Error::SymlinkLoop`
+    /// and not entered.
+    pub follow_links: bool,
+    /// Do not descend into directories on another filesystem than the root.
+    pub same_file_system: bool,
+    /// Skip paths matched by ignore files, and `.git` directories.
+    pub use_ignore_files: bool,
+    pub show_hidden: bool,
+}
+
+impl Default for WalkOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: None,
+            follow_links: false,
+            same_file_system: false,
+            use_ignore_files: false,
+            show_hidden: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    /// Levels below the root, starting at 1.
+    pub depth: usize,
+    /// Kind of the entry, or of its target when links are followed.
+    pub kind: FileKind,
+    /// The path itself is a symlink, whether or not it was followed.
+    pub is_symlink: bool,
+    follow_links: bool,
+}
+
+impl WalkEntry {
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Dir
+    }
+
+    /// Metadata of the entry, or of its target when links are followed.
+    pub fn metadata(&self) -> Result<fs::Metadata> {
+        let md = if self.follow_links {
+            fs::metadata(&self.path)
+        } else {
+            fs::symlink_metadata(&self.path)
+        };
+        md.map_err(|e| Error::from_io(&self.path, e))
+    }
+}
+
+/// Ignore rules in effect, with the depth of the directory they came from and the path
+/// of the walk root relative to it (non-empty only for directories above the root).
+struct Layer {
+    depth: usize,
+    prefix: String,
+    rules: IgnoreRules,
+}
+
+/// Iterator over everything below a root, depth first. Unreadable entries are yielded as
+/// errors and the walk continues.
+pub struct Walk {
+    it: walkdir::IntoIter,
+    root: PathBuf,
+    opts: WalkOptions,
+    layers: Vec<Layer>,
+}
+
+/// Walk everything below `root`, excluding `root` itself.
+pub fn walk(root: &Path, opts: &WalkOptions) -> Walk {
+    let mut it = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .follow_links(opts.follow_links)
+        .same_file_system(opts.same_file_system);
+    if let Some(depth) = opts.max_depth {
+        it = it.max_depth(depth);
+    }
+    let layers = if opts.use_ignore_files {
+        root_layers(root)
+    } else {
+        Vec::new()
+    };
+    Walk {
+        it: it.into_iter(),
+        root: root.to_path_buf(),
+        opts: opts.clone(),
+        layers,
+    }
+}
+
+impl Walk {
+    /// Do not descend into the directory yielded last.
+    pub fn skip_current_dir(&mut self) {
+        self.it.skip_current_dir();
+    }
+
+    fn ignored(&self, path: &Path, depth: usize, is_dir: bool) -> bool {
+        let below: Vec<String> = path
+            .strip_prefix(&self.root)
+            .unwrap_or(path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        for layer in self.layers.iter().rev() {
+            let mut rel = layer.prefix.clone();
+            for part in below.iter().take(depth).skip(layer.depth) {
+                if !rel.is_empty() {
+                    rel.push('/');
+                }
+                rel.push_str(part);
+            }
+            match layer.rules.matched(&rel, is_dir) {
+                Some(Verdict::Ignore) => return true,
+                Some(Verdict::Include) => return false,
+                None => {}
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<WalkEntry>;
+
+    fn next(&mut self) -> Option<Result<WalkEntry>> {
+        loop {
+            let dent = match self.it.next()? {
+                Ok(dent) => dent,
+                Err(err) => return Some(Err(convert(err))),
+            };
+            let depth = dent.depth();
+            while self.layers.last().is_some_and(|l| l.depth >= depth) {
+                self.layers.pop();
+            }
+
+            let is_dir = dent.file_type().is_dir();
+            let name = dent.file_name().to_string_lossy();
+            let skip = (!self.opts.show_hidden && is_hidden(&name))
+                || (self.opts.use_ignore_files
+                    && ((is_dir && name == ".git") || self.ignored(dent.path(), depth, is_dir)));
+            if skip {
+                if is_dir {
+                    self.it.skip_current_dir();
+                }
+                continue;
+            }
+
+            let descends = self.opts.max_depth.is_none_or(|max| depth < max);
+            if is_dir && self.opts.use_ignore_files && descends {
+                if let Some(rules) = IgnoreRules::load(dent.path()) {
+                    self.layers.push(Layer {
+                        depth,
+                        prefix: String::new(),
+                        rules,
+                    });
+                }
+            }
+            return Some(Ok(WalkEntry {
+                kind: FileKind::from_file_type(dent.file_type()),
+                is_symlink: dent.path_is_symlink(),
+                depth,
+                path: dent.into_path(),
+                follow_links: self.opts.follow_links,
+            }));
+        }
+    }
+}
+
+/// Rules of the root and, inside a git repository, of the directories up to its top.
+fn root_layers(root: &Path) -> Vec<Layer> {
+    let mut layers = Vec::new();
+    if let Ok(abs) = std::path::absolute(root) {
+        let repo = abs.ancestors().find(|dir| dir.join(".git").exists());
+        if let Some(repo) = repo.filter(|repo| *repo != abs) {
+            for dir in abs.ancestors().skip(1) {
+                if let Some(rules) = IgnoreRules::load(dir) {
+                    let prefix = abs.strip_prefix(dir).unwrap_or(&abs);
+                    let prefix = prefix
+                        .components()
+                        .map(|c| c.as_os_str().to_string_lossy())
+                        .collect::<Vec<_>>()
+                        .join("/");
+                    layers.push(Layer {
+                        depth: 0,
+                        prefix,
+                        rules,
+                    });
+                }
+                if dir == repo {
+                    break;
+                }
+            }
+            // Outermost first, so nearer directories are consulted before them.
+            layers.reverse();
+        }
+    }
+    if let Some(rules) = IgnoreRules::load(root) {
+        layers.push(Layer {
+            depth: 0,
+            prefix: String::new(),
+            rules,
+        });
+    }
+    layers
+}
+
+fn convert(err: walkdir::Error) -> Error {
+    let path = err.path().map(Path::to_path_buf).unwrap_or_default();
+    if err.loop_ancestor().is_some() {
+        return Error::SymlinkLoop { path };
+    }
+    match err.into_io_error() {
+        Some(io) => Error::from_io(path, io),
+        None => Error::Other(format!("cannot walk {}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    /// Paths walked below `root`, relative and `/`-separated, sorted.
+    fn walked(root: &Path, opts: &WalkOptions) -> Vec<String> {
+        let mut paths: Vec<String> = walk(root, opts)
+            .map(|entry| {
+                let entry = entry.unwrap();
+                let rel = entry.path.strip_prefix(root).unwrap();
+                assert_eq!(entry.depth, rel.components().count());
+                rel.to_string_lossy().replace('\\', "/")
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    fn tree() -> TempDir {
+        let tmp = TempDir::new();
+        tmp.write("a.txt", "");
+        tmp.write(".hidden", "");
+        tmp.write("dir/b.txt", "");
+        tmp.write("dir/sub/c.txt", "");
+        tmp.write(".config/d.txt", "");
+        tmp
+    }
+
+    #[test]
+    fn walks_everything_below_the_root() {
+        let tmp = tree();
+        assert_eq!(
+            walked(tmp.path(), &WalkOptions::default()),
+            [
+                ".config",
+                ".config/d.txt",
+                ".hidden",
+                "a.txt",
+                "dir",
+                "dir/b.txt",
+                "dir/sub",
+                "dir/sub/c.txt",
+            ]
+        );
+    }
+
+    #[test]
+    fn max_depth_limits_levels() {
+        let tmp = tree();
+        let opts = WalkOptions {
+            max_depth: Some(1),
+            show_hidden: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(tmp.path(), &opts), ["a.txt", "dir"]);
+        let opts = WalkOptions {
+            max_depth: Some(2),
+            show_hidden: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            walked(tmp.path(), &opts),
+            ["a.txt", "dir", "dir/b.txt", "dir/sub"]
+        );
+    }
+
+    #[test]
+    fn hidden_entries_and_their_contents_are_skipped() {
+        let tmp = tree();
+        let opts = WalkOptions {
+            show_hidden: false,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            walked(tmp.path(), &opts),
+            ["a.txt", "dir", "dir/b.txt", "dir/sub", "dir/sub/c.txt"]
+        );
+    }
+
+    #[test]
+    fn skip_current_dir_prunes_the_last_directory() {
+        let tmp = tree();
+        let opts = WalkOptions {
+            show_hidden: false,
+            ..WalkOptions::default()
+        };
+        let mut walk = walk(tmp.path(), &opts);
+        let mut seen = Vec::new();
+        while let Some(entry) = walk.next() {
+            let entry = entry.unwrap();
+            if entry.path.ends_with("sub") {
+                walk.skip_current_dir();
+            }
+            seen.push(
+                entry
+                    .path
+                    .file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .into_owned(),
+            );
+        }
+        seen.sort();
+        assert_eq!(seen, ["a.txt", "b.txt", "dir", "sub"]);
+    }
+
+    #[test]
+    fn ignore_files_apply_below_their_directory() {
+        let tmp = TempDir::new();
+        tmp.write(".gitignore", "*.log\ntarget/\n");
+        tmp.write("keep.txt", "");
+        tmp.write("top.log", "");
+        tmp.write("target/out.bin", "");
+        tmp.write("src/main.rs", "");
+        tmp.write("src/debug.log", "");
+        tmp.write("src/.nohrsignore", "!debug.log\n/generated.rs\n");
+        tmp.write("src/generated.rs", "");
+        tmp.write("src/nested/generated.rs", "");
+        tmp.write(".git/HEAD", "");
+
+        let opts = WalkOptions {
+            use_ignore_files: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            walked(tmp.path(), &opts),
+            [
+                ".gitignore",
+                "keep.txt",
+                "src",
+                "src/.nohrsignore",
+                "src/debug.log",
+                "src/main.rs",
+                "src/nested",
+                "src/nested/generated.rs",
+            ]
+        );
+        // Without ignore files everything shows, `.git` included.
+        assert!(walked(tmp.path(), &WalkOptions::default()).contains(&".git/HEAD".to_string()));
+    }
+
+    #[test]
+    fn rules_above_the_root_apply_inside_a_repository() {
+        let tmp = TempDir::new();
+        tmp.mkdir(".git");
+        tmp.write(".gitignore", "sub/skipped.txt\n*.bak\n");
+        tmp.write("sub/skipped.txt", "");
+        tmp.write("sub/kept.txt", "");
+        tmp.write("sub/old.bak", "");
+
+        let opts = WalkOptions {
+            use_ignore_files: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(walked(&tmp.path().join("sub"), &opts), ["kept.txt"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_reported_and_loops_not_entered() {
+        let tmp = TempDir::new();
+        tmp.write("dir/file.txt", "");
+        std::os::unix::fs::symlink(tmp.path().join("dir"), tmp.path().join("dir/loop")).unwrap();
+
+        let entries: Vec<WalkEntry> = walk(tmp.path(), &WalkOptions::default())
+            .collect::<Result<_>>()
+            .unwrap();
+        let link = entries.iter().find(|e| e.path.ends_with("loop")).unwrap();
+        assert!(link.is_symlink);
+        assert_eq!(link.kind, FileKind::Symlink);
+        assert!(link.metadata().unwrap().file_type().is_symlink());
+        assert_eq!(entries.len(), 3);
+
+        let opts = WalkOptions {
+            follow_links: true,
+            ..WalkOptions::default()
+        };
+        let results: Vec<Result<WalkEntry>> = walk(tmp.path(), &opts).collect();
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().any(|r| matches!(
+            r,
+            Err(Error::SymlinkLoop { path }) if path.ends_with("loop")
+        )));
+        assert!(results
+            .iter()
+            .filter_map(|r| r.as_ref().ok())
+            .all(|e| e.is_dir() || e.path.ends_with("file.txt")));
+    }
+}
@@ -0,0 +1,499 @@
+//! Live directory watching with debounced, per-path change events.
+//!
+//! Raw notifications arrive in bursts: a build or a `git checkout` touches thousands of
+//! paths, and a single editor save is often a create, a few writes and a rename. The
+//! watcher collects them on a background thread and, once the directory has been quiet
+//! for the debounce interval, sends one `Change` per path describing the net effect.
+//! Paths are stat'ed when a burst is flushed, so files that came and went within it
+//! produce nothing.
+
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::FileEntry;
+use crate::services::fs::listing::{is_hidden, stat_entry, NameFilter};
+use crate::services::fs::sort::{compare_entries, sort_entries, SortOptions};
+use notify::event::{ModifyKind, RenameMode};
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Above this many new rows a patch re-sorts the listing instead of inserting one by one.
+const RESORT_THRESHOLD: usize = 64;
+
+#[derive(Debug, Clone)]
+pub struct WatchParams {
+    pub path: PathBuf,
+    /// Also watch every directory below `path`.
+    pub recursive: bool,
+    /// Quiet period that ends a burst.
+    pub debounce: Duration,
+    /// Longest a change is held back while events keep arriving.
+    pub max_delay: Duration,
+}
+
+impl Default for WatchParams {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::new(),
+            recursive: false,
+            debounce: Duration::from_millis(200),
+            max_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Net change to one path over a burst of notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(PathBuf),
+    Removed(PathBuf),
+    /// The item's contents or metadata changed, or it was replaced by another item.
+    Modified(PathBuf),
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+    },
+}
+
+impl Change {
+    /// The path the change leaves behind: the destination of a rename, or the removed path.
+    pub fn path(&self) -> &Path {
+        match self {
+            Change::Added(p) | Change::Removed(p) | Change::Modified(p) => p,
+            Change::Renamed { to, .. } => to,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum WatchEvent {
+    /// Net changes since the previous event, at most one per path, ordered by path.
+    Changes(Vec<Change>),
+    /// Notifications were lost or the watched directory itself was moved or removed;
+    /// the listing has to be read again from scratch.
+    Rescan,
+    /// The platform watcher reported a problem. Watching continues where it can.
+    Error(Error),
+}
+
+/// Receiving end of a directory watch. Dropping it stops watching.
+pub struct DirWatcher {
+    rx: Receiver<WatchEvent>,
+    // Dropping the platform watcher disconnects the debounce thread, which then exits.
+    _watcher: RecommendedWatcher,
+}
+
+impl DirWatcher {
+    /// Non-blocking poll for UI loops. `Disconnected` means watching has stopped.
+    pub fn try_next(&self) -> std::result::Result<WatchEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+}
+
+impl Iterator for DirWatcher {
+    type Item = WatchEvent;
+
+    fn next(&mut self) -> Option<WatchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Start watching `params.path`.
+pub fn watch(params: WatchParams) -> Result<DirWatcher> {
+    let (raw_tx, raw_rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(move |res| {
+        let _ = raw_tx.send(res);
+    })
+    .map_err(|e| convert(&params.path, e))?;
+    let mode = if params.recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+    watcher
+        .watch(&params.path, mode)
+        .map_err(|e| convert(&params.path, e))?;
+
+    let (tx, rx) = mpsc::channel();
+    let root = Root::new(&params.path);
+    thread::spawn(move || debounce(&params, &root, &raw_rx, &tx));
+    Ok(DirWatcher {
+        rx,
+        _watcher: watcher,
+    })
+}
+
+/// Apply `changes` to a sorted listing of `dir` in place.
+///
+/// Rows that did not change keep their relative order, so a UI can restore its selection
+/// by path and leave the scroll position alone. Changed paths are stat'ed again; changes
+/// below `dir`'s direct children are ignored. New rows are held to `show_hidden` and
+/// `filter`, which have the same meaning as in `ListParams`, so the listing stays as it
+/// was read. Returns the errors met while stat'ing.
+pub fn apply_changes(
+    entries: &mut Vec<FileEntry>,
+    dir: &Path,
+    changes: &[Change],
+    sort: &SortOptions,
+    show_hidden: bool,
+    filter: Option<&str>,
+) -> Vec<Error> {
+    let filter = filter.filter(|f| !f.is_empty()).map(NameFilter::new);
+    let mut errors = Vec::new();
+    let mut touched: HashSet<&Path> = HashSet::new();
+    let mut fresh = Vec::new();
+    for change in changes {
+        if let Change::Renamed { from, .. } = change {
+            touched.insert(from);
+        }
+        let path = change.path();
+        touched.insert(path);
+        if matches!(change, Change::Removed(_)) || path.parent() != Some(dir) {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        if !show_hidden && is_hidden(&name) {
+            continue;
+        }
+        if filter.as_ref().is_some_and(|f| !f.matches(&name)) {
+            continue;
+        }
+        fresh.extend(stat_entry(name, path, &mut errors));
+    }
+    if touched.is_empty() {
+        return errors;
+    }
+
+    entries.retain(|e| !e.fs_path().is_ok_and(|p| touched.contains(p.as_path())));
+    if fresh.len() > RESORT_THRESHOLD {
+        entries.extend(fresh);
+        sort_entries(entries, sort);
+    } else {
+        for entry in fresh {
+            let at =
+                entries.partition_point(|e| compare_entries(e, &entry, sort) == Ordering::Less);
+            entries.insert(at, entry);
+        }
+    }
+    errors
+}
+
+fn debounce(
+    params: &WatchParams,
+    root: &Root,
+    raw: &Receiver<notify::Result<Event>>,
+    tx: &Sender<WatchEvent>,
+) {
+    let mut burst = Burst::default();
+    // When the current burst started and when its latest event arrived.
+    let mut window: Option<(Instant, Instant)> = None;
+    loop {
+        let next = match window {
+            None => raw.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some((first, last)) => {
+                let deadline = (last + params.debounce).min(first + params.max_delay);
+                raw.recv_timeout(deadline.saturating_duration_since(Instant::now()))
+            }
+        };
+        match next {
+            Ok(Ok(event)) => {
+                burst.add(root, event);
+                let now = Instant::now();
+                window = Some((window.map_or(now, |(first, _)| first), now));
+            }
+            Ok(Err(err)) => {
+                if tx
+                    .send(WatchEvent::Error(convert(&params.path, err)))
+                    .is_err()
+                {
+                    return;
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {
+                window = None;
+                if let Some(event) = burst.finish() {
+                    if tx.send(event).is_err() {
+                        return;
+                    }
+                }
+            }
+            Err(RecvTimeoutError::Disconnected) => return,
+        }
+    }
+}
+
+/// The watched directory, as the caller named it and as the platform may report it.
+struct Root {
+    path: PathBuf,
+    canonical: PathBuf,
+}
+
+impl Root {
+    fn new(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            canonical: fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf()),
+        }
+    }
+
+    /// `path` under the caller's name for the root. Some platforms report the resolved
+    /// path, e.g. `/private/tmp/x` for a watch on `/tmp/x` on macOS.
+    fn rebase(&self, path: &Path) -> PathBuf {
+        match path.strip_prefix(&self.canonical) {
+            Ok(rest) if self.canonical != self.path && rest.as_os_str().is_empty() => {
+                self.path.clone()
+            }
+            Ok(rest) if self.canonical != self.path => self.path.join(rest),
+            _ => path.to_path_buf(),
+        }
+    }
+}
+
+/// Notifications collected since the last flush.
+#[derive(Default)]
+struct Burst {
+    /// Whether each touched path existed before the burst began, as far as its first
+    /// notification tells. `None` when the notification does not say.
+    existed: HashMap<PathBuf, Option<bool>>,
+    /// Rename sources, keyed by destination.
+    renamed_from: HashMap<PathBuf, PathBuf>,
+    rescan: bool,
+}
+
+impl Burst {
+    fn add(&mut self, root: &Root, event: Event) {
+        if event.need_rescan() {
+            self.rescan = true;
+            return;
+        }
+        let existed = match event.kind {
+            EventKind::Access(_) => return,
+            EventKind::Create(_) => Some(false),
+            EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                if let [from, to] = event.paths.as_slice() {
+                    let (from, to) = (root.rebase(from), root.rebase(to));
+                    self.touch(root, &from, Some(true));
+                    self.touch(root, &to, Some(false));
+                    self.renamed_from.insert(to, from);
+                }
+                return;
+            }
+            EventKind::Modify(ModifyKind::Name(RenameMode::To)) => Some(false),
+            // Some platforms report both sides of a rename the same way.
+            EventKind::Modify(ModifyKind::Name(RenameMode::Any | RenameMode::Other)) => None,
+            EventKind::Remove(_) | EventKind::Modify(_) | EventKind::Any | EventKind::Other => {
+                Some(true)
+            }
+        };
+        for path in &event.paths {
+            self.touch(root, &root.rebase(path), existed);
+        }
+    }
+
+    fn touch(&mut self, root: &Root, path: &Path, existed: Option<bool>) {
+        if path == root.path {
+            // Changes to the directory's own metadata are not listing changes, but losing
+            // the directory is.
+            if fs::metadata(&root.path).is_err() {
+                self.rescan = true;
+            }
+            return;
+        }
+        self.existed.entry(path.to_path_buf()).or_insert(existed);
+    }
+
+    /// Turn the burst into an event, or `None` if it had no net effect.
+    fn finish(&mut self) -> Option<WatchEvent> {
+        let burst = std::mem::take(self);
+        if burst.rescan {
+            return Some(WatchEvent::Rescan);
+        }
+
+        let mut changes: BTreeMap<PathBuf, Change> = BTreeMap::new();
+        for (path, existed) in burst.existed {
+            let exists = fs::symlink_metadata(&path).is_ok();
+            let change = match (existed.unwrap_or(!exists), exists) {
+                (false, true) => Change::Added(path.clone()),
+                (true, false) => Change::Removed(path.clone()),
+                (true, true) => Change::Modified(path.clone()),
+                (false, false) => continue,
+            };
+            changes.insert(path, change);
+        }
+
+        // Pair each new path with the removed path it was renamed from, following
+        // chains like a -> b -> c through intermediate names that left no trace.
+        let added: Vec<PathBuf> = changes
+            .iter()
+            .filter(|(_, c)| matches!(c, Change::Added(_)))
+            .map(|(p, _)| p.clone())
+            .collect();
+        for to in added {
+            let mut from = burst.renamed_from.get(&to);
+            for _ in 0..burst.renamed_from.len() {
+                let Some(src) = from else { break };
+                match changes.get(src) {
+                    Some(Change::Removed(_)) => {
+                        let from = src.clone();
+                        changes.remove(&from);
+                        changes.insert(to.clone(), Change::Renamed { from, to });
+                        break;
+                    }
+                    None => from = burst.renamed_from.get(src),
+                    Some(_) => break,
+                }
+            }
+        }
+
+        (!changes.is_empty()).then(|| WatchEvent::Changes(changes.into_values().collect()))
+    }
+}
+
+fn convert(root: &Path, err: notify::Error) -> Error {
+    let path = err
+        .paths
+        .first()
+        .cloned()
+        .unwrap_or_else(|| root.to_path_buf());
+    match err.kind {
+        notify::ErrorKind::Io(e) => Error::from_io(path, e),
+        notify::ErrorKind::PathNotFound => Error::Vanished { path },
+        kind => Error::Other(format!("watching {}: {kind:?}", path.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use notify::event::{CreateKind, RemoveKind};
+
+    fn event(kind: EventKind, paths: &[&Path]) -> Event {
+        let mut event = Event::new(kind);
+        for path in paths {
+            event = event.add_path(path.to_path_buf());
+        }
+        event
+    }
+
+    fn changes(burst: &mut Burst) -> Vec<Change> {
+        match burst.finish() {
+            Some(WatchEvent::Changes(changes)) => changes,
+            None => Vec::new(),
+            Some(other) => panic!("unexpected {other:?}"),
+        }
+    }
+
+    #[test]
+    fn reports_net_effect_of_a_burst() {
+        let tmp = TempDir::new();
+        let root = Root::new(tmp.path());
+        let kept = tmp.write("kept", "");
+        let gone = tmp.path().join("gone");
+        let mut burst = Burst::default();
+        for path in [&kept, &gone] {
+            burst.add(&root, event(EventKind::Create(CreateKind::File), &[path]));
+        }
+        burst.add(&root, event(EventKind::Remove(RemoveKind::File), &[&gone]));
+        // The directory's own metadata is not a listing change.
+        burst.add(&root, event(EventKind::Any, &[tmp.path()]));
+        assert_eq!(changes(&mut burst), [Change::Added(kept)]);
+    }
+
+    #[test]
+    fn pairs_renames_through_intermediate_names() {
+        let tmp = TempDir::new();
+        let root = Root::new(tmp.path());
+        let (a, b) = (tmp.path().join("a"), tmp.path().join("b"));
+        let c = tmp.write("c", "");
+        let rename = EventKind::Modify(ModifyKind::Name(RenameMode::Both));
+        let mut burst = Burst::default();
+        burst.add(&root, event(rename, &[&a, &b]));
+        burst.add(&root, event(rename, &[&b, &c]));
+        assert_eq!(changes(&mut burst), [Change::Renamed { from: a, to: c }]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn maps_resolved_paths_onto_the_watched_name() {
+        let tmp = TempDir::new();
+        let real = tmp.mkdir("real");
+        let link = tmp.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+        let root = Root::new(&link);
+        let file = tmp.write("real/file", "");
+
+        let mut burst = Burst::default();
+        burst.add(&root, event(EventKind::Create(CreateKind::File), &[&file]));
+        burst.add(
+            &root,
+            event(EventKind::Any, &[&real.canonicalize().unwrap()]),
+        );
+        let changes = changes(&mut burst);
+        assert_eq!(changes, [Change::Added(link.join("file"))]);
+
+        fs::remove_dir_all(&real).unwrap();
+        burst.add(
+            &root,
+            event(EventKind::Remove(RemoveKind::Folder), &[&real]),
+        );
+        assert!(matches!(burst.finish(), Some(WatchEvent::Rescan)));
+    }
+
+    #[test]
+    fn patches_listing_with_the_listing_filter() {
+        let tmp = TempDir::new();
+        let old = tmp.write("old.rs", "");
+        let sort = SortOptions::default();
+        let mut entries = vec![FileEntry {
+            name: "old.rs".into(),
+            path: old.to_string_lossy().into_owned(),
+            ..Default::default()
+        }];
+        fs::remove_file(&old).unwrap();
+        let added: Vec<Change> = ["b.rs", "a.rs", "notes.txt", ".hidden.rs"]
+            .iter()
+            .map(|name| Change::Added(tmp.write(name, "")))
+            .chain([
+                Change::Removed(old),
+                Change::Added(tmp.write("sub/deep.rs", "")),
+            ])
+            .collect();
+        let errors = apply_changes(&mut entries, tmp.path(), &added, &sort, false, Some("*.RS"));
+        assert!(errors.is_empty());
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["a.rs", "b.rs"]);
+    }
+
+    #[test]
+    fn watches_a_directory() {
+        let tmp = TempDir::new();
+        let watcher = watch(WatchParams {
+            path: tmp.path().to_path_buf(),
+            debounce: Duration::from_millis(20),
+            ..Default::default()
+        })
+        .unwrap();
+        let file = tmp.write("new", "");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while Instant::now() < deadline {
+            match watcher.try_next() {
+                Ok(WatchEvent::Changes(changes)) => {
+                    assert_eq!(changes, [Change::Added(file)]);
+                    return;
+                }
+                Ok(other) => panic!("unexpected {other:?}"),
+                Err(_) => thread::sleep(Duration::from_millis(10)),
+            }
+        }
+        panic!("no change reported");
+    }
+}
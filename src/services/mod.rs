@@ -1 +1,6 @@
+pub mod frecency;
 pub mod fs;
+pub mod preview;
+pub mod search;
+pub mod thumbnail;
+pub mod trash;
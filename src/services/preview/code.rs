@@ -0,0 +1,429 @@
+//! Syntax-highlighted previews of source files.
+//!
+//! The language is looked up by file name and extension, then by the first line, which
+//! catches shebangs such as `#!/usr/bin/env python` and editor modelines. Highlighting is
+//! done on demand and in order, since each line depends on the parser state the previous
+//! one left, so only as much of a large file is highlighted as has been shown.
+//!
+//! Syntaxes and themes are syntect's bundled defaults, loaded once on first use. There is
+//! no settings store yet, so the theme is chosen only by the `NOHRS_PREVIEW_THEME`
+//! environment variable, which takes any of [`theme_names`].
+
+use std::fmt;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{Color, FontStyle, Style, Theme, ThemeSet};
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+
+/// Theme used unless another is configured; suits the light UI.
+pub const DEFAULT_THEME: &str = "InspiredGitHub";
+
+/// How a preview is highlighted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeOptions {
+    /// One of [`theme_names`]. `NOHRS_PREVIEW_THEME` overrides the default.
+    pub theme: String,
+    /// From the first line longer than this many bytes on, the rest of the file is shown
+    /// without highlighting, since a single huge line, as in minified code, can take
+    /// seconds to parse, and skipping it would leave the parser in the wrong state.
+    pub max_line_len: usize,
+}
+
+impl Default for CodeOptions {
+    fn default() -> Self {
+        Self {
+            theme: std::env::var("NOHRS_PREVIEW_THEME").unwrap_or_else(|_| DEFAULT_THEME.into()),
+            max_line_len: 4096,
+        }
+    }
+}
+
+/// Colors are `0xRRGGBB`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpanStyle {
+    pub color: u32,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+/// A run of a line in one style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Span {
+    /// Byte range within the line.
+    pub range: Range<usize>,
+    pub style: SpanStyle,
+}
+
+struct Assets {
+    syntaxes: SyntaxSet,
+    themes: ThemeSet,
+}
+
+fn assets() -> &'static Assets {
+    static ASSETS: OnceLock<Assets> = OnceLock::new();
+    ASSETS.get_or_init(|| Assets {
+        syntaxes: SyntaxSet::load_defaults_newlines(),
+        themes: ThemeSet::load_defaults(),
+    })
+}
+
+/// Themes that can be configured, sorted.
+pub fn theme_names() -> Vec<&'static str> {
+    assets().themes.themes.keys().map(String::as_str).collect()
+}
+
+/// The language of the file at `path` whose first line is `first_line`, if one is known.
+pub fn detect_language(path: &Path, first_line: &str) -> Option<&'static str> {
+    detect(path, first_line).map(|syntax| syntax.name.as_str())
+}
+
+fn detect(path: &Path, first_line: &str) -> Option<&'static SyntaxReference> {
+    let syntaxes = &assets().syntaxes;
+    let by_name = || {
+        // Some syntaxes list whole file names, such as `Makefile`, as extensions.
+        let name = path.file_name()?.to_str()?;
+        syntaxes.find_syntax_by_extension(name)
+    };
+    let by_extension = || {
+        let ext = path.extension()?.to_str()?;
+        syntaxes.find_syntax_by_extension(ext)
+    };
+    by_name()
+        .or_else(by_extension)
+        .or_else(|| syntaxes.find_syntax_by_first_line(first_line))
+        .filter(|syntax| syntax.name != "Plain Text")
+}
+
+/// A text file being shown with line numbers and highlighting.
+pub struct CodePreview {
+    text: String,
+    /// Byte range of each line in `text`, including its line ending.
+    lines: Vec<Range<usize>>,
+    language: Option<&'static str>,
+    highlighter: Option<HighlightLines<'static>>,
+    /// Spans of the lines highlighted so far, from the first.
+    spans: Vec<Vec<Span>>,
+    foreground: u32,
+    background: u32,
+    max_line_len: usize,
+}
+
+impl fmt::Debug for CodePreview {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("CodePreview")
+            .field("language", &self.language)
+            .field("lines", &self.lines.len())
+            .field("highlighted", &self.spans.len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CodePreview {
+    /// Prepare `text`, the contents of `path`, for highlighting. Nothing is highlighted
+    /// until lines are asked for. An unknown theme falls back to [`DEFAULT_THEME`].
+    pub fn new(text: String, path: &Path, options: &CodeOptions) -> Self {
+        let first_line = text.lines().next().unwrap_or_default();
+        let syntax = detect(path, first_line);
+        Self::with_syntax(text, syntax, options)
+    }
+
+    /// Like [`CodePreview::new`], for `text` in the language named by `token`, such as the
+    /// `rust` or `py` of a fenced code block. An unknown token leaves the text plain.
+    pub fn with_language(text: String, token: &str, options: &CodeOptions) -> Self {
+        let syntax = assets()
+            .syntaxes
+            .find_syntax_by_token(token)
+            .filter(|syntax| syntax.name != "Plain Text");
+        Self::with_syntax(text, syntax, options)
+    }
+
+    fn with_syntax(
+        text: String,
+        syntax: Option<&'static SyntaxReference>,
+        options: &CodeOptions,
+    ) -> Self {
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for (ix, _) in text.match_indices('\n') {
+            lines.push(start..ix + 1);
+            start = ix + 1;
+        }
+        if start < text.len() || lines.is_empty() {
+            lines.push(start..text.len());
+        }
+
+        let theme = theme(&options.theme);
+        Self {
+            language: syntax.map(|s| s.name.as_str()),
+            highlighter: syntax.map(|syntax| HighlightLines::new(syntax, theme)),
+            foreground: theme.settings.foreground.map_or(0x000000, rgb),
+            background: theme.settings.background.map_or(0xFFFFFF, rgb),
+            text,
+            lines,
+            spans: Vec::new(),
+            max_line_len: options.max_line_len,
+        }
+    }
+
+    /// Name of the detected language, e.g. `Rust`; `None` for plain text.
+    pub fn language(&self) -> Option<&'static str> {
+        self.language
+    }
+
+    pub fn line_count(&self) -> usize {
+        self.lines.len()
+    }
+
+    /// Color of text outside any span.
+    pub fn foreground(&self) -> u32 {
+        self.foreground
+    }
+
+    pub fn background(&self) -> u32 {
+        self.background
+    }
+
+    /// Line `ix` without its line ending, with its spans. Highlights every line up to it
+    /// that has not been yet.
+    pub fn line(&mut self, ix: usize) -> Option<(&str, &[Span])> {
+        self.highlight_until(ix + 1);
+        let range = self.lines.get(ix)?;
+        let line = self.text[range.clone()].trim_end_matches(['\n', '\r']);
+        Some((line, &self.spans[ix]))
+    }
+
+    /// Highlight the lines before `end`.
+    pub fn highlight_until(&mut self, end: usize) {
+        let end = end.min(self.lines.len());
+        while self.spans.len() < end {
+            let line = &self.text[self.lines[self.spans.len()].clone()];
+            let shown = line.trim_end_matches(['\n', '\r']).len();
+            if line.len() > self.max_line_len && self.highlighter.take().is_some() {
+                tracing::debug!(
+                    line = self.spans.len(),
+                    "highlighting stopped at a long line"
+                );
+            }
+            let regions = match &mut self.highlighter {
+                Some(highlighter) => {
+                    match highlighter.highlight_line(line, &assets().syntaxes) {
+                        Ok(regions) => Some(regions),
+                        Err(err) => {
+                            // The parser state is unusable from here on.
+                            tracing::debug!(%err, "highlighting stopped");
+                            self.highlighter = None;
+                            None
+                        }
+                    }
+                }
+                None => None,
+            };
+            let spans = match regions {
+                Some(regions) => spans(&regions, shown),
+                None if shown == 0 => Vec::new(),
+                None => vec![Span {
+                    range: 0..shown,
+                    style: SpanStyle {
+                        color: self.foreground,
+                        bold: false,
+                        italic: false,
+                        underline: false,
+                    },
+                }],
+            };
+            self.spans.push(spans);
+        }
+    }
+}
+
+fn theme(name: &str) -> &'static Theme {
+    let themes = &assets().themes.themes;
+    themes.get(name).unwrap_or_else(|| {
+        tracing::warn!(theme = name, "unknown preview theme");
+        &themes[DEFAULT_THEME]
+    })
+}
+
+/// Byte-range spans of highlighted `regions`, clipped to the first `len` bytes so the
+/// line ending is left out.
+fn spans(regions: &[(Style, &str)], len: usize) -> Vec<Span> {
+    let mut spans = Vec::with_capacity(regions.len());
+    let mut start = 0;
+    for (style, text) in regions {
+        let end = (start + text.len()).min(len);
+        if start < end {
+            spans.push(Span {
+                range: start..end,
+                style: SpanStyle {
+                    color: rgb(style.foreground),
+                    bold: style.font_style.contains(FontStyle::BOLD),
+                    italic: style.font_style.contains(FontStyle::ITALIC),
+                    underline: style.font_style.contains(FontStyle::UNDERLINE),
+                },
+            });
+        }
+        start += text.len();
+    }
+    spans
+}
+
+fn rgb(color: Color) -> u32 {
+    (color.r as u32) << 16 | (color.g as u32) << 8 | color.b as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> CodeOptions {
+        CodeOptions {
+            theme: DEFAULT_THEME.into(),
+            max_line_len: 4096,
+        }
+    }
+
+    fn rust(text: &str) -> CodePreview {
+        CodePreview::new(text.into(), Path::new("main.rs"), &options())
+    }
+
+    fn plain(preview: &CodePreview, len: usize) -> Vec<Span> {
+        vec![Span {
+            range: 0..len,
+            style: SpanStyle {
+                color: preview.foreground(),
+                bold: false,
+                italic: false,
+                underline: false,
+            },
+        }]
+    }
+
+    #[test]
+    fn theme_names_are_sorted_and_include_the_default() {
+        let names = theme_names();
+        assert!(names.contains(&DEFAULT_THEME));
+        assert!(names.windows(2).all(|w| w[0] <= w[1]));
+    }
+
+    #[test]
+    fn languages_are_detected_by_name_extension_and_first_line() {
+        assert_eq!(detect_language(Path::new("src/main.rs"), ""), Some("Rust"));
+        assert_eq!(detect_language(Path::new("Makefile"), ""), Some("Makefile"));
+        assert_eq!(
+            detect_language(Path::new("run"), "#!/usr/bin/env python"),
+            Some("Python")
+        );
+        assert_eq!(detect_language(Path::new("notes.txt"), "hello"), None);
+        assert_eq!(detect_language(Path::new("data"), ""), None);
+    }
+
+    #[test]
+    fn fenced_languages_are_found_by_token() {
+        let preview = CodePreview::with_language("x = 1".into(), "py", &options());
+        assert_eq!(preview.language(), Some("Python"));
+        let preview = CodePreview::with_language("x".into(), "no-such-language", &options());
+        assert_eq!(preview.language(), None);
+    }
+
+    #[test]
+    fn text_is_split_into_lines_without_their_endings() {
+        let mut preview = rust("a\r\nb\n\nc");
+        assert_eq!(preview.line_count(), 4);
+        let lines: Vec<_> = (0..4)
+            .map(|ix| preview.line(ix).unwrap().0.to_string())
+            .collect();
+        assert_eq!(lines, ["a", "b", "", "c"]);
+        assert!(preview.line(4).is_none());
+
+        assert_eq!(rust("a\n").line_count(), 1);
+        let mut empty = rust("");
+        assert_eq!(empty.line_count(), 1);
+        assert_eq!(empty.line(0), Some(("", &[][..])));
+    }
+
+    #[test]
+    fn spans_cover_each_line_and_leave_out_its_ending() {
+        let mut preview = rust("fn main() {\r\n    let x = \"hi\";\n}\n");
+        for ix in 0..preview.line_count() {
+            let (line, spans) = preview.line(ix).unwrap();
+            let (len, spans) = (line.len(), spans.to_vec());
+            assert!(!spans.is_empty());
+            assert_eq!(spans[0].range.start, 0);
+            assert_eq!(spans.last().unwrap().range.end, len);
+            assert!(spans.windows(2).all(|w| w[0].range.end == w[1].range.start));
+        }
+        let (_, spans) = preview.line(0).unwrap();
+        assert!(spans.len() > 1, "keyword and name should differ: {spans:?}");
+    }
+
+    #[test]
+    fn lines_are_highlighted_only_as_far_as_asked() {
+        let mut preview = rust("a\nb\nc\nd\n");
+        preview.highlight_until(2);
+        assert_eq!(preview.spans.len(), 2);
+        preview.line(0);
+        assert_eq!(preview.spans.len(), 2);
+        preview.highlight_until(100);
+        assert_eq!(preview.spans.len(), 4);
+    }
+
+    #[test]
+    fn unknown_languages_are_one_plain_span() {
+        let mut preview = CodePreview::new("hello world".into(), Path::new("a.txt"), &options());
+        assert_eq!(preview.language(), None);
+        let expected = plain(&preview, 11);
+        assert_eq!(preview.line(0).unwrap().1, expected.as_slice());
+    }
+
+    #[test]
+    fn highlighting_stops_at_the_first_overlong_line() {
+        let options = CodeOptions {
+            max_line_len: 20,
+            ..options()
+        };
+        let long = format!("/* {} */", "x".repeat(40));
+        let text = format!("fn main() {{}}\n{long}\nfn main() {{}}\n");
+        let mut preview = CodePreview::new(text, Path::new("main.rs"), &options);
+
+        assert!(preview.line(0).unwrap().1.len() > 1);
+        let expected = plain(&preview, long.len());
+        assert_eq!(preview.line(1).unwrap().1, expected.as_slice());
+        let expected = plain(&preview, "fn main() {}".len());
+        assert_eq!(preview.line(2).unwrap().1, expected.as_slice());
+    }
+
+    #[test]
+    fn unknown_themes_fall_back_to_the_default() {
+        let options = CodeOptions {
+            theme: "No Such Theme".into(),
+            ..options()
+        };
+        let fallback = CodePreview::new(String::new(), Path::new("a.rs"), &options);
+        let default = rust("");
+        assert_eq!(fallback.foreground(), default.foreground());
+        assert_eq!(fallback.background(), default.background());
+    }
+
+    #[test]
+    fn region_spans_are_clipped_to_the_line() {
+        let style = Style::default();
+        let regions = [(style, "ab"), (style, ""), (style, "cd\n")];
+        let ranges: Vec<_> = spans(&regions, 4).into_iter().map(|s| s.range).collect();
+        assert_eq!(ranges, [0..2, 2..4]);
+        assert!(spans(&[(style, "\n")], 0).is_empty());
+    }
+
+    #[test]
+    fn colors_are_packed_as_rgb() {
+        let color = Color {
+            r: 0x12,
+            g: 0x34,
+            b: 0x56,
+            a: 0xff,
+        };
+        assert_eq!(rgb(color), 0x123456);
+    }
+}
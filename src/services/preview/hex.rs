@@ -0,0 +1,479 @@
+//! Hex dumps of binary files.
+//!
+//! Rows of [`BYTES_PER_ROW`] bytes are read a page at a time as they are asked for, and
+//! only a few pages are kept, so files of any size open instantly and use little memory.
+//! The first bytes are checked for the magic numbers of common formats to summarise what
+//! the file is.
+
+use crate::core::errors::{Error, Result};
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const BYTES_PER_ROW: usize = 16;
+/// Bytes read at a time.
+const PAGE_SIZE: u64 = 64 * 1024;
+/// Pages kept in memory; enough for a screenful either side of a page boundary.
+const MAX_PAGES: usize = 8;
+/// Bytes read up front to detect the file type.
+const HEADER_SIZE: usize = 4096;
+
+/// One row of a dump.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HexRow {
+    /// Offset of the first byte in the file.
+    pub offset: u64,
+    pub bytes: Vec<u8>,
+}
+
+impl HexRow {
+    /// The bytes as two-digit hex, grouped by eight and padded to a full row.
+    pub fn hex(&self) -> String {
+        let mut out = String::with_capacity(BYTES_PER_ROW * 3 + 1);
+        for ix in 0..BYTES_PER_ROW {
+            if ix > 0 {
+                out.push(' ');
+            }
+            if ix == BYTES_PER_ROW / 2 {
+                out.push(' ');
+            }
+            match self.bytes.get(ix) {
+                Some(byte) => out.push_str(&format!("{byte:02x}")),
+                None => out.push_str("  "),
+            }
+        }
+        out
+    }
+
+    /// The bytes as ASCII, with `.` for anything unprintable.
+    pub fn ascii(&self) -> String {
+        self.bytes
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect()
+    }
+}
+
+/// A file being shown as a hex dump.
+pub struct HexPreview {
+    file: File,
+    len: u64,
+    summary: Option<String>,
+    /// Recently read pages by index, most recent last.
+    pages: VecDeque<(u64, Vec<u8>)>,
+}
+
+impl HexPreview {
+    /// Open `path` and detect its type. Nothing else is read until rows are asked for.
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut file = File::open(path).map_err(|e| Error::from_io(path, e))?;
+        let len = file.metadata().map_err(|e| Error::from_io(path, e))?.len();
+        let mut header = Vec::with_capacity(HEADER_SIZE);
+        (&mut file)
+            .take(HEADER_SIZE as u64)
+            .read_to_end(&mut header)
+            .map_err(|e| Error::from_io(path, e))?;
+        Ok(Self {
+            file,
+            len,
+            summary: describe(&header),
+            pages: VecDeque::new(),
+        })
+    }
+
+    /// Length of the file when it was opened.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// One line saying what the file is, if its magic number is known.
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    pub fn row_count(&self) -> u64 {
+        self.len.div_ceil(BYTES_PER_ROW as u64)
+    }
+
+    /// Hex digits needed for the largest offset, at least eight.
+    pub fn offset_width(&self) -> usize {
+        let digits = (u64::BITS - self.len.leading_zeros()).div_ceil(4) as usize;
+        digits.max(8)
+    }
+
+    /// Row `ix`, reading its page if it is not in memory. `None` past the end, or if the
+    /// file could not be read there, e.g. because it was truncated since it was opened.
+    pub fn row(&mut self, ix: u64) -> Option<HexRow> {
+        let offset = ix.checked_mul(BYTES_PER_ROW as u64)?;
+        if offset >= self.len {
+            return None;
+        }
+        let page = self.page(offset / PAGE_SIZE)?;
+        let start = (offset % PAGE_SIZE) as usize;
+        let end = (start + BYTES_PER_ROW).min(page.len());
+        let bytes = page.get(start..end)?.to_vec();
+        (!bytes.is_empty()).then_some(HexRow { offset, bytes })
+    }
+
+    fn page(&mut self, index: u64) -> Option<&[u8]> {
+        if let Some(pos) = self.pages.iter().position(|(ix, _)| *ix == index) {
+            let page = self.pages.remove(pos)?;
+            self.pages.push_back(page);
+        } else {
+            let mut data = Vec::with_capacity(PAGE_SIZE as usize);
+            let read = self
+                .file
+                .seek(SeekFrom::Start(index * PAGE_SIZE))
+                .and_then(|_| (&mut self.file).take(PAGE_SIZE).read_to_end(&mut data));
+            if let Err(err) = read {
+                tracing::debug!(%err, "hex preview read failed");
+                return None;
+            }
+            if self.pages.len() == MAX_PAGES {
+                self.pages.pop_front();
+            }
+            self.pages.push_back((index, data));
+        }
+        self.pages.back().map(|(_, data)| data.as_slice())
+    }
+}
+
+/// A one-line description of the file starting with `header`, if its format is one of
+/// those recognised: ELF, Mach-O, zip, PDF and SQLite.
+pub fn describe(header: &[u8]) -> Option<String> {
+    if header.starts_with(b"\x7fELF") {
+        return Some(describe_elf(header));
+    }
+    if let Some(macho) = describe_macho(header) {
+        return Some(macho);
+    }
+    if header.starts_with(b"PK\x03\x04") || header.starts_with(b"PK\x05\x06") {
+        return Some("Zip archive".into());
+    }
+    if let Some(rest) = header.strip_prefix(b"%PDF-") {
+        let version: String = rest
+            .iter()
+            .take_while(|b| b.is_ascii_digit() || **b == b'.')
+            .map(|&b| b as char)
+            .collect();
+        return Some(if version.is_empty() {
+            "PDF document".into()
+        } else {
+            format!("PDF document, version {version}")
+        });
+    }
+    if header.starts_with(b"SQLite format 3\0") {
+        return Some(describe_sqlite(header));
+    }
+    None
+}
+
+fn describe_elf(header: &[u8]) -> String {
+    let class = match header.get(4) {
+        Some(1) => "32-bit",
+        Some(2) => "64-bit",
+        _ => return "ELF file".into(),
+    };
+    let little = header.get(5) == Some(&1);
+    let half = |at: usize| {
+        let bytes = [*header.get(at)?, *header.get(at + 1)?];
+        Some(if little {
+            u16::from_le_bytes(bytes)
+        } else {
+            u16::from_be_bytes(bytes)
+        })
+    };
+    let kind = match half(16) {
+        Some(1) => "relocatable",
+        Some(2) => "executable",
+        Some(3) => "shared object",
+        Some(4) => "core dump",
+        _ => "file",
+    };
+    let machine = match half(18) {
+        Some(0x03) => Some("x86"),
+        Some(0x08) => Some("MIPS"),
+        Some(0x14) => Some("PowerPC"),
+        Some(0x15) => Some("PowerPC64"),
+        Some(0x28) => Some("ARM"),
+        Some(0x3e) => Some("x86-64"),
+        Some(0xb7) => Some("AArch64"),
+        Some(0xf3) => Some("RISC-V"),
+        _ => None,
+    };
+    let endian = if little { "LSB" } else { "MSB" };
+    match machine {
+        Some(machine) => format!("ELF {class} {endian} {kind}, {machine}"),
+        None => format!("ELF {class} {endian} {kind}"),
+    }
+}
+
+fn describe_macho(header: &[u8]) -> Option<String> {
+    let magic = header.get(..4)?;
+    // Universal binaries are always big-endian; Java class files share their magic, but
+    // give a version of 45 or more where a universal binary gives a small CPU count.
+    if magic == [0xca, 0xfe, 0xba, 0xbe] {
+        let count = u32::from_be_bytes(header.get(4..8)?.try_into().ok()?);
+        return (count < 45).then(|| {
+            let s = if count == 1 { "" } else { "s" };
+            format!("Mach-O universal binary with {count} architecture{s}")
+        });
+    }
+    let (class, little) = match magic {
+        [0xce, 0xfa, 0xed, 0xfe] => ("32-bit", true),
+        [0xcf, 0xfa, 0xed, 0xfe] => ("64-bit", true),
+        [0xfe, 0xed, 0xfa, 0xce] => ("32-bit", false),
+        [0xfe, 0xed, 0xfa, 0xcf] => ("64-bit", false),
+        _ => return None,
+    };
+    let word = |at: usize| {
+        let bytes: [u8; 4] = header.get(at..at + 4)?.try_into().ok()?;
+        Some(if little {
+            u32::from_le_bytes(bytes)
+        } else {
+            u32::from_be_bytes(bytes)
+        })
+    };
+    let cpu = match word(4).map(|cpu| cpu & 0x00ff_ffff) {
+        Some(7) => Some(if class == "64-bit" { "x86-64" } else { "i386" }),
+        Some(12) => Some(if class == "64-bit" { "arm64" } else { "arm" }),
+        Some(18) => Some("ppc"),
+        _ => None,
+    };
+    let kind = match word(12) {
+        Some(1) => "object",
+        Some(2) => "executable",
+        Some(6) => "dynamic library",
+        Some(8) => "bundle",
+        _ => "file",
+    };
+    Some(match cpu {
+        Some(cpu) => format!("Mach-O {class} {kind}, {cpu}"),
+        None => format!("Mach-O {class} {kind}"),
+    })
+}
+
+fn describe_sqlite(header: &[u8]) -> String {
+    let page_size = header
+        .get(16..18)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        // 1 encodes the largest page size, which does not fit in 16 bits.
+        .map(|size| if size == 1 { 65536 } else { u32::from(size) });
+    let pages = header
+        .get(28..32)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_be_bytes);
+    match (page_size, pages) {
+        (Some(size), Some(pages)) if size > 0 && pages > 0 => {
+            format!("SQLite 3 database, {pages} pages of {size} bytes")
+        }
+        _ => "SQLite 3 database".into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn header(parts: &[(usize, &[u8])]) -> Vec<u8> {
+        let mut header = vec![0; 64];
+        for (at, bytes) in parts {
+            header[*at..at + bytes.len()].copy_from_slice(bytes);
+        }
+        header
+    }
+
+    #[test]
+    fn rows_show_hex_grouped_by_eight_and_padded() {
+        let row = HexRow {
+            offset: 0,
+            bytes: (0..16).collect(),
+        };
+        assert_eq!(
+            row.hex(),
+            "00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f"
+        );
+        let short = HexRow {
+            offset: 16,
+            bytes: vec![0xab, 0x01],
+        };
+        assert_eq!(short.hex().len(), row.hex().len());
+        assert_eq!(short.hex().trim_end(), "ab 01");
+    }
+
+    #[test]
+    fn rows_show_printable_ascii() {
+        let row = HexRow {
+            offset: 0,
+            bytes: b"Hi \x00\x7f~\xe9\n".to_vec(),
+        };
+        assert_eq!(row.ascii(), "Hi ..~..");
+    }
+
+    #[test]
+    fn elf_files_give_class_endianness_type_and_machine() {
+        let exe = header(&[(0, b"\x7fELF\x02\x01"), (16, &[2, 0, 0x3e, 0])]);
+        assert_eq!(describe(&exe).unwrap(), "ELF 64-bit LSB executable, x86-64");
+        let lib = header(&[(0, b"\x7fELF\x01\x02"), (16, &[0, 3, 0, 0x28])]);
+        assert_eq!(describe(&lib).unwrap(), "ELF 32-bit MSB shared object, ARM");
+        let odd = header(&[(0, b"\x7fELF\x02\x01"), (16, &[9, 0, 0x99, 0])]);
+        assert_eq!(describe(&odd).unwrap(), "ELF 64-bit LSB file");
+        assert_eq!(describe(b"\x7fELF").unwrap(), "ELF file");
+    }
+
+    #[test]
+    fn mach_o_files_give_class_type_and_cpu() {
+        let exe = header(&[
+            (0, &[0xcf, 0xfa, 0xed, 0xfe]),
+            (4, &[0x0c, 0, 0, 0x01]),
+            (12, &[2, 0, 0, 0]),
+        ]);
+        assert_eq!(describe(&exe).unwrap(), "Mach-O 64-bit executable, arm64");
+        let lib = header(&[
+            (0, &[0xfe, 0xed, 0xfa, 0xce]),
+            (4, &[0, 0, 0, 7]),
+            (12, &[0, 0, 0, 6]),
+        ]);
+        assert_eq!(
+            describe(&lib).unwrap(),
+            "Mach-O 32-bit dynamic library, i386"
+        );
+        let fat = header(&[(0, &[0xca, 0xfe, 0xba, 0xbe]), (4, &[0, 0, 0, 2])]);
+        assert_eq!(
+            describe(&fat).unwrap(),
+            "Mach-O universal binary with 2 architectures"
+        );
+        let one = header(&[(0, &[0xca, 0xfe, 0xba, 0xbe]), (4, &[0, 0, 0, 1])]);
+        assert_eq!(
+            describe(&one).unwrap(),
+            "Mach-O universal binary with 1 architecture"
+        );
+        // A Java class file, version 52.
+        let class = header(&[(0, &[0xca, 0xfe, 0xba, 0xbe]), (4, &[0, 0, 0, 0x34])]);
+        assert_eq!(describe(&class), None);
+    }
+
+    #[test]
+    fn archives_documents_and_databases_are_recognised() {
+        assert_eq!(describe(b"PK\x03\x04rest").unwrap(), "Zip archive");
+        assert_eq!(describe(b"PK\x05\x06").unwrap(), "Zip archive");
+        assert_eq!(
+            describe(b"%PDF-1.7\n%...").unwrap(),
+            "PDF document, version 1.7"
+        );
+        assert_eq!(describe(b"%PDF-").unwrap(), "PDF document");
+
+        let sqlite = header(&[
+            (0, b"SQLite format 3\0"),
+            (16, &[0x10, 0]),
+            (28, &[0, 0, 0, 3]),
+        ]);
+        assert_eq!(
+            describe(&sqlite).unwrap(),
+            "SQLite 3 database, 3 pages of 4096 bytes"
+        );
+        let largest = header(&[
+            (0, b"SQLite format 3\0"),
+            (16, &[0, 1]),
+            (28, &[0, 0, 0, 1]),
+        ]);
+        assert_eq!(
+            describe(&largest).unwrap(),
+            "SQLite 3 database, 1 pages of 65536 bytes"
+        );
+        assert_eq!(describe(b"SQLite format 3\0").unwrap(), "SQLite 3 database");
+    }
+
+    #[test]
+    fn unknown_and_empty_headers_are_not_described() {
+        assert_eq!(describe(b""), None);
+        assert_eq!(describe(b"hello world"), None);
+        assert_eq!(describe(b"PK"), None);
+    }
+
+    #[test]
+    fn previews_count_rows_and_size_offsets() {
+        let dir = TempDir::new();
+        let path = dir.write("data.bin", (0..40u8).collect::<Vec<_>>());
+        let mut hex = HexPreview::open(&path).unwrap();
+        assert_eq!(hex.len(), 40);
+        assert!(!hex.is_empty());
+        assert_eq!(hex.summary(), None);
+        assert_eq!(hex.row_count(), 3);
+        assert_eq!(hex.offset_width(), 8);
+
+        let last = hex.row(2).unwrap();
+        assert_eq!(last.offset, 32);
+        assert_eq!(last.bytes, (32..40).collect::<Vec<u8>>());
+        assert_eq!(hex.row(3), None);
+        assert_eq!(hex.row(u64::MAX), None);
+
+        let empty = HexPreview::open(&dir.write("empty", "")).unwrap();
+        assert!(empty.is_empty());
+        assert_eq!(empty.row_count(), 0);
+
+        let big = dir.write("big", "");
+        File::options()
+            .write(true)
+            .open(&big)
+            .unwrap()
+            .set_len(1 << 36)
+            .unwrap();
+        assert_eq!(HexPreview::open(&big).unwrap().offset_width(), 10);
+    }
+
+    #[test]
+    fn previews_summarise_known_formats() {
+        let dir = TempDir::new();
+        let path = dir.write("doc.pdf", "%PDF-1.4\n");
+        let hex = HexPreview::open(&path).unwrap();
+        assert_eq!(hex.summary(), Some("PDF document, version 1.4"));
+    }
+
+    #[test]
+    fn rows_are_read_across_pages() {
+        let dir = TempDir::new();
+        let pages = MAX_PAGES as u64 + 2;
+        let data: Vec<u8> = (0..PAGE_SIZE * pages).map(|i| (i % 251) as u8).collect();
+        let path = dir.write("big.bin", &data);
+        let mut hex = HexPreview::open(&path).unwrap();
+
+        let rows_per_page = PAGE_SIZE / BYTES_PER_ROW as u64;
+        for ix in (0..pages).map(|page| page * rows_per_page + 1).chain([1]) {
+            let row = hex.row(ix).unwrap();
+            let start = row.offset as usize;
+            assert_eq!(row.bytes, &data[start..start + BYTES_PER_ROW]);
+        }
+        assert!(hex.pages.len() <= MAX_PAGES);
+        assert_eq!(hex.pages.back().map(|(ix, _)| *ix), Some(0));
+    }
+
+    #[test]
+    fn rows_truncated_away_are_missing() {
+        let dir = TempDir::new();
+        let path = dir.write("shrinks.bin", [7u8; 64]);
+        let mut hex = HexPreview::open(&path).unwrap();
+        File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(20)
+            .unwrap();
+        assert_eq!(hex.row_count(), 4);
+        assert_eq!(hex.row(0).unwrap().bytes, [7; 16]);
+        assert_eq!(hex.row(1).unwrap().bytes, [7; 4]);
+        assert_eq!(hex.row(2), None);
+    }
+}
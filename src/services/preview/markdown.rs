@@ -0,0 +1,687 @@
+//! Rendered previews of Markdown files.
+//!
+//! The file is parsed with comrak, with the GitHub extensions for tables, task lists,
+//! strikethrough, autolinks and footnotes, into a [`MarkdownDocument`]: a tree of blocks
+//! whose text carries styled byte ranges. It holds no UI types, so the preview pane only
+//! has to lay it out. Fenced code blocks become [code previews](super::code), highlighted
+//! as they are shown rather than while parsing, and relative image paths are resolved
+//! against the file's directory.
+
+use super::code::{CodeOptions, CodePreview};
+use comrak::nodes::{AstNode, ListType, NodeValue, TableAlignment};
+use comrak::{parse_document, Arena, Options};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// Extensions previewed as Markdown rather than as source.
+pub const EXTENSIONS: &[&str] = &["md", "markdown", "mdown", "mkd", "mkdn"];
+
+/// Whether `path` names a Markdown file.
+pub fn is_markdown(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| EXTENSIONS.iter().any(|e| ext.eq_ignore_ascii_case(e)))
+}
+
+/// How a run of text is shown. The default is plain body text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextStyle {
+    pub bold: bool,
+    pub italic: bool,
+    pub strikethrough: bool,
+    /// Inline code.
+    pub code: bool,
+    /// Target of the link the text is part of.
+    pub link: Option<String>,
+}
+
+/// Text with styled byte ranges. The ranges are sorted and do not overlap; text outside
+/// them is plain.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RichText {
+    pub text: String,
+    pub styles: Vec<(Range<usize>, TextStyle)>,
+}
+
+impl RichText {
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    fn push(&mut self, text: &str, style: &TextStyle) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        if *style != TextStyle::default() && !text.is_empty() {
+            self.styles.push((start..self.text.len(), style.clone()));
+        }
+    }
+
+    /// The part from byte `range.start` to `range.end`, with its styles.
+    fn slice(&self, range: Range<usize>) -> RichText {
+        let styles = self
+            .styles
+            .iter()
+            .filter(|(r, _)| r.start < range.end && r.end > range.start)
+            .map(|(r, style)| {
+                let start = r.start.max(range.start) - range.start;
+                let end = r.end.min(range.end) - range.start;
+                (start..end, style.clone())
+            })
+            .collect();
+        RichText {
+            text: self.text[range].to_string(),
+            styles,
+        }
+    }
+
+    /// Without leading and trailing whitespace, which soft breaks around images leave.
+    fn trimmed(&self) -> RichText {
+        let start = self.text.len() - self.text.trim_start().len();
+        let end = self.text.trim_end().len().max(start);
+        self.slice(start..end)
+    }
+}
+
+/// Where an image is loaded from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImageSource {
+    File(PathBuf),
+    /// A remote or `data:` URL, left as written. The preview does not load these.
+    Url(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug)]
+pub struct ListItem {
+    /// `Some(checked)` for a task list item.
+    pub task: Option<bool>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug)]
+pub enum Block {
+    Heading {
+        /// 1 to 6.
+        level: u8,
+        text: RichText,
+    },
+    Paragraph(RichText),
+    /// An image on its own; images within other text are split out into these.
+    Image {
+        source: ImageSource,
+        alt: String,
+    },
+    Code {
+        /// The language as written after the fence, if any.
+        language: Option<String>,
+        /// Highlighted as its lines are asked for.
+        code: Box<CodePreview>,
+    },
+    Quote(Vec<Block>),
+    List {
+        /// The first item's number for an ordered list.
+        start: Option<usize>,
+        items: Vec<ListItem>,
+    },
+    Table {
+        alignments: Vec<Alignment>,
+        header: Vec<RichText>,
+        rows: Vec<Vec<RichText>>,
+    },
+    Rule,
+    /// Raw HTML, which is shown as written.
+    Html(String),
+    /// A footnote's text. Footnotes come last, numbered in order of first reference, the
+    /// number being what their references show.
+    Footnote {
+        number: usize,
+        blocks: Vec<Block>,
+    },
+}
+
+/// A Markdown file parsed for display.
+#[derive(Debug, Default)]
+pub struct MarkdownDocument {
+    pub blocks: Vec<Block>,
+}
+
+impl MarkdownDocument {
+    /// Parse `text`, resolving relative image paths against `base_dir`, the directory
+    /// of the file. Code blocks will be highlighted with the theme in `options`.
+    pub fn parse(text: &str, base_dir: &Path, options: &CodeOptions) -> Self {
+        let arena = Arena::new();
+        let root = parse_document(&arena, text, &parse_options());
+        let mut converter = Converter {
+            base_dir,
+            options,
+            footnotes: 0,
+        };
+        let blocks = converter.blocks(root);
+        Self { blocks }
+    }
+}
+
+fn parse_options() -> Options {
+    let mut options = Options::default();
+    options.extension.strikethrough = true;
+    options.extension.table = true;
+    options.extension.autolink = true;
+    options.extension.tasklist = true;
+    options.extension.footnotes = true;
+    options
+}
+
+struct Converter<'a> {
+    base_dir: &'a Path,
+    options: &'a CodeOptions,
+    /// Footnotes seen so far. comrak moves them to the end in order of first reference.
+    footnotes: usize,
+}
+
+impl Converter<'_> {
+    fn blocks<'n>(&mut self, parent: &'n AstNode<'n>) -> Vec<Block> {
+        let mut blocks = Vec::new();
+        for node in parent.children() {
+            self.block(node, &mut blocks);
+        }
+        blocks
+    }
+
+    fn block<'n>(&mut self, node: &'n AstNode<'n>, out: &mut Vec<Block>) {
+        let value = node.data.borrow().value.clone();
+        match value {
+            NodeValue::Heading(heading) => out.push(Block::Heading {
+                level: heading.level,
+                text: self.text(node),
+            }),
+            NodeValue::Paragraph => self.paragraph(node, out),
+            NodeValue::CodeBlock(code) => {
+                let language = code
+                    .info
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .next()
+                    .filter(|token| !token.is_empty())
+                    .map(str::to_string);
+                let code = Box::new(CodePreview::with_language(
+                    code.literal.trim_end_matches('\n').to_string(),
+                    language.as_deref().unwrap_or_default(),
+                    self.options,
+                ));
+                out.push(Block::Code { language, code });
+            }
+            NodeValue::BlockQuote | NodeValue::MultilineBlockQuote(_) => {
+                out.push(Block::Quote(self.blocks(node)))
+            }
+            NodeValue::List(list) => {
+                let start = (list.list_type == ListType::Ordered).then_some(list.start);
+                let items = node
+                    .children()
+                    .map(|item| {
+                        let task = match &item.data.borrow().value {
+                            NodeValue::TaskItem(symbol) => Some(symbol.is_some()),
+                            _ => None,
+                        };
+                        ListItem {
+                            task,
+                            blocks: self.blocks(item),
+                        }
+                    })
+                    .collect();
+                out.push(Block::List { start, items });
+            }
+            NodeValue::Table(table) => {
+                let mut rows = node.children().map(|row| {
+                    row.children()
+                        .map(|cell| self.text(cell))
+                        .collect::<Vec<_>>()
+                });
+                let header = rows.next().unwrap_or_default();
+                out.push(Block::Table {
+                    alignments: table.alignments.iter().map(alignment).collect(),
+                    header,
+                    rows: rows.collect(),
+                });
+            }
+            NodeValue::ThematicBreak => out.push(Block::Rule),
+            NodeValue::HtmlBlock(html) => {
+                out.push(Block::Html(html.literal.trim_end().to_string()))
+            }
+            NodeValue::FootnoteDefinition(_) => {
+                self.footnotes += 1;
+                let number = self.footnotes;
+                out.push(Block::Footnote {
+                    number,
+                    blocks: self.blocks(node),
+                });
+            }
+            // Front matter is metadata, not content.
+            NodeValue::FrontMatter(_) => {}
+            _ => out.extend(self.blocks(node)),
+        }
+    }
+
+    /// A paragraph, split around the images in it.
+    fn paragraph<'n>(&mut self, node: &'n AstNode<'n>, out: &mut Vec<Block>) {
+        let mut inlines = Inlines::new(self.base_dir, true);
+        inlines.children(node, &TextStyle::default());
+        let mut start = 0;
+        for (at, source, alt) in inlines.images {
+            let text = inlines.text.slice(start..at).trimmed();
+            if !text.is_empty() {
+                out.push(Block::Paragraph(text));
+            }
+            out.push(Block::Image { source, alt });
+            start = at;
+        }
+        let text = inlines.text.slice(start..inlines.text.text.len()).trimmed();
+        if !text.is_empty() {
+            out.push(Block::Paragraph(text));
+        }
+    }
+
+    /// The text of `node`'s inlines, with images replaced by their descriptions.
+    fn text<'n>(&self, node: &'n AstNode<'n>) -> RichText {
+        let mut inlines = Inlines::new(self.base_dir, false);
+        inlines.children(node, &TextStyle::default());
+        inlines.text
+    }
+}
+
+/// Collects a run of inline nodes into one [`RichText`].
+struct Inlines<'a> {
+    base_dir: &'a Path,
+    text: RichText,
+    /// Images found, with the offset in `text` they were at, if they are collected
+    /// rather than replaced by their description.
+    images: Vec<(usize, ImageSource, String)>,
+    split_images: bool,
+}
+
+impl<'a> Inlines<'a> {
+    fn new(base_dir: &'a Path, split_images: bool) -> Self {
+        Self {
+            base_dir,
+            text: RichText::default(),
+            images: Vec::new(),
+            split_images,
+        }
+    }
+
+    fn children<'n>(&mut self, node: &'n AstNode<'n>, style: &TextStyle) {
+        for child in node.children() {
+            self.inline(child, style);
+        }
+    }
+
+    fn inline<'n>(&mut self, node: &'n AstNode<'n>, style: &TextStyle) {
+        let value = node.data.borrow().value.clone();
+        match value {
+            NodeValue::Text(text) => self.text.push(&text, style),
+            NodeValue::SoftBreak => self.text.push(" ", style),
+            NodeValue::LineBreak => self.text.push("\n", style),
+            NodeValue::Code(code) => {
+                let style = TextStyle {
+                    code: true,
+                    ..style.clone()
+                };
+                self.text.push(&code.literal, &style);
+            }
+            NodeValue::HtmlInline(html) => self.text.push(&html, style),
+            NodeValue::Emph => self.children(
+                node,
+                &TextStyle {
+                    italic: true,
+                    ..style.clone()
+                },
+            ),
+            NodeValue::Strong => self.children(
+                node,
+                &TextStyle {
+                    bold: true,
+                    ..style.clone()
+                },
+            ),
+            NodeValue::Strikethrough => self.children(
+                node,
+                &TextStyle {
+                    strikethrough: true,
+                    ..style.clone()
+                },
+            ),
+            NodeValue::Link(link) => self.children(
+                node,
+                &TextStyle {
+                    link: Some(link.url),
+                    ..style.clone()
+                },
+            ),
+            NodeValue::Image(link) => {
+                let mut alt = Inlines::new(self.base_dir, false);
+                alt.children(node, &TextStyle::default());
+                if self.split_images {
+                    let source = image_source(&link.url, self.base_dir);
+                    self.images
+                        .push((self.text.text.len(), source, alt.text.text));
+                } else {
+                    self.text.push(&alt.text.text, style);
+                }
+            }
+            NodeValue::FootnoteReference(footnote) => {
+                let style = TextStyle {
+                    link: Some(format!("#fn-{}", footnote.name)),
+                    ..style.clone()
+                };
+                self.text.push(&format!("[{}]", footnote.ix), &style);
+            }
+            _ => self.children(node, style),
+        }
+    }
+}
+
+fn alignment(alignment: &TableAlignment) -> Alignment {
+    match alignment {
+        TableAlignment::None => Alignment::None,
+        TableAlignment::Left => Alignment::Left,
+        TableAlignment::Center => Alignment::Center,
+        TableAlignment::Right => Alignment::Right,
+    }
+}
+
+/// Resolve an image `url` as written in a file in `base_dir`.
+fn image_source(url: &str, base_dir: &Path) -> ImageSource {
+    if let Some(path) = url.strip_prefix("file://") {
+        return ImageSource::File(PathBuf::from(percent_decode(path)));
+    }
+    let has_scheme = url
+        .split_once(':')
+        .is_some_and(|(scheme, _)| scheme.len() > 1 && scheme.chars().all(is_scheme_char));
+    if has_scheme {
+        return ImageSource::Url(url.to_string());
+    }
+    let path = url.split(['?', '#']).next().unwrap_or_default();
+    ImageSource::File(base_dir.join(percent_decode(path)))
+}
+
+fn is_scheme_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')
+}
+
+/// Decode `%XX` escapes, as in `my%20image.png`. Invalid escapes are kept as written.
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match escaped {
+            Some(byte) => {
+                out.push(byte);
+                i += 3;
+            }
+            None => {
+                out.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(out).unwrap_or_else(|_| s.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Vec<Block> {
+        MarkdownDocument::parse(text, Path::new("/docs"), &CodeOptions::default()).blocks
+    }
+
+    fn paragraph(block: &Block) -> &RichText {
+        match block {
+            Block::Paragraph(text) => text,
+            other => panic!("expected a paragraph, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn markdown_files_are_recognised_by_extension() {
+        assert!(is_markdown(Path::new("README.md")));
+        assert!(is_markdown(Path::new("notes.MARKDOWN")));
+        assert!(!is_markdown(Path::new("main.rs")));
+        assert!(!is_markdown(Path::new("md")));
+    }
+
+    #[test]
+    fn inline_styles_become_ranges() {
+        let blocks = parse("plain **bold** *it* ~~gone~~ `code` [link](https://x.org)");
+        let text = paragraph(&blocks[0]);
+        assert_eq!(text.text, "plain bold it gone code link");
+        let styled: Vec<_> = text
+            .styles
+            .iter()
+            .map(|(range, style)| (&text.text[range.clone()], style.clone()))
+            .collect();
+        let with = |f: fn(&mut TextStyle)| {
+            let mut style = TextStyle::default();
+            f(&mut style);
+            style
+        };
+        assert_eq!(
+            styled,
+            [
+                ("bold", with(|s| s.bold = true)),
+                ("it", with(|s| s.italic = true)),
+                ("gone", with(|s| s.strikethrough = true)),
+                ("code", with(|s| s.code = true)),
+                ("link", with(|s| s.link = Some("https://x.org".into()))),
+            ]
+        );
+    }
+
+    #[test]
+    fn headings_rules_and_quotes() {
+        let blocks = parse("## Title\n\n---\n\n> quoted\n");
+        assert!(matches!(&blocks[0], Block::Heading { level: 2, text } if text.text == "Title"));
+        assert!(matches!(blocks[1], Block::Rule));
+        match &blocks[2] {
+            Block::Quote(inner) => assert_eq!(paragraph(&inner[0]).text, "quoted"),
+            other => panic!("expected a quote, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn tables_keep_alignment_header_and_rows() {
+        let blocks = parse("| a | b | c | d |\n|---|:--|:-:|--:|\n| 1 | 2 | 3 | **4** |\n");
+        let Block::Table {
+            alignments,
+            header,
+            rows,
+        } = &blocks[0]
+        else {
+            panic!("expected a table, got {:?}", blocks[0]);
+        };
+        assert_eq!(
+            alignments,
+            &[
+                Alignment::None,
+                Alignment::Left,
+                Alignment::Center,
+                Alignment::Right
+            ]
+        );
+        let texts = |cells: &[RichText]| cells.iter().map(|c| c.text.clone()).collect::<Vec<_>>();
+        assert_eq!(texts(header), ["a", "b", "c", "d"]);
+        assert_eq!(rows.len(), 1);
+        assert_eq!(texts(&rows[0]), ["1", "2", "3", "4"]);
+        assert!(rows[0][3].styles[0].1.bold);
+    }
+
+    #[test]
+    fn lists_record_their_start_and_tasks() {
+        let blocks = parse("3. three\n4. four\n\n- [ ] todo\n- [x] done\n- plain\n");
+        match &blocks[0] {
+            Block::List { start, items } => {
+                assert_eq!(*start, Some(3));
+                assert_eq!(items.len(), 2);
+                assert_eq!(items[0].task, None);
+            }
+            other => panic!("expected a list, got {other:?}"),
+        }
+        match &blocks[1] {
+            Block::List { start, items } => {
+                assert_eq!(*start, None);
+                let tasks: Vec<_> = items.iter().map(|item| item.task).collect();
+                assert_eq!(tasks, [Some(false), Some(true), None]);
+                assert_eq!(paragraph(&items[1].blocks[0]).text, "done");
+            }
+            other => panic!("expected a list, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn footnotes_are_numbered_by_first_reference() {
+        let blocks = parse("See[^b] and[^a], again[^b].\n\n[^a]: Apple.\n[^b]: Banana.\n");
+        let text = paragraph(&blocks[0]);
+        assert_eq!(text.text, "See[1] and[2], again[1].");
+        let links: Vec<_> = text
+            .styles
+            .iter()
+            .map(|(_, style)| style.link.as_deref().unwrap())
+            .collect();
+        assert_eq!(links, ["#fn-b", "#fn-a", "#fn-b"]);
+
+        let notes: Vec<_> = blocks[1..]
+            .iter()
+            .map(|block| match block {
+                Block::Footnote { number, blocks } => (*number, paragraph(&blocks[0]).text.clone()),
+                other => panic!("expected a footnote, got {other:?}"),
+            })
+            .collect();
+        assert_eq!(notes, [(1, "Banana.".into()), (2, "Apple.".into())]);
+    }
+
+    #[test]
+    fn code_blocks_are_highlighted_by_their_language() {
+        let mut blocks =
+            parse("```rust,ignore\nfn main() {}\nlet x = 1;\n```\n\n```\nplain\n```\n");
+        match &mut blocks[0] {
+            Block::Code { language, code } => {
+                assert_eq!(language.as_deref(), Some("rust"));
+                assert_eq!(code.language(), Some("Rust"));
+                assert_eq!(code.line_count(), 2);
+                let (text, spans) = code.line(0).unwrap();
+                assert_eq!(text, "fn main() {}");
+                assert!(spans.len() > 1);
+                assert_eq!(code.line(1).unwrap().0, "let x = 1;");
+            }
+            other => panic!("expected code, got {other:?}"),
+        }
+        assert!(matches!(
+            &blocks[1],
+            Block::Code { language: None, code } if code.line_count() == 1 && code.language().is_none()
+        ));
+    }
+
+    #[test]
+    fn images_are_split_out_of_paragraphs() {
+        let blocks = parse("before ![a *cat*](cat%20one.png) after\n\n# ![logo](l.png) Name\n");
+        assert_eq!(paragraph(&blocks[0]).text, "before");
+        match &blocks[1] {
+            Block::Image { source, alt } => {
+                assert_eq!(
+                    source,
+                    &ImageSource::File(PathBuf::from("/docs/cat one.png"))
+                );
+                assert_eq!(alt, "a cat");
+            }
+            other => panic!("expected an image, got {other:?}"),
+        }
+        assert_eq!(paragraph(&blocks[2]).text, "after");
+        assert!(matches!(&blocks[3], Block::Heading { text, .. } if text.text == "logo Name"));
+    }
+
+    #[test]
+    fn front_matter_and_html_blocks() {
+        let mut options = parse_options();
+        options.extension.front_matter_delimiter = Some("---".into());
+        let arena = Arena::new();
+        let root = parse_document(&arena, "---\ntitle: x\n---\n<div>\nhi\n</div>\n", &options);
+        let mut converter = Converter {
+            base_dir: Path::new("/"),
+            options: &CodeOptions::default(),
+            footnotes: 0,
+        };
+        let blocks = converter.blocks(root);
+        assert!(matches!(&blocks[..], [Block::Html(html)] if html == "<div>\nhi\n</div>"));
+    }
+
+    #[test]
+    fn image_sources_resolve_relative_paths_and_keep_urls() {
+        let base = Path::new("/docs");
+        let file = |p: &str| ImageSource::File(PathBuf::from(p));
+        let url = |u: &str| ImageSource::Url(u.into());
+        assert_eq!(image_source("img/a.png", base), file("/docs/img/a.png"));
+        assert_eq!(
+            image_source("a%20b.png?raw=1#x", base),
+            file("/docs/a b.png")
+        );
+        assert_eq!(image_source("/abs/a.png", base), file("/abs/a.png"));
+        assert_eq!(
+            image_source("file:///tmp/a%23.png", base),
+            file("/tmp/a#.png")
+        );
+        assert_eq!(
+            image_source("https://x.org/a.png", base),
+            url("https://x.org/a.png")
+        );
+        assert_eq!(
+            image_source("data:image/png;base64,AA", base),
+            url("data:image/png;base64,AA")
+        );
+        // A one-letter scheme is a Windows drive.
+        assert_eq!(image_source("C:/a.png", base), file("/docs/C:/a.png"));
+    }
+
+    #[test]
+    fn percent_escapes_are_decoded_only_when_valid() {
+        assert_eq!(percent_decode("a%20b%2Fc"), "a b/c");
+        assert_eq!(percent_decode("%e2%9c%93"), "✓");
+        assert_eq!(percent_decode("100%"), "100%");
+        assert_eq!(percent_decode("%zz%4"), "%zz%4");
+        assert_eq!(percent_decode("%+1%-1"), "%+1%-1");
+        assert_eq!(percent_decode("%é"), "%é");
+        // Escapes that do not make UTF-8 leave the text as written.
+        assert_eq!(percent_decode("%ff"), "%ff");
+    }
+
+    #[test]
+    fn rich_text_slices_and_trims_with_styles() {
+        let mut text = RichText::default();
+        let bold = TextStyle {
+            bold: true,
+            ..TextStyle::default()
+        };
+        text.push("  ab", &TextStyle::default());
+        text.push("cd", &bold);
+        text.push("", &bold);
+        text.push("ef  ", &TextStyle::default());
+        assert_eq!(text.styles.len(), 1);
+        let trimmed = text.trimmed();
+        assert_eq!(trimmed.text, "abcdef");
+        assert_eq!(trimmed.styles, [(2..4, bold.clone())]);
+        let slice = text.slice(5..8);
+        assert_eq!(slice.text, "def");
+        assert_eq!(slice.styles, [(0..1, bold)]);
+        assert!(RichText::default().trimmed().is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+//! File previews for the explorer's preview pane.
+
+pub mod code;
+pub mod hex;
+pub mod markdown;
+
+use crate::core::errors::{Error, Result};
+use crate::services::search::content::looks_binary;
+use code::{CodeOptions, CodePreview};
+use hex::HexPreview;
+use markdown::MarkdownDocument;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// Largest part of a file previewed as text.
+pub const MAX_TEXT_SIZE: u64 = 2 * 1024 * 1024;
+
+/// A file's text as previewed.
+#[derive(Debug)]
+pub struct Text {
+    pub text: String,
+    /// The file is larger than [`MAX_TEXT_SIZE`] and only its start is shown.
+    pub truncated: bool,
+}
+
+/// The contents of `path` as text, or `None` if it is not a regular file, looks binary
+/// or is not UTF-8. Larger files are cut at [`MAX_TEXT_SIZE`], dropping a character the
+/// cut splits.
+pub fn read_text(path: &Path) -> Result<Option<Text>> {
+    let md = fs::metadata(path).map_err(|e| Error::from_io(path, e))?;
+    if !md.is_file() {
+        return Ok(None);
+    }
+    let mut bytes = Vec::new();
+    File::open(path)
+        .and_then(|file| file.take(MAX_TEXT_SIZE + 1).read_to_end(&mut bytes))
+        .map_err(|e| Error::from_io(path, e))?;
+    if looks_binary(&bytes) {
+        return Ok(None);
+    }
+    let truncated = bytes.len() as u64 > MAX_TEXT_SIZE;
+    bytes.truncate(MAX_TEXT_SIZE as usize);
+    let text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) if truncated && err.utf8_error().error_len().is_none() => {
+            let valid = err.utf8_error().valid_up_to();
+            let mut bytes = err.into_bytes();
+            bytes.truncate(valid);
+            String::from_utf8(bytes).expect("valid up to the cut")
+        }
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(Text { text, truncated }))
+}
+
+/// A file made ready for the preview pane.
+pub enum Preview {
+    Text {
+        code: Box<CodePreview>,
+        /// Also set for Markdown files, which are shown rendered.
+        markdown: Option<MarkdownDocument>,
+        /// Only the start of the file was read.
+        truncated: bool,
+    },
+    /// A regular file that is not text.
+    Hex(HexPreview),
+    /// Not a regular file.
+    Unavailable,
+}
+
+/// Read `path` and prepare it for the preview pane. Reads up to [`MAX_TEXT_SIZE`] and
+/// the first highlighted file loads the syntax definitions, so this is slow enough to
+/// keep off the UI thread.
+pub fn load(path: &Path, options: &CodeOptions) -> Result<Preview> {
+    match read_text(path)? {
+        Some(Text { text, truncated }) => {
+            let markdown = markdown::is_markdown(path).then(|| {
+                let base_dir = path.parent().unwrap_or(Path::new(""));
+                MarkdownDocument::parse(&text, base_dir, options)
+            });
+            Ok(Preview::Text {
+                code: Box::new(CodePreview::new(text, path, options)),
+                markdown,
+                truncated,
+            })
+        }
+        None if path.is_file() => Ok(Preview::Hex(HexPreview::open(path)?)),
+        None => Ok(Preview::Unavailable),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use std::path::PathBuf;
+
+    #[test]
+    fn reads_text_and_rejects_binary() {
+        let tmp = TempDir::new();
+        let text = read_text(&tmp.write("a.txt", "hello\n")).unwrap().unwrap();
+        assert_eq!(text.text, "hello\n");
+        assert!(!text.truncated);
+        assert!(read_text(&tmp.write("b.bin", "a\0b")).unwrap().is_none());
+        assert!(read_text(tmp.path()).unwrap().is_none());
+    }
+
+    #[test]
+    fn cuts_large_text_at_a_character_boundary() {
+        let tmp = TempDir::new();
+        // "é" is two bytes, so the cut falls inside one.
+        let data = format!("x{}", "é".repeat(MAX_TEXT_SIZE as usize));
+        let text = read_text(&tmp.write("big.txt", &data)).unwrap().unwrap();
+        assert!(text.truncated);
+        assert_eq!(text.text.len(), MAX_TEXT_SIZE as usize - 1);
+        assert!(data.starts_with(&text.text));
+    }
+
+    #[test]
+    fn loads_previews_by_kind_on_another_thread() {
+        let tmp = TempDir::new();
+        let load = |path: PathBuf| {
+            std::thread::spawn(move || load(&path, &CodeOptions::default()))
+                .join()
+                .unwrap()
+        };
+        match load(tmp.write("a.md", "# Title\n")).unwrap() {
+            Preview::Text {
+                code,
+                markdown,
+                truncated,
+            } => {
+                assert_eq!(code.line_count(), 1);
+                assert!(markdown.is_some());
+                assert!(!truncated);
+            }
+            _ => panic!("expected text"),
+        }
+        match load(tmp.write("a.rs", "fn main() {}\n")).unwrap() {
+            Preview::Text { markdown, .. } => assert!(markdown.is_none()),
+            _ => panic!("expected text"),
+        }
+        assert!(matches!(
+            load(tmp.write("b.bin", "a\0b")).unwrap(),
+            Preview::Hex(_)
+        ));
+        assert!(matches!(
+            load(tmp.path().to_path_buf()).unwrap(),
+            Preview::Unavailable
+        ));
+        assert!(load(tmp.path().join("missing")).is_err());
+    }
+}
@@ -0,0 +1,623 @@
+//! Full-text search of file contents below a root.
+//!
+//! One thread walks the tree (honoring ignore files by default) and hands files to a pool
+//! of workers, which skip binary files, match line by line and send every file with
+//! matches as one `ContentSearchEvent::File`, with the surrounding lines for context.
+//! Files are reported in the order workers finish them, not in walk order.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::services::fs::walk::{walk, WalkOptions};
+use regex::bytes::{Regex, RegexBuilder};
+use std::fs;
+use std::io::Read;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Bytes looked at to tell binary files apart.
+const BINARY_SNIFF_LEN: usize = 8192;
+/// Files waiting for a worker; the walk pauses once this many are queued.
+const QUEUE_LEN: usize = 256;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PatternKind {
+    /// The pattern is matched as typed.
+    #[default]
+    Literal,
+    /// The pattern is a regular expression in `regex` crate syntax.
+    Regex,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    /// Case-insensitive unless the pattern contains an uppercase letter.
+    #[default]
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ContentQuery {
+    pub pattern: String,
+    pub kind: PatternKind,
+    pub case: CaseMode,
+    /// Only match whole words.
+    pub whole_word: bool,
+}
+
+impl ContentQuery {
+    /// Compile the query. Fails with `Error::InvalidPattern` for a bad regex.
+    pub fn compile(&self) -> Result<Regex> {
+        let mut pattern = match self.kind {
+            PatternKind::Literal => regex::escape(&self.pattern),
+            PatternKind::Regex => self.pattern.clone(),
+        };
+        if self.whole_word {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+        let insensitive = match self.case {
+            CaseMode::Smart => !self.pattern.chars().any(char::is_uppercase),
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(insensitive)
+            // Matches never span lines, so `$` and `^` refer to the line.
+            .multi_line(true)
+            .build()
+            .map_err(|e| Error::InvalidPattern(e.to_string()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ContentSearchParams {
+    pub root: PathBuf,
+    pub query: ContentQuery,
+    /// What to walk. Ignore files are honored by default.
+    pub walk: WalkOptions,
+    /// Lines of context before and after each matching line.
+    pub context: usize,
+    /// Larger files are skipped.
+    pub max_file_size: u64,
+    /// Matching lines longer than this many characters are cut down to a window around
+    /// the first match.
+    pub max_line_len: usize,
+    /// Worker threads; 0 picks one per CPU.
+    pub threads: usize,
+}
+
+impl Default for ContentSearchParams {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::new(),
+            query: ContentQuery::default(),
+            walk: WalkOptions {
+                use_ignore_files: true,
+                ..Default::default()
+            },
+            context: 2,
+            max_file_size: 32 * 1024 * 1024,
+            max_line_len: 400,
+            threads: 0,
+        }
+    }
+}
+
+/// One matching line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineMatch {
+    /// 1-based.
+    pub line_number: u64,
+    /// 1-based character column of the first match.
+    pub column: usize,
+    /// The line without its terminator. Lossy when it is not valid UTF-8.
+    pub line: String,
+    /// Byte ranges of the matches within `line`.
+    pub ranges: Vec<Range<usize>>,
+    /// Up to `context` lines before and after, nearest last and first respectively.
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMatches {
+    pub path: PathBuf,
+    pub matches: Vec<LineMatch>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentSearchStats {
+    pub files_searched: u64,
+    pub files_matched: u64,
+    pub lines_matched: u64,
+    /// Files skipped because they look binary or are too large.
+    pub files_skipped: u64,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug)]
+pub enum ContentSearchEvent {
+    File(FileMatches),
+    /// A file or directory could not be read; the search continues.
+    EntryError(Error),
+    /// Every file has been searched; no further events follow.
+    Done(ContentSearchStats),
+}
+
+/// Receiving end of a content search. Dropping it cancels the search, so a UI can start
+/// a new one on every edit of the query.
+pub struct ContentSearch {
+    rx: Receiver<ContentSearchEvent>,
+    cancelled: CancelToken,
+}
+
+impl ContentSearch {
+    /// Non-blocking poll for UI loops. `Disconnected` means the search has ended.
+    pub fn try_next(&self) -> std::result::Result<ContentSearchEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for ContentSearch {
+    type Item = ContentSearchEvent;
+
+    fn next(&mut self) -> Option<ContentSearchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for ContentSearch {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    searched: AtomicU64,
+    matched: AtomicU64,
+    lines: AtomicU64,
+    skipped: AtomicU64,
+}
+
+/// Start searching below `params.root`. Fails right away if the query does not compile.
+pub fn search_content(params: ContentSearchParams) -> Result<ContentSearch> {
+    let regex = params.query.compile()?;
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let started = Instant::now();
+        let threads = match params.threads {
+            0 => thread::available_parallelism().map_or(4, |n| n.get()),
+            n => n,
+        };
+        let counters = Arc::new(Counters::default());
+        let (work_tx, work_rx) = mpsc::sync_channel::<PathBuf>(QUEUE_LEN);
+        let work_rx = Arc::new(Mutex::new(work_rx));
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                let work_rx = Arc::clone(&work_rx);
+                let tx = tx.clone();
+                let regex = regex.clone();
+                let params = params.clone();
+                let counters = Arc::clone(&counters);
+                let flag = flag.clone();
+                thread::spawn(move || loop {
+                    let next = work_rx.lock().unwrap_or_else(|e| e.into_inner()).recv();
+                    let Ok(path) = next else { return };
+                    if flag.is_cancelled() {
+                        return;
+                    }
+                    let event = match search_file(&path, &regex, &params) {
+                        Ok(Searched::Skipped) => {
+                            counters.skipped.fetch_add(1, Ordering::Relaxed);
+                            continue;
+                        }
+                        Ok(Searched::Matches(matches)) => {
+                            counters.searched.fetch_add(1, Ordering::Relaxed);
+                            if matches.is_empty() {
+                                continue;
+                            }
+                            counters.matched.fetch_add(1, Ordering::Relaxed);
+                            counters
+                                .lines
+                                .fetch_add(matches.len() as u64, Ordering::Relaxed);
+                            ContentSearchEvent::File(FileMatches { path, matches })
+                        }
+                        Err(err) => ContentSearchEvent::EntryError(err),
+                    };
+                    if tx.send(event).is_err() {
+                        flag.cancel();
+                        return;
+                    }
+                })
+            })
+            .collect();
+        // Only the workers hold the queue now, so the walk stops if they all give up.
+        drop(work_rx);
+
+        feed(&params, &work_tx, &tx, &flag);
+        drop(work_tx);
+        for worker in workers {
+            let _ = worker.join();
+        }
+        if flag.is_cancelled() {
+            return;
+        }
+        let _ = tx.send(ContentSearchEvent::Done(ContentSearchStats {
+            files_searched: counters.searched.load(Ordering::Relaxed),
+            files_matched: counters.matched.load(Ordering::Relaxed),
+            lines_matched: counters.lines.load(Ordering::Relaxed),
+            files_skipped: counters.skipped.load(Ordering::Relaxed),
+            elapsed: started.elapsed(),
+        }));
+    });
+
+    Ok(ContentSearch { rx, cancelled })
+}
+
+/// Walk the tree and queue every file for the workers.
+fn feed(
+    params: &ContentSearchParams,
+    work: &mpsc::SyncSender<PathBuf>,
+    tx: &Sender<ContentSearchEvent>,
+    cancelled: &CancelToken,
+) {
+    for entry in walk(&params.root, &params.walk) {
+        if cancelled.is_cancelled() {
+            return;
+        }
+        match entry {
+            Ok(entry) if entry.is_dir() => {}
+            Ok(entry) => {
+                if work.send(entry.path).is_err() {
+                    return;
+                }
+            }
+            Err(err) => {
+                if tx.send(ContentSearchEvent::EntryError(err)).is_err() {
+                    cancelled.cancel();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+enum Searched {
+    Matches(Vec<LineMatch>),
+    /// Binary, too large or not a regular file.
+    Skipped,
+}
+
+fn search_file(path: &Path, regex: &Regex, params: &ContentSearchParams) -> Result<Searched> {
+    let md = fs::metadata(path).map_err(|e| Error::from_io(path, e))?;
+    if !md.is_file() || md.len() > params.max_file_size {
+        return Ok(Searched::Skipped);
+    }
+    let mut file = fs::File::open(path).map_err(|e| Error::from_io(path, e))?;
+    let mut data = Vec::with_capacity(md.len() as usize);
+    file.read_to_end(&mut data)
+        .map_err(|e| Error::from_io(path, e))?;
+    if looks_binary(&data) {
+        return Ok(Searched::Skipped);
+    }
+    Ok(Searched::Matches(search_bytes(&data, regex, params)))
+}
+
+/// A NUL byte near the start is taken to mean the data is not text.
+pub(crate) fn looks_binary(data: &[u8]) -> bool {
+    data[..data.len().min(BINARY_SNIFF_LEN)].contains(&0)
+}
+
+/// Matching lines of `data`, with context.
+fn search_bytes(data: &[u8], regex: &Regex, params: &ContentSearchParams) -> Vec<LineMatch> {
+    let mut matches = Vec::new();
+    if !regex.is_match(data) {
+        return matches;
+    }
+    let lines: Vec<&[u8]> = data
+        .split(|&b| b == b'\n')
+        .map(|line| line.strip_suffix(b"\r").unwrap_or(line))
+        .collect();
+    // A trailing newline does not start another line.
+    let count = if data.ends_with(b"\n") {
+        lines.len() - 1
+    } else {
+        lines.len()
+    };
+    for (i, &raw) in lines[..count].iter().enumerate() {
+        let found: Vec<Range<usize>> = regex
+            .find_iter(raw)
+            .filter(|m| !m.is_empty())
+            .map(|m| m.range())
+            .collect();
+        if found.is_empty() {
+            continue;
+        }
+        let text = String::from_utf8_lossy(raw);
+        // Map byte offsets in the raw line to offsets in its lossy rendering.
+        let ranges: Vec<Range<usize>> = if text.len() == raw.len() {
+            found
+        } else {
+            found
+                .into_iter()
+                .map(|r| {
+                    String::from_utf8_lossy(&raw[..r.start]).len()
+                        ..String::from_utf8_lossy(&raw[..r.end]).len()
+                })
+                .collect()
+        };
+        let column = text[..ranges[0].start].chars().count() + 1;
+        let (line, ranges) = clip_line(&text, ranges, params.max_line_len);
+        let context = |range: Range<usize>| {
+            lines[range]
+                .iter()
+                .map(|l| clip_context(&String::from_utf8_lossy(l), params.max_line_len))
+                .collect()
+        };
+        matches.push(LineMatch {
+            line_number: i as u64 + 1,
+            column,
+            before: context(i.saturating_sub(params.context)..i),
+            after: context(i + 1..(i + 1 + params.context).min(count)),
+            line,
+            ranges,
+        });
+    }
+    matches
+}
+
+/// Cut an overlong line down to about `max` characters starting a little before its
+/// first match, shifting the match ranges along and dropping those cut off.
+fn clip_line(text: &str, ranges: Vec<Range<usize>>, max: usize) -> (String, Vec<Range<usize>>) {
+    if text.chars().count() <= max {
+        return (text.to_string(), ranges);
+    }
+    let first = ranges.first().map_or(0, |r| r.start);
+    let lead = max / 4;
+    let start = text[..first]
+        .char_indices()
+        .rev()
+        .nth(lead.saturating_sub(1))
+        .map_or(0, |(i, _)| i);
+    let end = text[start..]
+        .char_indices()
+        .nth(max)
+        .map_or(text.len(), |(i, _)| start + i);
+    let ranges = ranges
+        .into_iter()
+        .filter(|r| r.start >= start && r.end <= end)
+        .map(|r| r.start - start..r.end - start)
+        .collect();
+    (text[start..end].to_string(), ranges)
+}
+
+fn clip_context(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn query(pattern: &str) -> ContentQuery {
+        ContentQuery {
+            pattern: pattern.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn is_match(query: ContentQuery, text: &str) -> bool {
+        query.compile().unwrap().is_match(text.as_bytes())
+    }
+
+    fn search(data: &str, query: ContentQuery, params: ContentSearchParams) -> Vec<LineMatch> {
+        search_bytes(data.as_bytes(), &query.compile().unwrap(), &params)
+    }
+
+    #[test]
+    fn literal_patterns_are_escaped() {
+        assert!(is_match(query("a.b"), "xa.by"));
+        assert!(!is_match(query("a.b"), "axb"));
+        let regex = ContentQuery {
+            kind: PatternKind::Regex,
+            ..query("a.b")
+        };
+        assert!(is_match(regex, "axb"));
+    }
+
+    #[test]
+    fn case_modes() {
+        assert!(is_match(query("todo"), "TODO: x"));
+        assert!(!is_match(query("Todo"), "TODO: x"));
+        let sensitive = ContentQuery {
+            case: CaseMode::Sensitive,
+            ..query("todo")
+        };
+        assert!(!is_match(sensitive, "TODO"));
+        let insensitive = ContentQuery {
+            case: CaseMode::Insensitive,
+            ..query("Todo")
+        };
+        assert!(is_match(insensitive, "TODO"));
+    }
+
+    #[test]
+    fn whole_words() {
+        let word = ContentQuery {
+            whole_word: true,
+            ..query("cat")
+        };
+        assert!(is_match(word.clone(), "a cat."));
+        assert!(!is_match(word, "concatenate"));
+        let alternation = ContentQuery {
+            kind: PatternKind::Regex,
+            whole_word: true,
+            ..query("cat|dog")
+        };
+        assert!(!is_match(alternation.clone(), "dogma"));
+        assert!(is_match(alternation, "hot dog"));
+    }
+
+    #[test]
+    fn bad_regex_is_an_invalid_pattern() {
+        let bad = ContentQuery {
+            kind: PatternKind::Regex,
+            ..query("(unclosed")
+        };
+        assert!(matches!(bad.compile(), Err(Error::InvalidPattern(_))));
+        let params = ContentSearchParams {
+            query: bad,
+            ..Default::default()
+        };
+        assert!(matches!(
+            search_content(params),
+            Err(Error::InvalidPattern(_))
+        ));
+    }
+
+    #[test]
+    fn matching_lines_with_context() {
+        let data = "one\ntwo x\nthree\r\nfour x x\nfive\n";
+        let params = ContentSearchParams {
+            context: 1,
+            ..Default::default()
+        };
+        let found = search(data, query("x"), params);
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].line_number, 2);
+        assert_eq!(found[0].column, 5);
+        assert_eq!(found[0].line, "two x");
+        assert_eq!(found[0].ranges.len(), 1);
+        assert_eq!(found[0].ranges[0], 4..5);
+        assert_eq!(found[0].before, ["one"]);
+        assert_eq!(found[0].after, ["three"]);
+        assert_eq!(found[1].line_number, 4);
+        assert_eq!(found[1].ranges, [5..6, 7..8]);
+        assert_eq!(found[1].after, ["five"]);
+    }
+
+    #[test]
+    fn context_stops_at_the_ends() {
+        let found = search("x\nlast", query("x"), ContentSearchParams::default());
+        assert!(found[0].before.is_empty());
+        assert_eq!(found[0].after, ["last"]);
+        let found = search("a\nx\n", query("x"), ContentSearchParams::default());
+        assert_eq!(found[0].after, Vec::<String>::new());
+    }
+
+    #[test]
+    fn empty_matches_are_not_lines() {
+        let anything = ContentQuery {
+            kind: PatternKind::Regex,
+            ..query("z*")
+        };
+        let found = search("abc\nzz\n", anything, ContentSearchParams::default());
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].line_number, 2);
+    }
+
+    #[test]
+    fn columns_count_characters_and_ranges_follow_lossy_text() {
+        let found = search("héllo x", query("x"), ContentSearchParams::default());
+        assert_eq!(found[0].column, 7);
+        assert_eq!(found[0].ranges.len(), 1);
+        assert_eq!(found[0].ranges[0], 7..8);
+
+        let data = b"a\xffb x";
+        let found = search_bytes(
+            data,
+            &query("x").compile().unwrap(),
+            &ContentSearchParams::default(),
+        );
+        assert_eq!(found[0].line, "a\u{fffd}b x");
+        let range = found[0].ranges[0].clone();
+        assert_eq!(&found[0].line[range], "x");
+    }
+
+    #[test]
+    fn long_lines_are_clipped_around_the_match() {
+        let line = format!("{}needle{}", "a".repeat(100), "b".repeat(100));
+        let params = ContentSearchParams {
+            max_line_len: 40,
+            ..Default::default()
+        };
+        let found = search(&line, query("needle"), params);
+        let m = &found[0];
+        assert_eq!(m.column, 101);
+        assert_eq!(m.line.chars().count(), 40);
+        assert_eq!(&m.line[m.ranges[0].clone()], "needle");
+        assert_eq!(m.ranges[0].start, 10);
+    }
+
+    #[test]
+    fn binary_data_is_detected() {
+        assert!(looks_binary(b"ab\0cd"));
+        assert!(!looks_binary(b"plain text"));
+        let mut late = vec![b'a'; BINARY_SNIFF_LEN];
+        late.push(0);
+        assert!(!looks_binary(&late));
+    }
+
+    #[test]
+    fn searches_files_below_the_root() {
+        let tmp = TempDir::new();
+        tmp.write("a.txt", "needle\nhay\n");
+        tmp.write("sub/b.txt", "hay\nNEEDLE needle\n");
+        tmp.write("c.txt", "hay only\n");
+        tmp.write("bin.dat", b"needle\0\x01");
+        tmp.write("ignored/d.txt", "needle\n");
+        tmp.write(".gitignore", "ignored/\n");
+        tmp.write("big.txt", "needle ".repeat(100));
+
+        let params = ContentSearchParams {
+            root: tmp.path().to_path_buf(),
+            query: ContentQuery {
+                case: CaseMode::Sensitive,
+                ..query("needle")
+            },
+            max_file_size: 100,
+            threads: 2,
+            ..Default::default()
+        };
+        let mut files = Vec::new();
+        let mut stats = None;
+        for event in search_content(params).unwrap() {
+            match event {
+                ContentSearchEvent::File(file) => files.push(file),
+                ContentSearchEvent::EntryError(err) => panic!("{err}"),
+                ContentSearchEvent::Done(done) => stats = Some(done),
+            }
+        }
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        let paths: Vec<&Path> = files.iter().map(|f| f.path.as_path()).collect();
+        assert_eq!(
+            paths,
+            [tmp.path().join("a.txt"), tmp.path().join("sub/b.txt")]
+        );
+        assert_eq!(files[1].matches[0].ranges.len(), 1);
+        assert_eq!(files[1].matches[0].ranges[0], 7..13);
+        let stats = stats.unwrap();
+        assert_eq!(stats.files_searched, 4);
+        assert_eq!(stats.files_matched, 2);
+        assert_eq!(stats.lines_matched, 2);
+        assert_eq!(stats.files_skipped, 2);
+    }
+}
@@ -0,0 +1,314 @@
+//! fzf-style fuzzy matching.
+//!
+//! A query matches text when every character of each of its space-separated terms occurs
+//! in the text in order. Among the ways a term can match, the best-scoring alignment is
+//! found with a Smith-Waterman style dynamic program that rewards characters at word
+//! boundaries, after path separators and in consecutive runs, and penalizes gaps. The
+//! matched character positions are returned for highlighting.
+//!
+//! Matching is smart-case: case-insensitive unless the query contains an uppercase letter.
+
+const SCORE_MATCH: i32 = 16;
+const GAP_START: i32 = -3;
+const GAP_EXTENSION: i32 = -1;
+/// Start of the text or after whitespace.
+const BONUS_BOUNDARY_WHITE: i32 = 10;
+/// After a path separator.
+const BONUS_BOUNDARY_DELIMITER: i32 = 9;
+/// After punctuation such as `_`, `-` or `.`.
+const BONUS_BOUNDARY: i32 = 8;
+/// The punctuation itself, which is rarely typed by accident.
+const BONUS_NON_WORD: i32 = 8;
+/// A camelCase hump or the first digit after a letter.
+const BONUS_CAMEL: i32 = 7;
+/// Every character of a run continuing right after the previous match, at least.
+const BONUS_CONSECUTIVE: i32 = -(GAP_START + GAP_EXTENSION);
+/// The first character of a term counts its boundary bonus this many times.
+const FIRST_CHAR_MULTIPLIER: i32 = 2;
+/// Longer texts are matched on their first this many characters only.
+const MAX_TEXT_LEN: usize = 1024;
+
+/// A parsed fuzzy query.
+#[derive(Debug, Clone, Default)]
+pub struct FuzzyQuery {
+    terms: Vec<Vec<char>>,
+    case_sensitive: bool,
+}
+
+/// How a query matched a text.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FuzzyMatch {
+    /// Higher is better. Only comparable between matches of the same query.
+    pub score: i32,
+    /// Character (not byte) indices of the matched characters, ascending.
+    pub positions: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum CharClass {
+    White,
+    Delimiter,
+    NonWord,
+    Lower,
+    Upper,
+    Number,
+}
+
+impl FuzzyQuery {
+    pub fn new(pattern: &str) -> Self {
+        let case_sensitive = pattern.chars().any(char::is_uppercase);
+        let terms = pattern
+            .split_whitespace()
+            .map(|term| term.chars().collect())
+            .collect();
+        Self {
+            terms,
+            case_sensitive,
+        }
+    }
+
+    /// The query has no terms and matches everything.
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /// Any term contains a path separator, so matching against whole paths makes sense.
+    pub fn has_separator(&self) -> bool {
+        self.terms.iter().flatten().any(|&c| c == '/')
+    }
+
+    /// Match `text`. Every term has to match; the scores add up and the positions merge.
+    pub fn matches(&self, text: &str) -> Option<FuzzyMatch> {
+        let original: Vec<char> = text.chars().take(MAX_TEXT_LEN).collect();
+        let folded: Vec<char> = if self.case_sensitive {
+            original.clone()
+        } else {
+            original.iter().map(|&c| fold(c)).collect()
+        };
+        let mut result = FuzzyMatch::default();
+        for term in &self.terms {
+            let m = match_term(term, &folded, &original)?;
+            result.score += m.score;
+            result.positions.extend(m.positions);
+        }
+        result.positions.sort_unstable();
+        result.positions.dedup();
+        Some(result)
+    }
+}
+
+fn fold(c: char) -> char {
+    if c.is_ascii() {
+        c.to_ascii_lowercase()
+    } else {
+        c.to_lowercase().next().unwrap_or(c)
+    }
+}
+
+fn class_of(c: char) -> CharClass {
+    match c {
+        c if c.is_lowercase() => CharClass::Lower,
+        c if c.is_uppercase() => CharClass::Upper,
+        c if c.is_numeric() => CharClass::Number,
+        c if c.is_alphabetic() => CharClass::Lower,
+        c if c.is_whitespace() => CharClass::White,
+        '/' | '\\' => CharClass::Delimiter,
+        _ => CharClass::NonWord,
+    }
+}
+
+/// Bonus for matching a character of class `cur` right after one of class `prev`.
+fn bonus_for(prev: CharClass, cur: CharClass) -> i32 {
+    use CharClass::*;
+    match (prev, cur) {
+        (_, White | Delimiter | NonWord) => BONUS_NON_WORD,
+        (White, _) => BONUS_BOUNDARY_WHITE,
+        (Delimiter, _) => BONUS_BOUNDARY_DELIMITER,
+        (NonWord, _) => BONUS_BOUNDARY,
+        (Lower, Upper) | (Lower | Upper, Number) => BONUS_CAMEL,
+        _ => 0,
+    }
+}
+
+/// Best alignment of `term` in `text`. `original` is `text` before case folding and is
+/// only used to tell word boundaries.
+fn match_term(term: &[char], text: &[char], original: &[char]) -> Option<FuzzyMatch> {
+    let m = term.len();
+    if m == 0 {
+        return Some(FuzzyMatch::default());
+    }
+    // Cheap rejection, and the window the alignment has to lie in: from the first
+    // occurrence of the term's first character to the last of its last one.
+    let mut first = None;
+    let mut pi = 0;
+    for (j, &c) in text.iter().enumerate() {
+        if c == term[pi] {
+            first.get_or_insert(j);
+            pi += 1;
+            if pi == m {
+                break;
+            }
+        }
+    }
+    if pi < m {
+        return None;
+    }
+    let start = first?;
+    let end = text.iter().rposition(|&c| c == term[m - 1])? + 1;
+    let text = &text[start..end];
+    let width = text.len();
+
+    let bonus: Vec<i32> = (0..width)
+        .map(|j| {
+            let at = start + j;
+            let prev = if at == 0 {
+                CharClass::White
+            } else {
+                class_of(original[at - 1])
+            };
+            bonus_for(prev, class_of(original[at]))
+        })
+        .collect();
+
+    // score[i][j]: best score with term[i] matched at text[j]. run[i][j]: the bonus the
+    // consecutive run ending there earns per character. from[i][j]: where term[i - 1]
+    // was matched.
+    const NONE: i32 = i32::MIN / 2;
+    let mut score = vec![NONE; m * width];
+    let mut run = vec![0; m * width];
+    let mut from = vec![0usize; m * width];
+    for i in 0..m {
+        // Best predecessor at least one character back, with the gap already charged.
+        let mut gap_best = NONE;
+        let mut gap_from = 0;
+        for j in i..width {
+            if i > 0 && j >= 2 {
+                let candidate = score[(i - 1) * width + j - 2] + GAP_START;
+                gap_best += GAP_EXTENSION;
+                if candidate >= gap_best {
+                    gap_best = candidate;
+                    gap_from = j - 2;
+                }
+            }
+            if text[j] != term[i] {
+                continue;
+            }
+            let cell = i * width + j;
+            if i == 0 {
+                score[cell] = SCORE_MATCH + bonus[j] * FIRST_CHAR_MULTIPLIER;
+                run[cell] = bonus[j];
+                continue;
+            }
+            let diag = score[(i - 1) * width + j - 1];
+            if diag > NONE {
+                let run_bonus = bonus[j].max(run[cell - width - 1]).max(BONUS_CONSECUTIVE);
+                score[cell] = diag + SCORE_MATCH + run_bonus;
+                run[cell] = run_bonus;
+                from[cell] = j - 1;
+            }
+            if gap_best > NONE && gap_best + SCORE_MATCH + bonus[j] > score[cell] {
+                score[cell] = gap_best + SCORE_MATCH + bonus[j];
+                run[cell] = bonus[j];
+                from[cell] = gap_from;
+            }
+        }
+    }
+
+    let last = (m - 1) * width;
+    let (mut j, &best) = score[last..]
+        .iter()
+        .enumerate()
+        .max_by_key(|&(j, &s)| (s, std::cmp::Reverse(j)))?;
+    if best <= NONE {
+        return None;
+    }
+    let mut positions = vec![0; m];
+    for i in (0..m).rev() {
+        positions[i] = start + j;
+        j = from[i * width + j];
+    }
+    Some(FuzzyMatch {
+        score: best,
+        positions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn positions(query: &str, text: &str) -> Option<Vec<usize>> {
+        FuzzyQuery::new(query).matches(text).map(|m| m.positions)
+    }
+
+    fn score(query: &str, text: &str) -> i32 {
+        FuzzyQuery::new(query).matches(text).unwrap().score
+    }
+
+    #[test]
+    fn empty_query_matches_everything() {
+        let query = FuzzyQuery::new("  ");
+        assert!(query.is_empty());
+        assert_eq!(query.matches("anything"), Some(FuzzyMatch::default()));
+    }
+
+    #[test]
+    fn characters_must_appear_in_order() {
+        assert_eq!(positions("fb", "foo_bar"), Some(vec![0, 4]));
+        assert_eq!(positions("bf", "foo_bar"), None);
+        assert_eq!(positions("fooo", "foo_bar"), None);
+        assert_eq!(positions("x", ""), None);
+    }
+
+    #[test]
+    fn every_term_must_match() {
+        assert_eq!(
+            positions("bar foo", "foo_bar"),
+            Some(vec![0, 1, 2, 4, 5, 6])
+        );
+        assert_eq!(positions("foo baz", "foo_bar"), None);
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(positions("readme", "README.md").is_some());
+        assert!(positions("ReadMe", "readme.md").is_none());
+        assert!(positions("ReadMe", "ReadMe.md").is_some());
+        assert!(positions("école", "École.txt").is_some());
+    }
+
+    #[test]
+    fn positions_are_character_indices() {
+        assert_eq!(positions("r", "übr"), Some(vec![2]));
+    }
+
+    #[test]
+    fn boundaries_and_runs_score_higher() {
+        assert!(score("ml", "my_lib") > score("ml", "mylib"));
+        assert!(score("fb", "src/foo/bar") > score("fb", "srcfoobar"));
+        assert!(score("abc", "abcxx") > score("abc", "axbxc"));
+        assert!(score("b", "aB") > score("b", "ab"));
+        assert!(score("main", "main.rs") > score("main", "domain.rs"));
+    }
+
+    #[test]
+    fn the_best_alignment_is_found() {
+        // The first `b` is mid-word; the later one starts a word.
+        assert_eq!(positions("b", "abc_bcd"), Some(vec![4]));
+        // A whole run at a boundary beats the same characters spread out.
+        assert_eq!(positions("bar", "b_a_r_bar"), Some(vec![6, 7, 8]));
+    }
+
+    #[test]
+    fn separators_in_the_query() {
+        assert!(FuzzyQuery::new("src/ma").has_separator());
+        assert!(!FuzzyQuery::new("main").has_separator());
+    }
+
+    #[test]
+    fn long_texts_are_cut() {
+        let text = format!("{}z", "a".repeat(MAX_TEXT_LEN));
+        assert_eq!(positions("z", &text), None);
+        assert!(positions("a", &text).is_some());
+    }
+}
@@ -0,0 +1,1227 @@
+//! Persistent full-text index of everything below a set of roots.
+//!
+//! Built on tantivy and kept in the data directory. Each file and directory is one
+//! document with its path, name, extension, kind, size and modification time, plus the
+//! text of text files up to [`IndexConfig::max_content_size`]. [`SearchIndex::rebuild`]
+//! walks the configured roots from scratch; after that an [`IndexUpdater`] keeps the
+//! index current from watcher events. Queries combine tantivy's query syntax over names,
+//! paths and contents with metadata filters.
+//!
+//! Every document also lists the directories above it, so a subtree can be searched or
+//! deleted with a single term.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::core::paths;
+use crate::models::file_entry::FileKind;
+use crate::services::frecency::current_scores;
+use crate::services::fs::ignore::{IgnoreRules, Verdict};
+use crate::services::fs::listing::is_hidden;
+use crate::services::fs::walk::{walk, WalkOptions};
+use crate::services::fs::watch::{watch, Change, DirWatcher, WatchEvent, WatchParams};
+use crate::services::search::content::looks_binary;
+use crate::services::search::query::{self, Candidate};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::ops::Bound;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tantivy::collector::TopDocs;
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{AllQuery, BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::{
+    DateTime, Index, IndexReader, IndexWriter, ReloadPolicy, Searcher, TantivyDocument, Term,
+};
+
+const CONFIG_FILE: &str = "config.json";
+/// Subdirectory holding tantivy's files.
+const DATA_DIR: &str = "data";
+/// Memory the writer may use before it flushes a segment.
+const WRITER_HEAP: usize = 64 * 1024 * 1024;
+/// Documents added between two progress reports of a rebuild.
+const PROGRESS_EVERY: u64 = 1000;
+/// How often the updater looks at its watchers.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+/// How long the updater waits before trying again to watch a root it could not watch.
+const REWATCH_INTERVAL: Duration = Duration::from_secs(5);
+/// Query terms matching the name count this much more than matches in the content.
+const NAME_BOOST: f32 = 4.0;
+const PATH_BOOST: f32 = 2.0;
+/// Fewest hits fetched at a time when the index cannot express the whole query.
+const MIN_PAGE: usize = 256;
+
+/// What gets indexed. Saved next to the index; changes apply from the next rebuild.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexConfig {
+    pub roots: Vec<PathBuf>,
+    /// `.gitignore`-style patterns matched against paths relative to each root.
+    pub exclude: Vec<String>,
+    /// Also leave out what ignore files inside the roots exclude. Only applied by full
+    /// walks; single paths reported by the watcher are checked against `exclude` alone.
+    pub use_ignore_files: bool,
+    pub show_hidden: bool,
+    /// The text of larger files is not indexed; their names and metadata are.
+    pub max_content_size: u64,
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            roots: dirs::home_dir().into_iter().collect(),
+            exclude: ["node_modules/", "target/", ".cache/"]
+                .map(String::from)
+                .to_vec(),
+            use_ignore_files: true,
+            show_hidden: false,
+            max_content_size: 1024 * 1024,
+        }
+    }
+}
+
+/// Full-text terms plus metadata filters. Every filter that is set has to hold.
+#[derive(Debug, Clone)]
+pub struct IndexQuery {
+    /// Terms in tantivy's query syntax (`a b` needs both, `a OR b`, `"a phrase"`, `-not`),
+    /// matched against names, paths and contents. Empty matches everything.
+    pub text: String,
+    /// Only entries below this directory.
+    pub under: Option<PathBuf>,
+    /// Any of these extensions, without the dot. Case-insensitive.
+    pub extensions: Vec<String>,
+    pub kind: Option<FileKind>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<SystemTime>,
+    pub modified_before: Option<SystemTime>,
+    pub limit: usize,
+}
+
+impl Default for IndexQuery {
+    fn default() -> Self {
+        Self {
+            text: String::new(),
+            under: None,
+            extensions: Vec::new(),
+            kind: None,
+            min_size: None,
+            max_size: None,
+            modified_after: None,
+            modified_before: None,
+            limit: 200,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexHit {
+    pub path: PathBuf,
+    pub name: String,
+    pub kind: FileKind,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+    /// Relevance to the text terms; constant when there are none.
+    pub score: f32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexStatus {
+    pub documents: u64,
+    /// When the index was last committed, or `None` if it never has been.
+    pub last_update: Option<SystemTime>,
+    /// Bytes taken up by the index files.
+    pub size_on_disk: u64,
+    pub roots: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Fields {
+    /// The exact path; the key documents are replaced and deleted by.
+    path: Field,
+    /// Every directory above the path.
+    ancestors: Field,
+    name: Field,
+    /// The path again, tokenized for full-text matching.
+    path_text: Field,
+    /// Lowercase extension without the dot.
+    ext: Field,
+    kind: Field,
+    size: Field,
+    modified: Field,
+    content: Field,
+}
+
+fn schema() -> (Schema, Fields) {
+    let mut builder = Schema::builder();
+    let fields = Fields {
+        path: builder.add_text_field("path", STRING | STORED),
+        ancestors: builder.add_text_field("ancestors", STRING),
+        name: builder.add_text_field("name", TEXT | STORED),
+        path_text: builder.add_text_field("path_text", TEXT),
+        ext: builder.add_text_field("ext", STRING | STORED),
+        kind: builder.add_text_field("kind", STRING | STORED),
+        size: builder.add_u64_field("size", INDEXED | STORED | FAST),
+        modified: builder.add_date_field("modified", INDEXED | STORED | FAST),
+        content: builder.add_text_field("content", TEXT),
+    };
+    (builder.build(), fields)
+}
+
+/// An open index. Share it behind an `Arc`; all methods take `&self`.
+pub struct SearchIndex {
+    dir: PathBuf,
+    config: Mutex<IndexConfig>,
+    index: Index,
+    reader: IndexReader,
+    /// tantivy allows one writer per index, so updates take turns.
+    writer: Mutex<IndexWriter>,
+    fields: Fields,
+}
+
+impl SearchIndex {
+    /// Open the index stored in `dir`, creating an empty one if there is none yet.
+    pub fn open(dir: impl Into<PathBuf>) -> Result<Self> {
+        let dir = dir.into();
+        let data = dir.join(DATA_DIR);
+        fs::create_dir_all(&data).map_err(|e| Error::from_io(&data, e))?;
+        let config = load_config(&dir.join(CONFIG_FILE));
+        let (schema, fields) = schema();
+        let directory = MmapDirectory::open(&data).map_err(index_error)?;
+        let index = Index::open_or_create(directory, schema).map_err(index_error)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()
+            .map_err(index_error)?;
+        let writer = index.writer(WRITER_HEAP).map_err(index_error)?;
+        Ok(Self {
+            dir,
+            config: Mutex::new(config),
+            index,
+            reader,
+            writer: Mutex::new(writer),
+            fields,
+        })
+    }
+
+    /// Open the index in the nohrs data directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(paths::data_dir().join("index"))
+    }
+
+    /// The index in the data directory, opened once and shared while anyone holds it.
+    /// tantivy allows a single writer per index, so opening it twice would fail.
+    pub fn shared() -> Result<Arc<Self>> {
+        static SHARED: Mutex<Weak<SearchIndex>> = Mutex::new(Weak::new());
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = shared.upgrade() {
+            return Ok(index);
+        }
+        let index = Arc::new(Self::open_default()?);
+        *shared = Arc::downgrade(&index);
+        Ok(index)
+    }
+
+    pub fn config(&self) -> IndexConfig {
+        self.config
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Replace the configuration and save it atomically. What is already indexed stays
+    /// until the next rebuild.
+    pub fn set_config(&self, config: IndexConfig) -> Result<()> {
+        let path = self.dir.join(CONFIG_FILE);
+        let data = serde_json::to_vec_pretty(&config)
+            .map_err(|e| Error::Other(format!("cannot encode index config: {e}")))?;
+        let tmp = path.with_extension("json.tmp");
+        fs::write(&tmp, data).map_err(|e| Error::from_io(&tmp, e))?;
+        fs::rename(&tmp, &path).map_err(|e| Error::from_io(&path, e))?;
+        *self.config.lock().unwrap_or_else(|e| e.into_inner()) = config;
+        Ok(())
+    }
+
+    /// Drop every document and index the configured roots again, calling `progress` with
+    /// the number of documents added so far. Searches keep seeing the previous contents
+    /// until it completes, and keep them if it fails or is cancelled.
+    pub fn rebuild(&self, cancel: &CancelToken, progress: &mut dyn FnMut(u64)) -> Result<u64> {
+        let config = self.config();
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        writer.delete_all_documents().map_err(index_error)?;
+        let mut batch = Batch::new(&writer, self.fields, &config);
+        let mut cancelled = false;
+        for root in &config.roots {
+            match batch.add_tree(root, root, cancel, progress) {
+                Ok(()) => {}
+                Err(Error::Cancelled) => {
+                    cancelled = true;
+                    break;
+                }
+                Err(err) => tracing::warn!(root = %root.display(), %err, "cannot index root"),
+            }
+        }
+        let added = batch.added;
+        if cancelled {
+            writer.rollback().map_err(index_error)?;
+            return Err(Error::Cancelled);
+        }
+        self.commit(&mut writer)?;
+        Ok(added)
+    }
+
+    /// Bring the index up to date with `changes`, as reported by a watcher on `root`.
+    pub fn apply_changes(&self, root: &Path, changes: &[Change]) -> Result<()> {
+        let config = self.config();
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut batch = Batch::new(&writer, self.fields, &config);
+        let never = CancelToken::new();
+        for change in changes {
+            let result = match change {
+                Change::Removed(path) => {
+                    batch.delete_tree(path);
+                    Ok(())
+                }
+                Change::Added(path) => {
+                    batch.delete_tree(path);
+                    batch.add_tree(root, path, &never, &mut |_| {})
+                }
+                Change::Renamed { from, to } => {
+                    batch.delete_tree(from);
+                    batch.delete_tree(to);
+                    batch.add_tree(root, to, &never, &mut |_| {})
+                }
+                // Directories change whenever their children do; those arrive as their
+                // own changes, so only the entry itself is replaced.
+                Change::Modified(path) => {
+                    batch.delete(path);
+                    batch.add_one(root, path)
+                }
+            };
+            match result {
+                Ok(()) | Err(Error::Vanished { .. }) => {}
+                Err(err) => tracing::debug!(%err, "cannot index changed path"),
+            }
+        }
+        self.commit(&mut writer)
+    }
+
+    /// Index everything below `root` again, e.g. after the watcher lost notifications.
+    pub fn reindex(&self, root: &Path) -> Result<()> {
+        let config = self.config();
+        let mut writer = self.writer.lock().unwrap_or_else(|e| e.into_inner());
+        let mut batch = Batch::new(&writer, self.fields, &config);
+        batch.delete_tree(root);
+        batch.add_tree(root, root, &CancelToken::new(), &mut |_| {})?;
+        self.commit(&mut writer)
+    }
+
+    /// When the index was last committed, or `None` if it never has been.
+    pub fn last_update(&self) -> Result<Option<SystemTime>> {
+        let metas = self.index.load_metas().map_err(index_error)?;
+        Ok(metas
+            .payload
+            .as_deref()
+            .and_then(|p| p.parse().ok())
+            .map(|secs| UNIX_EPOCH + Duration::from_secs(secs)))
+    }
+
+    pub fn status(&self) -> Result<IndexStatus> {
+        let last_update = self.last_update()?;
+        let data = self.dir.join(DATA_DIR);
+        let size_on_disk = fs::read_dir(&data)
+            .map_err(|e| Error::from_io(&data, e))?
+            .filter_map(|entry| entry.ok()?.metadata().ok())
+            .filter(|md| md.is_file())
+            .map(|md| md.len())
+            .sum();
+        Ok(IndexStatus {
+            documents: self.reader.searcher().num_docs(),
+            last_update,
+            size_on_disk,
+            roots: self.config().roots,
+        })
+    }
+
+    /// Best matches first. Fails with `Error::InvalidPattern` if the text does not parse.
+    pub fn search(&self, query: &IndexQuery) -> Result<Vec<IndexHit>> {
+        let compiled = self.compile(query)?;
+        self.fetch(&self.reader.searcher(), &*compiled, query.limit.max(1), 0)
+    }
+
+    /// The tantivy query for `query`, ignoring its limit.
+    fn compile(&self, query: &IndexQuery) -> Result<Box<dyn Query>> {
+        let f = self.fields;
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = Vec::new();
+        if !query.text.trim().is_empty() {
+            let mut parser =
+                QueryParser::for_index(&self.index, vec![f.name, f.path_text, f.content]);
+            parser.set_conjunction_by_default();
+            parser.set_field_boost(f.name, NAME_BOOST);
+            parser.set_field_boost(f.path_text, PATH_BOOST);
+            let text = parser
+                .parse_query(&query.text)
+                .map_err(|e| Error::InvalidPattern(e.to_string()))?;
+            clauses.push((Occur::Must, text));
+        }
+        if let Some(dir) = &query.under {
+            let dir = dir
+                .to_str()
+                .ok_or_else(|| Error::InvalidName { path: dir.clone() })?;
+            clauses.push((Occur::Must, Box::new(term_query(f.ancestors, dir))));
+        }
+        if !query.extensions.is_empty() {
+            let any = query
+                .extensions
+                .iter()
+                .map(|ext| {
+                    let ext = ext.trim_start_matches('.').to_lowercase();
+                    (
+                        Occur::Should,
+                        Box::new(term_query(f.ext, &ext)) as Box<dyn Query>,
+                    )
+                })
+                .collect();
+            clauses.push((Occur::Must, Box::new(BooleanQuery::new(any))));
+        }
+        if let Some(kind) = query.kind {
+            clauses.push((Occur::Must, Box::new(term_query(f.kind, kind.as_str()))));
+        }
+        if query.min_size.is_some() || query.max_size.is_some() {
+            clauses.push((
+                Occur::Must,
+                Box::new(RangeQuery::new_u64_bounds(
+                    "size".to_string(),
+                    query.min_size.map_or(Bound::Unbounded, Bound::Included),
+                    query.max_size.map_or(Bound::Unbounded, Bound::Included),
+                )),
+            ));
+        }
+        if query.modified_after.is_some() || query.modified_before.is_some() {
+            clauses.push((
+                Occur::Must,
+                Box::new(RangeQuery::new_date_bounds(
+                    "modified".to_string(),
+                    query
+                        .modified_after
+                        .map_or(Bound::Unbounded, |t| Bound::Included(to_date(t))),
+                    query
+                        .modified_before
+                        .map_or(Bound::Unbounded, |t| Bound::Excluded(to_date(t))),
+                )),
+            ));
+        }
+        Ok(if clauses.is_empty() {
+            Box::new(AllQuery)
+        } else {
+            Box::new(BooleanQuery::new(clauses))
+        })
+    }
+
+    /// The hits ranked `offset..offset + limit` for `query`.
+    fn fetch(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        limit: usize,
+        offset: usize,
+    ) -> Result<Vec<IndexHit>> {
+        let f = self.fields;
+        let top = searcher
+            .search(query, &TopDocs::with_limit(limit).and_offset(offset))
+            .map_err(index_error)?;
+        let mut hits = Vec::with_capacity(top.len());
+        for (score, address) in top {
+            let doc: TantivyDocument = searcher.doc(address).map_err(index_error)?;
+            let text = |field| {
+                doc.get_first(field)
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string()
+            };
+            hits.push(IndexHit {
+                path: PathBuf::from(text(f.path)),
+                name: text(f.name),
+                kind: text(f.kind).parse().unwrap_or_default(),
+                size: doc.get_first(f.size).and_then(|v| v.as_u64()).unwrap_or(0),
+                modified: doc
+                    .get_first(f.modified)
+                    .and_then(|v| v.as_datetime())
+                    .map(from_date),
+                score,
+            });
+        }
+        Ok(hits)
+    }
+
+    /// Run a parsed [`Query`](query::Query) below `under`: the index answers what it can
+    /// and the hits are checked against the rest of the query.
+    ///
+    /// Hits are fetched from the index a page at a time until `limit` of them pass the
+    /// check or the index runs out, so selective queries still fill the limit.
+    pub fn search_parsed(
+        &self,
+        query: &query::Query,
+        under: Option<PathBuf>,
+        limit: usize,
+    ) -> Result<Vec<IndexHit>> {
+        let now = SystemTime::now();
+        let compiled = self.compile(&IndexQuery {
+            under,
+            ..query.index_query(now)
+        })?;
+        let searcher = self.reader.searcher();
+        let limit = limit.max(1);
+        let page = limit.max(MIN_PAGE);
+        let mut hits = Vec::new();
+        let mut offset = 0;
+        loop {
+            let fetched = self.fetch(&searcher, &*compiled, page, offset)?;
+            let exhausted = fetched.len() < page;
+            offset += fetched.len();
+            hits.extend(fetched.into_iter().filter(|hit| {
+                let candidate = Candidate {
+                    path: &hit.path,
+                    name: &hit.name,
+                    kind: hit.kind,
+                    size: hit.size,
+                    modified: hit.modified,
+                };
+                query.matches(&candidate, now)
+            }));
+            if hits.len() >= limit || exhausted {
+                hits.truncate(limit);
+                return Ok(hits);
+            }
+        }
+    }
+
+    /// Commit pending changes, recording the time as the commit payload, and make them
+    /// visible to searches.
+    fn commit(&self, writer: &mut IndexWriter) -> Result<()> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        let mut prepared = writer.prepare_commit().map_err(index_error)?;
+        prepared.set_payload(&now.to_string());
+        prepared.commit().map_err(index_error)?;
+        self.reader.reload().map_err(index_error)
+    }
+}
+
+/// Additions and deletions made under one hold of the writer.
+struct Batch<'a> {
+    writer: &'a IndexWriter,
+    fields: Fields,
+    config: &'a IndexConfig,
+    excludes: IgnoreRules,
+    added: u64,
+}
+
+impl<'a> Batch<'a> {
+    fn new(writer: &'a IndexWriter, fields: Fields, config: &'a IndexConfig) -> Self {
+        let mut excludes = IgnoreRules::default();
+        excludes.add_patterns(&config.exclude.join("\n"));
+        Self {
+            writer,
+            fields,
+            config,
+            excludes,
+            added: 0,
+        }
+    }
+
+    /// Delete the document for `path` alone.
+    fn delete(&self, path: &Path) {
+        if let Some(path) = path.to_str() {
+            self.writer
+                .delete_term(Term::from_field_text(self.fields.path, path));
+        }
+    }
+
+    /// Delete the documents for `path` and everything below it.
+    fn delete_tree(&self, path: &Path) {
+        self.delete(path);
+        if let Some(path) = path.to_str() {
+            self.writer
+                .delete_term(Term::from_field_text(self.fields.ancestors, path));
+        }
+    }
+
+    /// `path`, which lies below `root`, is hidden or matches an exclusion pattern, or a
+    /// directory between them does.
+    fn excluded(&self, root: &Path, path: &Path, is_dir: bool) -> bool {
+        let Ok(rel) = path.strip_prefix(root) else {
+            return true;
+        };
+        let parts: Vec<_> = rel
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        let mut prefix = String::new();
+        for (i, part) in parts.iter().enumerate() {
+            if !self.config.show_hidden && is_hidden(part) {
+                return true;
+            }
+            if !prefix.is_empty() {
+                prefix.push('/');
+            }
+            prefix.push_str(part);
+            let dir = is_dir || i + 1 < parts.len();
+            if self.excludes.matched(&prefix, dir) == Some(Verdict::Ignore) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Index `path` alone, unless it is excluded.
+    fn add_one(&mut self, root: &Path, path: &Path) -> Result<()> {
+        let md = fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+        if self.excluded(root, path, md.is_dir()) {
+            return Ok(());
+        }
+        self.add(path, &md)
+    }
+
+    /// Index `start` and everything below it that is not excluded. `start` is `root` or
+    /// lies below it; `root` itself gets no document.
+    fn add_tree(
+        &mut self,
+        root: &Path,
+        start: &Path,
+        cancel: &CancelToken,
+        progress: &mut dyn FnMut(u64),
+    ) -> Result<()> {
+        if start != root {
+            let md = fs::symlink_metadata(start).map_err(|e| Error::from_io(start, e))?;
+            if self.excluded(root, start, md.is_dir()) {
+                return Ok(());
+            }
+            self.add(start, &md)?;
+            if !md.is_dir() {
+                return Ok(());
+            }
+        }
+        let opts = WalkOptions {
+            use_ignore_files: self.config.use_ignore_files,
+            show_hidden: self.config.show_hidden,
+            ..Default::default()
+        };
+        let mut entries = walk(start, &opts);
+        while let Some(entry) = entries.next() {
+            if cancel.is_cancelled() {
+                return Err(Error::Cancelled);
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    tracing::debug!(%err, "skipping unreadable entry");
+                    continue;
+                }
+            };
+            if self.excluded(root, &entry.path, entry.is_dir()) {
+                if entry.is_dir() {
+                    entries.skip_current_dir();
+                }
+                continue;
+            }
+            match entry.metadata().and_then(|md| self.add(&entry.path, &md)) {
+                Ok(()) if self.added.is_multiple_of(PROGRESS_EVERY) => progress(self.added),
+                Ok(()) => {}
+                Err(err) => tracing::debug!(%err, "skipping entry"),
+            }
+        }
+        Ok(())
+    }
+
+    fn add(&mut self, path: &Path, md: &fs::Metadata) -> Result<()> {
+        let f = self.fields;
+        let text = path.to_str().ok_or_else(|| Error::InvalidName {
+            path: path.to_path_buf(),
+        })?;
+        let kind = FileKind::from_file_type(md.file_type());
+        let mut doc = TantivyDocument::default();
+        doc.add_text(f.path, text);
+        doc.add_text(f.path_text, text);
+        for dir in path.ancestors().skip(1).filter_map(Path::to_str) {
+            if !dir.is_empty() {
+                doc.add_text(f.ancestors, dir);
+            }
+        }
+        if let Some(name) = path.file_name() {
+            doc.add_text(f.name, name.to_string_lossy());
+        }
+        if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+            doc.add_text(f.ext, ext.to_lowercase());
+        }
+        doc.add_text(f.kind, kind.as_str());
+        doc.add_u64(f.size, md.len());
+        if let Ok(modified) = md.modified() {
+            doc.add_date(f.modified, to_date(modified));
+        }
+        if kind == FileKind::File && md.len() <= self.config.max_content_size {
+            match fs::read(path) {
+                Ok(data) if !looks_binary(&data) => {
+                    doc.add_text(f.content, String::from_utf8_lossy(&data));
+                }
+                Ok(_) => {}
+                Err(err) => tracing::debug!(path = %path.display(), %err, "cannot read contents"),
+            }
+        }
+        self.writer.add_document(doc).map_err(index_error)?;
+        self.added += 1;
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub enum RebuildEvent {
+    /// Documents added so far.
+    Progress(u64),
+    Done {
+        documents: u64,
+        elapsed: Duration,
+    },
+    Failed(Error),
+}
+
+/// Receiving end of a background rebuild. Dropping it cancels the rebuild, which leaves
+/// the index as it was.
+pub struct Rebuild {
+    rx: Receiver<RebuildEvent>,
+    cancelled: CancelToken,
+}
+
+impl Rebuild {
+    /// Non-blocking poll for UI loops. `Disconnected` means the rebuild has ended.
+    pub fn try_next(&self) -> std::result::Result<RebuildEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for Rebuild {
+    type Item = RebuildEvent;
+
+    fn next(&mut self) -> Option<RebuildEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for Rebuild {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Rebuild `index` on a background thread.
+pub fn start_rebuild(index: Arc<SearchIndex>) -> Rebuild {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let started = Instant::now();
+        let progress_tx = tx.clone();
+        let mut progress = move |n| {
+            let _ = progress_tx.send(RebuildEvent::Progress(n));
+        };
+        let event = match index.rebuild(&flag, &mut progress) {
+            Ok(documents) => RebuildEvent::Done {
+                documents,
+                elapsed: started.elapsed(),
+            },
+            Err(Error::Cancelled) => return,
+            Err(err) => RebuildEvent::Failed(err),
+        };
+        let _ = tx.send(event);
+    });
+
+    Rebuild { rx, cancelled }
+}
+
+/// Keeps an index up to date with changes below its roots. Dropping it stops watching.
+pub struct IndexUpdater {
+    index: Arc<SearchIndex>,
+    cancelled: CancelToken,
+}
+
+impl IndexUpdater {
+    /// An updater for [`SearchIndex::shared`], started once and shared while anyone
+    /// holds it, so pages showing the index do not each watch its roots.
+    pub fn shared() -> Result<Arc<Self>> {
+        static SHARED: Mutex<Weak<IndexUpdater>> = Mutex::new(Weak::new());
+        let mut shared = SHARED.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(updater) = shared.upgrade() {
+            return Ok(updater);
+        }
+        let updater = Arc::new(start_updater(SearchIndex::shared()?));
+        *shared = Arc::downgrade(&updater);
+        Ok(updater)
+    }
+
+    /// The index being kept up to date.
+    pub fn index(&self) -> &Arc<SearchIndex> {
+        &self.index
+    }
+}
+
+impl Drop for IndexUpdater {
+    fn drop(&mut self) {
+        self.cancelled.cancel();
+    }
+}
+
+/// A root of the index and its watcher, while it has one.
+struct WatchedRoot {
+    root: PathBuf,
+    watcher: Option<DirWatcher>,
+    /// Changes may have been missed while it was not watched, so it is indexed again once
+    /// it is.
+    missed: bool,
+    /// No attempt to watch it is made before this.
+    retry_at: Instant,
+}
+
+impl WatchedRoot {
+    fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            watcher: None,
+            missed: false,
+            retry_at: Instant::now(),
+        }
+    }
+
+    /// Start watching the root if it is not watched and is a directory. Returns whether
+    /// it was started. Failures are retried after [`REWATCH_INTERVAL`].
+    fn rewatch(&mut self) -> bool {
+        let now = Instant::now();
+        if self.watcher.is_some() || now < self.retry_at {
+            return false;
+        }
+        if !self.root.is_dir() {
+            self.missed = true;
+            return false;
+        }
+        let params = WatchParams {
+            path: self.root.clone(),
+            recursive: true,
+            debounce: Duration::from_secs(1),
+            max_delay: Duration::from_secs(10),
+        };
+        match watch(params) {
+            Ok(watcher) => {
+                self.watcher = Some(watcher);
+                true
+            }
+            Err(err) => {
+                tracing::warn!(root = %self.root.display(), %err, "cannot watch index root");
+                self.retry_at = now + REWATCH_INTERVAL;
+                self.missed = true;
+                false
+            }
+        }
+    }
+
+    /// Apply what the watcher reported to `index`. The watcher is dropped when it stops
+    /// or asks for a rescan, which is also how the loss or replacement of the root is
+    /// reported, so the root is watched afresh and indexed again.
+    fn poll(&mut self, index: &SearchIndex) {
+        let Some(watcher) = &self.watcher else {
+            return;
+        };
+        let mut lost = false;
+        loop {
+            let result = match watcher.try_next() {
+                Ok(WatchEvent::Changes(changes)) => index.apply_changes(&self.root, &changes),
+                Ok(WatchEvent::Error(err)) => Err(err),
+                Ok(WatchEvent::Rescan) | Err(TryRecvError::Disconnected) => {
+                    lost = true;
+                    break;
+                }
+                Err(TryRecvError::Empty) => break,
+            };
+            if let Err(err) = result {
+                tracing::warn!(root = %self.root.display(), %err, "index update failed");
+            }
+        }
+        if !lost {
+            return;
+        }
+        self.watcher = None;
+        self.missed = true;
+        // Until it comes back, a root that is gone has nothing in it: reindexing it only
+        // drops its documents.
+        if !self.root.is_dir() {
+            if let Err(err) = index.reindex(&self.root) {
+                tracing::warn!(root = %self.root.display(), %err, "index update failed");
+            }
+        }
+    }
+}
+
+/// Watch the roots `index` is configured with and apply their changes as they come.
+///
+/// Roots that do not exist or cannot be watched yet, and roots that are removed or
+/// replaced while watched, are watched as soon as their directory can be, and are then
+/// indexed again. Roots added to or dropped from the configuration are picked up too.
+pub fn start_updater(index: Arc<SearchIndex>) -> IndexUpdater {
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+    let mut roots: Vec<WatchedRoot> = index
+        .config()
+        .roots
+        .into_iter()
+        .map(WatchedRoot::new)
+        .collect();
+    for root in &mut roots {
+        root.rewatch();
+    }
+
+    let watched = Arc::clone(&index);
+    thread::spawn(move || {
+        let index = watched;
+        while !flag.is_cancelled() {
+            let configured = index.config().roots;
+            roots.retain(|r| configured.contains(&r.root));
+            for root in configured {
+                if !roots.iter().any(|r| r.root == root) {
+                    roots.push(WatchedRoot::new(root));
+                }
+            }
+            for root in &mut roots {
+                if root.rewatch() && root.missed {
+                    root.missed = false;
+                    if let Err(err) = index.reindex(&root.root) {
+                        tracing::warn!(root = %root.root.display(), %err, "index update failed");
+                    }
+                }
+                root.poll(&index);
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+
+    IndexUpdater { index, cancelled }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct IndexSearchParams {
+    pub query: query::Query,
+    /// Only hits below this directory.
+    pub under: Option<PathBuf>,
+    pub limit: usize,
+    /// Weight the index's relevance by how often and recently the user opened each hit.
+    pub frecency: bool,
+}
+
+pub enum IndexSearchEvent {
+    /// The shared index is open. Keeping `updater` keeps the index current.
+    Opened {
+        updater: Arc<IndexUpdater>,
+        status: Option<IndexStatus>,
+    },
+    /// Best matches first. Ends the search.
+    Hits(Vec<IndexHit>),
+    /// The index could not be opened or the query not run. Ends the search.
+    Failed(Error),
+}
+
+/// Receiving end of a query of the shared index. Dropping it discards the result.
+pub struct IndexSearch {
+    rx: Receiver<IndexSearchEvent>,
+}
+
+impl IndexSearch {
+    /// Non-blocking poll for UI loops. `Disconnected` means the search has ended.
+    pub fn try_next(&self) -> std::result::Result<IndexSearchEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+}
+
+impl Iterator for IndexSearch {
+    type Item = IndexSearchEvent;
+
+    fn next(&mut self) -> Option<IndexSearchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+/// Query the [shared](IndexUpdater::shared) index on a background thread, opening it and
+/// starting its updater first if nobody has yet. Selective queries can read much of the
+/// index, and frecency is read from its database, so neither belongs on a UI thread.
+pub fn search_index(params: IndexSearchParams) -> IndexSearch {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let updater = match IndexUpdater::shared() {
+            Ok(updater) => updater,
+            Err(err) => {
+                let _ = tx.send(IndexSearchEvent::Failed(err));
+                return;
+            }
+        };
+        let index = Arc::clone(updater.index());
+        let status = index.status().ok();
+        if tx
+            .send(IndexSearchEvent::Opened { updater, status })
+            .is_err()
+        {
+            return;
+        }
+        let event = match index.search_parsed(&params.query, params.under, params.limit) {
+            Ok(mut hits) => {
+                let scores = if params.frecency {
+                    current_scores()
+                } else {
+                    Default::default()
+                };
+                // Stable, so hits the user never opened keep the index's order.
+                if !scores.is_empty() {
+                    hits.sort_by(|a, b| {
+                        let a = a.score as f64 * scores.relevance_factor(&a.path);
+                        let b = b.score as f64 * scores.relevance_factor(&b.path);
+                        b.total_cmp(&a)
+                    });
+                }
+                IndexSearchEvent::Hits(hits)
+            }
+            Err(err) => IndexSearchEvent::Failed(err),
+        };
+        let _ = tx.send(event);
+    });
+    IndexSearch { rx }
+}
+
+fn load_config(path: &Path) -> IndexConfig {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|err| {
+            tracing::warn!(path = %path.display(), %err, "ignoring unreadable index config");
+            IndexConfig::default()
+        }),
+        Err(_) => IndexConfig::default(),
+    }
+}
+
+fn term_query(field: Field, text: &str) -> TermQuery {
+    TermQuery::new(Term::from_field_text(field, text), IndexRecordOption::Basic)
+}
+
+fn to_date(time: SystemTime) -> DateTime {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(d) => d.as_secs() as i64,
+        Err(e) => -(e.duration().as_secs() as i64),
+    };
+    DateTime::from_timestamp_secs(secs)
+}
+
+fn from_date(date: DateTime) -> SystemTime {
+    let secs = date.into_timestamp_secs();
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    }
+}
+
+fn index_error(err: impl std::fmt::Display) -> Error {
+    Error::Index(err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+    use crate::services::search::query::Query;
+
+    #[test]
+    fn missing_roots_are_watched_once_created() {
+        let tmp = TempDir::new();
+        let mut root = WatchedRoot::new(tmp.path().join("later"));
+        assert!(!root.rewatch());
+        assert!(root.watcher.is_none());
+        assert!(root.missed);
+
+        tmp.mkdir("later");
+        assert!(root.rewatch());
+        assert!(root.watcher.is_some());
+        // Already watched.
+        assert!(!root.rewatch());
+    }
+
+    #[test]
+    fn searches_the_shared_index_in_the_background() {
+        let tmp = TempDir::new();
+        let root = tmp.mkdir("root");
+        tmp.write("root/notes.txt", "");
+        tmp.write("root/other.md", "");
+        let index = SearchIndex::shared().unwrap();
+        index
+            .set_config(IndexConfig {
+                roots: vec![root.clone()],
+                ..IndexConfig::default()
+            })
+            .unwrap();
+        index.rebuild(&CancelToken::new(), &mut |_| {}).unwrap();
+
+        let mut search = search_index(IndexSearchParams {
+            query: Query::parse("ext:txt").unwrap(),
+            under: Some(root.clone()),
+            limit: 10,
+            frecency: false,
+        });
+        let Some(IndexSearchEvent::Opened { updater, status }) = search.next() else {
+            panic!("expected the index to open first");
+        };
+        assert!(Arc::ptr_eq(updater.index(), &index));
+        assert_eq!(status.map(|s| s.roots), Some(vec![root.clone()]));
+        let Some(IndexSearchEvent::Hits(hits)) = search.next() else {
+            panic!("expected hits");
+        };
+        let paths: Vec<PathBuf> = hits.into_iter().map(|hit| hit.path).collect();
+        assert_eq!(paths, [root.join("notes.txt")]);
+        assert!(search.next().is_none());
+    }
+
+    #[test]
+    fn selective_queries_fill_the_limit_past_the_first_page() {
+        let tmp = TempDir::new();
+        let root = tmp.mkdir("root");
+        for i in 0..2 * MIN_PAGE {
+            tmp.write(&format!("root/a{i}.txt"), "");
+        }
+        for i in 0..3 {
+            tmp.write(&format!("root/b{i}.txt"), "");
+        }
+        let index = SearchIndex::open(tmp.path().join("index")).unwrap();
+        index
+            .set_config(IndexConfig {
+                roots: vec![root.clone()],
+                ..IndexConfig::default()
+            })
+            .unwrap();
+        index.rebuild(&CancelToken::new(), &mut |_| {}).unwrap();
+
+        // The index cannot exclude names, so nearly every hit it returns is filtered out.
+        let query = Query::parse("ext:txt -name:a").unwrap();
+        let mut names: Vec<String> = index
+            .search_parsed(&query, Some(root.clone()), 3)
+            .unwrap()
+            .into_iter()
+            .map(|hit| hit.name)
+            .collect();
+        names.sort();
+        assert_eq!(names, ["b0.txt", "b1.txt", "b2.txt"]);
+        assert_eq!(index.search_parsed(&query, Some(root), 2).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn round_trip_through_rebuild_changes_and_search() {
+        let tmp = TempDir::new();
+        let root = tmp.mkdir("root");
+        tmp.write("root/notes/report.txt", "quarterly figures");
+        tmp.write("root/notes/draft.md", "nothing yet");
+        tmp.write("root/target/out.txt", "quarterly build output");
+        tmp.write("root/.hidden/secret.txt", "quarterly secret");
+
+        let index = SearchIndex::open(tmp.path().join("index")).unwrap();
+        index
+            .set_config(IndexConfig {
+                roots: vec![root.clone()],
+                ..IndexConfig::default()
+            })
+            .unwrap();
+        // The `target/` exclusion and hidden entries leave the directory and two files.
+        assert_eq!(index.rebuild(&CancelToken::new(), &mut |_| {}).unwrap(), 3);
+        assert_eq!(index.status().unwrap().documents, 3);
+        assert!(index.last_update().unwrap().is_some());
+
+        let found = |text: &str| -> Vec<PathBuf> {
+            let query = Query::parse(text).unwrap();
+            let mut paths: Vec<PathBuf> = index
+                .search_parsed(&query, Some(root.clone()), 100)
+                .unwrap()
+                .into_iter()
+                .map(|hit| hit.path)
+                .collect();
+            paths.sort();
+            paths
+        };
+        assert_eq!(found("quarterly"), [root.join("notes/report.txt")]);
+        assert_eq!(found("report"), [root.join("notes/report.txt")]);
+        assert_eq!(found("ext:md"), [root.join("notes/draft.md")]);
+        assert_eq!(found("kind:dir"), [root.join("notes")]);
+        assert_eq!(found("size:>12"), [root.join("notes/report.txt")]);
+        assert_eq!(
+            found("ext:txt,md -name:draft"),
+            [root.join("notes/report.txt")]
+        );
+
+        let added = tmp.write("root/notes/fresh.txt", "annual summary");
+        index
+            .apply_changes(&root, &[Change::Added(added.clone())])
+            .unwrap();
+        assert_eq!(found("annual"), [added]);
+
+        fs::rename(root.join("notes"), root.join("papers")).unwrap();
+        index
+            .apply_changes(
+                &root,
+                &[Change::Renamed {
+                    from: root.join("notes"),
+                    to: root.join("papers"),
+                }],
+            )
+            .unwrap();
+        assert_eq!(found("quarterly"), [root.join("papers/report.txt")]);
+        assert_eq!(found("kind:dir"), [root.join("papers")]);
+
+        fs::write(root.join("papers/report.txt"), "revised numbers").unwrap();
+        index
+            .apply_changes(&root, &[Change::Modified(root.join("papers/report.txt"))])
+            .unwrap();
+        assert!(found("quarterly").is_empty());
+        assert_eq!(found("revised"), [root.join("papers/report.txt")]);
+
+        fs::remove_file(root.join("papers/draft.md")).unwrap();
+        index
+            .apply_changes(&root, &[Change::Removed(root.join("papers/draft.md"))])
+            .unwrap();
+        assert!(found("ext:md").is_empty());
+
+        fs::remove_dir_all(root.join("papers")).unwrap();
+        index
+            .apply_changes(&root, &[Change::Removed(root.join("papers"))])
+            .unwrap();
+        assert!(found("").is_empty());
+        assert_eq!(index.status().unwrap().documents, 0);
+    }
+
+    #[test]
+    fn config_is_saved_with_the_index() {
+        let tmp = TempDir::new();
+        let config = IndexConfig {
+            roots: vec![tmp.path().join("a")],
+            exclude: vec!["*.log".into()],
+            ..IndexConfig::default()
+        };
+        SearchIndex::open(tmp.path().join("index"))
+            .unwrap()
+            .set_config(config.clone())
+            .unwrap();
+        assert_eq!(
+            load_config(&tmp.path().join("index").join(CONFIG_FILE)),
+            config
+        );
+    }
+}
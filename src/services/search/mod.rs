@@ -0,0 +1,6 @@
+pub mod content;
+pub mod fuzzy;
+pub mod index;
+pub mod names;
+pub mod query;
+pub mod saved;
@@ -0,0 +1,332 @@
+//! Fuzzy search over the names and relative paths of everything below a root.
+//!
+//! The tree is walked on a background thread. Each entry's file name is matched first,
+//! and its path relative to the root only when the name does not match or the query
+//! contains a `/`, so `main` ranks `src/main.rs` above `domain/x.rs`. The best hits so far
+//! are kept ranked and re-sent as they change, so a UI can simply show the latest list.
+//!
+//! The free text of the [`Query`] is what gets matched fuzzily; its other clauses filter
+//! entries before they are ranked. Paths used often and recently get a boost from their
+//! [frecency](crate::services::frecency).
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::Error;
+use crate::models::file_entry::FileKind;
+use crate::services::frecency::Scores;
+use crate::services::fs::walk::{walk, WalkEntry, WalkOptions};
+use crate::services::search::fuzzy::FuzzyQuery;
+use crate::services::search::query::{Candidate, Query};
+use std::cmp::Ordering;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Least time between two `NameSearchEvent::Ranked` updates.
+const UPDATE_INTERVAL: Duration = Duration::from_millis(50);
+/// Matching the file name rather than a directory above it is worth this much.
+const NAME_BONUS: i32 = 32;
+
+#[derive(Debug, Clone)]
+pub struct NameSearchParams {
+    pub root: PathBuf,
+    pub query: Query,
+    /// What to walk. Ignore files are honored by default.
+    pub walk: WalkOptions,
+    /// Number of ranked hits kept.
+    pub limit: usize,
+    /// Frecency of the paths the user has been using; empty to rank by the match alone.
+    pub frecency: Scores,
+}
+
+impl Default for NameSearchParams {
+    fn default() -> Self {
+        Self {
+            root: PathBuf::new(),
+            query: Query::default(),
+            walk: WalkOptions {
+                use_ignore_files: true,
+                ..Default::default()
+            },
+            limit: 500,
+            frecency: Scores::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameHit {
+    pub path: PathBuf,
+    /// `path` relative to the search root, `/`-separated.
+    pub rel_path: String,
+    pub kind: FileKind,
+    pub score: i32,
+    /// Character indices into `rel_path` of the matched characters.
+    pub positions: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub enum NameSearchEvent {
+    /// The best hits so far, best first. Replaces the previous list.
+    Ranked(Vec<NameHit>),
+    /// A directory could not be read; the search continues.
+    EntryError(Error),
+    /// The walk is complete and the last `Ranked` list is final.
+    Done { scanned: u64, elapsed: Duration },
+}
+
+/// Receiving end of a name search. Dropping it cancels the search.
+pub struct NameSearch {
+    rx: Receiver<NameSearchEvent>,
+    cancelled: CancelToken,
+}
+
+impl NameSearch {
+    /// Non-blocking poll for UI loops. `Disconnected` means the search has ended.
+    pub fn try_next(&self) -> std::result::Result<NameSearchEvent, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for NameSearch {
+    type Item = NameSearchEvent;
+
+    fn next(&mut self) -> Option<NameSearchEvent> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for NameSearch {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Start searching below `params.root`.
+pub fn search_names(params: NameSearchParams) -> NameSearch {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let started = Instant::now();
+        let now = SystemTime::now();
+        let query = FuzzyQuery::new(&params.query.text());
+        let mut ranked: Vec<NameHit> = Vec::new();
+        let mut changed = false;
+        let mut last_sent = started;
+        let mut scanned = 0;
+        for entry in walk(&params.root, &params.walk) {
+            if flag.is_cancelled() {
+                return;
+            }
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    if tx.send(NameSearchEvent::EntryError(err)).is_err() {
+                        return;
+                    }
+                    continue;
+                }
+            };
+            scanned += 1;
+            if !accepts(&params.query, &entry, now) {
+                continue;
+            }
+            let rel = entry
+                .path
+                .strip_prefix(&params.root)
+                .unwrap_or(&entry.path)
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            if let Some(hit) = rank_path(&query, &rel) {
+                let boost = params.frecency.boost(&entry.path);
+                let hit = NameHit {
+                    path: entry.path,
+                    rel_path: rel,
+                    kind: entry.kind,
+                    score: hit.0 + boost,
+                    positions: hit.1,
+                };
+                changed |= insert_ranked(&mut ranked, hit, params.limit);
+            }
+            if changed && last_sent.elapsed() >= UPDATE_INTERVAL {
+                if tx.send(NameSearchEvent::Ranked(ranked.clone())).is_err() {
+                    return;
+                }
+                changed = false;
+                last_sent = Instant::now();
+            }
+        }
+        if changed {
+            let _ = tx.send(NameSearchEvent::Ranked(ranked));
+        }
+        let _ = tx.send(NameSearchEvent::Done {
+            scanned,
+            elapsed: started.elapsed(),
+        });
+    });
+
+    NameSearch { rx, cancelled }
+}
+
+/// Whether `entry` passes the query's filters. Metadata is only read when they need it.
+fn accepts(query: &Query, entry: &WalkEntry, now: SystemTime) -> bool {
+    let name = entry
+        .path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let mut candidate = Candidate {
+        path: &entry.path,
+        name: &name,
+        kind: entry.kind,
+        size: 0,
+        modified: None,
+    };
+    if query.needs_metadata() {
+        let Ok(md) = entry.metadata() else {
+            return false;
+        };
+        candidate.size = md.len();
+        candidate.modified = md.modified().ok();
+    }
+    query.matches(&candidate, now)
+}
+
+/// Score a `/`-separated relative path: by its file name where that matches, otherwise
+/// by the whole path. Returns the score and the matched positions within `rel`.
+pub fn rank_path(query: &FuzzyQuery, rel: &str) -> Option<(i32, Vec<usize>)> {
+    if query.is_empty() {
+        return Some((0, Vec::new()));
+    }
+    let name_start = rel.rfind('/').map_or(0, |i| i + 1);
+    if !query.has_separator() {
+        if let Some(m) = query.matches(&rel[name_start..]) {
+            let offset = rel[..name_start].chars().count();
+            let positions = m.positions.iter().map(|p| p + offset).collect();
+            return Some((m.score + NAME_BONUS, positions));
+        }
+    }
+    query.matches(rel).map(|m| (m.score, m.positions))
+}
+
+/// Best first; among equal scores, shorter paths first, then alphabetical.
+pub fn compare_hits(a: &NameHit, b: &NameHit) -> Ordering {
+    b.score
+        .cmp(&a.score)
+        .then_with(|| a.rel_path.len().cmp(&b.rel_path.len()))
+        .then_with(|| a.rel_path.cmp(&b.rel_path))
+}
+
+/// Insert `hit` into the ranked list, keeping at most `limit`. Returns whether it made it.
+fn insert_ranked(ranked: &mut Vec<NameHit>, hit: NameHit, limit: usize) -> bool {
+    let at = ranked.partition_point(|h| compare_hits(h, &hit) == Ordering::Less);
+    if at >= limit {
+        return false;
+    }
+    ranked.insert(at, hit);
+    ranked.truncate(limit);
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn hit(rel: &str, score: i32) -> NameHit {
+        NameHit {
+            path: PathBuf::from(rel),
+            rel_path: rel.to_string(),
+            kind: FileKind::File,
+            score,
+            positions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn names_rank_above_directories() {
+        let query = FuzzyQuery::new("main");
+        let (name_score, positions) = rank_path(&query, "src/main.rs").unwrap();
+        assert_eq!(positions, [4, 5, 6, 7]);
+        let (dir_score, positions) = rank_path(&query, "main/x.rs").unwrap();
+        assert_eq!(positions, [0, 1, 2, 3]);
+        assert!(name_score > dir_score);
+        assert!(rank_path(&query, "src/lib.rs").is_none());
+    }
+
+    #[test]
+    fn separators_match_the_whole_path() {
+        let query = FuzzyQuery::new("src/lib");
+        let (_, positions) = rank_path(&query, "src/lib.rs").unwrap();
+        assert_eq!(positions, [0, 1, 2, 3, 4, 5, 6]);
+        assert!(rank_path(&query, "lib.rs").is_none());
+        assert_eq!(
+            rank_path(&FuzzyQuery::new(""), "any"),
+            Some((0, Vec::new()))
+        );
+    }
+
+    #[test]
+    fn hits_rank_by_score_then_length_then_name() {
+        let mut ranked = Vec::new();
+        for hit in [hit("bb", 5), hit("a", 1), hit("ccc", 5), hit("aa", 5)] {
+            insert_ranked(&mut ranked, hit, 3);
+        }
+        let order: Vec<&str> = ranked.iter().map(|h| h.rel_path.as_str()).collect();
+        assert_eq!(order, ["aa", "bb", "ccc"]);
+        assert!(!insert_ranked(&mut ranked, hit("z", 0), 3));
+        assert!(insert_ranked(&mut ranked, hit("z", 9), 3));
+        assert_eq!(ranked[0].rel_path, "z");
+        assert_eq!(ranked.len(), 3);
+    }
+
+    fn final_hits(params: NameSearchParams) -> (Vec<NameHit>, u64) {
+        let mut hits = Vec::new();
+        let mut scanned = None;
+        for event in search_names(params) {
+            match event {
+                NameSearchEvent::Ranked(ranked) => hits = ranked,
+                NameSearchEvent::EntryError(err) => panic!("{err}"),
+                NameSearchEvent::Done { scanned: n, .. } => scanned = Some(n),
+            }
+        }
+        (hits, scanned.expect("search finished"))
+    }
+
+    #[test]
+    fn searches_below_the_root() {
+        let tmp = TempDir::new();
+        tmp.write("src/main.rs", "");
+        tmp.write("src/domain.rs", "");
+        tmp.write("main.txt", "");
+        tmp.write("ignored/main.rs", "");
+        tmp.write(".gitignore", "ignored/\n");
+
+        let (hits, scanned) = final_hits(NameSearchParams {
+            root: tmp.path().to_path_buf(),
+            query: Query::parse("main ext:rs").unwrap(),
+            ..Default::default()
+        });
+        let rels: Vec<&str> = hits.iter().map(|h| h.rel_path.as_str()).collect();
+        assert_eq!(rels, ["src/main.rs", "src/domain.rs"]);
+        assert_eq!(hits[0].path, tmp.path().join("src/main.rs"));
+        assert_eq!(hits[0].kind, FileKind::File);
+        assert_eq!(scanned, 5);
+
+        let (hits, _) = final_hits(NameSearchParams {
+            root: tmp.path().to_path_buf(),
+            query: Query::parse("main").unwrap(),
+            limit: 1,
+            ..Default::default()
+        });
+        assert_eq!(hits.len(), 1);
+    }
+}
@@ -0,0 +1,804 @@
+//! The search query language shared by the explorer's filter box, the Search page and
+//! the index.
+//!
+//! A query is a list of whitespace-separated clauses, all of which have to hold:
+//!
+//! - `report`: free text, matched fuzzily against names by the caller
+//! - `"annual report"`: quotes keep spaces inside a clause, also in values
+//! - `ext:pdf`, `ext:jpg,png`: the extension, any of a comma-separated list
+//! - `size:>10mb`, `size:<=4k`, `size:1mb..1gb`: the size, in `b`, `k`, `m`, `g` or `t`
+//!   (powers of 1024); a bare size matches exactly
+//! - `modified:<7d` (within the last seven days), `modified:>1y`, `modified:2024-05-01`,
+//!   `modified:>=2024-01-01`, `modified:2024-01-01..2024-03-31`; a bare age means within
+//! - `kind:file`, `kind:dir`, `kind:link`
+//! - `tag:work`: a freedesktop tag
+//! - `path:src`, `name:test`: case-insensitive substring of the path or of the name
+//!
+//! A leading `-` negates a clause, e.g. `-path:node_modules` or `-draft`; negated free
+//! text excludes names containing it. Keys are case-insensitive. Dates are UTC.
+
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::FileKind;
+use crate::services::fs::tags::read_tags;
+use crate::services::search::index::IndexQuery;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// A parsed query: every clause has to hold.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    pub clauses: Vec<Clause>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub negated: bool,
+    pub filter: Filter,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Filter {
+    /// Free text.
+    Text(String),
+    /// Lowercase extensions without the dot; any of them.
+    Ext(Vec<String>),
+    Size(Comparison<u64>),
+    Modified(Comparison<TimePoint>),
+    Kind(FileKind),
+    Tag(String),
+    /// Lowercased substring of the path.
+    Path(String),
+    /// Lowercased substring of the name.
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparison<T> {
+    Cmp(Op, T),
+    /// Both ends included; an open end is `None`.
+    Between(Option<T>, Option<T>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimePoint {
+    /// This long before now.
+    Ago(Duration),
+    /// A calendar day, as days since 1970-01-01.
+    Date(i64),
+}
+
+/// Modification times a `modified:` clause accepts: from `after` (included) to `before`
+/// (excluded).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TimeRange {
+    pub after: Option<SystemTime>,
+    pub before: Option<SystemTime>,
+}
+
+/// What a query is checked against.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub path: &'a Path,
+    pub name: &'a str,
+    pub kind: FileKind,
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl Query {
+    /// Parse `input`. Fails with `Error::InvalidQuery` pointing at the offending clause.
+    pub fn parse(input: &str) -> Result<Self> {
+        let clauses = tokenize(input)?
+            .into_iter()
+            .map(|token| parse_clause(&token))
+            .collect::<Result<_>>()?;
+        Ok(Self { clauses })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    /// The free text to match fuzzily: the terms that are not negated, space-separated.
+    pub fn text(&self) -> String {
+        self.clauses
+            .iter()
+            .filter(|c| !c.negated)
+            .filter_map(|c| match &c.filter {
+                Filter::Text(text) => Some(text.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Some clause looks at the size or modification time, so candidates need metadata.
+    pub fn needs_metadata(&self) -> bool {
+        self.clauses
+            .iter()
+            .any(|c| matches!(c.filter, Filter::Size(_) | Filter::Modified(_)))
+    }
+
+    /// Check every clause except free text that is not negated, which is left to the
+    /// caller's fuzzy matching and ranking.
+    pub fn matches(&self, candidate: &Candidate, now: SystemTime) -> bool {
+        self.clauses.iter().all(|clause| match &clause.filter {
+            Filter::Text(_) if !clause.negated => true,
+            filter => filter.holds(candidate, now) != clause.negated,
+        })
+    }
+
+    /// The part of the query the index can answer by itself: the free text and the
+    /// filters that are not negated. Hits still have to be checked with [`Query::matches`].
+    pub fn index_query(&self, now: SystemTime) -> IndexQuery {
+        let mut query = IndexQuery {
+            text: self
+                .clauses
+                .iter()
+                .filter(|c| !c.negated)
+                .filter_map(|c| match &c.filter {
+                    // Quoted, so characters with a meaning in the index's syntax are not
+                    // taken as operators.
+                    Filter::Text(text) => Some(format!("\"{}\"", text.replace('"', " "))),
+                    _ => None,
+                })
+                .collect::<Vec<_>>()
+                .join(" "),
+            ..Default::default()
+        };
+        for clause in self.clauses.iter().filter(|c| !c.negated) {
+            match &clause.filter {
+                Filter::Ext(exts) if query.extensions.is_empty() => {
+                    query.extensions = exts.clone();
+                }
+                Filter::Kind(kind) if query.kind.is_none() => query.kind = Some(*kind),
+                Filter::Size(cmp) => {
+                    let (lo, hi) = size_bounds(cmp);
+                    query.min_size = Some(query.min_size.map_or(lo, |m| m.max(lo)));
+                    query.max_size = Some(query.max_size.map_or(hi, |m| m.min(hi)));
+                }
+                Filter::Modified(cmp) => {
+                    let range = time_range(cmp, now);
+                    if let Some(after) = range.after {
+                        query.modified_after =
+                            Some(query.modified_after.map_or(after, |t| t.max(after)));
+                    }
+                    if let Some(before) = range.before {
+                        query.modified_before =
+                            Some(query.modified_before.map_or(before, |t| t.min(before)));
+                    }
+                }
+                _ => {}
+            }
+        }
+        query
+    }
+}
+
+impl Filter {
+    fn holds(&self, c: &Candidate, now: SystemTime) -> bool {
+        match self {
+            Filter::Text(text) => c.name.to_lowercase().contains(&text.to_lowercase()),
+            Filter::Name(text) => c.name.to_lowercase().contains(text),
+            Filter::Path(text) => c.path.to_string_lossy().to_lowercase().contains(text),
+            Filter::Ext(exts) => c
+                .path
+                .extension()
+                .and_then(|e| e.to_str())
+                .is_some_and(|ext| exts.iter().any(|x| x.eq_ignore_ascii_case(ext))),
+            Filter::Size(cmp) => {
+                let (lo, hi) = size_bounds(cmp);
+                c.kind != FileKind::Dir && (lo..=hi).contains(&c.size)
+            }
+            Filter::Modified(cmp) => {
+                let range = time_range(cmp, now);
+                c.modified.is_some_and(|t| {
+                    range.after.is_none_or(|after| t >= after)
+                        && range.before.is_none_or(|before| t < before)
+                })
+            }
+            Filter::Kind(kind) => c.kind == *kind,
+            Filter::Tag(tag) => read_tags(c.path).iter().any(|t| t.to_lowercase() == *tag),
+        }
+    }
+}
+
+/// Inclusive bounds; `lo > hi` when nothing matches.
+fn size_bounds(cmp: &Comparison<u64>) -> (u64, u64) {
+    match *cmp {
+        Comparison::Cmp(Op::Lt, 0) => (1, 0),
+        Comparison::Cmp(Op::Lt, n) => (0, n - 1),
+        Comparison::Cmp(Op::Le, n) => (0, n),
+        Comparison::Cmp(Op::Eq, n) => (n, n),
+        Comparison::Cmp(Op::Ge, n) => (n, u64::MAX),
+        Comparison::Cmp(Op::Gt, n) => n.checked_add(1).map_or((1, 0), |n| (n, u64::MAX)),
+        Comparison::Between(lo, hi) => (lo.unwrap_or(0), hi.unwrap_or(u64::MAX)),
+    }
+}
+
+/// Modification times a comparison accepts, as of `now`. An age compares by how long
+/// ago, so `<7d` is the last seven days; a date covers its whole day.
+pub fn time_range(cmp: &Comparison<TimePoint>, now: SystemTime) -> TimeRange {
+    let at = |point: TimePoint| match point {
+        TimePoint::Ago(age) => now.checked_sub(age).unwrap_or(UNIX_EPOCH),
+        TimePoint::Date(day) => day_start(day),
+    };
+    let end = |point: TimePoint| match point {
+        TimePoint::Ago(age) => now.checked_sub(age).unwrap_or(UNIX_EPOCH),
+        TimePoint::Date(day) => day_start(day + 1),
+    };
+    match *cmp {
+        // Within the last `age`, or bare.
+        Comparison::Cmp(Op::Lt | Op::Le | Op::Eq, point @ TimePoint::Ago(_)) => TimeRange {
+            after: Some(at(point)),
+            before: None,
+        },
+        Comparison::Cmp(Op::Gt | Op::Ge, point @ TimePoint::Ago(_)) => TimeRange {
+            after: None,
+            before: Some(at(point)),
+        },
+        Comparison::Cmp(op, point @ TimePoint::Date(_)) => match op {
+            Op::Lt => TimeRange {
+                after: None,
+                before: Some(at(point)),
+            },
+            Op::Le => TimeRange {
+                after: None,
+                before: Some(end(point)),
+            },
+            Op::Eq => TimeRange {
+                after: Some(at(point)),
+                before: Some(end(point)),
+            },
+            Op::Ge => TimeRange {
+                after: Some(at(point)),
+                before: None,
+            },
+            Op::Gt => TimeRange {
+                after: Some(end(point)),
+                before: None,
+            },
+        },
+        Comparison::Between(from, to) => {
+            // Ages count backwards, so `1d..7d` reaches from seven days ago to one.
+            let (from, to) = match (from, to) {
+                (Some(TimePoint::Ago(a)), Some(TimePoint::Ago(b))) if a < b => {
+                    (Some(TimePoint::Ago(b)), Some(TimePoint::Ago(a)))
+                }
+                ends => ends,
+            };
+            TimeRange {
+                after: from.map(at),
+                before: to.map(end),
+            }
+        }
+    }
+}
+
+fn day_start(day: i64) -> SystemTime {
+    let secs = day.saturating_mul(DAY as i64);
+    if secs >= 0 {
+        UNIX_EPOCH + Duration::from_secs(secs as u64)
+    } else {
+        UNIX_EPOCH - Duration::from_secs(secs.unsigned_abs())
+    }
+}
+
+/// One clause as typed, quotes removed, with the 1-based column it starts at.
+struct Token {
+    column: usize,
+    text: String,
+    /// Byte offset of the first `:` outside quotes.
+    colon: Option<usize>,
+    /// Started with an unquoted `-`.
+    negated: bool,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().enumerate().peekable();
+    while let Some(&(ix, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut token = Token {
+            column: ix + 1,
+            text: String::new(),
+            colon: None,
+            negated: false,
+        };
+        let mut quote_column = None;
+        while let Some(&(ix, c)) = chars.peek() {
+            if quote_column.is_none() && c.is_whitespace() {
+                break;
+            }
+            chars.next();
+            match c {
+                '"' if quote_column.is_some() => quote_column = None,
+                '"' => quote_column = Some(ix + 1),
+                '-' if quote_column.is_none() && token.text.is_empty() && !token.negated => {
+                    token.negated = true;
+                }
+                ':' if quote_column.is_none() && token.colon.is_none() => {
+                    token.colon = Some(token.text.len());
+                    token.text.push(c);
+                }
+                _ => token.text.push(c),
+            }
+        }
+        if let Some(column) = quote_column {
+            return Err(invalid(column, "unterminated quote"));
+        }
+        if token.text.is_empty() {
+            let message = if token.negated {
+                "nothing to match after `-`"
+            } else {
+                "nothing to match between the quotes"
+            };
+            return Err(invalid(token.column, message));
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
+fn parse_clause(token: &Token) -> Result<Clause> {
+    let filter = match token.colon {
+        Some(colon) if is_key(&token.text[..colon], &token.text[colon + 1..]) => {
+            let key = token.text[..colon].to_lowercase();
+            let value = &token.text[colon + 1..];
+            let at = |message: String| invalid(token.column, message);
+            if value.is_empty() {
+                return Err(at(format!("`{key}:` needs a value")));
+            }
+            match key.as_str() {
+                "ext" => Filter::Ext(
+                    value
+                        .split(',')
+                        .map(|e| e.trim().trim_start_matches('.').to_lowercase())
+                        .filter(|e| !e.is_empty())
+                        .collect(),
+                ),
+                "size" => Filter::Size(parse_comparison(value, parse_size).map_err(at)?),
+                "modified" | "mtime" => {
+                    Filter::Modified(parse_comparison(value, parse_time_point).map_err(at)?)
+                }
+                "kind" | "type" => Filter::Kind(parse_kind(value).map_err(at)?),
+                "tag" => Filter::Tag(value.to_lowercase()),
+                "path" => Filter::Path(value.to_lowercase()),
+                "name" => Filter::Name(value.to_lowercase()),
+                _ => {
+                    return Err(at(format!(
+                        "unknown key `{key}`; expected ext, size, modified, kind, tag, path or name"
+                    )))
+                }
+            }
+        }
+        _ => Filter::Text(token.text.clone()),
+    };
+    Ok(Clause {
+        negated: token.negated,
+        filter,
+    })
+}
+
+/// Letters only, and not a URL scheme, so free text such as `12:30` or `http://` is not
+/// taken for a key.
+fn is_key(text: &str, value: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphabetic()) && !value.starts_with("//")
+}
+
+fn parse_comparison<T>(
+    value: &str,
+    parse: impl Fn(&str) -> std::result::Result<T, String>,
+) -> std::result::Result<Comparison<T>, String> {
+    if let Some((lo, hi)) = value.split_once("..") {
+        if lo.is_empty() && hi.is_empty() {
+            return Err("a range needs at least one end".to_string());
+        }
+        let end = |s: &str| (!s.is_empty()).then(|| parse(s)).transpose();
+        return Ok(Comparison::Between(end(lo)?, end(hi)?));
+    }
+    let (op, rest) = [
+        ("<=", Op::Le),
+        (">=", Op::Ge),
+        ("<", Op::Lt),
+        (">", Op::Gt),
+        ("=", Op::Eq),
+    ]
+    .iter()
+    .find_map(|(prefix, op)| value.strip_prefix(prefix).map(|rest| (*op, rest)))
+    .unwrap_or((Op::Eq, value));
+    Ok(Comparison::Cmp(op, parse(rest)?))
+}
+
+/// A number with an optional unit, split at the first character that is not part of
+/// the number.
+fn split_number(text: &str) -> std::result::Result<(f64, String), String> {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    let number = text[..end]
+        .parse::<f64>()
+        .map_err(|_| format!("expected a number in `{text}`"))?;
+    Ok((number, text[end..].to_lowercase()))
+}
+
+fn parse_size(text: &str) -> std::result::Result<u64, String> {
+    let (number, unit) = split_number(text)?;
+    let multiplier: u64 = match unit.as_str() {
+        "" | "b" => 1,
+        "k" | "kb" | "kib" => 1 << 10,
+        "m" | "mb" | "mib" => 1 << 20,
+        "g" | "gb" | "gib" => 1 << 30,
+        "t" | "tb" | "tib" => 1 << 40,
+        _ => {
+            return Err(format!(
+                "unknown size unit `{unit}`; expected b, k, m, g or t"
+            ))
+        }
+    };
+    Ok((number * multiplier as f64).round() as u64)
+}
+
+fn parse_time_point(text: &str) -> std::result::Result<TimePoint, String> {
+    if let Some(day) = parse_date(text)? {
+        return Ok(TimePoint::Date(day));
+    }
+    let not_age =
+        || format!("expected an age such as `7d` or a date such as `2024-05-01`, not `{text}`");
+    let (number, unit) = split_number(text)?;
+    let seconds: u64 = match unit.as_str() {
+        "s" | "sec" | "secs" => 1,
+        "m" | "min" | "mins" => 60,
+        "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60,
+        "d" | "day" | "days" => DAY,
+        "w" | "week" | "weeks" => 7 * DAY,
+        "mo" | "month" | "months" => 30 * DAY,
+        "y" | "year" | "years" => 365 * DAY,
+        _ => return Err(not_age()),
+    };
+    // Fails for ages too long to represent, which are not ages anyone means.
+    Duration::try_from_secs_f64(number * seconds as f64)
+        .map(TimePoint::Ago)
+        .map_err(|_| not_age())
+}
+
+/// `YYYY-MM-DD` as days since 1970-01-01. `None` if `text` does not look like a date.
+fn parse_date(text: &str) -> std::result::Result<Option<i64>, String> {
+    let parts: Vec<&str> = text.split('-').collect();
+    let [y, m, d] = parts[..] else {
+        return Ok(None);
+    };
+    if y.len() != 4 || !text.chars().all(|c| c.is_ascii_digit() || c == '-') {
+        return Ok(None);
+    }
+    let bad = || format!("`{text}` is not a valid date");
+    let (year, month, day): (i64, u32, u32) = (
+        y.parse().map_err(|_| bad())?,
+        m.parse().map_err(|_| bad())?,
+        d.parse().map_err(|_| bad())?,
+    );
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return Err(bad()),
+    };
+    if day == 0 || day > days_in_month {
+        return Err(bad());
+    }
+    Ok(Some(days_from_civil(year, month, day)))
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let m = month as i64;
+    let doy = (153 * (if m > 2 { m - 3 } else { m + 9 }) + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
+
+fn parse_kind(text: &str) -> std::result::Result<FileKind, String> {
+    match text.to_lowercase().as_str() {
+        "f" | "file" => Ok(FileKind::File),
+        "d" | "dir" | "directory" | "folder" => Ok(FileKind::Dir),
+        "l" | "link" | "symlink" => Ok(FileKind::Symlink),
+        other => other
+            .parse()
+            .map_err(|()| format!("unknown kind `{text}`; expected file, dir or link")),
+    }
+}
+
+fn invalid(column: usize, message: impl Into<String>) -> Error {
+    Error::InvalidQuery {
+        column,
+        message: message.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(input: &str) -> Vec<(bool, Filter)> {
+        Query::parse(input)
+            .unwrap()
+            .clauses
+            .into_iter()
+            .map(|c| (c.negated, c.filter))
+            .collect()
+    }
+
+    fn filter(input: &str) -> Filter {
+        let mut parsed = filters(input);
+        assert_eq!(parsed.len(), 1, "{input}");
+        parsed.remove(0).1
+    }
+
+    fn error(input: &str) -> (usize, String) {
+        match Query::parse(input) {
+            Err(Error::InvalidQuery { column, message }) => (column, message),
+            other => panic!("{input}: {other:?}"),
+        }
+    }
+
+    fn candidate<'a>(path: &'a Path, kind: FileKind, size: u64) -> Candidate<'a> {
+        Candidate {
+            path,
+            name: path.file_name().unwrap().to_str().unwrap(),
+            kind,
+            size,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn terms_and_negation() {
+        let query = Query::parse("  report -draft  q3 ").unwrap();
+        assert_eq!(
+            query
+                .clauses
+                .iter()
+                .map(|c| (c.negated, c.filter.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (false, Filter::Text("report".into())),
+                (true, Filter::Text("draft".into())),
+                (false, Filter::Text("q3".into())),
+            ]
+        );
+        assert_eq!(query.text(), "report q3");
+        assert!(Query::parse("").unwrap().is_empty());
+        // Only a leading `-` negates.
+        assert_eq!(filter("a-b"), Filter::Text("a-b".into()));
+        assert_eq!(filters("--x"), [(true, Filter::Text("-x".into()))]);
+    }
+
+    #[test]
+    fn quotes_keep_spaces() {
+        assert_eq!(
+            filters(r#""annual report" name:"my file" -"old draft""#),
+            [
+                (false, Filter::Text("annual report".into())),
+                (false, Filter::Name("my file".into())),
+                (true, Filter::Text("old draft".into())),
+            ]
+        );
+        // A quoted colon does not make a key.
+        assert_eq!(filter(r#""ext:pdf""#), Filter::Text("ext:pdf".into()));
+    }
+
+    #[test]
+    fn malformed_terms_point_at_their_column() {
+        assert_eq!(error(r#"a "open"#), (3, "unterminated quote".into()));
+        assert_eq!(error("a -"), (3, "nothing to match after `-`".into()));
+        assert_eq!(
+            error(r#"a """#),
+            (3, "nothing to match between the quotes".into())
+        );
+        assert_eq!(error(r#"-"""#).1, "nothing to match after `-`");
+        assert_eq!(error("x ext:").0, 3);
+        assert!(error("colour:red").1.starts_with("unknown key `colour`"));
+    }
+
+    #[test]
+    fn keys_need_letters() {
+        assert_eq!(filter("http://x"), Filter::Text("http://x".into()));
+        assert_eq!(filter("12:30"), Filter::Text("12:30".into()));
+        assert_eq!(filter("EXT:Md"), Filter::Ext(vec!["md".into()]));
+        assert_eq!(filter("type:d"), Filter::Kind(FileKind::Dir));
+        assert_eq!(filter("tag:Work"), Filter::Tag("work".into()));
+        assert_eq!(filter("path:Src"), Filter::Path("src".into()));
+        assert!(error("kind:socketish").1.starts_with("unknown kind"));
+    }
+
+    #[test]
+    fn extension_lists() {
+        assert_eq!(
+            filter("ext:.JPG,png,,"),
+            Filter::Ext(vec!["jpg".into(), "png".into()])
+        );
+    }
+
+    #[test]
+    fn sizes() {
+        let size = |input: &str| match filter(input) {
+            Filter::Size(cmp) => cmp,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(size("size:>10mb"), Comparison::Cmp(Op::Gt, 10 << 20));
+        assert_eq!(size("size:<=4k"), Comparison::Cmp(Op::Le, 4096));
+        assert_eq!(size("size:1.5K"), Comparison::Cmp(Op::Eq, 1536));
+        assert_eq!(size("size:=7"), Comparison::Cmp(Op::Eq, 7));
+        assert_eq!(
+            size("size:1mb..1gb"),
+            Comparison::Between(Some(1 << 20), Some(1 << 30))
+        );
+        assert_eq!(size("size:..2t"), Comparison::Between(None, Some(2 << 40)));
+        assert!(error("size:10x").1.starts_with("unknown size unit"));
+        assert!(error("size:big").1.starts_with("expected a number"));
+        assert_eq!(error("size:..").1, "a range needs at least one end");
+        // Too large to count saturates rather than failing.
+        assert_eq!(
+            size(&format!("size:{}t", "9".repeat(30))),
+            Comparison::Cmp(Op::Eq, u64::MAX)
+        );
+    }
+
+    #[test]
+    fn ages_and_dates() {
+        let modified = |input: &str| match filter(input) {
+            Filter::Modified(cmp) => cmp,
+            other => panic!("{other:?}"),
+        };
+        let days = |n: u64| TimePoint::Ago(Duration::from_secs(n * DAY));
+        assert_eq!(modified("modified:<7d"), Comparison::Cmp(Op::Lt, days(7)));
+        assert_eq!(modified("mtime:>1y"), Comparison::Cmp(Op::Gt, days(365)));
+        assert_eq!(
+            modified("modified:90min"),
+            Comparison::Cmp(Op::Eq, TimePoint::Ago(Duration::from_secs(90 * 60)))
+        );
+        assert_eq!(
+            modified("modified:1970-01-02"),
+            Comparison::Cmp(Op::Eq, TimePoint::Date(1))
+        );
+        assert_eq!(
+            modified("modified:2024-02-29..2024-03-01"),
+            Comparison::Between(Some(TimePoint::Date(19782)), Some(TimePoint::Date(19783)))
+        );
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert!(error("modified:2023-02-29").1.contains("not a valid date"));
+        assert!(error("modified:2024-13-01").1.contains("not a valid date"));
+        assert!(error("modified:7x").1.starts_with("expected an age"));
+    }
+
+    #[test]
+    fn ages_too_long_to_represent_are_errors() {
+        for input in [
+            "modified:<99999999999999999999y",
+            "modified:99999999999999999999999999999999years",
+            &format!("modified:{}d", "9".repeat(400)),
+        ] {
+            let (column, message) = error(input);
+            assert_eq!(column, 1);
+            assert!(message.starts_with("expected an age"), "{message}");
+        }
+    }
+
+    #[test]
+    fn time_ranges() {
+        let now = UNIX_EPOCH + Duration::from_secs(100 * DAY);
+        let ago = |d: u64| UNIX_EPOCH + Duration::from_secs((100 - d) * DAY);
+        let week = TimePoint::Ago(Duration::from_secs(7 * DAY));
+        let day = TimePoint::Ago(Duration::from_secs(DAY));
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Lt, week), now),
+            TimeRange {
+                after: Some(ago(7)),
+                before: None
+            }
+        );
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Gt, week), now),
+            TimeRange {
+                after: None,
+                before: Some(ago(7))
+            }
+        );
+        // Written either way round, an age range reaches from the older end.
+        assert_eq!(
+            time_range(&Comparison::Between(Some(day), Some(week)), now),
+            TimeRange {
+                after: Some(ago(7)),
+                before: Some(ago(1))
+            }
+        );
+        let date = TimePoint::Date(10);
+        let start = |d: u64| UNIX_EPOCH + Duration::from_secs(d * DAY);
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Eq, date), now),
+            TimeRange {
+                after: Some(start(10)),
+                before: Some(start(11))
+            }
+        );
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Gt, date), now).after,
+            Some(start(11))
+        );
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Le, date), now).before,
+            Some(start(11))
+        );
+        // Ages beyond what the clock can represent stop at the epoch.
+        let long = TimePoint::Ago(Duration::from_secs(u64::MAX));
+        assert_eq!(
+            time_range(&Comparison::Cmp(Op::Lt, long), now).after,
+            Some(UNIX_EPOCH)
+        );
+    }
+
+    #[test]
+    fn matching_candidates() {
+        let now = SystemTime::now();
+        let file = Path::new("/work/Src/Report.PDF");
+        let dir = Path::new("/work/reports");
+        let holds = |input: &str, c: &Candidate| Query::parse(input).unwrap().matches(c, now);
+        let pdf = candidate(file, FileKind::File, 2048);
+        let folder = candidate(dir, FileKind::Dir, 4096);
+
+        // Plain free text is left to the caller; negated, it excludes names.
+        assert!(holds("unrelated", &pdf));
+        assert!(!holds("-report", &pdf));
+        assert!(holds("-draft", &pdf));
+        assert!(holds("ext:pdf", &pdf));
+        assert!(!holds("ext:pdf", &folder));
+        assert!(holds("size:>1k size:<4k", &pdf));
+        assert!(!holds("size:>1k", &folder));
+        assert!(holds("kind:dir -kind:file", &folder));
+        assert!(holds("path:src name:report", &pdf));
+        assert!(!holds("-path:/work", &pdf));
+
+        assert!(!holds("modified:<1d", &pdf));
+        let recent = Candidate {
+            modified: Some(now - Duration::from_secs(60)),
+            ..pdf
+        };
+        assert!(holds("modified:<1d", &recent));
+        assert!(!holds("modified:>1d", &recent));
+    }
+
+    #[test]
+    fn what_the_index_answers() {
+        let query =
+            Query::parse(r#"a "b c" -d ext:md,txt kind:file size:>1k size:<1m -ext:rs"#).unwrap();
+        assert!(query.needs_metadata());
+        assert!(!Query::parse("ext:md").unwrap().needs_metadata());
+        let index = query.index_query(SystemTime::now());
+        assert_eq!(index.text, r#""a" "b c""#);
+        assert_eq!(index.extensions, ["md", "txt"]);
+        assert_eq!(index.kind, Some(FileKind::File));
+        assert_eq!(index.min_size, Some(1025));
+        assert_eq!(index.max_size, Some((1 << 20) - 1));
+        assert_eq!(index.modified_after, None);
+    }
+}
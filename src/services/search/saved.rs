@@ -0,0 +1,379 @@
+//! Saved searches, browsed as smart folders.
+//!
+//! A saved search is a query in the [query language](crate::services::search::query)
+//! together with the directory it searches below, under a name. Opening one lists its
+//! current results as if they were the contents of a directory. The definitions are kept
+//! in the data directory and can be exported to, and imported from, a JSON file.
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::core::paths;
+use crate::models::file_entry::FileEntry;
+use crate::services::fs::listing::stat_entry;
+use crate::services::search::index::SearchIndex;
+use crate::services::search::names::{search_names, NameSearchEvent, NameSearchParams};
+use crate::services::search::query::Query;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Bumped when the file format changes incompatibly.
+const VERSION: u32 = 1;
+/// Most results a smart folder shows.
+const RESULT_LIMIT: usize = 5000;
+
+/// Where a smart folder's results come from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultSource {
+    /// Walk the root each time: always current, slower on large trees.
+    #[default]
+    Walk,
+    /// Ask the persistent index: instant, as current as the index.
+    Index,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SavedSearch {
+    /// Unique among saved searches; shown as the folder's label.
+    pub name: String,
+    pub query: String,
+    pub root: PathBuf,
+    #[serde(default)]
+    pub source: ResultSource,
+    /// Keep the results up to date while the folder is open.
+    #[serde(default)]
+    pub live: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    version: u32,
+    searches: Vec<SavedSearch>,
+}
+
+/// The saved searches, persisted to a JSON file on every change.
+#[derive(Debug)]
+pub struct SavedSearches {
+    path: PathBuf,
+    state: State,
+}
+
+impl SavedSearches {
+    /// Open the definitions stored at `path`, starting empty if it does not exist yet.
+    /// A file that cannot be parsed, or was written by a newer version, is moved aside
+    /// to `<name>.json.bak` so the next save does not overwrite it.
+    pub fn open(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let state = match read_state(&path) {
+            Ok(state) => state,
+            Err(Error::Vanished { .. }) => State::default(),
+            Err(Error::Other(err)) => {
+                let backup = path.with_extension("json.bak");
+                fs::rename(&path, &backup).map_err(|e| Error::from_io(&path, e))?;
+                tracing::warn!(
+                    path = %path.display(),
+                    backup = %backup.display(),
+                    %err,
+                    "moved unreadable saved searches aside"
+                );
+                State::default()
+            }
+            Err(err) => return Err(err),
+        };
+        Ok(Self { path, state })
+    }
+
+    /// Open the definitions in the nohrs data directory.
+    pub fn open_default() -> Result<Self> {
+        Self::open(paths::data_dir().join("saved_searches.json"))
+    }
+
+    /// In the order they were first saved.
+    pub fn list(&self) -> &[SavedSearch] {
+        &self.state.searches
+    }
+
+    pub fn get(&self, name: &str) -> Option<&SavedSearch> {
+        self.state.searches.iter().find(|s| s.name == name)
+    }
+
+    /// Save `search`, replacing the one with the same name. Fails with
+    /// `Error::InvalidQuery` if its query does not parse.
+    pub fn save(&mut self, search: SavedSearch) -> Result<()> {
+        Query::parse(&search.query)?;
+        match self
+            .state
+            .searches
+            .iter_mut()
+            .find(|s| s.name == search.name)
+        {
+            Some(existing) => *existing = search,
+            None => self.state.searches.push(search),
+        }
+        self.write()
+    }
+
+    /// Forget the search called `name`. Returns whether there was one.
+    pub fn remove(&mut self, name: &str) -> Result<bool> {
+        let before = self.state.searches.len();
+        self.state.searches.retain(|s| s.name != name);
+        if self.state.searches.len() == before {
+            return Ok(false);
+        }
+        self.write()?;
+        Ok(true)
+    }
+
+    /// Write every definition to `path`, in the same format the store uses.
+    pub fn export(&self, path: &Path) -> Result<()> {
+        write_state(path, &self.state)
+    }
+
+    /// Add the definitions from a file written by [`SavedSearches::export`], replacing
+    /// those with the same names. Definitions whose query does not parse are skipped.
+    /// Returns how many were imported.
+    pub fn import(&mut self, path: &Path) -> Result<usize> {
+        let imported = read_state(path)?;
+        let mut count = 0;
+        for search in imported.searches {
+            if let Err(err) = Query::parse(&search.query) {
+                tracing::warn!(name = %search.name, %err, "skipping saved search");
+                continue;
+            }
+            match self
+                .state
+                .searches
+                .iter_mut()
+                .find(|s| s.name == search.name)
+            {
+                Some(existing) => *existing = search,
+                None => self.state.searches.push(search),
+            }
+            count += 1;
+        }
+        self.write()?;
+        Ok(count)
+    }
+
+    fn write(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir).map_err(|e| Error::from_io(dir, e))?;
+        }
+        write_state(&self.path, &self.state)
+    }
+}
+
+fn read_state(path: &Path) -> Result<State> {
+    let data = fs::read(path).map_err(|e| Error::from_io(path, e))?;
+    let state: State = serde_json::from_slice(&data)
+        .map_err(|e| Error::Other(format!("cannot read saved searches: {e}")))?;
+    if state.version != VERSION {
+        return Err(Error::Other(format!(
+            "saved searches have unknown version {}",
+            state.version
+        )));
+    }
+    Ok(state)
+}
+
+/// Write atomically via a temporary file.
+fn write_state(path: &Path, state: &State) -> Result<()> {
+    let state = State {
+        version: VERSION,
+        searches: state.searches.clone(),
+    };
+    let data = serde_json::to_vec_pretty(&state)
+        .map_err(|e| Error::Other(format!("cannot encode saved searches: {e}")))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, data).map_err(|e| Error::from_io(&tmp, e))?;
+    fs::rename(&tmp, path).map_err(|e| Error::from_io(path, e))
+}
+
+/// The current results of `search` as listing entries, best matches first. Runs on the
+/// calling thread and stops with `Error::Cancelled` once `cancel` is set.
+pub fn run_saved_search(search: &SavedSearch, cancel: &CancelToken) -> Result<Vec<FileEntry>> {
+    let query = Query::parse(&search.query)?;
+    let paths: Vec<PathBuf> = match search.source {
+        ResultSource::Walk => {
+            let mut ranked = Vec::new();
+            let params = NameSearchParams {
+                root: search.root.clone(),
+                query,
+                limit: RESULT_LIMIT,
+                ..Default::default()
+            };
+            for event in search_names(params) {
+                if cancel.is_cancelled() {
+                    return Err(Error::Cancelled);
+                }
+                match event {
+                    NameSearchEvent::Ranked(hits) => ranked = hits,
+                    NameSearchEvent::EntryError(err) => {
+                        tracing::debug!(%err, "smart folder skipped an entry");
+                    }
+                    NameSearchEvent::Done { .. } => break,
+                }
+            }
+            ranked.into_iter().map(|hit| hit.path).collect()
+        }
+        ResultSource::Index => SearchIndex::shared()?
+            .search_parsed(&query, Some(search.root.clone()), RESULT_LIMIT)?
+            .into_iter()
+            .map(|hit| hit.path)
+            .collect(),
+    };
+
+    let mut errors = Vec::new();
+    let mut entries = Vec::with_capacity(paths.len());
+    for path in paths {
+        if cancel.is_cancelled() {
+            return Err(Error::Cancelled);
+        }
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        entries.extend(stat_entry(name, &path, &mut errors));
+    }
+    for err in errors {
+        tracing::debug!(%err, "smart folder result could not be read");
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn search(name: &str, query: &str, root: &Path) -> SavedSearch {
+        SavedSearch {
+            name: name.to_string(),
+            query: query.to_string(),
+            root: root.to_path_buf(),
+            source: ResultSource::Walk,
+            live: false,
+        }
+    }
+
+    fn names(saved: &SavedSearches) -> Vec<&str> {
+        saved.list().iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn saves_replaces_and_removes() {
+        let tmp = TempDir::new();
+        let file = tmp.path().join("nested/saved.json");
+        let mut saved = SavedSearches::open(&file).unwrap();
+        assert!(saved.list().is_empty());
+
+        saved.save(search("pdfs", "ext:pdf", tmp.path())).unwrap();
+        saved.save(search("big", "size:>1g", tmp.path())).unwrap();
+        saved
+            .save(search("pdfs", "ext:pdf -draft", tmp.path()))
+            .unwrap();
+        assert_eq!(names(&saved), ["pdfs", "big"]);
+        assert_eq!(saved.get("pdfs").unwrap().query, "ext:pdf -draft");
+        assert!(saved.get("none").is_none());
+
+        assert!(matches!(
+            saved.save(search("bad", "size:lots", tmp.path())),
+            Err(Error::InvalidQuery { .. })
+        ));
+        assert_eq!(names(&saved), ["pdfs", "big"]);
+
+        assert!(saved.remove("big").unwrap());
+        assert!(!saved.remove("big").unwrap());
+
+        let reopened = SavedSearches::open(&file).unwrap();
+        assert_eq!(reopened.list(), saved.list());
+    }
+
+    #[test]
+    fn unreadable_files_start_empty() {
+        let tmp = TempDir::new();
+        let garbage = tmp.write("garbage.json", "{ not json");
+        let mut saved = SavedSearches::open(&garbage).unwrap();
+        assert!(saved.list().is_empty());
+        saved.save(search("pdfs", "ext:pdf", tmp.path())).unwrap();
+        let backup = tmp.path().join("garbage.json.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), "{ not json");
+
+        let newer = r#"{"version": 99, "searches": []}"#;
+        let future = tmp.write("future.json", newer);
+        assert!(SavedSearches::open(&future).unwrap().list().is_empty());
+        let backup = tmp.path().join("future.json.bak");
+        assert_eq!(fs::read_to_string(backup).unwrap(), newer);
+    }
+
+    #[test]
+    fn optional_fields_default() {
+        let tmp = TempDir::new();
+        let file = tmp.write(
+            "saved.json",
+            r#"{"version": 1, "searches": [{"name": "a", "query": "x", "root": "/r"}]}"#,
+        );
+        let saved = SavedSearches::open(&file).unwrap();
+        let a = saved.get("a").unwrap();
+        assert_eq!(a.source, ResultSource::Walk);
+        assert!(!a.live);
+    }
+
+    #[test]
+    fn export_and_import() {
+        let tmp = TempDir::new();
+        let mut first = SavedSearches::open(tmp.path().join("first.json")).unwrap();
+        first.save(search("pdfs", "ext:pdf", tmp.path())).unwrap();
+        first.save(search("logs", "ext:log", tmp.path())).unwrap();
+        let exported = tmp.path().join("export.json");
+        first.export(&exported).unwrap();
+
+        let mut second = SavedSearches::open(tmp.path().join("second.json")).unwrap();
+        second.save(search("pdfs", "old", tmp.path())).unwrap();
+        second.save(search("mine", "x", tmp.path())).unwrap();
+        assert_eq!(second.import(&exported).unwrap(), 2);
+        assert_eq!(names(&second), ["pdfs", "mine", "logs"]);
+        assert_eq!(second.get("pdfs").unwrap().query, "ext:pdf");
+
+        // Bad queries in an imported file are skipped, not fatal.
+        let mixed = tmp.write(
+            "mixed.json",
+            r#"{"version": 1, "searches": [
+                {"name": "ok", "query": "kind:dir", "root": "/"},
+                {"name": "bad", "query": "size:lots", "root": "/"}
+            ]}"#,
+        );
+        assert_eq!(second.import(&mixed).unwrap(), 1);
+        assert!(second.get("bad").is_none());
+        assert!(second.import(&tmp.path().join("missing.json")).is_err());
+
+        let reopened = SavedSearches::open(tmp.path().join("second.json")).unwrap();
+        assert_eq!(reopened.list(), second.list());
+    }
+
+    #[test]
+    fn runs_a_walk_search() {
+        let tmp = TempDir::new();
+        tmp.write("docs/report.pdf", "");
+        tmp.write("docs/report.txt", "");
+        tmp.write("docs/draft-report.pdf", "");
+        let folder = search("pdfs", "report ext:pdf -draft", tmp.path());
+        let entries = run_saved_search(&folder, &CancelToken::new()).unwrap();
+        let found: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(found, ["report.pdf"]);
+        assert_eq!(
+            entries[0].fs_path().unwrap(),
+            tmp.path().join("docs/report.pdf")
+        );
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(matches!(
+            run_saved_search(&folder, &cancel),
+            Err(Error::Cancelled)
+        ));
+        let bad = search("bad", "\"open", tmp.path());
+        assert!(run_saved_search(&bad, &CancelToken::new()).is_err());
+    }
+}
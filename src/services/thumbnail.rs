@@ -0,0 +1,718 @@
+//! Image thumbnails, cached on disk.
+//!
+//! The cache follows the [freedesktop thumbnail spec]: a thumbnail is a PNG named after
+//! the MD5 of the original's `file://` URI, in a directory per size, and records the
+//! original's modification time and length in `Thumb::` text chunks. A thumbnail whose
+//! recorded time or length no longer match is stale and made again. Images that cannot
+//! be decoded get an empty marker under `fail/nohrs` so they are not retried until they
+//! change.
+//!
+//! PNG and JPEG are always supported; the `image-formats` feature adds GIF, WebP, BMP and
+//! TIFF. EXIF orientation is applied, so photos come out upright.
+//!
+//! [freedesktop thumbnail spec]: https://specifications.freedesktop.org/thumbnail-spec/latest/
+
+use crate::core::cancel::CancelToken;
+use crate::core::errors::{Error, Result};
+use crate::core::paths;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use md5::{Digest, Md5};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::UNIX_EPOCH;
+
+/// Files larger than this are not decoded.
+pub const MAX_SOURCE_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Name under `fail/` for the markers of images nohrs could not thumbnail.
+const APP_NAME: &str = "nohrs";
+
+/// The sizes the spec defines. A thumbnail fits in a square of [`ThumbnailSize::pixels`]
+/// a side; smaller images are not scaled up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ThumbnailSize {
+    Normal,
+    Large,
+    XLarge,
+    XxLarge,
+}
+
+impl ThumbnailSize {
+    pub fn pixels(self) -> u32 {
+        match self {
+            Self::Normal => 128,
+            Self::Large => 256,
+            Self::XLarge => 512,
+            Self::XxLarge => 1024,
+        }
+    }
+
+    /// The directory the spec keeps thumbnails of this size in.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Large => "large",
+            Self::XLarge => "x-large",
+            Self::XxLarge => "xx-large",
+        }
+    }
+}
+
+/// Whether thumbnails can be made for `path`, judged by its extension.
+pub fn is_supported(path: &Path) -> bool {
+    ImageFormat::from_path(path).is_ok_and(|format| format.reading_enabled())
+}
+
+/// The `file://` URI the spec keys `path` by. `path` should be absolute.
+pub fn file_uri(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path_bytes(path).iter() {
+        // Escaped as GLib does, so the hashes match those of other file managers.
+        if byte.is_ascii_alphanumeric() || b"!$&'()*+,-./:=@_~".contains(&byte) {
+            uri.push(byte as char);
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+    std::borrow::Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> std::borrow::Cow<'_, [u8]> {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let path = if path.starts_with('/') {
+        path
+    } else {
+        format!("/{path}")
+    };
+    std::borrow::Cow::Owned(path.into_bytes())
+}
+
+/// What the spec records about an original: its URI, and the modification time in whole
+/// seconds and length it had when the thumbnail was made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Source {
+    uri: String,
+    mtime: u64,
+    size: u64,
+}
+
+impl Source {
+    fn of(path: &Path) -> Result<Self> {
+        let md = fs::metadata(path).map_err(|e| Error::from_io(path, e))?;
+        let mtime = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        Ok(Self {
+            uri: file_uri(path),
+            mtime,
+            size: md.len(),
+        })
+    }
+
+    /// Whether a thumbnail with these text chunks was made from this version of the file.
+    fn matches(&self, chunks: &[(String, String)]) -> bool {
+        let get = |key: &str| chunks.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+        get("Thumb::URI") == Some(&self.uri)
+            && get("Thumb::MTime").and_then(|v| v.parse().ok()) == Some(self.mtime)
+            // Optional in the spec, so only a recorded length that differs counts.
+            && get("Thumb::Size").is_none_or(|v| v.parse().ok() == Some(self.size))
+    }
+}
+
+/// The thumbnail cache under a root directory. Safe to share between threads.
+#[derive(Debug, Clone)]
+pub struct ThumbnailCache {
+    root: PathBuf,
+}
+
+impl ThumbnailCache {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The cache in [`paths::thumbnail_dir`].
+    pub fn open_default() -> Self {
+        Self::new(paths::thumbnail_dir())
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Where the thumbnail of `path` at `size` is kept, whether or not it exists.
+    pub fn thumbnail_path(&self, path: &Path, size: ThumbnailSize) -> PathBuf {
+        self.root
+            .join(size.dir_name())
+            .join(format!("{}.png", md5_hex(file_uri(path).as_bytes())))
+    }
+
+    fn failure_path(&self, path: &Path) -> PathBuf {
+        self.root
+            .join("fail")
+            .join(APP_NAME)
+            .join(format!("{}.png", md5_hex(file_uri(path).as_bytes())))
+    }
+
+    /// The cached thumbnail of `path` at `size`, if there is one and it is up to date.
+    pub fn lookup(&self, path: &Path, size: ThumbnailSize) -> Option<PathBuf> {
+        let source = Source::of(path).ok()?;
+        let thumb = self.thumbnail_path(path, size);
+        source.matches(&read_chunks(&thumb)?).then_some(thumb)
+    }
+
+    /// The thumbnail of `path` at `size`, made and cached first if there is no current
+    /// one. `None` if the file is not a supported image, is too large, is itself in the
+    /// cache, or failed to decode before and has not changed since. A decoding failure
+    /// is returned as `Error::Image` and remembered.
+    pub fn thumbnail(&self, path: &Path, size: ThumbnailSize) -> Result<Option<PathBuf>> {
+        if !is_supported(path) || path.starts_with(&self.root) {
+            return Ok(None);
+        }
+        let source = Source::of(path)?;
+        if source.size > MAX_SOURCE_SIZE {
+            return Ok(None);
+        }
+        let thumb = self.thumbnail_path(path, size);
+        if read_chunks(&thumb).is_some_and(|chunks| source.matches(&chunks)) {
+            return Ok(Some(thumb));
+        }
+        let failure = self.failure_path(path);
+        if read_chunks(&failure).is_some_and(|chunks| source.matches(&chunks)) {
+            return Ok(None);
+        }
+
+        let image = match decode(path) {
+            Ok(image) => image,
+            Err(err @ Error::Image(_)) => {
+                let marker = DynamicImage::new_rgba8(1, 1);
+                if let Err(err) = write_png(&failure, &marker, &source, None) {
+                    tracing::debug!(%err, "cannot record thumbnail failure");
+                }
+                return Err(err);
+            }
+            Err(err) => return Err(err),
+        };
+        let (width, height) = (image.width(), image.height());
+        let pixels = size.pixels();
+        let scaled = if width > pixels || height > pixels {
+            image.thumbnail(pixels, pixels)
+        } else {
+            image
+        };
+        write_png(&thumb, &scaled, &source, Some((width, height)))?;
+        Ok(Some(thumb))
+    }
+
+    /// Forget every thumbnail of `path`, e.g. after it was deleted.
+    pub fn remove(&self, path: &Path) {
+        let sizes = [
+            ThumbnailSize::Normal,
+            ThumbnailSize::Large,
+            ThumbnailSize::XLarge,
+            ThumbnailSize::XxLarge,
+        ];
+        for thumb in sizes
+            .into_iter()
+            .map(|size| self.thumbnail_path(path, size))
+            .chain([self.failure_path(path)])
+        {
+            let _ = fs::remove_file(thumb);
+        }
+    }
+}
+
+/// Decode `path` upright.
+fn decode(path: &Path) -> Result<DynamicImage> {
+    let image_err = |e: image::ImageError| Error::Image(format!("{}: {e}", path.display()));
+    let reader = ImageReader::open(path)
+        .map_err(|e| Error::from_io(path, e))?
+        .with_guessed_format()
+        .map_err(|e| Error::from_io(path, e))?;
+    let mut decoder = reader.into_decoder().map_err(image_err)?;
+    let orientation = decoder.orientation().map_err(image_err)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(image_err)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// The `tEXt` chunks of the PNG at `path`, or `None` if it cannot be read.
+fn read_chunks(path: &Path) -> Option<Vec<(String, String)>> {
+    let file = File::open(path).ok()?;
+    let reader = png::Decoder::new(BufReader::new(file)).read_info().ok()?;
+    Some(
+        reader
+            .info()
+            .uncompressed_latin1_text
+            .iter()
+            .map(|chunk| (chunk.keyword.clone(), chunk.text.clone()))
+            .collect(),
+    )
+}
+
+/// Write `image` as a thumbnail of `source`, atomically and readable only by the user as
+/// the spec asks. `original` is the full image's width and height.
+fn write_png(
+    path: &Path,
+    image: &DynamicImage,
+    source: &Source,
+    original: Option<(u32, u32)>,
+) -> Result<()> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let dir = path.parent().unwrap_or(Path::new("."));
+    create_private_dir(dir)?;
+    let tmp = dir.join(format!(
+        ".{}-{}-{}.png",
+        APP_NAME,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    let png_err = |e: png::EncodingError| Error::Image(format!("{}: {e}", tmp.display()));
+
+    let rgba = image.to_rgba8();
+    let file = create_private_file(&tmp)?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), rgba.width(), rgba.height());
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut chunks = vec![
+        ("Thumb::URI", source.uri.clone()),
+        ("Thumb::MTime", source.mtime.to_string()),
+        ("Thumb::Size", source.size.to_string()),
+        ("Software", APP_NAME.to_string()),
+    ];
+    if let Some((width, height)) = original {
+        chunks.push(("Thumb::Image::Width", width.to_string()));
+        chunks.push(("Thumb::Image::Height", height.to_string()));
+    }
+    for (key, value) in chunks {
+        encoder.add_text_chunk(key.into(), value).map_err(png_err)?;
+    }
+    let written = encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgba))
+        .map_err(png_err);
+    if let Err(err) = written {
+        let _ = fs::remove_file(&tmp);
+        return Err(err);
+    }
+    fs::rename(&tmp, path).map_err(|e| Error::from_io(path, e))
+}
+
+#[cfg(unix)]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    use std::os::unix::fs::DirBuilderExt;
+    fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)
+        .map_err(|e| Error::from_io(dir, e))
+}
+
+#[cfg(not(unix))]
+fn create_private_dir(dir: &Path) -> Result<()> {
+    fs::create_dir_all(dir).map_err(|e| Error::from_io(dir, e))
+}
+
+#[cfg(unix)]
+fn create_private_file(path: &Path) -> Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)
+        .map_err(|e| Error::from_io(path, e))
+}
+
+#[cfg(not(unix))]
+fn create_private_file(path: &Path) -> Result<File> {
+    File::create_new(path).map_err(|e| Error::from_io(path, e))
+}
+
+/// Thumbnail of one file from a [`ThumbnailStream`].
+#[derive(Debug)]
+pub struct ThumbnailResult {
+    pub path: PathBuf,
+    pub size: ThumbnailSize,
+    /// As returned by [`ThumbnailCache::thumbnail`].
+    pub thumbnail: Result<Option<PathBuf>>,
+}
+
+/// Receiving end of background thumbnailing. Dropping it cancels the rest.
+pub struct ThumbnailStream {
+    rx: Receiver<ThumbnailResult>,
+    cancelled: CancelToken,
+}
+
+impl ThumbnailStream {
+    /// Non-blocking poll for UI loops. `Disconnected` means every thumbnail has been sent.
+    pub fn try_next(&self) -> std::result::Result<ThumbnailResult, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.cancel();
+    }
+}
+
+impl Iterator for ThumbnailStream {
+    type Item = ThumbnailResult;
+
+    fn next(&mut self) -> Option<ThumbnailResult> {
+        self.rx.recv().ok()
+    }
+}
+
+impl Drop for ThumbnailStream {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+/// Make thumbnails of the supported images among `paths` at `size` on a background
+/// thread, serving cached ones first and otherwise in the order given.
+pub fn generate_thumbnails(
+    paths: Vec<PathBuf>,
+    size: ThumbnailSize,
+    cache: Arc<ThumbnailCache>,
+) -> ThumbnailStream {
+    let (tx, rx) = mpsc::channel();
+    let cancelled = CancelToken::new();
+    let flag = cancelled.clone();
+
+    thread::spawn(move || {
+        let (cached, pending): (Vec<_>, Vec<_>) = paths
+            .into_iter()
+            .filter(|p| is_supported(p))
+            .partition(|p| cache.lookup(p, size).is_some());
+        for path in cached.into_iter().chain(pending) {
+            if flag.is_cancelled() {
+                return;
+            }
+            let thumbnail = cache.thumbnail(&path, size);
+            let result = ThumbnailResult {
+                path,
+                size,
+                thumbnail,
+            };
+            if tx.send(result).is_err() {
+                return;
+            }
+        }
+    });
+
+    ThumbnailStream { rx, cancelled }
+}
+
+/// Hex MD5 digest of `data`, which the spec names thumbnails by.
+fn md5_hex(data: &[u8]) -> String {
+    Md5::digest(data)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn write_image(dir: &TempDir, rel: &str, width: u32, height: u32) -> PathBuf {
+        let path = dir.path().join(rel);
+        image::RgbaImage::from_pixel(width, height, image::Rgba([200, 30, 30, 255]))
+            .save(&path)
+            .unwrap();
+        path
+    }
+
+    fn chunk(chunks: &[(String, String)], key: &str) -> Option<String> {
+        chunks
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn file_uris_are_escaped_like_glib() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        assert_eq!(
+            file_uri(Path::new("/home/me/a b/ü#1%.png")),
+            "file:///home/me/a%20b/%C3%BC%231%25.png"
+        );
+        assert_eq!(
+            file_uri(Path::new("/x/!$&'()*+,-.:=@_~")),
+            "file:///x/!$&'()*+,-.:=@_~"
+        );
+        let raw = Path::new(OsStr::from_bytes(b"/x/\xff"));
+        assert_eq!(file_uri(raw), "file:///x/%FF");
+    }
+
+    #[test]
+    fn sizes_follow_the_spec() {
+        let sizes = [
+            ThumbnailSize::Normal,
+            ThumbnailSize::Large,
+            ThumbnailSize::XLarge,
+            ThumbnailSize::XxLarge,
+        ];
+        let spec: Vec<_> = sizes.iter().map(|s| (s.pixels(), s.dir_name())).collect();
+        assert_eq!(
+            spec,
+            [
+                (128, "normal"),
+                (256, "large"),
+                (512, "x-large"),
+                (1024, "xx-large")
+            ]
+        );
+    }
+
+    #[test]
+    fn supported_images_are_judged_by_extension() {
+        assert!(is_supported(Path::new("a.png")));
+        assert!(is_supported(Path::new("a.JPG")));
+        assert!(is_supported(Path::new("a.jpeg")));
+        assert!(!is_supported(Path::new("a.txt")));
+        assert!(!is_supported(Path::new("png")));
+    }
+
+    #[test]
+    fn sources_match_only_the_version_recorded() {
+        let source = Source {
+            uri: "file:///a.png".into(),
+            mtime: 100,
+            size: 42,
+        };
+        let chunks = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+        let uri = ("Thumb::URI", "file:///a.png");
+        let mtime = ("Thumb::MTime", "100");
+        assert!(source.matches(&chunks(&[uri, mtime, ("Thumb::Size", "42")])));
+        assert!(source.matches(&chunks(&[("Software", "x"), mtime, uri])));
+        assert!(!source.matches(&chunks(&[uri, mtime, ("Thumb::Size", "41")])));
+        assert!(!source.matches(&chunks(&[uri, mtime, ("Thumb::Size", "big")])));
+        assert!(!source.matches(&chunks(&[uri, ("Thumb::MTime", "101")])));
+        assert!(!source.matches(&chunks(&[uri, ("Thumb::MTime", "soon")])));
+        assert!(!source.matches(&chunks(&[("Thumb::URI", "file:///b.png"), mtime])));
+        assert!(!source.matches(&chunks(&[uri])));
+        assert!(!source.matches(&chunks(&[mtime])));
+    }
+
+    #[test]
+    fn thumbnails_are_scaled_cached_and_tagged() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let photo = write_image(&dir, "photo.png", 300, 200);
+
+        assert_eq!(cache.lookup(&photo, ThumbnailSize::Normal), None);
+        let thumb = cache.thumbnail(&photo, ThumbnailSize::Normal).unwrap();
+        let expected = cache.thumbnail_path(&photo, ThumbnailSize::Normal);
+        assert_eq!(thumb.as_ref(), Some(&expected));
+        assert!(expected.starts_with(dir.path().join("cache/normal")));
+        assert_eq!(
+            expected.file_name().unwrap().to_str().unwrap(),
+            format!("{}.png", md5_hex(file_uri(&photo).as_bytes()))
+        );
+
+        let image = image::open(&expected).unwrap();
+        assert_eq!((image.width(), image.height()), (128, 85));
+        let chunks = read_chunks(&expected).unwrap();
+        let source = Source::of(&photo).unwrap();
+        assert!(source.matches(&chunks));
+        assert_eq!(
+            chunk(&chunks, "Thumb::Image::Width").as_deref(),
+            Some("300")
+        );
+        assert_eq!(
+            chunk(&chunks, "Thumb::Image::Height").as_deref(),
+            Some("200")
+        );
+        assert_eq!(chunk(&chunks, "Software").as_deref(), Some(APP_NAME));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&expected).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        assert_eq!(cache.lookup(&photo, ThumbnailSize::Normal), Some(expected));
+        assert_eq!(cache.lookup(&photo, ThumbnailSize::Large), None);
+        // Nothing but the thumbnail is left in its directory.
+        let names: Vec<_> = fs::read_dir(dir.path().join("cache/normal"))
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 1);
+    }
+
+    #[test]
+    fn small_images_are_not_scaled_up() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let icon = write_image(&dir, "icon.png", 16, 8);
+        let thumb = cache
+            .thumbnail(&icon, ThumbnailSize::Large)
+            .unwrap()
+            .unwrap();
+        let image = image::open(thumb).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+    }
+
+    #[test]
+    fn changed_originals_get_new_thumbnails() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let photo = write_image(&dir, "photo.png", 300, 200);
+        let thumb = cache
+            .thumbnail(&photo, ThumbnailSize::Normal)
+            .unwrap()
+            .unwrap();
+
+        // A different length makes it stale even within the same second.
+        write_image(&dir, "photo.png", 200, 400);
+        assert_eq!(cache.lookup(&photo, ThumbnailSize::Normal), None);
+        let again = cache.thumbnail(&photo, ThumbnailSize::Normal).unwrap();
+        assert_eq!(again, Some(thumb.clone()));
+        let image = image::open(&thumb).unwrap();
+        assert_eq!((image.width(), image.height()), (64, 128));
+        assert!(cache.lookup(&photo, ThumbnailSize::Normal).is_some());
+    }
+
+    #[test]
+    fn undecodable_images_are_remembered_until_they_change() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let broken = dir.write("broken.png", "not a png");
+
+        let err = cache.thumbnail(&broken, ThumbnailSize::Normal).unwrap_err();
+        assert!(matches!(err, Error::Image(_)), "{err:?}");
+        let marker = cache.failure_path(&broken);
+        assert!(marker.starts_with(dir.path().join("cache/fail").join(APP_NAME)));
+        assert!(Source::of(&broken)
+            .unwrap()
+            .matches(&read_chunks(&marker).unwrap()));
+        assert!(!cache
+            .thumbnail_path(&broken, ThumbnailSize::Normal)
+            .exists());
+
+        assert_eq!(
+            cache.thumbnail(&broken, ThumbnailSize::Normal).unwrap(),
+            None
+        );
+        assert_eq!(
+            cache.thumbnail(&broken, ThumbnailSize::Large).unwrap(),
+            None
+        );
+
+        dir.write("broken.png", "still not a png");
+        assert!(cache.thumbnail(&broken, ThumbnailSize::Normal).is_err());
+
+        let fixed = write_image(&dir, "broken.png", 4, 4);
+        assert!(cache
+            .thumbnail(&fixed, ThumbnailSize::Normal)
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn unsupported_large_and_cached_files_are_skipped() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let text = dir.write("notes.txt", "hello");
+        assert_eq!(cache.thumbnail(&text, ThumbnailSize::Normal).unwrap(), None);
+
+        let huge = dir.write("huge.png", "");
+        File::options()
+            .write(true)
+            .open(&huge)
+            .unwrap()
+            .set_len(MAX_SOURCE_SIZE + 1)
+            .unwrap();
+        assert_eq!(cache.thumbnail(&huge, ThumbnailSize::Normal).unwrap(), None);
+        assert!(!cache.failure_path(&huge).exists());
+
+        let photo = write_image(&dir, "photo.png", 4, 4);
+        let thumb = cache
+            .thumbnail(&photo, ThumbnailSize::Normal)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            cache.thumbnail(&thumb, ThumbnailSize::Normal).unwrap(),
+            None
+        );
+
+        let missing = dir.path().join("missing.png");
+        assert!(cache.thumbnail(&missing, ThumbnailSize::Normal).is_err());
+    }
+
+    #[test]
+    fn remove_forgets_every_size_and_the_failure_marker() {
+        let dir = TempDir::new();
+        let cache = ThumbnailCache::new(dir.path().join("cache"));
+        let photo = write_image(&dir, "photo.png", 4, 4);
+        let small = cache
+            .thumbnail(&photo, ThumbnailSize::Normal)
+            .unwrap()
+            .unwrap();
+        let large = cache
+            .thumbnail(&photo, ThumbnailSize::XxLarge)
+            .unwrap()
+            .unwrap();
+        let broken = dir.write("broken.png", "x");
+        assert!(cache.thumbnail(&broken, ThumbnailSize::Normal).is_err());
+
+        cache.remove(&photo);
+        cache.remove(&broken);
+        assert!(!small.exists());
+        assert!(!large.exists());
+        assert!(!cache.failure_path(&broken).exists());
+    }
+
+    #[test]
+    fn streams_serve_cached_thumbnails_first() {
+        let dir = TempDir::new();
+        let cache = Arc::new(ThumbnailCache::new(dir.path().join("cache")));
+        let first = write_image(&dir, "a.png", 4, 4);
+        let second = write_image(&dir, "b.png", 4, 4);
+        cache.thumbnail(&second, ThumbnailSize::Normal).unwrap();
+        let text = dir.write("c.txt", "x");
+
+        let stream = generate_thumbnails(
+            vec![first.clone(), text, second.clone()],
+            ThumbnailSize::Normal,
+            cache.clone(),
+        );
+        let results: Vec<_> = stream.collect();
+        let paths: Vec<_> = results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(paths, [second, first]);
+        for result in results {
+            assert_eq!(result.size, ThumbnailSize::Normal);
+            let thumb = result.thumbnail.unwrap().unwrap();
+            assert_eq!(
+                thumb,
+                cache.thumbnail_path(&result.path, ThumbnailSize::Normal)
+            );
+        }
+    }
+}
@@ -0,0 +1,530 @@
+//! The desktop trash: move items in, list them, put them back and purge them.
+//!
+//! Built on the `trash` crate. Moving to the trash works everywhere; listing, restoring and
+//! purging need the freedesktop trash (Linux and BSDs) or Windows and fail with
+//! `Error::NotImplemented` elsewhere.
+
+use crate::core::errors::{Error, Result};
+use crate::models::file_entry::{FileKind, Timestamp};
+use crate::services::fs::ops::{free_name, ConflictPolicy};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// An item currently in the trash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TrashEntry {
+    /// Platform identifier used to restore or purge the item. On freedesktop systems this
+    /// is the path of its `.trashinfo` file.
+    pub id: String,
+    pub name: String,
+    pub original_path: PathBuf,
+    /// When the item was trashed, if the trash recorded it.
+    pub deleted_at: Option<Timestamp>,
+    /// `Dir` or `File`, or `Unknown` when the trashed item could not be inspected.
+    pub kind: FileKind,
+    /// Size in bytes, for files.
+    pub size: Option<u64>,
+    /// Number of direct children, for directories.
+    pub entries: Option<usize>,
+}
+
+/// Outcome of [`restore`].
+#[derive(Debug, Default)]
+pub struct RestoreReport {
+    /// Where each restored item ended up.
+    pub restored: Vec<PathBuf>,
+    /// Ids left in the trash because their original location is occupied.
+    pub skipped: Vec<String>,
+    /// Items left in the trash under `ConflictPolicy::Ask`, for the caller to decide on and
+    /// restore again with a concrete policy.
+    pub conflicts: Vec<TrashEntry>,
+    /// Ids that could not be restored, with the reason. Under `ConflictPolicy::Error` this
+    /// includes items whose original location is occupied.
+    pub failed: Vec<(String, Error)>,
+}
+
+/// Move `path` to the trash. Use a [`TrashBatch`] to find out the id it gets there.
+pub fn move_to_trash(path: &Path) -> Result<()> {
+    fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+    ::trash::delete(path).map_err(convert)
+}
+
+/// Moves items to the trash and finds out the ids they get there.
+///
+/// The ids are found by comparing a listing of the trash taken before the first item with
+/// one taken when they are asked for, so trashing many items lists the trash twice rather
+/// than twice per item. An id names the item trashed here even when the trash holds older
+/// items from the same path.
+#[derive(Debug, Default)]
+pub struct TrashBatch {
+    /// Ids in the trash before the first item, once listed; `None` inside when the trash
+    /// cannot be listed.
+    before: Option<Option<HashSet<OsString>>>,
+    /// Where each item came from, as the trash records it.
+    trashed: Vec<PathBuf>,
+}
+
+impl TrashBatch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move `path` to the trash. Returns its index in [`TrashBatch::ids`].
+    pub fn trash(&mut self, path: &Path) -> Result<usize> {
+        fs::symlink_metadata(path).map_err(|e| Error::from_io(path, e))?;
+        let recorded = recorded_path(path);
+        if self.before.is_none() {
+            let ids = backend::list()
+                .ok()
+                .map(|items| items.into_iter().map(|i| i.id).collect());
+            self.before = Some(ids);
+        }
+        ::trash::delete(path).map_err(convert)?;
+        self.trashed.push(recorded);
+        Ok(self.trashed.len() - 1)
+    }
+
+    /// The id of each item, in the order they were trashed. `None` where the trash cannot
+    /// be listed, and for items trashed from the same path more than once in the batch,
+    /// which cannot be told apart.
+    pub fn ids(&self) -> Vec<Option<String>> {
+        let unknown = vec![None; self.trashed.len()];
+        let Some(Some(before)) = &self.before else {
+            return unknown;
+        };
+        let after = match backend::list() {
+            Ok(items) => items,
+            Err(err) => {
+                tracing::debug!(%err, "cannot find the ids of trashed items");
+                return unknown;
+            }
+        };
+        let mut new: HashMap<PathBuf, Vec<OsString>> = HashMap::new();
+        for item in after {
+            if !before.contains(&item.id) {
+                new.entry(item.original_path()).or_default().push(item.id);
+            }
+        }
+        let mut times: HashMap<&Path, usize> = HashMap::new();
+        for path in &self.trashed {
+            *times.entry(path).or_default() += 1;
+        }
+        self.trashed
+            .iter()
+            .map(|path| match new.get(path).map(Vec::as_slice) {
+                Some([id]) if times[path.as_path()] == 1 => Some(id.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// `path` as the trash records it: with its parent directory resolved, as the `trash`
+/// crate does, but not the item itself, which may be a symlink.
+fn recorded_path(path: &Path) -> PathBuf {
+    let (Some(parent), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let parent = if parent.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        parent
+    };
+    match fs::canonicalize(parent) {
+        Ok(parent) => parent.join(name),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// Everything in the trash, most recently deleted first. Items deleted within the same
+/// second are ordered by id.
+pub fn list() -> Result<Vec<TrashEntry>> {
+    let mut entries: Vec<TrashEntry> = backend::list()?.iter().map(entry_of).collect();
+    entries.sort_by(|a, b| {
+        b.deleted_at
+            .cmp(&a.deleted_at)
+            .then_with(|| a.id.cmp(&b.id))
+    });
+    Ok(entries)
+}
+
+/// The most recently trashed item that came from `original`.
+///
+/// Deletion times only have one-second resolution, so of several items from `original`
+/// trashed within the same second this picks the one with the lowest id, which need not be
+/// the newest. Keep the id found by a [`TrashBatch`] to refer to a specific item.
+pub fn find_latest(original: &Path) -> Result<Option<TrashEntry>> {
+    Ok(list()?.into_iter().find(|e| e.original_path == original))
+}
+
+/// Put the items with the given ids back where they came from.
+///
+/// When the original location is occupied, `on_conflict` decides: `Overwrite` moves the
+/// occupant to the trash first, `KeepBoth` restores under a " (2)"-style name,
+/// `OverwriteIfNewer` compares modification times, and `Ask` leaves the item in the trash
+/// and reports it in [`RestoreReport::conflicts`].
+///
+/// Only fails as a whole when the trash cannot be read. Items that cannot be restored are
+/// reported in [`RestoreReport::failed`] and the rest are still restored.
+pub fn restore(ids: &[String], on_conflict: ConflictPolicy) -> Result<RestoreReport> {
+    let items = backend::list()?;
+    let mut report = RestoreReport::default();
+    for id in ids {
+        if let Err(err) = restore_one(&items, id, on_conflict, &mut report) {
+            report.failed.push((id.clone(), err));
+        }
+    }
+    Ok(report)
+}
+
+fn restore_one(
+    items: &[::trash::TrashItem],
+    id: &str,
+    on_conflict: ConflictPolicy,
+    report: &mut RestoreReport,
+) -> Result<()> {
+    let item = find(items, id)?;
+    let dest = item.original_path();
+    let Ok(occupant) = fs::symlink_metadata(&dest) else {
+        backend::restore(item)?;
+        report.restored.push(dest);
+        return Ok(());
+    };
+
+    let overwrite = match on_conflict {
+        ConflictPolicy::Error => return Err(Error::AlreadyExists { path: dest }),
+        ConflictPolicy::Overwrite => true,
+        ConflictPolicy::Skip => false,
+        ConflictPolicy::OverwriteIfNewer => {
+            let trashed = backend::stored_path(item)
+                .and_then(|p| fs::symlink_metadata(p).ok())
+                .and_then(|m| m.modified().ok());
+            matches!((trashed, occupant.modified().ok()), (Some(t), Some(o)) if t > o)
+        }
+        ConflictPolicy::KeepBoth => {
+            let is_dir = backend::stored_path(item).is_some_and(|p| p.is_dir());
+            let alt = free_name(&dest, is_dir, &HashSet::new());
+            backend::restore_to(item, &alt)?;
+            report.restored.push(alt);
+            return Ok(());
+        }
+        ConflictPolicy::Ask => {
+            report.conflicts.push(entry_of(item));
+            return Ok(());
+        }
+    };
+    if overwrite {
+        move_to_trash(&dest)?;
+        backend::restore(item)?;
+        report.restored.push(dest);
+    } else {
+        report.skipped.push(id.to_string());
+    }
+    Ok(())
+}
+
+/// Permanently delete the items with the given ids. Requires `confirmed`.
+pub fn purge(ids: &[String], confirmed: bool) -> Result<()> {
+    if !confirmed {
+        return Err(Error::ConfirmationRequired("purging items from the trash"));
+    }
+    let items = backend::list()?;
+    let selected = ids
+        .iter()
+        .map(|id| find(&items, id).cloned())
+        .collect::<Result<Vec<_>>>()?;
+    backend::purge(selected)
+}
+
+/// Permanently delete everything in the trash. Requires `confirmed`.
+pub fn empty(confirmed: bool) -> Result<()> {
+    if !confirmed {
+        return Err(Error::ConfirmationRequired("emptying the trash"));
+    }
+    backend::purge(backend::list()?)
+}
+
+fn find<'a>(items: &'a [::trash::TrashItem], id: &str) -> Result<&'a ::trash::TrashItem> {
+    items
+        .iter()
+        .find(|i| i.id.to_string_lossy() == id)
+        .ok_or_else(|| Error::Vanished {
+            path: PathBuf::from(id),
+        })
+}
+
+fn entry_of(item: &::trash::TrashItem) -> TrashEntry {
+    let (kind, size, entries) = match backend::metadata(item) {
+        Some(::trash::TrashItemSize::Bytes(n)) => (FileKind::File, Some(n), None),
+        Some(::trash::TrashItemSize::Entries(n)) => (FileKind::Dir, None, Some(n)),
+        None => (FileKind::Unknown, None, None),
+    };
+    TrashEntry {
+        id: item.id.to_string_lossy().into_owned(),
+        name: item.name.to_string_lossy().into_owned(),
+        original_path: item.original_path(),
+        deleted_at: (item.time_deleted >= 0).then_some(Timestamp {
+            secs: item.time_deleted,
+            nanos: 0,
+        }),
+        kind,
+        size,
+        entries,
+    }
+}
+
+fn convert(err: ::trash::Error) -> Error {
+    match err {
+        #[cfg(all(
+            unix,
+            not(target_os = "macos"),
+            not(target_os = "ios"),
+            not(target_os = "android")
+        ))]
+        ::trash::Error::FileSystem { path, source } => Error::from_io(path, source),
+        ::trash::Error::RestoreCollision { path, .. } => Error::AlreadyExists { path },
+        ::trash::Error::CouldNotAccess { target } => Error::Vanished {
+            path: PathBuf::from(target),
+        },
+        other => Error::Other(format!("trash: {other:?}")),
+    }
+}
+
+#[cfg(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+))]
+mod backend {
+    use super::convert;
+    use crate::core::errors::Result;
+    use ::trash::os_limited;
+    use ::trash::{TrashItem, TrashItemSize};
+    use std::path::{Path, PathBuf};
+
+    pub fn list() -> Result<Vec<TrashItem>> {
+        os_limited::list().map_err(convert)
+    }
+
+    pub fn metadata(item: &TrashItem) -> Option<TrashItemSize> {
+        os_limited::metadata(item).ok().map(|m| m.size)
+    }
+
+    pub fn restore(item: &TrashItem) -> Result<()> {
+        os_limited::restore_all([item.clone()]).map_err(convert)
+    }
+
+    pub fn purge(items: Vec<TrashItem>) -> Result<()> {
+        os_limited::purge_all(items).map_err(convert)
+    }
+
+    /// Where the trashed data lives: `<trash>/files/<name>` next to `<trash>/info/<name>.trashinfo`.
+    #[cfg(unix)]
+    pub fn stored_path(item: &TrashItem) -> Option<PathBuf> {
+        let info = Path::new(&item.id);
+        let name = info.file_stem()?;
+        Some(info.parent()?.parent()?.join("files").join(name))
+    }
+
+    #[cfg(not(unix))]
+    pub fn stored_path(_item: &TrashItem) -> Option<PathBuf> {
+        None
+    }
+
+    /// Restore `item` to `dest` instead of its original location.
+    #[cfg(unix)]
+    pub fn restore_to(item: &TrashItem, dest: &Path) -> Result<()> {
+        use crate::core::errors::Error;
+        let stored = stored_path(item).ok_or_else(|| Error::Vanished {
+            path: PathBuf::from(&item.id),
+        })?;
+        crate::services::fs::ops::move_exact(&stored, dest)?;
+        let info = Path::new(&item.id);
+        std::fs::remove_file(info).map_err(|e| Error::from_io(info, e))
+    }
+
+    #[cfg(not(unix))]
+    pub fn restore_to(_item: &TrashItem, _dest: &Path) -> Result<()> {
+        Err(crate::core::errors::Error::NotImplemented(
+            "restoring to another location",
+        ))
+    }
+}
+
+#[cfg(not(any(
+    target_os = "windows",
+    all(
+        unix,
+        not(target_os = "macos"),
+        not(target_os = "ios"),
+        not(target_os = "android")
+    )
+)))]
+mod backend {
+    use crate::core::errors::{Error, Result};
+    use ::trash::{TrashItem, TrashItemSize};
+    use std::path::{Path, PathBuf};
+
+    pub fn list() -> Result<Vec<TrashItem>> {
+        Err(Error::NotImplemented("listing the trash on this platform"))
+    }
+
+    pub fn metadata(_item: &TrashItem) -> Option<TrashItemSize> {
+        None
+    }
+
+    pub fn restore(_item: &TrashItem) -> Result<()> {
+        Err(Error::NotImplemented(
+            "restoring from the trash on this platform",
+        ))
+    }
+
+    pub fn purge(_items: Vec<TrashItem>) -> Result<()> {
+        Err(Error::NotImplemented("purging the trash on this platform"))
+    }
+
+    pub fn stored_path(_item: &TrashItem) -> Option<PathBuf> {
+        None
+    }
+
+    pub fn restore_to(_item: &TrashItem, _dest: &Path) -> Result<()> {
+        Err(Error::NotImplemented(
+            "restoring from the trash on this platform",
+        ))
+    }
+}
+
+#[cfg(all(test, unix, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+    use crate::core::test_support::TempDir;
+
+    fn trash(path: &Path) -> TrashEntry {
+        let mut batch = TrashBatch::new();
+        batch.trash(path).unwrap();
+        let id = batch.ids().pop().flatten().unwrap();
+        list().unwrap().into_iter().find(|e| e.id == id).unwrap()
+    }
+
+    #[test]
+    fn trashes_lists_and_restores() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a.txt", "abc");
+        let entry = trash(&file);
+        assert!(!file.exists());
+        assert_eq!(entry.name, "a.txt");
+        assert_eq!(entry.original_path, file);
+        assert_eq!(entry.kind, FileKind::File);
+        assert_eq!(entry.size, Some(3));
+        assert!(list().unwrap().iter().any(|e| e.id == entry.id));
+
+        let report = restore(&[entry.id], ConflictPolicy::Error).unwrap();
+        assert_eq!(report.restored, vec![file.clone()]);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "abc");
+    }
+
+    #[test]
+    fn restore_resolves_conflicts() {
+        let tmp = TempDir::new();
+        let file = tmp.write("a.txt", "trashed");
+        let entry = trash(&file);
+        fs::write(&file, "occupant").unwrap();
+        let ids = [entry.id.clone()];
+
+        let report = restore(&ids, ConflictPolicy::Error).unwrap();
+        assert!(matches!(
+            &report.failed[..],
+            [(id, Error::AlreadyExists { .. })] if *id == entry.id
+        ));
+        let report = restore(&ids, ConflictPolicy::Skip).unwrap();
+        assert_eq!(report.skipped, ids);
+        let report = restore(&ids, ConflictPolicy::Ask).unwrap();
+        assert_eq!(report.conflicts[0].id, entry.id);
+
+        let report = restore(&ids, ConflictPolicy::KeepBoth).unwrap();
+        let alt = tmp.path().join("a (2).txt");
+        assert_eq!(report.restored, vec![alt.clone()]);
+        assert_eq!(fs::read_to_string(&alt).unwrap(), "trashed");
+        assert_eq!(fs::read_to_string(&file).unwrap(), "occupant");
+    }
+
+    #[test]
+    fn overwriting_restore_trashes_the_occupant() {
+        let tmp = TempDir::new();
+        let dir = tmp.mkdir("dir");
+        tmp.write("dir/inner", "");
+        let entry = trash(&dir);
+        assert_eq!((entry.kind, entry.entries), (FileKind::Dir, Some(1)));
+        tmp.write("dir/other", "");
+
+        restore(&[entry.id], ConflictPolicy::Overwrite).unwrap();
+        assert!(dir.join("inner").exists());
+        let occupant = find_latest(&dir).unwrap().unwrap();
+        purge(std::slice::from_ref(&occupant.id), true).unwrap();
+        assert!(!list().unwrap().iter().any(|e| e.id == occupant.id));
+    }
+
+    #[test]
+    fn destructive_calls_need_confirmation() {
+        assert!(matches!(
+            purge(&[], false),
+            Err(Error::ConfirmationRequired(_))
+        ));
+        assert!(matches!(empty(false), Err(Error::ConfirmationRequired(_))));
+        let report = restore(&["missing".into()], ConflictPolicy::Error).unwrap();
+        assert!(matches!(&report.failed[..], [(_, Error::Vanished { .. })]));
+    }
+
+    #[test]
+    fn batches_find_the_ids_of_what_they_trashed() {
+        let tmp = TempDir::new();
+        let a = tmp.write("a", "a");
+        let b = tmp.write("sub/../b", "b");
+        let mut batch = TrashBatch::new();
+        assert_eq!(batch.trash(&a).unwrap(), 0);
+        assert_eq!(batch.trash(&b).unwrap(), 1);
+        tmp.write("a", "again");
+        batch.trash(&a).unwrap();
+        let ids = batch.ids();
+        let entries = list().unwrap();
+        let b_entry = entries
+            .iter()
+            .find(|e| Some(&e.id) == ids[1].as_ref())
+            .unwrap();
+        assert_eq!(b_entry.original_path, tmp.path().join("b"));
+        // Trashed from the same path twice, so neither can be told apart.
+        assert_eq!(ids[0], None);
+        assert_eq!(ids[2], None);
+        let from_a: Vec<String> = entries
+            .into_iter()
+            .filter(|e| e.original_path == a)
+            .map(|e| e.id)
+            .collect();
+        purge(&from_a, true).unwrap();
+        purge(&[ids[1].clone().unwrap()], true).unwrap();
+    }
+
+    #[test]
+    fn restore_keeps_going_after_a_failed_item() {
+        let tmp = TempDir::new();
+        let a = tmp.write("a", "a");
+        let b = tmp.write("b", "b");
+        let a_id = trash(&a).id;
+        let b_id = trash(&b).id;
+        fs::write(&a, "occupant").unwrap();
+
+        let report = restore(&[a_id.clone(), b_id], ConflictPolicy::Error).unwrap();
+        assert_eq!(report.restored, vec![b.clone()]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, a_id);
+        assert_eq!(fs::read_to_string(&b).unwrap(), "b");
+        purge(&[a_id], true).unwrap();
+    }
+}
@@ -2,8 +2,14 @@
 
 use crate::core::telemetry::logging::init_logging;
 use crate::pages::{
-    explorer::ExplorerPage, extensions::ExtensionsPage, git::GitPage, s3::S3Page,
-    search::SearchPage, settings::SettingsPage, PageKind,
+    explorer::ExplorerPage,
+    extensions::ExtensionsPage,
+    git::GitPage,
+    s3::S3Page,
+    search::{RevealInExplorer, SaveSearch, SearchPage},
+    settings::SettingsPage,
+    usage::DiskUsagePage,
+    PageKind,
 };
 use crate::ui::assets::Assets;
 use crate::ui::components::layout::footer::{footer, FooterProps};
@@ -46,21 +52,50 @@ impl NohrsApp {
                 let explorer = cx.new(|cx| {
                     ExplorerPage::new(resizable.clone(), search_input.clone(), cx.focus_handle())
                 });
-                let search = cx.new(|_cx| SearchPage::new());
+                let search_root_input = cx.new(|cx| InputState::new(window, cx));
+                let search_query_input = cx.new(|cx| InputState::new(window, cx));
+                let search = cx.new(|_cx| SearchPage::new(search_root_input, search_query_input));
                 let git = cx.new(|_cx| GitPage::new());
+                let usage_root_input = cx.new(|cx| InputState::new(window, cx));
+                let usage = cx.new(|cx| DiskUsagePage::new(usage_root_input, cx.focus_handle()));
                 let s3 = cx.new(|_cx| S3Page::new());
                 let extensions = cx.new(|_cx| ExtensionsPage::new());
                 let settings = cx.new(|_cx| SettingsPage::new());
 
-                let view = cx.new(|_cx| RootView {
-                    current_page: PageKind::Explorer,
-                    focus_handle,
-                    explorer,
-                    search,
-                    git,
-                    s3,
-                    extensions,
-                    settings,
+                let view = cx.new(|cx| {
+                    let subs = vec![
+                        cx.subscribe_in(
+                            &search,
+                            window,
+                            |view: &mut RootView, _search, event: &RevealInExplorer, window, cx| {
+                                let path = event.0.clone();
+                                view.explorer
+                                    .update(cx, |explorer, cx| explorer.reveal(path, window, cx));
+                                view.set_page(PageKind::Explorer, cx);
+                            },
+                        ),
+                        cx.subscribe_in(
+                            &search,
+                            window,
+                            |view: &mut RootView, _search, event: &SaveSearch, _window, cx| {
+                                let search = event.0.clone();
+                                view.explorer
+                                    .update(cx, |explorer, cx| explorer.save_search(search, cx));
+                            },
+                        ),
+                    ];
+                    RootView {
+                        current_page: PageKind::Explorer,
+                        focus_handle,
+                        explorer,
+                        search,
+                        git,
+                        usage,
+                        s3,
+                        extensions,
+                        settings,
+                        _subs: subs,
+                    }
                 });
 
                 cx.new(|cx| Root::new(view.into(), window, cx))
@@ -77,9 +112,12 @@ pub struct RootView {
     explorer: Entity<ExplorerPage>,
     search: Entity<SearchPage>,
     git: Entity<GitPage>,
+    usage: Entity<DiskUsagePage>,
     s3: Entity<S3Page>,
     extensions: Entity<ExtensionsPage>,
     settings: Entity<SettingsPage>,
+    // Kept alive for as long as the view.
+    _subs: Vec<gpui::Subscription>,
 }
 
 impl RootView {
@@ -237,6 +275,7 @@ impl RootView {
             PageKind::Explorer => self.explorer.clone().into_any_element(),
             PageKind::Search => self.search.clone().into_any_element(),
             PageKind::Git => self.git.clone().into_any_element(),
+            PageKind::Usage => self.usage.clone().into_any_element(),
             PageKind::S3 => self.s3.clone().into_any_element(),
             PageKind::Extensions => self.extensions.clone().into_any_element(),
             PageKind::Settings => self.settings.clone().into_any_element(),
@@ -1,16 +1,16 @@
 #![cfg(feature = "gui")]
 
-use crate::services::fs::listing::FileEntryDto;
+use crate::models::file_entry::{FileEntry, FileKind};
 use crate::ui::theme::theme;
 use gpui::{div, px, rgb, ParentElement, Styled, Window};
 use gpui_component::list::{List, ListDelegate, ListItem};
 use gpui_component::{Icon, IconName, IndexPath};
 
 pub struct FileListDelegate {
-    pub items: Vec<FileEntryDto>,
+    pub items: Vec<FileEntry>,
     pub selected: Option<IndexPath>,
     // Callback hooks
-    pub on_confirm: Option<Box<dyn Fn(&FileEntryDto) + 'static>>,
+    pub on_confirm: Option<Box<dyn Fn(&FileEntry) + 'static>>,
 }
 
 impl FileListDelegate {
@@ -22,12 +22,12 @@ impl FileListDelegate {
         }
     }
 
-    pub fn set_items(&mut self, items: Vec<FileEntryDto>) {
+    pub fn set_items(&mut self, items: Vec<FileEntry>) {
         self.items = items;
         self.selected = None;
     }
 
-    pub fn get_selected(&self) -> Option<&FileEntryDto> {
+    pub fn get_selected(&self) -> Option<&FileEntry> {
         self.selected.map(|ix| self.items.get(ix.row)).flatten()
     }
 }
@@ -48,8 +48,8 @@ impl ListDelegate for FileListDelegate {
         let item = self.items.get(ix.row)?;
 
         // Icon based on file type
-        let icon_name = match item.kind.as_str() {
-            "dir" => IconName::Folder,
+        let icon_name = match item.kind {
+            FileKind::Dir => IconName::Folder,
             _ => IconName::File,
         };
 
@@ -60,7 +60,7 @@ impl ListDelegate for FileListDelegate {
             theme::GRAY_50
         };
 
-        let file_type = get_file_type(&item.name, &item.kind);
+        let file_type = get_file_type(item);
 
         let mut row = ListItem::new(ix.clone())
             .py(px(6.0)) // Reduced from 12.0 for compact rows
@@ -116,10 +116,10 @@ impl ListDelegate for FileListDelegate {
                             .text_color(rgb(theme::FG_SECONDARY))
                             .text_ellipsis()
                             .whitespace_nowrap()
-                            .child(match item.kind.as_str() {
-                                "file" => human_bytes(item.size),
-                                "dir" => "-".to_string(),
-                                other => other.to_string(),
+                            .child(match item.kind {
+                                FileKind::File => human_bytes(item.size),
+                                FileKind::Dir => "-".to_string(),
+                                other => other.as_str().to_string(),
                             }),
                     )
                     .child(
@@ -158,7 +158,7 @@ impl ListDelegate for FileListDelegate {
             let ptr = cb as *const _;
             row = row.on_click(move |_, _, _| unsafe {
                 // SAFETY: lifetime tied to delegate existence within app
-                let f: &Box<dyn Fn(&FileEntryDto)> = &*ptr;
+                let f: &Box<dyn Fn(&FileEntry)> = &*ptr;
                 (f)(&item_clone);
             });
         }
@@ -225,20 +225,31 @@ pub fn format_date(timestamp: &u64) -> String {
     }
 }
 
-pub fn get_file_type(name: &str, kind: &str) -> String {
-    match kind {
-        "dir" => "Folder".to_string(),
-        "file" => {
-            if let Some(ext) = std::path::Path::new(name)
-                .extension()
-                .and_then(|e| e.to_str())
-            {
-                ext.to_uppercase()
-            } else {
-                "File".to_string()
-            }
-        }
-        "symlink" => "Link".to_string(),
-        other => other.to_string(),
+/// Render Unix mode bits the way `ls -l` does, e.g. `drwxr-xr-x`.
+pub fn format_mode(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o010000 => 'p',
+        0o140000 => 's',
+        0o060000 => 'b',
+        0o020000 => 'c',
+        _ => '-',
+    };
+    let mut s = String::with_capacity(10);
+    s.push(kind);
+    for shift in [6, 3, 0] {
+        let bits = (mode >> shift) & 0o7;
+        s.push(if bits & 0o4 != 0 { 'r' } else { '-' });
+        s.push(if bits & 0o2 != 0 { 'w' } else { '-' });
+        s.push(if bits & 0o1 != 0 { 'x' } else { '-' });
+    }
+    s
+}
+
+pub fn get_file_type(item: &FileEntry) -> String {
+    match item.extension() {
+        Some(ext) => ext.to_uppercase(),
+        None => item.kind.label().to_string(),
     }
 }